@@ -1,7 +1,219 @@
 use anyhow::{anyhow, Context, Result};
 use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 use std::io::{BufRead, Write};
+use std::path::{Path, PathBuf};
 use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Maximum number of buffered `output` events; the oldest are dropped first.
+const MAX_OUTPUT_EVENTS: usize = 1000;
+
+/// Maximum number of buffered non-`output` events; the oldest are dropped first.
+const MAX_OTHER_EVENTS: usize = 200;
+
+/// How long `shutdown` waits for the adapter to answer `disconnect` before killing it.
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// High-frequency events that the default filter drops.
+const DEFAULT_DENIED_EVENTS: &[&str] = &["loadedSource", "module", "progressUpdate", "invalidated"];
+
+/// Which adapter events are buffered: those named in `allow` (every event when `None`) and not
+/// in `deny`. Thread state, the last stop, and the lifecycle are tracked from every event.
+#[derive(Clone, Debug)]
+struct EventFilter {
+    allow: Option<BTreeSet<String>>,
+    deny: BTreeSet<String>,
+}
+
+impl Default for EventFilter {
+    fn default() -> Self {
+        Self {
+            allow: None,
+            deny: DEFAULT_DENIED_EVENTS
+                .iter()
+                .map(|e| e.to_string())
+                .collect(),
+        }
+    }
+}
+
+impl EventFilter {
+    fn admits(&self, event: &str) -> bool {
+        self.allow
+            .as_ref()
+            .is_none_or(|allow| allow.contains(event))
+            && !self.deny.contains(event)
+    }
+
+    fn to_json(&self) -> Value {
+        json!({"allow": self.allow, "deny": self.deny})
+    }
+}
+
+/// Buffered adapter events and the filter deciding which are kept. Clearing keeps the filter.
+#[derive(Debug, Default)]
+struct EventBuffer {
+    /// Bodies of `output` events, with `category` defaulted to `console`.
+    output: VecDeque<Value>,
+    /// Other admitted events as `{ event, body }`.
+    other: VecDeque<Value>,
+    filter: EventFilter,
+}
+
+impl EventBuffer {
+    fn clear(&mut self) {
+        self.output.clear();
+        self.other.clear();
+    }
+
+    /// Buffer `event` if the filter admits it, dropping the oldest entry when full.
+    fn push(&mut self, event: &str, body: Option<&Value>) {
+        if !self.filter.admits(event) {
+            return;
+        }
+        let (buffer, max, entry) = if event == "output" {
+            let mut body = body.cloned().unwrap_or_else(|| json!({}));
+            if let Some(obj) = body.as_object_mut() {
+                // DAP treats a missing category as `console`.
+                obj.entry("category").or_insert_with(|| json!("console"));
+            }
+            (&mut self.output, MAX_OUTPUT_EVENTS, body)
+        } else {
+            let entry = json!({"event": event, "body": body});
+            (&mut self.other, MAX_OTHER_EVENTS, entry)
+        };
+        if buffer.len() == max {
+            buffer.pop_front();
+        }
+        buffer.push_back(entry);
+    }
+}
+
+/// Last known execution state of a debuggee thread, derived from `stopped`, `continued`, and
+/// `thread` events plus successful stepping/continue requests.
+#[derive(Clone, Debug, Default)]
+struct ThreadState {
+    name: Option<String>,
+    state: &'static str,
+    reason: Option<String>,
+    description: Option<String>,
+    /// Stack frame chosen with `dap_select_frame` during the current stop.
+    selected_frame: Option<Value>,
+}
+
+impl ThreadState {
+    /// Record a state change. Frame ids only hold for one stop, so any change also drops the
+    /// selected frame.
+    fn set(&mut self, state: &'static str, reason: Option<&str>, description: Option<&str>) {
+        self.selected_frame = None;
+        self.state = state;
+        self.reason = reason.map(str::to_string);
+        self.description = description.map(str::to_string);
+    }
+}
+
+/// Debuggee lifecycle as reported by `process`, `exited`, and `terminated` events.
+#[derive(Clone, Debug, Default)]
+struct Lifecycle {
+    /// Body of the `process` event (name, systemProcessId, startMethod, ...).
+    process: Option<Value>,
+    exit_code: Option<i64>,
+    terminated: bool,
+}
+
+/// The request a tool call is currently blocked on, and the `cancel` sent for it, if any.
+#[derive(Default)]
+struct InFlight {
+    seq: Option<i64>,
+    command: Option<String>,
+    cancel_seq: Option<i64>,
+}
+
+/// Adapter stdin and sequence counter shared with `dap_cancel`, which must reach the adapter
+/// while another tool call holds the manager and is blocked reading a response.
+#[derive(Clone)]
+pub struct CancelHandle {
+    stdin: Arc<Mutex<Option<ChildStdin>>>,
+    next_seq: Arc<AtomicI64>,
+    in_flight: Arc<Mutex<InFlight>>,
+    trace_file: Option<PathBuf>,
+}
+
+impl CancelHandle {
+    fn alloc_seq(&self) -> i64 {
+        self.next_seq.fetch_add(1, Ordering::SeqCst)
+    }
+
+    fn send(&self, message: &Value) -> Result<()> {
+        let body = serde_json::to_string(message)?;
+        let mut stdin = self.stdin.lock().unwrap();
+        let w = stdin
+            .as_mut()
+            .ok_or_else(|| anyhow!("DAP adapter is not running"))?;
+        DapAdapterManager::write_content_length(w, &body, self.trace_file.as_deref())
+    }
+
+    /// Send `cancel` for `request_id` (default: the request currently in flight) and/or
+    /// `progress_id` without waiting for the reply. A request in flight is abandoned once the
+    /// adapter answers the cancel, even if the adapter never answers the request itself.
+    pub fn cancel(&self, request_id: Option<i64>, progress_id: Option<&str>) -> Result<Value> {
+        let mut in_flight = self.in_flight.lock().unwrap();
+        let request_id = request_id.or(in_flight.seq);
+        let mut arguments = json!({});
+        if let Some(id) = request_id {
+            arguments["requestId"] = json!(id);
+        }
+        if let Some(id) = progress_id {
+            arguments["progressId"] = json!(id);
+        }
+        if arguments.as_object().is_some_and(|a| a.is_empty()) {
+            return Err(anyhow!(
+                "nothing to cancel: no request is in flight; pass requestId or progressId"
+            ));
+        }
+        let seq = self.alloc_seq();
+        self.send(&json!({
+            "seq": seq,
+            "type": "request",
+            "command": "cancel",
+            "arguments": arguments
+        }))?;
+        let abandoned = in_flight
+            .seq
+            .map(|in_flight_seq| json!({"seq": in_flight_seq, "command": in_flight.command}));
+        if in_flight.seq.is_some() {
+            in_flight.cancel_seq = Some(seq);
+        }
+        Ok(json!({
+            "cancelSeq": seq,
+            "arguments": arguments,
+            "abandoned": abandoned
+        }))
+    }
+
+    fn begin(&self, seq: i64, command: &str) {
+        *self.in_flight.lock().unwrap() = InFlight {
+            seq: Some(seq),
+            command: Some(command.to_string()),
+            cancel_seq: None,
+        };
+    }
+
+    fn finish(&self) {
+        *self.in_flight.lock().unwrap() = InFlight::default();
+    }
+
+    /// True when `msg` answers the `cancel` sent for the request in flight.
+    fn answers_cancel(&self, msg: &Value) -> bool {
+        let cancel_seq = self.in_flight.lock().unwrap().cancel_seq;
+        cancel_seq.is_some()
+            && msg.get("type").and_then(|x| x.as_str()) == Some("response")
+            && msg.get("request_seq").and_then(|x| x.as_i64()) == cancel_seq
+    }
+}
 
 /// Minimal DAP (Debug Adapter Protocol) client manager that speaks Content-Length framed JSON.
 /// The DAP wire messages are not JSON-RPC 2.0; they use { type, seq, command, arguments } for
@@ -10,33 +222,105 @@ use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
 pub struct DapAdapterManager {
     cmd: Option<String>,
     child: Option<Child>,
-    stdin: Option<ChildStdin>,
     stdout: Option<std::io::BufReader<ChildStdout>>,
-    next_seq: i64,
+    /// Holds the adapter stdin and the sequence counter.
+    shared: CancelHandle,
     capabilities: Option<Value>,
+    events: EventBuffer,
+    threads: BTreeMap<i64, ThreadState>,
+    /// Body of the most recent `stopped` event.
+    last_stop: Option<Value>,
+    /// Thread of the last `dap_select_frame`; the frame itself lives in its `ThreadState`.
+    selected_thread: Option<i64>,
+    lifecycle: Lifecycle,
+    /// `source` responses by `sourceReference`; references are only valid for one session.
+    sources: HashMap<i64, Value>,
+    /// Arguments of the last successful `setBreakpoints` per `source.path`, so a single
+    /// breakpoint can be changed by re-sending the whole set.
+    source_breakpoints: BTreeMap<String, Value>,
+    /// Breakpoints as the adapter reported them per `source.path` (`""` when an event carries
+    /// no path): the `setBreakpoints` response, kept current by `breakpoint` events.
+    bound_breakpoints: BTreeMap<String, Vec<Value>>,
+    init_overrides: Option<Value>,
+    /// JSONL file receiving every message exchanged with the adapter (`DAP_TRACE_FILE`).
+    trace_file: Option<PathBuf>,
 }
 
 impl DapAdapterManager {
     pub fn new() -> Self {
         let cmd = std::env::var("DAP_ADAPTER_CMD").ok();
+        let trace_file = std::env::var_os("DAP_TRACE_FILE")
+            .filter(|v| !v.is_empty())
+            .map(PathBuf::from);
         Self {
             cmd,
             child: None,
-            stdin: None,
             stdout: None,
-            next_seq: 1,
+            shared: CancelHandle {
+                stdin: Arc::new(Mutex::new(None)),
+                next_seq: Arc::new(AtomicI64::new(1)),
+                in_flight: Arc::new(Mutex::new(InFlight::default())),
+                trace_file: trace_file.clone(),
+            },
             capabilities: None,
+            events: EventBuffer::default(),
+            threads: BTreeMap::new(),
+            last_stop: None,
+            selected_thread: None,
+            lifecycle: Lifecycle::default(),
+            sources: HashMap::new(),
+            source_breakpoints: BTreeMap::new(),
+            bound_breakpoints: BTreeMap::new(),
+            init_overrides: None,
+            trace_file,
+        }
+    }
+
+    /// Handle for `dap_cancel`; stays valid across adapter restarts.
+    pub fn cancel_handle(&self) -> CancelHandle {
+        self.shared.clone()
+    }
+
+    /// Append one message to the trace file as `{ ts, direction, body }`, keeping the body
+    /// verbatim as a string. Write failures go to stderr and never fail the exchange.
+    fn trace(trace_file: Option<&Path>, direction: &str, body: &str) {
+        let Some(path) = trace_file else {
+            return;
+        };
+        let ts_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let record = json!({"ts": ts_ms, "direction": direction, "body": body});
+        let written = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut f| writeln!(f, "{record}"));
+        if let Err(err) = written {
+            eprintln!(
+                "mcp-dap: failed to write DAP_TRACE_FILE {}: {err}",
+                path.display()
+            );
         }
     }
 
-    fn write_content_length(w: &mut ChildStdin, body: &str) -> Result<()> {
+    fn write_content_length(
+        w: &mut ChildStdin,
+        body: &str,
+        trace_file: Option<&Path>,
+    ) -> Result<()> {
+        Self::trace(trace_file, "send", body);
         write!(w, "Content-Length: {}\r\n\r\n", body.len())?;
         w.write_all(body.as_bytes())?;
         w.flush()?;
         Ok(())
     }
 
-    fn read_content_length(r: &mut std::io::BufReader<ChildStdout>) -> Result<String> {
+    fn read_content_length(
+        r: &mut std::io::BufReader<ChildStdout>,
+        trace_file: Option<&Path>,
+    ) -> Result<String> {
         let mut content_length: Option<usize> = None;
         let mut line = String::new();
         loop {
@@ -56,13 +340,19 @@ impl DapAdapterManager {
         let mut buf = vec![0u8; len];
         use std::io::Read;
         r.read_exact(&mut buf)?;
-        String::from_utf8(buf).context("utf8 body")
+        let body = String::from_utf8(buf).context("utf8 body")?;
+        Self::trace(trace_file, "recv", &body);
+        Ok(body)
     }
 
     fn ensure_started(&mut self, override_cmd: Option<&str>) -> Result<()> {
-        if self.child.is_some() {
+        if self.adapter_alive() {
             return Ok(());
         }
+        if self.child.is_some() {
+            // The adapter exited on its own; reap it and start over with a clean session.
+            self.stop();
+        }
         let Some(cmd) = override_cmd
             .map(|s| s.to_string())
             .or_else(|| self.cmd.clone())
@@ -71,41 +361,34 @@ impl DapAdapterManager {
                 "DAP adapter not configured. Set DAP_ADAPTER_CMD or pass arguments.adapterCommand."
             ));
         };
-        let mut child = Command::new(cmd)
+        let mut command = Command::new(cmd);
+        command
             .stdin(Stdio::piped())
             .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
-            .spawn()
-            .context("spawn dap adapter")?;
+            .stderr(Stdio::inherit());
+        Self::apply_adapter_environment(&mut command)?;
+        let mut child = command.spawn().context("spawn dap adapter")?;
         let stdin = child.stdin.take().ok_or_else(|| anyhow!("no stdin"))?;
         let stdout = child.stdout.take().ok_or_else(|| anyhow!("no stdout"))?;
-        self.stdin = Some(stdin);
+        *self.shared.stdin.lock().unwrap() = Some(stdin);
         self.stdout = Some(std::io::BufReader::new(stdout));
         self.child = Some(child);
 
         // Send initialize request
+        let arguments = self.initialize_arguments()?;
         let seq = self.alloc_seq();
-            let init = json!({
-                "seq": seq,
-                "type": "request",
-                "command": "initialize",
-                "arguments": {
-                "clientID": "mcp-dap",
-                "adapterID": "mcp-dap",
-                "pathFormat": "path",
-                "linesStartAt1": true,
-                "columnsStartAt1": true,
-                "supportsRunInTerminalRequest": false
-            }
+        let init = json!({
+            "seq": seq,
+            "type": "request",
+            "command": "initialize",
+            "arguments": arguments
         });
-        let s = serde_json::to_string(&init)?;
-        let w = self.stdin.as_mut().unwrap();
-        Self::write_content_length(w, &s)?;
+        self.shared.send(&init)?;
 
         // Read messages until the initialize response arrives.
         let r = self.stdout.as_mut().unwrap();
         loop {
-            let body = Self::read_content_length(r)?;
+            let body = Self::read_content_length(r, self.trace_file.as_deref())?;
             let v: Value = serde_json::from_str(&body).context("parse dap message")?;
             match (v.get("type").and_then(|x| x.as_str()), v.get("seq")) {
                 (Some("response"), _) => {
@@ -117,18 +400,1113 @@ impl DapAdapterManager {
                         break;
                     }
                 }
-                _ => {
-                    // Ignore events and other traffic for now.
+                _ => Self::capture_event(
+                    &mut self.events,
+                    &mut self.threads,
+                    &mut self.last_stop,
+                    &mut self.lifecycle,
+                    &mut self.bound_breakpoints,
+                    &v,
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// Buffer events the filter admits, track thread state from `stopped`, `continued`, and
+    /// `thread` events (remembering the latest `stopped` body), record `process`, `exited`,
+    /// and `terminated` in `lifecycle`, and apply `breakpoint` events to the adapter's
+    /// breakpoints. State is tracked whatever the filter says.
+    fn capture_event(
+        events: &mut EventBuffer,
+        threads: &mut BTreeMap<i64, ThreadState>,
+        last_stop: &mut Option<Value>,
+        lifecycle: &mut Lifecycle,
+        breakpoints: &mut BTreeMap<String, Vec<Value>>,
+        msg: &Value,
+    ) {
+        if msg.get("type").and_then(|x| x.as_str()) != Some("event") {
+            return;
+        }
+        let body = msg.get("body");
+        let field = |key: &str| body.and_then(|b| b.get(key));
+        let text = |key: &str| field(key).and_then(|v| v.as_str());
+        let thread_id = field("threadId").and_then(|v| v.as_i64());
+        let Some(event) = msg.get("event").and_then(|x| x.as_str()) else {
+            return;
+        };
+        events.push(event, body);
+        match event {
+            "stopped" => {
+                let all = field("allThreadsStopped")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(false);
+                if let Some(id) = thread_id {
+                    threads.entry(id).or_default();
+                }
+                for (id, thread) in threads.iter_mut() {
+                    if all || Some(*id) == thread_id {
+                        thread.set("stopped", text("reason"), text("description"));
+                    }
+                }
+                *last_stop = body.cloned();
+            }
+            "continued" => {
+                // A missing `allThreadsContinued` means every thread resumed.
+                let all = field("allThreadsContinued")
+                    .and_then(|v| v.as_bool())
+                    .unwrap_or(true);
+                Self::mark_running(threads, thread_id, all);
+            }
+            "process" => {
+                lifecycle.process = body.cloned();
+            }
+            "exited" => {
+                lifecycle.exit_code = field("exitCode").and_then(|v| v.as_i64());
+            }
+            "terminated" => {
+                lifecycle.terminated = true;
+            }
+            "breakpoint" => {
+                if let Some(update) = field("breakpoint") {
+                    Self::apply_breakpoint_event(breakpoints, text("reason"), update);
+                }
+            }
+            "thread" => {
+                if let Some(id) = thread_id {
+                    match text("reason") {
+                        Some("exited") => {
+                            threads.remove(&id);
+                        }
+                        reason => threads.entry(id).or_default().set("running", reason, None),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Apply one `breakpoint` event: `removed` drops the breakpoint with the same `id`, and
+    /// `changed`/`new` merge the reported fields into it (adding it when the id is unknown),
+    /// moving it when its `source.path` changed. Updated entries carry the event `reason` as
+    /// `lastEvent`.
+    fn apply_breakpoint_event(
+        breakpoints: &mut BTreeMap<String, Vec<Value>>,
+        reason: Option<&str>,
+        update: &Value,
+    ) {
+        let id = update.get("id").and_then(|v| v.as_i64());
+        let existing = id.and_then(|id| {
+            breakpoints.iter().find_map(|(path, list)| {
+                list.iter()
+                    .position(|bp| bp.get("id").and_then(|v| v.as_i64()) == Some(id))
+                    .map(|index| (path.clone(), index))
+            })
+        });
+        let mut entry = json!({});
+        if let Some((path, index)) = &existing {
+            if let Some(list) = breakpoints.get_mut(path) {
+                entry = list.remove(*index);
+                if list.is_empty() {
+                    breakpoints.remove(path);
                 }
             }
         }
+        if reason == Some("removed") {
+            return;
+        }
+        if let (Some(obj), Some(fields)) = (entry.as_object_mut(), update.as_object()) {
+            for (key, value) in fields {
+                obj.insert(key.clone(), value.clone());
+            }
+            obj.insert("lastEvent".into(), json!(reason.unwrap_or("changed")));
+        }
+        let new_path = update
+            .pointer("/source/path")
+            .and_then(|p| p.as_str())
+            .map(str::to_string);
+        match (existing, new_path) {
+            // Same source: put it back where it was so the listing keeps the request order.
+            (Some((path, index)), new_path) if new_path.as_ref().is_none_or(|p| *p == path) => {
+                let list = breakpoints.entry(path).or_default();
+                list.insert(index.min(list.len()), entry);
+            }
+            (_, new_path) => breakpoints
+                .entry(new_path.unwrap_or_default())
+                .or_default()
+                .push(entry),
+        }
+    }
+
+    fn mark_running(threads: &mut BTreeMap<i64, ThreadState>, thread_id: Option<i64>, all: bool) {
+        if let Some(id) = thread_id {
+            threads.entry(id).or_default();
+        }
+        for (id, thread) in threads.iter_mut() {
+            if all || Some(*id) == thread_id {
+                thread.set("running", None, None);
+            }
+        }
+    }
+
+    /// Refresh the thread list with a `threads` request (which also drains any pending
+    /// events) and report each thread's last known state. Threads the adapter no longer lists
+    /// are dropped; threads seen only in the listing report `unknown`.
+    pub fn thread_states(&mut self, adapter_cmd: Option<&str>) -> Result<Vec<Value>> {
+        let listing = self.request("threads", json!({}), adapter_cmd)?;
+        let listed: Vec<(i64, Option<String>)> = listing
+            .get("threads")
+            .and_then(|t| t.as_array())
+            .map(|items| {
+                items
+                    .iter()
+                    .filter_map(|t| {
+                        let id = t.get("id")?.as_i64()?;
+                        let name = t.get("name").and_then(|n| n.as_str()).map(str::to_string);
+                        Some((id, name))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.threads
+            .retain(|id, _| listed.iter().any(|(listed_id, _)| listed_id == id));
+        for (id, name) in listed {
+            let thread = self.threads.entry(id).or_insert_with(|| ThreadState {
+                state: "unknown",
+                ..ThreadState::default()
+            });
+            thread.name = name;
+        }
+        Ok(self
+            .threads
+            .iter()
+            .map(|(id, t)| {
+                json!({
+                    "id": id,
+                    "name": t.name,
+                    "state": t.state,
+                    "reason": t.reason,
+                    "description": t.description
+                })
+            })
+            .collect())
+    }
+
+    /// `variables` for `arguments`, with nested `variablesReference`s expanded up to `depth`
+    /// levels into `children` arrays. Variables left unexpanded at the depth limit carry
+    /// `truncatedAtDepth`; references already expanded elsewhere in the tree carry `cycle`.
+    pub fn variables_expanded(
+        &mut self,
+        arguments: Value,
+        depth: u32,
+        adapter_cmd: Option<&str>,
+    ) -> Result<Value> {
+        let mut seen = HashSet::new();
+        if let Some(root) = arguments.get("variablesReference").and_then(|x| x.as_i64()) {
+            seen.insert(root);
+        }
+        let mut response = self.request("variables", arguments, adapter_cmd)?;
+        if let Some(vars) = response.get_mut("variables").and_then(|v| v.as_array_mut()) {
+            self.expand_variables(vars, 1, depth, &mut seen, adapter_cmd)?;
+        }
+        Ok(response)
+    }
+
+    fn expand_variables(
+        &mut self,
+        vars: &mut [Value],
+        level: u32,
+        depth: u32,
+        seen: &mut HashSet<i64>,
+        adapter_cmd: Option<&str>,
+    ) -> Result<()> {
+        for var in vars {
+            let reference = var
+                .get("variablesReference")
+                .and_then(|x| x.as_i64())
+                .unwrap_or(0);
+            if reference <= 0 {
+                continue;
+            }
+            if level > depth {
+                var["truncatedAtDepth"] = json!(depth);
+                continue;
+            }
+            if !seen.insert(reference) {
+                var["cycle"] = json!(true);
+                continue;
+            }
+            let listing = self.request(
+                "variables",
+                json!({"variablesReference": reference}),
+                adapter_cmd,
+            )?;
+            let mut children = listing
+                .get("variables")
+                .and_then(|v| v.as_array())
+                .cloned()
+                .unwrap_or_default();
+            self.expand_variables(&mut children, level + 1, depth, seen, adapter_cmd)?;
+            var["children"] = Value::Array(children);
+        }
         Ok(())
     }
 
+    /// Render an `evaluate` body (or a variable) as a nested value: leaves become their value
+    /// string and expanded containers an object of child name to rendered child. Children are
+    /// fetched breadth-first until `max_nodes` values are collected; containers left over by
+    /// the cap, or already expanded elsewhere in the tree (cycles), keep their value string.
+    /// Returns `{ value, nodes, truncated }`.
+    pub fn value_tree(
+        &mut self,
+        evaluated: &Value,
+        max_nodes: usize,
+        adapter_cmd: Option<&str>,
+    ) -> Result<Value> {
+        build_value_tree(evaluated, max_nodes, |reference| {
+            self.request(
+                "variables",
+                json!({"variablesReference": reference}),
+                adapter_cmd,
+            )
+        })
+    }
+
+    /// Page through `stackTrace` for `thread_id` from `start_frame`, `page` frames at a time,
+    /// until the adapter's `totalFrames` is reached, a short page arrives, or `cap` frames are
+    /// collected. Returns `{ stackFrames, totalFrames, truncated }`.
+    pub fn stack_trace_all(
+        &mut self,
+        thread_id: i64,
+        start_frame: u64,
+        page: u64,
+        cap: usize,
+        adapter_cmd: Option<&str>,
+    ) -> Result<Value> {
+        let mut frames: Vec<Value> = Vec::new();
+        let mut total_frames: Option<u64> = None;
+        let mut next = start_frame;
+        let truncated = loop {
+            let levels = page.min((cap - frames.len()) as u64);
+            let body = self.request(
+                "stackTrace",
+                json!({"threadId": thread_id, "startFrame": next, "levels": levels}),
+                adapter_cmd,
+            )?;
+            if let Some(total) = body.get("totalFrames").and_then(|x| x.as_u64()) {
+                total_frames = Some(total);
+            }
+            let batch = body
+                .get("stackFrames")
+                .and_then(|f| f.as_array())
+                .cloned()
+                .unwrap_or_default();
+            let received = batch.len() as u64;
+            frames.extend(batch);
+            next += received;
+            if received < levels || total_frames.is_some_and(|total| next >= total) {
+                break false;
+            }
+            if frames.len() >= cap {
+                break true;
+            }
+        };
+        Ok(json!({
+            "stackFrames": frames,
+            "totalFrames": total_frames,
+            "truncated": truncated
+        }))
+    }
+
+    /// Summarize the current stop of `thread_id` (default: the thread of the latest `stopped`
+    /// event): reason, top frame from `stackTrace`, and `exceptionInfo` for exception stops.
+    /// `severity` is `error` for unhandled exceptions, `warning` for other exceptions, and
+    /// `info` for breakpoints, steps, pauses and the like.
+    pub fn stop_summary(
+        &mut self,
+        thread_id: Option<i64>,
+        adapter_cmd: Option<&str>,
+    ) -> Result<Value> {
+        // Drains pending events so the stop state is current.
+        self.thread_states(adapter_cmd)?;
+        let last_stop = self.last_stop.clone().unwrap_or_else(|| json!({}));
+        let thread_id = thread_id
+            .or_else(|| last_stop.get("threadId").and_then(|x| x.as_i64()))
+            .or_else(|| {
+                self.threads
+                    .iter()
+                    .find(|(_, t)| t.state == "stopped")
+                    .map(|(id, _)| *id)
+            })
+            .ok_or_else(|| anyhow!("no current stop: no stopped thread is known"))?;
+        let thread = self
+            .threads
+            .get(&thread_id)
+            .filter(|t| t.state == "stopped")
+            .cloned()
+            .ok_or_else(|| anyhow!("thread {thread_id} is not stopped"))?;
+        // Event-only details apply when the latest stop covered this thread.
+        let event = if last_stop.get("threadId").and_then(|x| x.as_i64()) == Some(thread_id)
+            || last_stop.get("allThreadsStopped").and_then(|x| x.as_bool()) == Some(true)
+        {
+            last_stop
+        } else {
+            json!({})
+        };
+
+        let (top_frame, stack_error) = match self.request(
+            "stackTrace",
+            json!({"threadId": thread_id, "startFrame": 0, "levels": 1}),
+            adapter_cmd,
+        ) {
+            Ok(body) => {
+                let mut frames: Vec<Value> = body
+                    .get("stackFrames")
+                    .and_then(|f| f.as_array())
+                    .map(|f| f.iter().take(1).cloned().collect())
+                    .unwrap_or_default();
+                self.resolve_frame_sources(&mut frames, adapter_cmd);
+                let frame = frames.first().map(|f| {
+                    let source = f.get("source");
+                    json!({
+                        "id": f.get("id"),
+                        "name": f.get("name"),
+                        "path": source.and_then(|s| s.get("path")),
+                        "sourceReference": source.and_then(|s| s.get("sourceReference")),
+                        "sourceContent": source.and_then(|s| s.get("content")),
+                        "line": f.get("line"),
+                        "column": f.get("column")
+                    })
+                });
+                (frame, None)
+            }
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        let reason = thread.reason.clone().unwrap_or_default();
+        let is_exception = reason == "exception";
+        let supports_exception_info = self
+            .capabilities
+            .as_ref()
+            .and_then(|c| c.get("supportsExceptionInfoRequest"))
+            .and_then(|x| x.as_bool())
+            .unwrap_or(false);
+        let exception_info = if is_exception && supports_exception_info {
+            self.request("exceptionInfo", json!({"threadId": thread_id}), adapter_cmd)
+                .ok()
+        } else {
+            None
+        };
+        let severity = match exception_info
+            .as_ref()
+            .and_then(|info| info.get("breakMode"))
+            .and_then(|x| x.as_str())
+        {
+            _ if !is_exception => "info",
+            Some("unhandled") | Some("userUnhandled") => "error",
+            _ => "warning",
+        };
+
+        Ok(json!({
+            "threadId": thread_id,
+            "threadName": thread.name,
+            "reason": reason,
+            "description": thread.description,
+            "text": event.get("text"),
+            "hitBreakpointIds": event.get("hitBreakpointIds"),
+            "allThreadsStopped": event.get("allThreadsStopped").and_then(|x| x.as_bool()).unwrap_or(false),
+            "isException": is_exception,
+            "severity": severity,
+            "topFrame": top_frame,
+            "stackTraceError": stack_error,
+            "exceptionInfo": exception_info
+        }))
+    }
+
+    /// Content of a source that has no on-disk path, via the `source` request. Responses are
+    /// cached by `source_reference` (when non-zero) for the rest of the session. Returns the
+    /// response body and whether it came from the cache.
+    pub fn source(
+        &mut self,
+        source: Option<Value>,
+        source_reference: i64,
+        adapter_cmd: Option<&str>,
+    ) -> Result<(Value, bool)> {
+        if let Some(body) = self.sources.get(&source_reference) {
+            return Ok((body.clone(), true));
+        }
+        let mut arguments = json!({"sourceReference": source_reference});
+        if let Some(source) = source {
+            arguments["source"] = source;
+        }
+        let body = self.request("source", arguments, adapter_cmd)?;
+        if source_reference > 0 {
+            self.sources.insert(source_reference, body.clone());
+        }
+        Ok((body, false))
+    }
+
+    /// Attach `content` (and `mimeType`) to the `source` of frames that carry a
+    /// `sourceReference` but no `path`. A failed fetch sets `contentError` on that source only.
+    pub fn resolve_frame_sources(&mut self, frames: &mut [Value], adapter_cmd: Option<&str>) {
+        for frame in frames {
+            let Some(source) = frame.get_mut("source").filter(|s| s.is_object()) else {
+                continue;
+            };
+            let reference = source
+                .get("sourceReference")
+                .and_then(|x| x.as_i64())
+                .unwrap_or(0);
+            if reference <= 0 || source.get("path").is_some_and(|p| p.is_string()) {
+                continue;
+            }
+            match self.source(Some(source.clone()), reference, adapter_cmd) {
+                Ok((body, _)) => {
+                    source["content"] = body.get("content").cloned().unwrap_or(Value::Null);
+                    if let Some(mime) = body.get("mimeType") {
+                        source["mimeType"] = mime.clone();
+                    }
+                }
+                Err(e) => source["contentError"] = json!(e.to_string()),
+            }
+        }
+    }
+
+    /// Evaluate each expression with context `watch`, in order, reporting failures per entry.
+    /// Without `frame_id` the selected frame, else the top frame of the current stop is used;
+    /// when nothing is stopped the expressions are evaluated without a frame (global scope).
+    pub fn watch(
+        &mut self,
+        expressions: &[String],
+        frame_id: Option<i64>,
+        adapter_cmd: Option<&str>,
+    ) -> Result<Value> {
+        let frame_id = match frame_id {
+            Some(id) => Some(id),
+            None => self.current_frame_id(adapter_cmd)?,
+        };
+        let watches: Vec<Value> = expressions
+            .iter()
+            .map(|expression| {
+                let mut args = json!({"expression": expression, "context": "watch"});
+                if let Some(id) = frame_id {
+                    args["frameId"] = json!(id);
+                }
+                match self.request("evaluate", args, adapter_cmd) {
+                    Ok(body) => json!({
+                        "expression": expression,
+                        "value": body.get("result"),
+                        "type": body.get("type"),
+                        "variablesReference": body.get("variablesReference"),
+                        "error": null
+                    }),
+                    Err(e) => json!({
+                        "expression": expression,
+                        "value": null,
+                        "type": null,
+                        "variablesReference": null,
+                        "error": e.to_string()
+                    }),
+                }
+            })
+            .collect();
+        Ok(json!({"frameId": frame_id, "watches": watches}))
+    }
+
+    /// Look up `name` in a frame: the frame's scopes are searched in order (expensive scopes
+    /// last) for the first segment, and each further dotted segment (`obj.field`, `items.0`)
+    /// is looked up among the children of the previous match. When that finds nothing, `name`
+    /// is evaluated with context `watch`. The frame is `frame_id`, else the selected frame of
+    /// `thread_id` or its top frame, else the selected frame or the top frame of the current
+    /// stop.
+    pub fn get_variable(
+        &mut self,
+        name: &str,
+        frame_id: Option<i64>,
+        thread_id: Option<i64>,
+        adapter_cmd: Option<&str>,
+    ) -> Result<Value> {
+        let frame_id = match (frame_id, thread_id) {
+            (Some(id), _) => id,
+            (None, Some(thread_id)) => match self.selected_frame_in(thread_id) {
+                Some(id) => id,
+                None => {
+                    let body = self.request(
+                        "stackTrace",
+                        json!({"threadId": thread_id, "startFrame": 0, "levels": 1}),
+                        adapter_cmd,
+                    )?;
+                    body.get("stackFrames")
+                        .and_then(|f| f.get(0))
+                        .and_then(|f| f.get("id"))
+                        .and_then(|x| x.as_i64())
+                        .ok_or_else(|| anyhow!("thread {thread_id} has no stack frames"))?
+                }
+            },
+            (None, None) => self
+                .current_frame_id(adapter_cmd)?
+                .ok_or_else(|| anyhow!("no thread is stopped; pass threadId or frameId"))?,
+        };
+        let path: Vec<&str> = name.split('.').map(str::trim).collect();
+        let scopes = self.request("scopes", json!({"frameId": frame_id}), adapter_cmd)?;
+        let mut scopes = scopes
+            .get("scopes")
+            .and_then(|s| s.as_array())
+            .cloned()
+            .unwrap_or_default();
+        scopes.sort_by_key(|s| s.get("expensive").and_then(|e| e.as_bool()) == Some(true));
+        let mut searched = Vec::new();
+        for scope in &scopes {
+            let reference = scope
+                .get("variablesReference")
+                .and_then(|x| x.as_i64())
+                .unwrap_or(0);
+            let scope_name = scope.get("name").and_then(|n| n.as_str()).unwrap_or("");
+            searched.push(scope_name.to_string());
+            if let Some(var) = self.find_in_variables(reference, &path, adapter_cmd)? {
+                return Ok(json!({
+                    "name": name,
+                    "frameId": frame_id,
+                    "source": "scopes",
+                    "scope": scope_name,
+                    "value": var.get("value"),
+                    "type": var.get("type"),
+                    "variablesReference": var.get("variablesReference").cloned().unwrap_or(json!(0)),
+                    "evaluateName": var.get("evaluateName"),
+                }));
+            }
+        }
+        let args = json!({"expression": name, "context": "watch", "frameId": frame_id});
+        match self.request("evaluate", args, adapter_cmd) {
+            Ok(body) => Ok(json!({
+                "name": name,
+                "frameId": frame_id,
+                "source": "evaluate",
+                "scope": null,
+                "value": body.get("result"),
+                "type": body.get("type"),
+                "variablesReference": body.get("variablesReference").cloned().unwrap_or(json!(0)),
+                "evaluateName": name,
+            })),
+            Err(e) => Err(anyhow!(
+                "'{name}' not found in scopes [{}] of frame {frame_id}, and evaluating it failed: {e}",
+                searched.join(", ")
+            )),
+        }
+    }
+
+    /// Follow `path` from the variables of `reference`: the first segment names a variable,
+    /// each later one a child of the previous match. Index children match with or without
+    /// brackets (`0` finds `[0]`).
+    fn find_in_variables(
+        &mut self,
+        mut reference: i64,
+        path: &[&str],
+        adapter_cmd: Option<&str>,
+    ) -> Result<Option<Value>> {
+        let mut found = None;
+        for segment in path {
+            if reference <= 0 {
+                return Ok(None);
+            }
+            let listing = self.request(
+                "variables",
+                json!({"variablesReference": reference}),
+                adapter_cmd,
+            )?;
+            let bracketed = format!("[{segment}]");
+            let var = listing
+                .get("variables")
+                .and_then(|v| v.as_array())
+                .and_then(|vars| {
+                    vars.iter().find(|v| {
+                        v.get("name")
+                            .and_then(|n| n.as_str())
+                            .is_some_and(|n| n == *segment || n == bracketed)
+                    })
+                })
+                .cloned();
+            let Some(var) = var else {
+                return Ok(None);
+            };
+            reference = var
+                .get("variablesReference")
+                .and_then(|x| x.as_i64())
+                .unwrap_or(0);
+            found = Some(var);
+        }
+        Ok(found)
+    }
+
+    /// Make a frame of a stopped thread (default: the thread of the current stop) the default
+    /// for `evaluate`, `scopes`, and variable lookups: `frame_id` if given, else the frame at
+    /// `level` (default 0, the top). The choice is dropped when the thread resumes or stops
+    /// again.
+    pub fn select_frame(
+        &mut self,
+        thread_id: Option<i64>,
+        frame_id: Option<i64>,
+        level: Option<usize>,
+        adapter_cmd: Option<&str>,
+    ) -> Result<Value> {
+        let thread_id = match thread_id {
+            Some(id) => id,
+            None => self
+                .current_thread_id(adapter_cmd)?
+                .ok_or_else(|| anyhow!("no thread is stopped; pass threadId"))?,
+        };
+        let body = self.request("stackTrace", json!({"threadId": thread_id}), adapter_cmd)?;
+        let frames = body
+            .get("stackFrames")
+            .and_then(|f| f.as_array())
+            .cloned()
+            .unwrap_or_default();
+        let id_of = |frame: &Value| frame.get("id").and_then(|x| x.as_i64());
+        let index = match frame_id {
+            Some(id) => frames.iter().position(|f| id_of(f) == Some(id)),
+            None => Some(level.unwrap_or(0)).filter(|&i| i < frames.len()),
+        };
+        let Some(index) = index else {
+            let ids: Vec<i64> = frames.iter().filter_map(id_of).collect();
+            let wanted = match frame_id {
+                Some(id) => format!("frame {id}"),
+                None => format!("level {}", level.unwrap_or(0)),
+            };
+            return Err(anyhow!(
+                "{wanted} is not on the stack of thread {thread_id} (frames {ids:?})"
+            ));
+        };
+        let frame = frames[index].clone();
+        self.threads.entry(thread_id).or_default().selected_frame = Some(frame.clone());
+        self.selected_thread = Some(thread_id);
+        Ok(json!({
+            "threadId": thread_id,
+            "frameId": id_of(&frame),
+            "level": index,
+            "frame": frame
+        }))
+    }
+
+    /// Forget the selected frame; returns whether one was selected.
+    pub fn clear_frame_selection(&mut self) -> bool {
+        let had = self.selected_frame().is_some();
+        if let Some(thread) = self
+            .selected_thread
+            .take()
+            .and_then(|id| self.threads.get_mut(&id))
+        {
+            thread.selected_frame = None;
+        }
+        had
+    }
+
+    /// The frame picked with `dap_select_frame` and its thread, while that stop lasts.
+    fn selected_frame(&self) -> Option<(i64, &Value)> {
+        let thread_id = self.selected_thread?;
+        let frame = self.threads.get(&thread_id)?.selected_frame.as_ref()?;
+        Some((thread_id, frame))
+    }
+
+    /// Id of the selected frame, if one is selected and its thread has not moved on.
+    pub fn selected_frame_id(&self) -> Option<i64> {
+        self.selected_frame()
+            .and_then(|(_, frame)| frame.get("id"))
+            .and_then(|x| x.as_i64())
+    }
+
+    /// Id of the selected frame when it belongs to `thread_id`.
+    fn selected_frame_in(&self, thread_id: i64) -> Option<i64> {
+        self.selected_frame()
+            .filter(|(id, _)| *id == thread_id)
+            .and_then(|(_, frame)| frame.get("id"))
+            .and_then(|x| x.as_i64())
+    }
+
+    /// Thread the latest stop reported, or any stopped thread, after refreshing thread states.
+    fn current_thread_id(&mut self, adapter_cmd: Option<&str>) -> Result<Option<i64>> {
+        self.thread_states(adapter_cmd)?;
+        let stopped = |id: &i64| self.threads.get(id).is_some_and(|t| t.state == "stopped");
+        Ok(self
+            .last_stop
+            .as_ref()
+            .and_then(|stop| stop.get("threadId"))
+            .and_then(|x| x.as_i64())
+            .filter(stopped)
+            .or_else(|| {
+                self.threads
+                    .iter()
+                    .find(|(_, t)| t.state == "stopped")
+                    .map(|(id, _)| *id)
+            }))
+    }
+
+    /// Id of the selected frame, else of the top frame of the thread the latest stop reported
+    /// (or any stopped thread).
+    fn current_frame_id(&mut self, adapter_cmd: Option<&str>) -> Result<Option<i64>> {
+        if let Some(id) = self.selected_frame_id() {
+            return Ok(Some(id));
+        }
+        let Some(thread_id) = self.current_thread_id(adapter_cmd)? else {
+            return Ok(None);
+        };
+        let body = self.request(
+            "stackTrace",
+            json!({"threadId": thread_id, "startFrame": 0, "levels": 1}),
+            adapter_cmd,
+        )?;
+        Ok(body
+            .get("stackFrames")
+            .and_then(|f| f.get(0))
+            .and_then(|f| f.get("id"))
+            .and_then(|x| x.as_i64()))
+    }
+
+    /// Consolidated session snapshot: debuggee process, thread states (refreshed with a
+    /// `threads` request while the session is live), the latest stop, the most recent
+    /// `output_limit` output events, and exit/termination.
+    pub fn status(&mut self, output_limit: usize, adapter_cmd: Option<&str>) -> Value {
+        let adapter_running = self.adapter_alive();
+        let (threads, threads_error) = if adapter_running && !self.lifecycle.terminated {
+            match self.thread_states(adapter_cmd) {
+                Ok(threads) => (threads, None),
+                Err(e) => (Vec::new(), Some(e.to_string())),
+            }
+        } else {
+            (Vec::new(), None)
+        };
+        let stopped = threads.iter().any(|t| t["state"] == "stopped");
+        json!({
+            "adapterRunning": adapter_running,
+            "process": self.lifecycle.process,
+            "threads": threads,
+            "threadsError": threads_error,
+            "lastStop": if stopped { self.last_stop.clone() } else { None },
+            "selectedFrame": self.selected_frame().map(|(thread_id, frame)| json!({
+                "threadId": thread_id,
+                "frameId": frame.get("id"),
+                "frame": frame
+            })),
+            "breakpoints": self.breakpoint_report(None),
+            "output": {
+                "buffered": self.events.output.len(),
+                "recent": self.output_events(None, Some(output_limit))
+            },
+            "events": {
+                "buffered": self.events.other.len(),
+                "recent": self.recent_events(output_limit)
+            },
+            "eventFilter": self.events.filter.to_json(),
+            "exitCode": self.lifecycle.exit_code,
+            "exitSignal": self.exit_signal(),
+            "terminated": self.lifecycle.terminated
+        })
+    }
+
+    /// Breakpoints as the adapter last reported them, for every source or only `path`. While
+    /// the session is live a `threads` round trip first drains `breakpoint` events the adapter
+    /// sent since the last request. Never starts the adapter.
+    pub fn list_breakpoints(&mut self, path: Option<&str>, adapter_cmd: Option<&str>) -> Value {
+        if self.adapter_alive() && !self.lifecycle.terminated {
+            let _ = self.request("threads", json!({}), adapter_cmd);
+        }
+        self.breakpoint_report(path)
+    }
+
+    /// `{ sources: [{ path, breakpoints }], total, verified, unverified, relocated }` from the
+    /// tracked adapter breakpoints. `relocated` counts breakpoints whose `line` differs from
+    /// the line they were requested on.
+    fn breakpoint_report(&self, path: Option<&str>) -> Value {
+        let sources: Vec<(&String, &Vec<Value>)> = self
+            .bound_breakpoints
+            .iter()
+            .filter(|(p, _)| path.is_none_or(|wanted| wanted == p.as_str()))
+            .collect();
+        let all = || sources.iter().flat_map(|(_, list)| list.iter());
+        let verified = all()
+            .filter(|bp| bp.get("verified").and_then(|v| v.as_bool()) == Some(true))
+            .count();
+        let relocated = all()
+            .filter(|bp| match (bp.get("line"), bp.get("requestedLine")) {
+                (Some(line), Some(requested)) => line != requested,
+                _ => false,
+            })
+            .count();
+        let total = all().count();
+        json!({
+            "sources": sources
+                .iter()
+                .map(|(p, list)| json!({
+                    "path": (!p.is_empty()).then_some(p),
+                    "breakpoints": list
+                }))
+                .collect::<Vec<_>>(),
+            "total": total,
+            "verified": verified,
+            "unverified": total - verified,
+            "relocated": relocated
+        })
+    }
+
+    /// Signal number behind a negative `exitCode`, the convention of adapters that report a
+    /// signal-killed debuggee as `-signal` (e.g. debugpy).
+    fn exit_signal(&self) -> Option<i64> {
+        self.lifecycle
+            .exit_code
+            .filter(|code| *code < 0)
+            .map(|code| -code)
+    }
+
+    /// How the debuggee ended, from the `exited` and `terminated` events of the current
+    /// session. Never starts the adapter.
+    pub fn exit_status(&self) -> Value {
+        json!({
+            "exited": self.lifecycle.exit_code.is_some(),
+            "exitCode": self.lifecycle.exit_code,
+            "signal": self.exit_signal(),
+            "terminated": self.lifecycle.terminated,
+            "process": self.lifecycle.process
+        })
+    }
+
+    /// Buffered `output` event bodies in arrival order, optionally filtered by category and
+    /// truncated to the most recent `limit` entries.
+    pub fn output_events(&self, category: Option<&str>, limit: Option<usize>) -> Vec<Value> {
+        let matching: Vec<Value> = self
+            .events
+            .output
+            .iter()
+            .filter(|body| {
+                category.is_none() || body.get("category").and_then(|c| c.as_str()) == category
+            })
+            .cloned()
+            .collect();
+        let skip = limit.map_or(0, |n| matching.len().saturating_sub(n));
+        matching.into_iter().skip(skip).collect()
+    }
+
+    /// The most recent `limit` buffered non-`output` events, as `{ event, body }`.
+    fn recent_events(&self, limit: usize) -> Vec<Value> {
+        let skip = self.events.other.len().saturating_sub(limit);
+        self.events.other.iter().skip(skip).cloned().collect()
+    }
+
+    /// Update which events are buffered and return the resulting filter. `allow` replaces the
+    /// allow list (`Some(None)` admits every event), `deny` replaces the deny list, and `reset`
+    /// restores the default before either is applied. Already buffered events are kept.
+    pub fn set_event_filter(
+        &mut self,
+        allow: Option<Option<Vec<String>>>,
+        deny: Option<Vec<String>>,
+        reset: bool,
+    ) -> Value {
+        let filter = &mut self.events.filter;
+        if reset {
+            *filter = EventFilter::default();
+        }
+        if let Some(allow) = allow {
+            filter.allow = allow.map(|names| names.into_iter().collect());
+        }
+        if let Some(deny) = deny {
+            filter.deny = deny.into_iter().collect();
+        }
+        filter.to_json()
+    }
+
+    /// Apply `DAP_ADAPTER_CWD` (working directory) and `DAP_ADAPTER_ENV` (JSON object of
+    /// variables; `null` removes one) to the adapter command.
+    fn apply_adapter_environment(command: &mut Command) -> Result<()> {
+        if let Some(cwd) = std::env::var("DAP_ADAPTER_CWD")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+        {
+            let dir = std::path::PathBuf::from(&cwd);
+            if !dir.is_dir() {
+                return Err(anyhow!("DAP_ADAPTER_CWD is not a directory: {cwd}"));
+            }
+            command.current_dir(dir);
+        }
+        if let Some(raw) = std::env::var("DAP_ADAPTER_ENV")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+        {
+            let vars: Value = serde_json::from_str(&raw).context("parse DAP_ADAPTER_ENV")?;
+            let vars = vars
+                .as_object()
+                .ok_or_else(|| anyhow!("DAP_ADAPTER_ENV must be a JSON object"))?;
+            for (key, value) in vars {
+                match value {
+                    Value::Null => {
+                        command.env_remove(key);
+                    }
+                    Value::String(s) => {
+                        command.env(key, s);
+                    }
+                    Value::Number(_) | Value::Bool(_) => {
+                        command.env(key, value.to_string());
+                    }
+                    _ => {
+                        return Err(anyhow!(
+                            "DAP_ADAPTER_ENV value for {key} must be a string, number, boolean, or null"
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Arguments for the DAP `initialize` request: built-in defaults, deep-merged with the
+    /// `DAP_INIT_ARGS` JSON object, then with overrides from `set_initialize_overrides`.
+    /// The defaults advertise variable types, paging, and memory references, which the
+    /// variables tools pass through; set any of them to `false` for adapters that misbehave.
+    fn initialize_arguments(&self) -> Result<Value> {
+        let mut arguments = json!({
+            "clientID": "mcp-dap",
+            "adapterID": "mcp-dap",
+            "pathFormat": "path",
+            "linesStartAt1": true,
+            "columnsStartAt1": true,
+            "supportsRunInTerminalRequest": false,
+            "supportsVariableType": true,
+            "supportsVariablePaging": true,
+            "supportsMemoryReferences": true
+        });
+        if let Some(raw) = std::env::var("DAP_INIT_ARGS")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+        {
+            let env_args: Value = serde_json::from_str(&raw).context("parse DAP_INIT_ARGS")?;
+            if !env_args.is_object() {
+                return Err(anyhow!("DAP_INIT_ARGS must be a JSON object"));
+            }
+            deep_merge(&mut arguments, &env_args);
+        }
+        if let Some(overrides) = &self.init_overrides {
+            deep_merge(&mut arguments, overrides);
+        }
+        Ok(arguments)
+    }
+
+    /// Adapter command a request would use: the per-call override, else `DAP_ADAPTER_CMD`.
+    pub fn adapter_command<'a>(&'a self, override_cmd: Option<&'a str>) -> Option<&'a str> {
+        override_cmd.or(self.cmd.as_deref())
+    }
+
+    /// Override `initialize` arguments for the next adapter start. If the adapter is already
+    /// running it is stopped so the next request re-initializes with the new arguments.
+    pub fn set_initialize_overrides(&mut self, overrides: Value) {
+        self.init_overrides = Some(overrides);
+        if self.child.is_some() {
+            self.stop();
+        }
+    }
+
+    /// Terminate the adapter process and reset per-session state.
+    fn stop(&mut self) {
+        *self.shared.stdin.lock().unwrap() = None;
+        self.stdout = None;
+        if let Some(mut child) = self.child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        self.capabilities = None;
+        self.events.clear();
+        self.threads.clear();
+        self.last_stop = None;
+        self.selected_thread = None;
+        self.lifecycle = Lifecycle::default();
+        self.sources.clear();
+        self.source_breakpoints.clear();
+        self.bound_breakpoints.clear();
+        self.shared.next_seq.store(1, Ordering::SeqCst);
+    }
+
+    /// End the session for good: ask a running adapter to `disconnect` with
+    /// `terminateDebuggee: true`, waiting at most `DISCONNECT_TIMEOUT` for the answer, then kill
+    /// and reap the adapter whatever it answered and reset per-session state. An adapter that is
+    /// not running is not started.
+    pub fn shutdown(&mut self) -> Value {
+        let was_running = self.adapter_alive();
+        let (disconnect, disconnect_error) = if was_running {
+            match self.disconnect_within(DISCONNECT_TIMEOUT) {
+                Ok(body) => (body, Value::Null),
+                Err(e) => (Value::Null, json!(e.to_string())),
+            }
+        } else {
+            (Value::Null, Value::Null)
+        };
+        let had_process = self.child.is_some();
+        self.stop();
+        json!({
+            "adapterRunning": was_running,
+            "adapterReaped": had_process,
+            "disconnect": disconnect,
+            "disconnectError": disconnect_error
+        })
+    }
+
+    /// Send `disconnect` and wait up to `timeout` for its response. The reply is read on a
+    /// helper thread that takes over stdout, so the session must be stopped afterwards; killing
+    /// the adapter ends a read that is still blocked.
+    fn disconnect_within(&mut self, timeout: Duration) -> Result<Value> {
+        let seq = self.alloc_seq();
+        self.shared.send(&json!({
+            "seq": seq,
+            "type": "request",
+            "command": "disconnect",
+            "arguments": {"terminateDebuggee": true}
+        }))?;
+        let mut stdout = self
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("adapter stdout closed"))?;
+        let trace_file = self.trace_file.clone();
+        let (tx, rx) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let reply = loop {
+                let message = Self::read_content_length(&mut stdout, trace_file.as_deref())
+                    .and_then(|body| {
+                        serde_json::from_str::<Value>(&body).context("parse dap message")
+                    });
+                match message {
+                    Ok(v)
+                        if v.get("type").and_then(|x| x.as_str()) == Some("response")
+                            && v.get("request_seq").and_then(|x| x.as_i64()) == Some(seq) =>
+                    {
+                        break Ok(v)
+                    }
+                    Ok(_) => continue,
+                    Err(e) => break Err(e),
+                }
+            };
+            let _ = tx.send(reply);
+        });
+        let v = rx.recv_timeout(timeout).map_err(|_| {
+            anyhow!(
+                "adapter did not answer disconnect within {}s",
+                timeout.as_secs()
+            )
+        })??;
+        if v.get("success").and_then(|x| x.as_bool()).unwrap_or(true) {
+            Ok(v.get("body").cloned().unwrap_or_else(|| json!({})))
+        } else {
+            let msg = v
+                .get("message")
+                .and_then(|x| x.as_str())
+                .unwrap_or("dap error");
+            Err(anyhow!("{}", msg))
+        }
+    }
+
+    /// Whether the adapter process is running (reaping it if it has exited).
+    fn adapter_alive(&mut self) -> bool {
+        self.child
+            .as_mut()
+            .is_some_and(|child| matches!(child.try_wait(), Ok(None)))
+    }
+
     fn alloc_seq(&mut self) -> i64 {
-        let s = self.next_seq;
-        self.next_seq += 1;
-        s
+        self.shared.alloc_seq()
     }
 
     pub fn request(
@@ -137,28 +1515,299 @@ impl DapAdapterManager {
         arguments: Value,
         adapter_cmd: Option<&str>,
     ) -> Result<Value> {
+        let breakpoints = (command == "setBreakpoints").then(|| arguments.clone());
+        let (_, body) = self.request_with_seq(command, arguments, adapter_cmd)?;
+        if let Some(arguments) = breakpoints {
+            self.record_bound_breakpoints(&arguments, &body);
+            self.record_source_breakpoints(arguments);
+        }
+        Ok(body)
+    }
+
+    /// Replace a source's adapter breakpoints with a `setBreakpoints` response, noting on each
+    /// the line it was requested on (responses are in request order) so relocations show.
+    fn record_bound_breakpoints(&mut self, arguments: &Value, body: &Value) {
+        let Some(path) = arguments.pointer("/source/path").and_then(|p| p.as_str()) else {
+            return;
+        };
+        let requested = arguments.get("breakpoints").and_then(|b| b.as_array());
+        let bound: Vec<Value> = body
+            .get("breakpoints")
+            .and_then(|b| b.as_array())
+            .into_iter()
+            .flatten()
+            .enumerate()
+            .map(|(index, bp)| {
+                let mut bp = bp.clone();
+                let line = requested
+                    .and_then(|r| r.get(index))
+                    .and_then(|r| r.get("line"))
+                    .cloned();
+                if let (Some(obj), Some(line)) = (bp.as_object_mut(), line) {
+                    obj.insert("requestedLine".into(), line);
+                }
+                bp
+            })
+            .collect();
+        if bound.is_empty() {
+            self.bound_breakpoints.remove(path);
+        } else {
+            self.bound_breakpoints.insert(path.to_string(), bound);
+        }
+    }
+
+    /// Remember the breakpoints just set for a source; an empty list forgets the source.
+    fn record_source_breakpoints(&mut self, mut arguments: Value) {
+        let Some(path) = arguments
+            .pointer("/source/path")
+            .and_then(|p| p.as_str())
+            .map(str::to_string)
+        else {
+            return;
+        };
+        let empty = arguments
+            .get("breakpoints")
+            .and_then(|b| b.as_array())
+            .is_none_or(|b| b.is_empty());
+        if empty {
+            self.source_breakpoints.remove(&path);
+            return;
+        }
+        // `sourceModified` describes that one request; it must not be repeated on a re-send.
+        if let Some(obj) = arguments.as_object_mut() {
+            obj.remove("sourceModified");
+        }
+        self.source_breakpoints.insert(path, arguments);
+    }
+
+    /// Set (or, with `None`, clear) the `hitCondition` of the breakpoints on `line` of `path`
+    /// and re-send the source's whole tracked breakpoint set, keeping the others unchanged.
+    pub fn set_breakpoint_hit_condition(
+        &mut self,
+        path: &str,
+        line: i64,
+        hit_condition: Option<&str>,
+        adapter_cmd: Option<&str>,
+    ) -> Result<Value> {
+        let mut arguments = self.source_breakpoints.get(path).cloned().ok_or_else(|| {
+            anyhow!("no breakpoints set for {path}; set them with dap_set_breakpoints first")
+        })?;
+        let breakpoints = arguments
+            .get_mut("breakpoints")
+            .and_then(|b| b.as_array_mut())
+            .ok_or_else(|| anyhow!("tracked breakpoints for {path} are not an array"))?;
+        let mut matched = 0;
+        for bp in breakpoints.iter_mut() {
+            if bp.get("line").and_then(|l| l.as_i64()) != Some(line) {
+                continue;
+            }
+            matched += 1;
+            if let Some(obj) = bp.as_object_mut() {
+                match hit_condition {
+                    Some(hc) => obj.insert("hitCondition".into(), json!(hc)),
+                    None => obj.remove("hitCondition"),
+                };
+            }
+        }
+        if matched == 0 {
+            let lines: Vec<i64> = breakpoints
+                .iter()
+                .filter_map(|bp| bp.get("line").and_then(|l| l.as_i64()))
+                .collect();
+            return Err(anyhow!(
+                "no breakpoint on line {line} of {path}; breakpoints are on lines {lines:?}"
+            ));
+        }
+        let body = self.request("setBreakpoints", arguments, adapter_cmd)?;
+        Ok(json!({
+            "source": {"path": path},
+            "line": line,
+            "hitCondition": hit_condition,
+            "updated": matched,
+            "breakpoints": body.get("breakpoints").cloned().unwrap_or_else(|| json!([]))
+        }))
+    }
+
+    /// `request`, also returning the `seq` the request was sent with.
+    fn request_with_seq(
+        &mut self,
+        command: &str,
+        arguments: Value,
+        adapter_cmd: Option<&str>,
+    ) -> Result<(i64, Value)> {
+        if matches!(command, "launch" | "attach") && self.lifecycle.terminated {
+            // The previous session sent `terminated`: start a new adapter instead of issuing
+            // the new session's requests against the ended one.
+            self.stop();
+        }
         self.ensure_started(adapter_cmd)?;
         let seq = self.alloc_seq();
+        let thread_id = arguments.get("threadId").and_then(|x| x.as_i64());
         let req = json!({
             "seq": seq,
             "type": "request",
             "command": command,
             "arguments": arguments
         });
-        let s = serde_json::to_string(&req)?;
-        let w = self.stdin.as_mut().unwrap();
+        self.shared.begin(seq, command);
+        let result = self
+            .shared
+            .send(&req)
+            .and_then(|()| self.await_response(command, seq, thread_id));
+        self.shared.finish();
+        result.map(|body| (seq, body))
+    }
+
+    /// Read adapter messages until a `stopped` event, or `terminated`/`exited` (returning
+    /// `None`). A stop captured while awaiting the resuming request counts. The resuming
+    /// request stays marked in flight, so `dap_cancel` can abandon the wait.
+    fn wait_for_stop(&mut self, command: &str, seq: i64) -> Result<Option<Value>> {
+        self.shared.begin(seq, command);
+        let result = loop {
+            if let Some(stop) = self.last_stop.clone() {
+                break Ok(Some(stop));
+            }
+            if self.lifecycle.terminated || self.lifecycle.exit_code.is_some() {
+                break Ok(None);
+            }
+            let r = self.stdout.as_mut().unwrap();
+            let v = match Self::read_content_length(r, self.trace_file.as_deref())
+                .and_then(|body| serde_json::from_str::<Value>(&body).context("parse dap message"))
+            {
+                Ok(v) => v,
+                Err(e) => break Err(e),
+            };
+            Self::capture_event(
+                &mut self.events,
+                &mut self.threads,
+                &mut self.last_stop,
+                &mut self.lifecycle,
+                &mut self.bound_breakpoints,
+                &v,
+            );
+            if self.shared.answers_cancel(&v) {
+                break Err(anyhow!(
+                    "waiting for a stop after {command} {seq} abandoned by dap_cancel"
+                ));
+            }
+        };
+        self.shared.finish();
+        result
+    }
+
+    /// Continue `thread_id` until `expression`, evaluated in the top frame of each stop, is
+    /// truthy, at most `max_iterations` times. Stops also end the loop when the debuggee
+    /// terminates. Evaluation failures (e.g. a name not in scope at this stop) count as false
+    /// and are reported in `evaluateError`.
+    pub fn continue_until(
+        &mut self,
+        thread_id: i64,
+        expression: &str,
+        max_iterations: u64,
+        adapter_cmd: Option<&str>,
+    ) -> Result<Value> {
+        let mut outcome = json!({
+            "conditionMet": false,
+            "reason": "maxIterations",
+            "iterations": 0,
+            "threadId": thread_id,
+            "frameId": null,
+            "value": null,
+            "evaluateError": null,
+            "stop": null
+        });
+        for iteration in 1..=max_iterations {
+            self.last_stop = None;
+            let (seq, _) =
+                self.request_with_seq("continue", json!({"threadId": thread_id}), adapter_cmd)?;
+            outcome["iterations"] = json!(iteration);
+            for key in ["frameId", "value", "evaluateError", "stop"] {
+                outcome[key] = Value::Null;
+            }
+            let Some(stop) = self.wait_for_stop("continue", seq)? else {
+                outcome["reason"] = json!("terminated");
+                return Ok(outcome);
+            };
+            let stopped_thread = stop
+                .get("threadId")
+                .and_then(|x| x.as_i64())
+                .unwrap_or(thread_id);
+            outcome["threadId"] = json!(stopped_thread);
+            outcome["stop"] = stop;
+            let frame_id = self
+                .request(
+                    "stackTrace",
+                    json!({"threadId": stopped_thread, "startFrame": 0, "levels": 1}),
+                    adapter_cmd,
+                )?
+                .get("stackFrames")
+                .and_then(|f| f.get(0))
+                .and_then(|f| f.get("id"))
+                .and_then(|x| x.as_i64());
+            outcome["frameId"] = json!(frame_id);
+            let mut args = json!({"expression": expression, "context": "watch"});
+            if let Some(id) = frame_id {
+                args["frameId"] = json!(id);
+            }
+            match self.request("evaluate", args, adapter_cmd) {
+                Ok(body) => {
+                    let value = body.get("result").and_then(|x| x.as_str()).unwrap_or("");
+                    outcome["value"] = json!(value);
+                    outcome["evaluateError"] = Value::Null;
+                    if is_truthy(value) {
+                        outcome["conditionMet"] = json!(true);
+                        outcome["reason"] = json!("condition");
+                        return Ok(outcome);
+                    }
+                }
+                Err(e) => {
+                    outcome["value"] = Value::Null;
+                    outcome["evaluateError"] = json!(e.to_string());
+                }
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// Read until the response to `seq`, buffering output events seen along the way. Gives up
+    /// with an error once the adapter answers a `dap_cancel` issued for this request.
+    fn await_response(&mut self, command: &str, seq: i64, thread_id: Option<i64>) -> Result<Value> {
         let r = self.stdout.as_mut().unwrap();
-        Self::write_content_length(w, &s)?;
-        // Read until matching response; ignore events.
         loop {
-            let body = Self::read_content_length(r)?;
+            let body = Self::read_content_length(r, self.trace_file.as_deref())?;
             let v: Value = serde_json::from_str(&body).context("parse dap message")?;
+            Self::capture_event(
+                &mut self.events,
+                &mut self.threads,
+                &mut self.last_stop,
+                &mut self.lifecycle,
+                &mut self.bound_breakpoints,
+                &v,
+            );
+            if self.shared.answers_cancel(&v) {
+                return Err(anyhow!("{command} request {seq} abandoned by dap_cancel"));
+            }
             if v.get("type").and_then(|x| x.as_str()) == Some("response")
                 && v.get("request_seq").and_then(|x| x.as_i64()) == Some(seq)
             {
                 let ok = v.get("success").and_then(|x| x.as_bool()).unwrap_or(true);
                 if ok {
-                    return Ok(v.get("body").cloned().unwrap_or_else(|| json!({})));
+                    let body = v.get("body").cloned().unwrap_or_else(|| json!({}));
+                    // Adapters do not send `continued` for client-initiated resumes.
+                    match command {
+                        "continue" | "reverseContinue" => {
+                            let all = body
+                                .get("allThreadsContinued")
+                                .and_then(|x| x.as_bool())
+                                .unwrap_or(true);
+                            Self::mark_running(&mut self.threads, thread_id, all);
+                        }
+                        "next" | "stepIn" | "stepOut" | "stepBack" => {
+                            Self::mark_running(&mut self.threads, thread_id, false);
+                        }
+                        _ => {}
+                    }
+                    return Ok(body);
                 } else {
                     let msg = v
                         .get("message")
@@ -184,3 +1833,314 @@ impl DapAdapterManager {
         }
     }
 }
+
+/// `value_tree` with `variables` fetching the listing of a `variablesReference`.
+fn build_value_tree(
+    evaluated: &Value,
+    max_nodes: usize,
+    mut variables: impl FnMut(i64) -> Result<Value>,
+) -> Result<Value> {
+    let display = |v: &Value| {
+        v.get("result")
+            .or_else(|| v.get("value"))
+            .cloned()
+            .unwrap_or(Value::Null)
+    };
+    let reference_of = |v: &Value| {
+        v.get("variablesReference")
+            .and_then(|x| x.as_i64())
+            .unwrap_or(0)
+    };
+    let root = reference_of(evaluated);
+    if root <= 0 {
+        return Ok(json!({"value": display(evaluated), "nodes": 1, "truncated": false}));
+    }
+    let mut tree = json!({});
+    let mut nodes = 1;
+    let mut truncated = false;
+    let mut seen = HashSet::from([root]);
+    // JSON pointer of each container still to expand, with its reference.
+    let mut queue = VecDeque::from([(String::new(), root)]);
+    while let Some((pointer, reference)) = queue.pop_front() {
+        if nodes >= max_nodes {
+            truncated = true;
+            break;
+        }
+        let listing = variables(reference)?;
+        let mut children = serde_json::Map::new();
+        let vars = listing.get("variables").and_then(|v| v.as_array());
+        for var in vars.into_iter().flatten() {
+            if nodes >= max_nodes {
+                truncated = true;
+                break;
+            }
+            let Some(name) = var.get("name").and_then(|n| n.as_str()) else {
+                continue;
+            };
+            nodes += 1;
+            children.insert(name.to_string(), display(var));
+            let child = reference_of(var);
+            if child > 0 && seen.insert(child) {
+                let escaped = name.replace('~', "~0").replace('/', "~1");
+                queue.push_back((format!("{pointer}/{escaped}"), child));
+            }
+        }
+        if let Some(slot) = tree.pointer_mut(&pointer) {
+            *slot = Value::Object(children);
+        }
+    }
+    Ok(json!({"value": tree, "nodes": nodes, "truncated": truncated}))
+}
+
+/// Whether an `evaluate` result string reads as true: anything except the usual falsy
+/// renderings across languages (`false`, `0`, `None`, `null`, `nil`, `undefined`, empty).
+fn is_truthy(value: &str) -> bool {
+    !matches!(
+        value.trim(),
+        "" | "false" | "False" | "FALSE" | "0" | "0.0" | "None" | "null" | "nil" | "undefined"
+    )
+}
+
+/// Recursively merge `overlay` into `base`: objects merge key by key, any other value replaces.
+fn deep_merge(base: &mut Value, overlay: &Value) {
+    match (base, overlay) {
+        (Value::Object(base_map), Value::Object(overlay_map)) => {
+            for (key, value) in overlay_map {
+                match base_map.get_mut(key) {
+                    Some(existing) => deep_merge(existing, value),
+                    None => {
+                        base_map.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deep_merge_lets_the_overlay_win() {
+        let mut base = json!({
+            "clientID": "mcp-dap",
+            "linesStartAt1": true,
+            "nested": {"keep": 1, "replace": [1, 2], "object": {"a": 1}},
+        });
+        deep_merge(
+            &mut base,
+            &json!({
+                "linesStartAt1": false,
+                "nested": {"replace": [3], "object": {"b": 2}, "added": null},
+                "extra": "x",
+            }),
+        );
+        assert_eq!(
+            base,
+            json!({
+                "clientID": "mcp-dap",
+                "linesStartAt1": false,
+                "nested": {"keep": 1, "replace": [3], "object": {"a": 1, "b": 2}, "added": null},
+                "extra": "x",
+            })
+        );
+
+        // A non-object on either side replaces the value outright.
+        let mut base = json!({"a": {"b": 1}});
+        deep_merge(&mut base, &json!({"a": 5}));
+        assert_eq!(base, json!({"a": 5}));
+        let mut base = json!({"a": 5});
+        deep_merge(&mut base, &json!({"a": {"b": 1}}));
+        assert_eq!(base, json!({"a": {"b": 1}}));
+    }
+
+    #[test]
+    fn truthiness_of_evaluate_results() {
+        for value in ["true", "1", "-1", "'text'", "[]", "0x0", " True "] {
+            assert!(is_truthy(value), "{value:?}");
+        }
+        for value in [
+            "",
+            "  ",
+            "false",
+            "False",
+            "FALSE",
+            "0",
+            "0.0",
+            "None",
+            "null",
+            "nil",
+            "undefined",
+            " 0 ",
+        ] {
+            assert!(!is_truthy(value), "{value:?}");
+        }
+    }
+
+    #[test]
+    fn value_tree_stops_at_the_node_cap_and_at_cycles() {
+        let var = |name: &str, value: &str, reference: i64| json!({"name": name, "value": value, "variablesReference": reference});
+        let listings = HashMap::from([
+            (
+                1,
+                json!({"variables": [
+                    var("a", "1", 0),
+                    var("b", "{...}", 2),
+                    {"value": "nameless"},
+                    var("c/d~", "[2]", 3),
+                ]}),
+            ),
+            (
+                2,
+                json!({"variables": [var("x", "10", 0), var("parent", "{...}", 1)]}),
+            ),
+            (
+                3,
+                json!({"variables": [var("0", "7", 0), var("1", "8", 0)]}),
+            ),
+        ]);
+        let evaluated = json!({"result": "{...}", "variablesReference": 1});
+        let mut fetched = Vec::new();
+        let tree = |max_nodes: usize, fetched: &mut Vec<i64>| {
+            build_value_tree(&evaluated, max_nodes, |reference| {
+                fetched.push(reference);
+                listings
+                    .get(&reference)
+                    .cloned()
+                    .ok_or_else(|| anyhow!("unknown reference {reference}"))
+            })
+            .expect("value tree")
+        };
+
+        // Breadth-first; the back reference to the root keeps its value string.
+        assert_eq!(
+            tree(100, &mut fetched),
+            json!({
+                "value": {
+                    "a": "1",
+                    "b": {"x": "10", "parent": "{...}"},
+                    "c/d~": {"0": "7", "1": "8"},
+                },
+                "nodes": 8,
+                "truncated": false,
+            })
+        );
+        assert_eq!(fetched, [1, 2, 3]);
+
+        // The cap stops mid-listing, and containers left in the queue keep their value string.
+        fetched.clear();
+        assert_eq!(
+            tree(5, &mut fetched),
+            json!({
+                "value": {"a": "1", "b": {"x": "10"}, "c/d~": "[2]"},
+                "nodes": 5,
+                "truncated": true,
+            })
+        );
+        assert_eq!(fetched, [1, 2]);
+
+        fetched.clear();
+        assert_eq!(
+            tree(1, &mut fetched),
+            json!({"value": {}, "nodes": 1, "truncated": true})
+        );
+        assert!(fetched.is_empty());
+
+        let leaf = json!({"result": "42", "variablesReference": 0});
+        let value = build_value_tree(&leaf, 10, |_| Err(anyhow!("leaves are not expanded")));
+        assert_eq!(
+            value.expect("leaf value"),
+            json!({"value": "42", "nodes": 1, "truncated": false})
+        );
+    }
+
+    #[test]
+    fn event_filter_matching() {
+        let names = |list: &[&str]| list.iter().map(|e| e.to_string()).collect::<BTreeSet<_>>();
+        let default = EventFilter::default();
+        let allow_only = EventFilter {
+            allow: Some(names(&["stopped", "output", "module"])),
+            deny: BTreeSet::new(),
+        };
+        let allow_and_deny = EventFilter {
+            allow: Some(names(&["stopped", "output"])),
+            deny: names(&["output"]),
+        };
+        let nothing = EventFilter {
+            allow: Some(BTreeSet::new()),
+            deny: BTreeSet::new(),
+        };
+        let cases: [(&EventFilter, &str, bool); 11] = [
+            (&default, "stopped", true),
+            (&default, "output", true),
+            (&default, "loadedSource", false),
+            (&default, "module", false),
+            (&default, "progressUpdate", false),
+            (&allow_only, "module", true),
+            (&allow_only, "thread", false),
+            // Deny wins over allow.
+            (&allow_and_deny, "output", false),
+            (&allow_and_deny, "stopped", true),
+            (&nothing, "stopped", false),
+            // Names are matched exactly.
+            (&allow_only, "Stopped", false),
+        ];
+        for (filter, event, admitted) in cases {
+            assert_eq!(
+                filter.admits(event),
+                admitted,
+                "{event} with {}",
+                filter.to_json()
+            );
+        }
+    }
+
+    #[test]
+    fn event_buffer_keeps_admitted_events_only() {
+        let mut buffer = EventBuffer::default();
+        buffer.push("module", Some(&json!({"reason": "new"})));
+        buffer.push("stopped", Some(&json!({"reason": "breakpoint"})));
+        buffer.push("output", Some(&json!({"output": "hi\n"})));
+        buffer.push(
+            "output",
+            Some(&json!({"category": "stderr", "output": "oops\n"})),
+        );
+        assert_eq!(
+            Vec::from(buffer.other.clone()),
+            [json!({"event": "stopped", "body": {"reason": "breakpoint"}})]
+        );
+        assert_eq!(
+            Vec::from(buffer.output.clone()),
+            [
+                json!({"category": "console", "output": "hi\n"}),
+                json!({"category": "stderr", "output": "oops\n"}),
+            ]
+        );
+
+        // Clearing keeps the filter; a full buffer drops its oldest entry.
+        buffer.filter.deny.clear();
+        buffer.clear();
+        for i in 0..=MAX_OTHER_EVENTS {
+            buffer.push("module", Some(&json!({"n": i})));
+        }
+        assert_eq!(buffer.other.len(), MAX_OTHER_EVENTS);
+        assert_eq!(buffer.other[0]["body"]["n"], 1);
+        assert!(buffer.output.is_empty());
+    }
+
+    #[test]
+    fn set_event_filter_replaces_lists_and_resets() {
+        let mut manager = DapAdapterManager::new();
+        let filter = manager.set_event_filter(Some(Some(vec!["stopped".into()])), None, false);
+        assert_eq!(
+            filter,
+            json!({"allow": ["stopped"], "deny": ["invalidated", "loadedSource", "module", "progressUpdate"]})
+        );
+        let filter = manager.set_event_filter(Some(None), Some(vec!["output".into()]), false);
+        assert_eq!(filter, json!({"allow": null, "deny": ["output"]}));
+        let filter = manager.set_event_filter(None, None, true);
+        assert_eq!(filter, EventFilter::default().to_json());
+    }
+}
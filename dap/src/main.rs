@@ -8,7 +8,33 @@ use serde_json::{json, Value};
 use std::collections::HashSet;
 use std::sync::Arc;
 
-fn schema(value: Value) -> Arc<JsonObject> {
+/// Upper bound for `dap_variables` `depth`; each level costs one request per expandable child.
+const MAX_VARIABLE_DEPTH: u64 = 8;
+
+/// `dap_stack_trace` with `allFrames`: frames requested per `stackTrace` page and the most
+/// frames collected in total.
+const STACK_PAGE_FRAMES: u64 = 200;
+const MAX_STACK_FRAMES: usize = 5000;
+
+/// `dap_continue_until`: continues attempted when `maxIterations` is not given, and the cap.
+const DEFAULT_CONTINUE_ITERATIONS: u64 = 100;
+const MAX_CONTINUE_ITERATIONS: u64 = 1000;
+
+/// `dap_evaluate` with `format: "tree"`: values rendered when `maxNodes` is not given, and the cap.
+const DEFAULT_TREE_NODES: u64 = 200;
+const MAX_TREE_NODES: u64 = 5000;
+
+/// Output events included in `dap_status` when `outputLimit` is not given.
+const DEFAULT_STATUS_OUTPUT: u64 = 20;
+
+/// Tool input schema; every tool also takes the `sessionId` selecting its debug session.
+fn schema(mut value: Value) -> Arc<JsonObject> {
+    if let Some(props) = value.get_mut("properties").and_then(Value::as_object_mut) {
+        props.insert(
+            "sessionId".into(),
+            json!({"type": "string", "description": "Debug session to use (default: the shared session); create more with dap_initialize"}),
+        );
+    }
     Arc::new(
         value
             .as_object()
@@ -41,48 +67,201 @@ fn tools() -> Vec<McpTool> {
         "type": "object",
         "properties": {
             "source": {"type": "object", "properties": {"path": {"type": "string"}}, "required": ["path"]},
-            "breakpoints": {"type": "array"},
-            "lines": {"type": "array", "items": {"type": "integer", "minimum": 1}},
+            "breakpoints": {"type": "array", "items": {"type": "object", "properties": {"line": {"type": "integer", "minimum": 1}, "column": {"type": "integer", "minimum": 1}}, "required": ["line"]}},
+            "lines": {"type": "array", "items": {"oneOf": [
+                {"type": "integer", "minimum": 1},
+                {"type": "object", "properties": {"line": {"type": "integer", "minimum": 1}, "column": {"type": "integer", "minimum": 1}}, "required": ["line"]}
+            ]}},
+            "columns": {"type": "array", "description": "Optional columns parallel to `lines`", "items": {"type": ["integer", "null"], "minimum": 1}},
             "sourceModified": {"type": "boolean"},
             "adapterCommand": {"type": "string"}
         },
         "required": ["source"]
     });
+    let hit_condition_schema = json!({
+        "type": "object",
+        "properties": {
+            "source": {"type": "object", "properties": {"path": {"type": "string"}}, "required": ["path"]},
+            "line": {"type": "integer", "minimum": 1, "description": "Line of a breakpoint previously set for this source"},
+            "hitCondition": {"type": ["string", "null"], "description": "Adapter-specific hit condition, e.g. `5` or `>= 5` to skip the first hits; null or empty clears it"},
+            "adapterCommand": {"type": "string"}
+        },
+        "required": ["source", "line"]
+    });
+    let function_breakpoints_schema = json!({
+        "type": "object",
+        "properties": {
+            "breakpoints": {"type": "array", "description": "Replaces all function breakpoints; an empty array clears them", "items": {"type": "object", "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "condition": {"type": "string"},
+                "hitCondition": {"type": "string"}
+            }, "required": ["name"]}},
+            "adapterCommand": {"type": "string"}
+        },
+        "required": ["breakpoints"]
+    });
     let thread_id_schema = json!({
         "type": "object",
         "properties": {"threadId": {"type": "integer", "minimum": 1}, "adapterCommand": {"type": "string"}},
         "required": ["threadId"]
     });
+    let stop_summary_schema = json!({
+        "type": "object",
+        "properties": {"threadId": {"type": "integer", "minimum": 1}, "adapterCommand": {"type": "string"}}
+    });
     let stack_trace_schema = json!({
         "type": "object",
-        "properties": {"threadId": {"type": "integer", "minimum": 1}, "startFrame": {"type": "integer"}, "levels": {"type": "integer"}, "adapterCommand": {"type": "string"}},
+        "properties": {
+            "threadId": {"type": "integer", "minimum": 1},
+            "startFrame": {"type": "integer"},
+            "levels": {"type": "integer", "description": "Frames per request; with allFrames, the page size"},
+            "allFrames": {"type": "boolean", "description": format!("Page through stackTrace until every frame (at most {MAX_STACK_FRAMES}) is collected")},
+            "resolveSources": {"type": "boolean", "description": "Fetch the content of frames whose source has a sourceReference but no path"},
+            "adapterCommand": {"type": "string"}
+        },
         "required": ["threadId"]
     });
+    let source_schema = json!({
+        "type": "object",
+        "properties": {
+            "sourceReference": {"type": "integer", "minimum": 1, "description": "From a stack frame's or loaded source's `source.sourceReference`"},
+            "source": {"type": "object", "description": "DAP Source; its sourceReference is used when the top-level one is omitted"},
+            "adapterCommand": {"type": "string"}
+        }
+    });
     let scopes_schema = json!({
         "type": "object",
-        "properties": {"frameId": {"type": "integer", "minimum": 1}, "adapterCommand": {"type": "string"}},
-        "required": ["frameId"]
+        "properties": {
+            "frameId": {"type": "integer", "minimum": 1, "description": "Defaults to the frame selected with dap_select_frame"},
+            "adapterCommand": {"type": "string"}
+        }
+    });
+    let select_frame_schema = json!({
+        "type": "object",
+        "properties": {
+            "threadId": {"type": "integer", "minimum": 1, "description": "Stopped thread; defaults to the thread of the current stop"},
+            "frameId": {"type": "integer", "description": "Frame to select; must be on the thread's stack"},
+            "level": {"type": "integer", "minimum": 0, "description": "Select the frame at this stack depth instead (0 = top, the default)"},
+            "clear": {"type": "boolean", "description": "Drop the current selection instead of selecting"},
+            "adapterCommand": {"type": "string"}
+        }
     });
     let variables_schema = json!({
         "type": "object",
-        "properties": {"variablesReference": {"type": "integer", "minimum": 1}, "adapterCommand": {"type": "string"}},
+        "properties": {
+            "variablesReference": {"type": "integer", "minimum": 1},
+            "start": {"type": "integer", "minimum": 0, "description": "Index of the first child to return (paged variables)"},
+            "count": {"type": "integer", "minimum": 0, "description": "Number of children to return; 0 or omitted returns all"},
+            "depth": {"type": "integer", "minimum": 0, "maximum": MAX_VARIABLE_DEPTH, "description": "Auto-expand nested variablesReferences this many levels into `children` arrays"},
+            "adapterCommand": {"type": "string"}
+        },
         "required": ["variablesReference"]
     });
     let evaluate_schema = json!({
         "type": "object",
-        "properties": {"expression": {"type": "string"}, "frameId": {"type": "integer"}, "context": {"type": "string"}, "adapterCommand": {"type": "string"}},
+        "properties": {
+            "expression": {"type": "string", "description": "Expression or, in the repl context, statements; may span several lines"},
+            "frameId": {"type": "integer", "description": "Frame whose scope is used; many adapters need it to resolve locals"},
+            "context": {"type": "string", "default": "repl", "description": "watch, repl, hover, clipboard, or variables"},
+            "format": {"type": "string", "enum": ["raw", "tree"], "default": "raw", "description": "tree: also resolve variablesReferences into a nested value"},
+            "maxNodes": {"type": "integer", "minimum": 1, "maximum": MAX_TREE_NODES, "default": DEFAULT_TREE_NODES, "description": "With format tree, the most values rendered"},
+            "adapterCommand": {"type": "string"}
+        },
         "required": ["expression"]
     });
+    let continue_until_schema = json!({
+        "type": "object",
+        "properties": {
+            "threadId": {"type": "integer", "minimum": 1},
+            "expression": {"type": "string", "description": "Condition evaluated (context `watch`) in the top frame at each stop"},
+            "maxIterations": {"type": "integer", "minimum": 1, "maximum": MAX_CONTINUE_ITERATIONS, "default": DEFAULT_CONTINUE_ITERATIONS},
+            "adapterCommand": {"type": "string"}
+        },
+        "required": ["threadId", "expression"]
+    });
+    let watch_schema = json!({
+        "type": "object",
+        "properties": {
+            "expressions": {"type": "array", "items": {"type": "string"}, "description": "Expressions evaluated in order with context `watch`"},
+            "frameId": {"type": "integer", "description": "Frame to evaluate in; defaults to the top frame of the current stop"},
+            "adapterCommand": {"type": "string"}
+        },
+        "required": ["expressions"]
+    });
+    let get_variable_schema = json!({
+        "type": "object",
+        "properties": {
+            "name": {"type": "string", "minLength": 1, "description": "Variable name; dotted names (`obj.field`, `items.0`) descend into children"},
+            "frameId": {"type": "integer", "description": "Frame to search; takes precedence over threadId"},
+            "threadId": {"type": "integer", "minimum": 1, "description": "Search this thread's top frame; defaults to the top frame of the current stop"},
+            "adapterCommand": {"type": "string"}
+        },
+        "required": ["name"]
+    });
+    let output_schema = json!({
+        "type": "object",
+        "properties": {
+            "category": {"type": "string", "description": "Only return output with this category (e.g. stdout, stderr, console)"},
+            "limit": {"type": "integer", "minimum": 1, "description": "Return at most this many of the most recent events"}
+        }
+    });
+    let cancel_schema = json!({
+        "type": "object",
+        "properties": {
+            "requestId": {"type": "integer", "minimum": 1, "description": "seq of the request to cancel; defaults to the request another tool call is blocked on"},
+            "progressId": {"type": "string", "description": "Id of a progress to cancel"},
+            "adapterCommand": {"type": "string"}
+        }
+    });
+    let status_schema = json!({
+        "type": "object",
+        "properties": {
+            "outputLimit": {"type": "integer", "minimum": 0, "default": DEFAULT_STATUS_OUTPUT, "description": "Number of most recent output events to include"},
+            "adapterCommand": {"type": "string"}
+        }
+    });
+    let list_breakpoints_schema = json!({
+        "type": "object",
+        "properties": {
+            "path": {"type": "string", "description": "Only list breakpoints of this source path"},
+            "adapterCommand": {"type": "string"}
+        }
+    });
     let disconnect_schema = json!({
         "type": "object",
         "properties": {"terminateDebuggee": {"type": "boolean"}, "restart": {"type": "boolean"}, "adapterCommand": {"type": "string"}}
     });
+    let event_filter_schema = json!({
+        "type": "object",
+        "properties": {
+            "allow": {"type": ["array", "null"], "items": {"type": "string"}, "description": "Only buffer these events; null buffers every event not denied"},
+            "deny": {"type": "array", "items": {"type": "string"}, "description": "Never buffer these events (replaces the current deny list)"},
+            "reset": {"type": "boolean", "description": "Restore the default filter before applying allow/deny"}
+        }
+    });
+    let empty_schema = json!({
+        "type": "object",
+        "properties": {}
+    });
+
+    let initialize_schema = json!({
+        "type": "object",
+        "properties": {
+            "adapterCommand": {"type": "string"},
+            "newSession": {"type": "boolean", "description": "Start a separate session under a generated sessionId, returned in the result"},
+            "initializeArguments": {
+                "type": "object",
+                "description": "Deep-merged over the default DAP initialize arguments (e.g. adapterID, locale, pathFormat); restarts a running adapter"
+            }
+        },
+        "additionalProperties": true
+    });
 
     vec![
         McpTool::new(
             "dap_initialize",
-            "Start adapter and report capabilities",
-            schema(adapter_only_schema.clone()),
+            "Start adapter and report capabilities; an unknown sessionId or newSession creates an independent session",
+            schema(initialize_schema),
         ),
         McpTool::new("dap_call", "DAP custom call", schema(dap_call_schema)),
         McpTool::new(
@@ -100,6 +279,21 @@ fn tools() -> Vec<McpTool> {
             "Set breakpoints for a source",
             schema(set_breakpoints_schema),
         ),
+        McpTool::new(
+            "dap_list_breakpoints",
+            "Breakpoints as the adapter last reported them, including later verification and relocation from breakpoint events",
+            schema(list_breakpoints_schema),
+        ),
+        McpTool::new(
+            "dap_set_breakpoint_hit_condition",
+            "Change the hitCondition of one line's breakpoint, re-sending the source's other breakpoints unchanged",
+            schema(hit_condition_schema),
+        ),
+        McpTool::new(
+            "dap_set_function_breakpoints",
+            "Set breakpoints on functions by name, replacing any previous function breakpoints",
+            schema(function_breakpoints_schema),
+        ),
         McpTool::new(
             "dap_configuration_done",
             "Configuration done",
@@ -110,6 +304,11 @@ fn tools() -> Vec<McpTool> {
             "Continue execution",
             schema(thread_id_schema.clone()),
         ),
+        McpTool::new(
+            "dap_continue_until",
+            "Continue repeatedly until an expression is truthy at a stop, or the iteration cap or program end is reached",
+            schema(continue_until_schema),
+        ),
         McpTool::new("dap_next", "Step over", schema(thread_id_schema.clone())),
         McpTool::new("dap_step_in", "Step in", schema(thread_id_schema.clone())),
         McpTool::new("dap_step_out", "Step out", schema(thread_id_schema.clone())),
@@ -118,11 +317,36 @@ fn tools() -> Vec<McpTool> {
             "List threads",
             schema(adapter_only_schema.clone()),
         ),
+        McpTool::new(
+            "dap_thread_states",
+            "Threads with their last known state (running, stopped, unknown) from DAP events",
+            schema(adapter_only_schema.clone()),
+        ),
+        McpTool::new(
+            "dap_stop_summary",
+            "Why and where the debuggee stopped: reason, severity, top frame, and exception info",
+            schema(stop_summary_schema),
+        ),
+        McpTool::new(
+            "dap_status",
+            "Session snapshot: debuggee process, thread states, last stop, recent output, exit code, and termination",
+            schema(status_schema),
+        ),
         McpTool::new(
             "dap_stack_trace",
             "Get stack trace",
             schema(stack_trace_schema),
         ),
+        McpTool::new(
+            "dap_source",
+            "Content of a source without an on-disk path (generated or decompiled code), by sourceReference",
+            schema(source_schema),
+        ),
+        McpTool::new(
+            "dap_select_frame",
+            "Select the frame dap_evaluate, dap_scopes, dap_watch, and dap_get_variable use when not given one, until the thread resumes or stops again",
+            schema(select_frame_schema),
+        ),
         McpTool::new("dap_scopes", "Get scopes for frame", schema(scopes_schema)),
         McpTool::new(
             "dap_variables",
@@ -134,19 +358,204 @@ fn tools() -> Vec<McpTool> {
             "Evaluate expression",
             schema(evaluate_schema),
         ),
+        McpTool::new(
+            "dap_watch",
+            "Evaluate a list of watch expressions at the current stop; failures are reported per expression",
+            schema(watch_schema),
+        ),
+        McpTool::new(
+            "dap_get_variable",
+            "Value of one variable by name (dotted for fields) in a frame's scopes, falling back to evaluating the name",
+            schema(get_variable_schema),
+        ),
+        McpTool::new(
+            "dap_output",
+            "Program output captured from DAP output events",
+            schema(output_schema),
+        ),
+        McpTool::new(
+            "dap_set_event_filter",
+            "Choose which adapter events are buffered by name (allow/deny lists); call without arguments to inspect the current filter",
+            schema(event_filter_schema),
+        ),
+        McpTool::new(
+            "dap_cancel",
+            "Cancel an in-progress request or progress; abandons a tool call blocked on it",
+            schema(cancel_schema),
+        ),
         McpTool::new(
             "dap_disconnect",
             "Disconnect debugger",
             schema(disconnect_schema),
         ),
+        McpTool::new(
+            "dap_exit_status",
+            "Exit code of the debuggee from the exited event (and signal, for negative codes), plus whether the session terminated",
+            schema(empty_schema.clone()),
+        ),
+        McpTool::new(
+            "dap_shutdown",
+            "Disconnect (terminating the debuggee) and kill the adapter process, resetting the session",
+            schema(empty_schema),
+        ),
+        McpTool::new(
+            "dap_unsupported",
+            "Tools and DAP commands hidden because the adapter lacks the capability that enables them",
+            schema(adapter_only_schema.clone()),
+        ),
     ]
 }
 
+/// Tools that are only listed when the adapter advertises the paired capability.
+const GATED_TOOLS: &[(&str, &str)] = &[
+    ("dap_configuration_done", "supportsConfigurationDoneRequest"),
+    ("dap_cancel", "supportsCancelRequest"),
+    (
+        "dap_set_function_breakpoints",
+        "supportsFunctionBreakpoints",
+    ),
+    (
+        "dap_set_breakpoint_hit_condition",
+        "supportsHitConditionalBreakpoints",
+    ),
+];
+
+/// Optional DAP requests (reachable through `dap_call`) and the capability that enables each.
+const GATED_COMMANDS: &[(&str, &str)] = &[
+    ("configurationDone", "supportsConfigurationDoneRequest"),
+    ("terminate", "supportsTerminateRequest"),
+    ("restart", "supportsRestartRequest"),
+    ("stepBack", "supportsStepBack"),
+    ("reverseContinue", "supportsStepBack"),
+    ("restartFrame", "supportsRestartFrame"),
+    ("gotoTargets", "supportsGotoTargetsRequest"),
+    ("stepInTargets", "supportsStepInTargetsRequest"),
+    ("completions", "supportsCompletionsRequest"),
+    ("modules", "supportsModulesRequest"),
+    ("loadedSources", "supportsLoadedSourcesRequest"),
+    ("exceptionInfo", "supportsExceptionInfoRequest"),
+    ("setVariable", "supportsSetVariable"),
+    ("setExpression", "supportsSetExpression"),
+    ("setFunctionBreakpoints", "supportsFunctionBreakpoints"),
+    ("setDataBreakpoints", "supportsDataBreakpoints"),
+    ("dataBreakpointInfo", "supportsDataBreakpoints"),
+    (
+        "setInstructionBreakpoints",
+        "supportsInstructionBreakpoints",
+    ),
+    ("breakpointLocations", "supportsBreakpointLocationsRequest"),
+    ("terminateThreads", "supportsTerminateThreadsRequest"),
+    ("readMemory", "supportsReadMemoryRequest"),
+    ("writeMemory", "supportsWriteMemoryRequest"),
+    ("disassemble", "supportsDisassembleRequest"),
+    ("cancel", "supportsCancelRequest"),
+];
+
+/// Launch/attach fields known adapters cannot start without, keyed on the start of the adapter
+/// executable's file name: at least one field of each entry must be present.
+const REQUIRED_SESSION_FIELDS: &[(&str, &str, &[&str])] = &[
+    ("debugpy", "launch", &["program", "module", "code"]),
+    ("debugpy", "attach", &["connect", "listen", "processId"]),
+    ("lldb-dap", "launch", &["program"]),
+    ("lldb-dap", "attach", &["pid", "program"]),
+    ("lldb-vscode", "launch", &["program"]),
+    ("lldb-vscode", "attach", &["pid", "program"]),
+    ("codelldb", "launch", &["program", "cargo"]),
+    ("codelldb", "attach", &["pid", "program"]),
+    ("dlv", "launch", &["program"]),
+    ("dlv", "attach", &["processId"]),
+    ("netcoredbg", "launch", &["program"]),
+    ("netcoredbg", "attach", &["processId"]),
+];
+
+/// Launch/attach fields filled in for known adapters when the caller leaves them out.
+const DEFAULT_SESSION_FIELDS: &[(&str, &str, &str, &str)] = &[
+    // The default integrated terminal needs runInTerminal, which the bridge does not offer;
+    // the internal console routes program output through `output` events instead.
+    ("debugpy", "launch", "console", "internalConsole"),
+];
+
+/// Check `arguments` of a `launch`/`attach` against the known adapter's required fields and
+/// fill in its defaults. Unknown adapters and non-object arguments pass through unchanged.
+fn prepare_session_arguments(
+    request: &str,
+    arguments: &mut Value,
+    adapter: Option<&str>,
+) -> Result<(), ErrorData> {
+    let Some(name) = adapter
+        .map(std::path::Path::new)
+        .and_then(|p| p.file_name())
+        .and_then(|n| n.to_str())
+    else {
+        return Ok(());
+    };
+    let Some(obj) = arguments.as_object_mut() else {
+        return Ok(());
+    };
+    for (prefix, req, fields) in REQUIRED_SESSION_FIELDS {
+        if *req != request || !name.starts_with(prefix) {
+            continue;
+        }
+        if !fields
+            .iter()
+            .any(|f| obj.get(*f).is_some_and(|v| !v.is_null()))
+        {
+            let wanted = if fields.len() == 1 {
+                format!("'{}'", fields[0])
+            } else {
+                format!("one of {}", fields.join(", "))
+            };
+            return Err(ErrorData::invalid_params(
+                format!("{prefix} {request} needs {wanted} in arguments"),
+                Some(json!({"adapter": prefix, "request": request, "missing": fields})),
+            ));
+        }
+    }
+    for (prefix, req, key, value) in DEFAULT_SESSION_FIELDS {
+        if *req == request && name.starts_with(prefix) {
+            obj.entry(key.to_string()).or_insert_with(|| json!(value));
+        }
+    }
+    Ok(())
+}
+
+fn capability_enabled(caps: &Value, capability: &str) -> bool {
+    caps.get(capability)
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// Report which gated tools and optional DAP commands the adapter does not support, so callers
+/// can tell a missing tool apart from a bridge limitation.
+fn unsupported_report(caps: Option<&Value>) -> Value {
+    let Some(caps) = caps else {
+        return json!({
+            "adapterConfigured": false,
+            "hiddenTools": [],
+            "unsupportedCommands": []
+        });
+    };
+    let hidden_tools: Vec<Value> = GATED_TOOLS
+        .iter()
+        .filter(|(_, capability)| !capability_enabled(caps, capability))
+        .map(|(tool, capability)| json!({"tool": tool, "capability": capability}))
+        .collect();
+    let unsupported_commands: Vec<Value> = GATED_COMMANDS
+        .iter()
+        .filter(|(_, capability)| !capability_enabled(caps, capability))
+        .map(|(command, capability)| json!({"command": command, "capability": capability}))
+        .collect();
+    json!({
+        "adapterConfigured": true,
+        "hiddenTools": hidden_tools,
+        "unsupportedCommands": unsupported_commands
+    })
+}
+
 fn filter_tools_by_capabilities(mut all: Vec<McpTool>, caps: Option<Value>) -> Vec<McpTool> {
     let Some(caps) = caps else {
         return all;
     };
-    let obj = caps.as_object().cloned().unwrap_or_default();
     let mut allowed = HashSet::<String>::new();
     for name in [
         "dap_initialize",
@@ -154,28 +563,49 @@ fn filter_tools_by_capabilities(mut all: Vec<McpTool>, caps: Option<Value>) -> V
         "dap_launch",
         "dap_attach",
         "dap_set_breakpoints",
+        "dap_list_breakpoints",
         "dap_continue",
+        "dap_continue_until",
         "dap_next",
         "dap_step_in",
         "dap_step_out",
         "dap_threads",
+        "dap_thread_states",
+        "dap_stop_summary",
+        "dap_status",
+        "dap_exit_status",
         "dap_stack_trace",
+        "dap_source",
+        "dap_select_frame",
         "dap_scopes",
         "dap_variables",
         "dap_evaluate",
+        "dap_watch",
+        "dap_get_variable",
+        "dap_output",
+        "dap_set_event_filter",
         "dap_disconnect",
+        "dap_shutdown",
+        "dap_unsupported",
     ] {
         allowed.insert(name.to_string());
     }
-    if obj
-        .get("supportsConfigurationDoneRequest")
-        .and_then(|v| v.as_bool())
-        .unwrap_or(false)
-    {
-        allowed.insert("dap_configuration_done".to_string());
-    }
 
-    all.retain(|tool| allowed.contains(tool.name.as_ref()));
+    all.retain_mut(|tool| {
+        if allowed.contains(tool.name.as_ref()) {
+            return true;
+        }
+        let Some((_, capability)) = GATED_TOOLS.iter().find(|(name, _)| *name == tool.name) else {
+            return false;
+        };
+        if !capability_enabled(&caps, capability) {
+            return false;
+        }
+        let base = tool.description.clone().unwrap_or_default();
+        tool.description =
+            Some(format!("{base} (enabled by adapter capability {capability})").into());
+        true
+    });
     all
 }
 
@@ -195,7 +625,7 @@ fn handle_structured_call(
 ) -> Result<CallToolResult, ErrorData> {
     let (command, payload) = match tool {
         "dap_launch" | "dap_attach" => {
-            let arguments = args.get("arguments").cloned().ok_or_else(|| {
+            let mut arguments = args.get("arguments").cloned().ok_or_else(|| {
                 ErrorData::invalid_params("Missing required field: arguments", None)
             })?;
             let cmd = if tool == "dap_launch" {
@@ -203,6 +633,7 @@ fn handle_structured_call(
             } else {
                 "attach"
             };
+            prepare_session_arguments(cmd, &mut arguments, manager.adapter_command(adapter_cmd))?;
             (cmd, arguments)
         }
         "dap_set_breakpoints" => {
@@ -210,19 +641,22 @@ fn handle_structured_call(
                 .get("source")
                 .cloned()
                 .ok_or_else(|| ErrorData::invalid_params("Missing required field: source", None))?;
-            let mut breakpoints = args.get("breakpoints").cloned();
-            if breakpoints.is_none() {
-                if let Some(lines) = args.get("lines").and_then(|v| v.as_array()) {
-                    let values: Vec<Value> = lines
-                        .iter()
-                        .filter_map(|v| v.as_i64())
-                        .map(|line| json!({"line": line}))
-                        .collect();
-                    breakpoints = Some(json!(values));
+            let breakpoints = match args.get("breakpoints") {
+                Some(Value::Array(items)) => {
+                    for bp in items {
+                        validate_breakpoint(bp)?;
+                    }
+                    Value::Array(items.clone())
                 }
-            }
-            let mut obj =
-                json!({"source": source, "breakpoints": breakpoints.unwrap_or_else(|| json!([]))});
+                Some(_) => {
+                    return Err(ErrorData::invalid_params(
+                        "Field 'breakpoints' must be an array",
+                        None,
+                    ))
+                }
+                None => breakpoints_from_lines(args)?,
+            };
+            let mut obj = json!({"source": source, "breakpoints": breakpoints});
             if let Some(sm) = args.get("sourceModified").cloned() {
                 obj.as_object_mut()
                     .unwrap()
@@ -230,7 +664,31 @@ fn handle_structured_call(
             }
             ("setBreakpoints", obj)
         }
+        "dap_set_function_breakpoints" => {
+            let breakpoints = function_breakpoints(args)?;
+            let mut result = manager
+                .request(
+                    "setFunctionBreakpoints",
+                    json!({"breakpoints": breakpoints}),
+                    adapter_cmd,
+                )
+                .map_err(|e| ErrorData::internal_error(format!("dap error: {e}"), None))?;
+            // Adapters answer in request order; label each result with its function.
+            if let Some(Value::Array(results)) = result.get_mut("breakpoints") {
+                for (bp, requested) in results.iter_mut().zip(&breakpoints) {
+                    if let Some(obj) = bp.as_object_mut() {
+                        obj.insert("name".into(), requested["name"].clone());
+                    }
+                }
+            }
+            return Ok(CallToolResult::structured(json!({
+                "tool": tool,
+                "status": "ok",
+                "result": result
+            })));
+        }
         "dap_configuration_done" => ("configurationDone", json!({})),
+        "dap_cancel" => ("cancel", cancel_arguments(args)?),
         "dap_continue" => {
             let thread_id = require_i64(args, "threadId")?;
             ("continue", json!({"threadId": thread_id}))
@@ -250,51 +708,110 @@ fn handle_structured_call(
         "dap_threads" => ("threads", json!({})),
         "dap_stack_trace" => {
             let thread_id = require_i64(args, "threadId")?;
-            let mut payload = json!({"threadId": thread_id});
-            if let Some(sf) = args.get("startFrame").cloned() {
-                payload
-                    .as_object_mut()
-                    .unwrap()
-                    .insert("startFrame".into(), sf);
+            let mut result = if args.get("allFrames").and_then(|v| v.as_bool()) == Some(true) {
+                let start = optional_u64(args, "startFrame", u64::MAX)?.unwrap_or(0);
+                let page = optional_u64(args, "levels", u64::MAX)?
+                    .filter(|n| *n > 0)
+                    .unwrap_or(STACK_PAGE_FRAMES);
+                manager.stack_trace_all(thread_id, start, page, MAX_STACK_FRAMES, adapter_cmd)
+            } else {
+                let mut payload = json!({"threadId": thread_id});
+                if let Some(sf) = args.get("startFrame").cloned() {
+                    payload
+                        .as_object_mut()
+                        .unwrap()
+                        .insert("startFrame".into(), sf);
+                }
+                if let Some(levels) = args.get("levels").cloned() {
+                    payload
+                        .as_object_mut()
+                        .unwrap()
+                        .insert("levels".into(), levels);
+                }
+                manager.request("stackTrace", payload, adapter_cmd)
             }
-            if let Some(levels) = args.get("levels").cloned() {
-                payload
-                    .as_object_mut()
-                    .unwrap()
-                    .insert("levels".into(), levels);
+            .map_err(|e| ErrorData::internal_error(format!("dap error: {e}"), None))?;
+            if args.get("resolveSources").and_then(|v| v.as_bool()) == Some(true) {
+                if let Some(Value::Array(frames)) = result.get_mut("stackFrames") {
+                    manager.resolve_frame_sources(frames, adapter_cmd);
+                }
+            }
+            return Ok(CallToolResult::structured(json!({
+                "tool": tool,
+                "status": "ok",
+                "result": result
+            })));
+        }
+        "dap_source" => {
+            let source = args.get("source").filter(|v| !v.is_null());
+            if source.is_some_and(|s| !s.is_object()) {
+                return Err(ErrorData::invalid_params(
+                    "Field 'source' must be an object",
+                    None,
+                ));
             }
-            ("stackTrace", payload)
+            let reference = match args.get("sourceReference").filter(|v| !v.is_null()) {
+                Some(v) => v.as_i64().filter(|n| *n > 0).ok_or_else(|| {
+                    ErrorData::invalid_params(
+                        "Field 'sourceReference' must be an integer >= 1",
+                        None,
+                    )
+                })?,
+                None => source
+                    .and_then(|s| s.get("sourceReference"))
+                    .and_then(|v| v.as_i64())
+                    .filter(|n| *n > 0)
+                    .ok_or_else(|| {
+                        ErrorData::invalid_params(
+                            "Missing required field: sourceReference (or source.sourceReference)",
+                            None,
+                        )
+                    })?,
+            };
+            let (body, cached) = manager
+                .source(source.cloned(), reference, adapter_cmd)
+                .map_err(|e| ErrorData::internal_error(format!("dap error: {e}"), None))?;
+            return Ok(CallToolResult::structured(json!({
+                "tool": tool,
+                "status": "ok",
+                "sourceReference": reference,
+                "cached": cached,
+                "result": body
+            })));
         }
         "dap_scopes" => {
-            let frame_id = require_i64(args, "frameId")?;
+            let frame_id = match args.get("frameId") {
+                None | Some(Value::Null) => manager.selected_frame_id().ok_or_else(|| {
+                    ErrorData::invalid_params(
+                        "Missing required field: frameId (no frame is selected with dap_select_frame)",
+                        None,
+                    )
+                })?,
+                Some(_) => require_i64(args, "frameId")?,
+            };
             ("scopes", json!({"frameId": frame_id}))
         }
         "dap_variables" => {
             let vr = require_i64(args, "variablesReference")?;
-            ("variables", json!({"variablesReference": vr}))
-        }
-        "dap_evaluate" => {
-            let expression = args
-                .get("expression")
-                .and_then(|v| v.as_str())
-                .ok_or_else(|| {
-                    ErrorData::invalid_params("Missing required field: expression", None)
-                })?;
-            let mut payload = json!({"expression": expression});
-            if let Some(fid) = args.get("frameId").cloned() {
-                payload
-                    .as_object_mut()
-                    .unwrap()
-                    .insert("frameId".into(), fid);
+            let mut payload = json!({"variablesReference": vr});
+            for key in ["start", "count"] {
+                if let Some(n) = optional_u64(args, key, u64::MAX)? {
+                    payload[key] = json!(n);
+                }
             }
-            if let Some(ctx) = args.get("context").cloned() {
-                payload
-                    .as_object_mut()
-                    .unwrap()
-                    .insert("context".into(), ctx);
+            if let Some(depth) = optional_u64(args, "depth", MAX_VARIABLE_DEPTH)? {
+                let result = manager
+                    .variables_expanded(payload, depth as u32, adapter_cmd)
+                    .map_err(|e| ErrorData::internal_error(format!("dap error: {e}"), None))?;
+                return Ok(CallToolResult::structured(json!({
+                    "tool": tool,
+                    "status": "ok",
+                    "result": result
+                })));
             }
-            ("evaluate", payload)
+            ("variables", payload)
         }
+        "dap_evaluate" => return evaluate(args, adapter_cmd, manager),
         "dap_disconnect" => {
             let mut payload = json!({});
             if let Some(td) = args.get("terminateDebuggee").cloned() {
@@ -329,13 +846,419 @@ fn handle_structured_call(
     })))
 }
 
+/// `dap_evaluate`: multiline input is passed with `\n` line endings and `context` defaults to
+/// `repl`. Without `frameId` the frame selected with `dap_select_frame` is used, if any. A
+/// failure without a frame says how to retry in one, since many adapters only resolve locals
+/// when given one.
+fn evaluate(
+    args: &JsonObject,
+    adapter_cmd: Option<&str>,
+    manager: &mut DapAdapterManager,
+) -> Result<CallToolResult, ErrorData> {
+    let expression = args
+        .get("expression")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| ErrorData::invalid_params("Missing required field: expression", None))?;
+    let expression = expression.replace("\r\n", "\n");
+    let expression = expression.trim_end_matches('\n');
+    if expression.trim().is_empty() {
+        return Err(ErrorData::invalid_params(
+            "Field 'expression' must not be empty",
+            None,
+        ));
+    }
+    let context = match args.get("context") {
+        None | Some(Value::Null) => "repl",
+        Some(Value::String(ctx)) => ctx.as_str(),
+        Some(_) => {
+            return Err(ErrorData::invalid_params(
+                "Field 'context' must be a string",
+                None,
+            ))
+        }
+    };
+    let frame_id = match args.get("frameId") {
+        None | Some(Value::Null) => None,
+        Some(v) => Some(v.as_i64().ok_or_else(|| {
+            ErrorData::invalid_params("Field 'frameId' must be an integer", None)
+        })?),
+    };
+    let frame_id = frame_id.or_else(|| manager.selected_frame_id());
+    let tree = match args.get("format") {
+        None | Some(Value::Null) => false,
+        Some(Value::String(f)) if f == "raw" => false,
+        Some(Value::String(f)) if f == "tree" => true,
+        Some(_) => {
+            return Err(ErrorData::invalid_params(
+                "Field 'format' must be \"raw\" or \"tree\"",
+                None,
+            ))
+        }
+    };
+    let max_nodes = optional_u64(args, "maxNodes", MAX_TREE_NODES)?
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_TREE_NODES);
+    let mut payload = json!({"expression": expression, "context": context});
+    if let Some(fid) = frame_id {
+        payload["frameId"] = json!(fid);
+    }
+    let mut result = manager
+        .request("evaluate", payload, adapter_cmd)
+        .map_err(|e| match frame_id {
+            Some(_) => ErrorData::internal_error(format!("dap error: {e}"), None),
+            None => ErrorData::internal_error(
+                format!("dap error: {e} (no frameId was given and no frame is selected; if the expression uses locals, pass a frameId from dap_stack_trace or select one with dap_select_frame)"),
+                Some(json!({"missingFrameId": true, "context": context})),
+            ),
+        })?;
+    if let Some(body) = result.as_object_mut() {
+        // Required by the spec, but some adapters omit it for plain values.
+        body.entry("variablesReference").or_insert(json!(0));
+    }
+    let mut response = json!({
+        "tool": "dap_evaluate",
+        "status": "ok",
+        "context": context,
+        "frameId": frame_id,
+        "result": result
+    });
+    if tree {
+        response["tree"] = manager
+            .value_tree(&result, max_nodes as usize, adapter_cmd)
+            .map_err(|e| ErrorData::internal_error(format!("dap error: {e}"), None))?;
+    }
+    Ok(CallToolResult::structured(response))
+}
+
+/// `FunctionBreakpoint`s from `breakpoints`: each needs a non-empty `name`; `condition` and
+/// `hitCondition` must be strings when given.
+fn function_breakpoints(args: &JsonObject) -> Result<Vec<Value>, ErrorData> {
+    let Some(Value::Array(items)) = args.get("breakpoints") else {
+        return Err(ErrorData::invalid_params(
+            "Missing required field: breakpoints (array)",
+            None,
+        ));
+    };
+    items
+        .iter()
+        .map(|bp| {
+            let invalid = |msg: &str| {
+                ErrorData::invalid_params(msg.to_string(), Some(json!({"breakpoint": bp})))
+            };
+            let obj = bp
+                .as_object()
+                .ok_or_else(|| invalid("Function breakpoint entries must be objects"))?;
+            let name = obj
+                .get("name")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.trim().is_empty())
+                .ok_or_else(|| invalid("Function breakpoint name must be a non-empty string"))?;
+            let mut out = json!({"name": name});
+            for key in ["condition", "hitCondition"] {
+                match obj.get(key) {
+                    None | Some(Value::Null) => {}
+                    Some(Value::String(s)) => out[key] = json!(s),
+                    Some(_) => {
+                        return Err(invalid(&format!(
+                            "Function breakpoint {key} must be a string"
+                        )))
+                    }
+                }
+            }
+            Ok(out)
+        })
+        .collect()
+}
+
+fn validate_breakpoint(bp: &Value) -> Result<(), ErrorData> {
+    let invalid =
+        |msg: &str| ErrorData::invalid_params(msg.to_string(), Some(json!({"breakpoint": bp})));
+    let obj = bp
+        .as_object()
+        .ok_or_else(|| invalid("Breakpoint entries must be objects"))?;
+    match obj.get("line").and_then(|v| v.as_i64()) {
+        Some(line) if line >= 1 => {}
+        Some(_) => return Err(invalid("Breakpoint line must be >= 1")),
+        None => return Err(invalid("Breakpoint line must be an integer")),
+    }
+    match obj.get("column") {
+        None | Some(Value::Null) => Ok(()),
+        Some(col) => match col.as_i64() {
+            Some(c) if c >= 1 => Ok(()),
+            Some(_) => Err(invalid("Breakpoint column must be >= 1")),
+            None => Err(invalid("Breakpoint column must be an integer")),
+        },
+    }
+}
+
+/// Build `SourceBreakpoint` objects from `lines` (integers or `{line, column}` entries) and an
+/// optional parallel `columns` array.
+fn breakpoints_from_lines(args: &JsonObject) -> Result<Value, ErrorData> {
+    let lines = match args.get("lines") {
+        None => return Ok(json!([])),
+        Some(Value::Array(lines)) => lines,
+        Some(_) => {
+            return Err(ErrorData::invalid_params(
+                "Field 'lines' must be an array",
+                None,
+            ))
+        }
+    };
+    let columns = match args.get("columns") {
+        None => None,
+        Some(Value::Array(cols)) if cols.len() == lines.len() => Some(cols),
+        Some(Value::Array(_)) => {
+            return Err(ErrorData::invalid_params(
+                "Field 'columns' must have the same length as 'lines'",
+                None,
+            ))
+        }
+        Some(_) => {
+            return Err(ErrorData::invalid_params(
+                "Field 'columns' must be an array",
+                None,
+            ))
+        }
+    };
+    let mut out = Vec::with_capacity(lines.len());
+    for (i, entry) in lines.iter().enumerate() {
+        let mut bp = match entry {
+            Value::Object(_) => entry.clone(),
+            other => json!({"line": other}),
+        };
+        if let Some(col) = columns
+            .and_then(|cols| cols.get(i))
+            .filter(|c| !c.is_null())
+        {
+            bp.as_object_mut()
+                .unwrap()
+                .insert("column".into(), col.clone());
+        }
+        validate_breakpoint(&bp)?;
+        out.push(bp);
+    }
+    Ok(Value::Array(out))
+}
+
+/// `cancel` arguments from `dap_cancel`; at least one of `requestId` and `progressId`.
+fn cancel_arguments(args: &JsonObject) -> Result<Value, ErrorData> {
+    let mut arguments = json!({});
+    if let Some(id) = args.get("requestId").filter(|v| !v.is_null()) {
+        let id = id.as_i64().ok_or_else(|| {
+            ErrorData::invalid_params("Field 'requestId' must be an integer", None)
+        })?;
+        arguments["requestId"] = json!(id);
+    }
+    if let Some(id) = args.get("progressId").filter(|v| !v.is_null()) {
+        let id = id.as_str().ok_or_else(|| {
+            ErrorData::invalid_params("Field 'progressId' must be a string", None)
+        })?;
+        arguments["progressId"] = json!(id);
+    }
+    if arguments.as_object().is_some_and(|a| a.is_empty()) {
+        return Err(ErrorData::invalid_params(
+            "dap_cancel needs requestId or progressId when no request is in flight",
+            None,
+        ));
+    }
+    Ok(arguments)
+}
+
 fn require_i64(args: &JsonObject, key: &str) -> Result<i64, ErrorData> {
     args.get(key)
         .and_then(|v| v.as_i64())
         .ok_or_else(|| ErrorData::invalid_params(format!("Missing required field: {key}"), None))
 }
 
+/// An optional integer field; `null` counts as missing.
+fn optional_i64(args: &JsonObject, key: &str) -> Result<Option<i64>, ErrorData> {
+    match args.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => v.as_i64().map(Some).ok_or_else(|| {
+            ErrorData::invalid_params(format!("Field '{key}' must be an integer"), None)
+        }),
+    }
+}
+
+/// An optional array of strings; `null` is kept apart from a missing field as `Some(None)`.
+fn optional_strings(
+    args: &JsonObject,
+    key: &str,
+) -> Result<Option<Option<Vec<String>>>, ErrorData> {
+    match args.get(key) {
+        None => Ok(None),
+        Some(Value::Null) => Ok(Some(None)),
+        Some(Value::Array(items)) => items
+            .iter()
+            .map(|v| v.as_str().map(str::to_string))
+            .collect::<Option<Vec<_>>>()
+            .map(|names| Some(Some(names)))
+            .ok_or_else(|| {
+                ErrorData::invalid_params(format!("Field '{key}' must contain only strings"), None)
+            }),
+        Some(_) => Err(ErrorData::invalid_params(
+            format!("Field '{key}' must be an array of strings"),
+            None,
+        )),
+    }
+}
+
+fn optional_u64(args: &JsonObject, key: &str, max: u64) -> Result<Option<u64>, ErrorData> {
+    match args.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(v) => match v.as_u64() {
+            Some(n) if n <= max => Ok(Some(n)),
+            _ => Err(ErrorData::invalid_params(
+                format!("Field '{key}' must be an integer between 0 and {max}"),
+                None,
+            )),
+        },
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     mcp::run().await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session_arguments_require_and_default_known_adapter_fields() {
+        let cases: &[(&str, Option<&str>, Value, Option<Value>)] = &[
+            // debugpy launches get the internal console unless one is chosen.
+            (
+                "launch",
+                Some("/usr/bin/debugpy-adapter"),
+                json!({"program": "app.py"}),
+                Some(json!({"program": "app.py", "console": "internalConsole"})),
+            ),
+            (
+                "launch",
+                Some("debugpy"),
+                json!({"module": "pkg", "console": "integratedTerminal"}),
+                Some(json!({"module": "pkg", "console": "integratedTerminal"})),
+            ),
+            // Defaults apply to launch only.
+            (
+                "attach",
+                Some("debugpy"),
+                json!({"processId": 42}),
+                Some(json!({"processId": 42})),
+            ),
+            ("launch", Some("debugpy"), json!({"cwd": "/work"}), None),
+            // A null field does not count as present.
+            ("launch", Some("lldb-dap"), json!({"program": null}), None),
+            ("attach", Some("dlv"), json!({"pid": 7}), None),
+            // Unknown adapters, no adapter, and non-object arguments pass through.
+            ("launch", Some("my-adapter"), json!({}), Some(json!({}))),
+            ("launch", None, json!({}), Some(json!({}))),
+            ("launch", Some("debugpy"), json!(null), Some(json!(null))),
+        ];
+        for (request, adapter, arguments, expected) in cases {
+            let mut prepared = arguments.clone();
+            let outcome = prepare_session_arguments(request, &mut prepared, *adapter);
+            match expected {
+                Some(expected) => {
+                    assert!(outcome.is_ok(), "{request} {adapter:?} {arguments}");
+                    assert_eq!(&prepared, expected, "{request} {adapter:?}");
+                }
+                None => {
+                    let err = outcome.expect_err("missing required field");
+                    assert_eq!(err.code, rmcp::model::ErrorCode::INVALID_PARAMS);
+                }
+            }
+        }
+
+        let mut arguments = json!({"cwd": "/work"});
+        let err = prepare_session_arguments("launch", &mut arguments, Some("debugpy"))
+            .expect_err("debugpy launch without a target");
+        assert_eq!(
+            err.message,
+            "debugpy launch needs one of program, module, code in arguments"
+        );
+        let err = prepare_session_arguments("launch", &mut json!({}), Some("codelldb"))
+            .expect_err("codelldb launch without a target");
+        assert_eq!(
+            err.data,
+            Some(
+                json!({"adapter": "codelldb", "request": "launch", "missing": ["program", "cargo"]})
+            )
+        );
+    }
+
+    #[test]
+    fn breakpoints_from_lines_validate_lines_and_columns() {
+        let args = |value: Value| value.as_object().cloned().expect("object arguments");
+        let accepted = [
+            (json!({}), json!([])),
+            (json!({"lines": []}), json!([])),
+            (
+                json!({"lines": [3, 10]}),
+                json!([{"line": 3}, {"line": 10}]),
+            ),
+            (
+                json!({"lines": [3, {"line": 4, "column": 2, "condition": "x > 1"}]}),
+                json!([{"line": 3}, {"line": 4, "column": 2, "condition": "x > 1"}]),
+            ),
+            // `columns` runs parallel to `lines`; null leaves an entry's own column alone.
+            (
+                json!({"lines": [3, {"line": 4, "column": 2}, 5], "columns": [7, null, 1]}),
+                json!([{"line": 3, "column": 7}, {"line": 4, "column": 2}, {"line": 5, "column": 1}]),
+            ),
+            (
+                json!({"lines": [{"line": 4, "column": 2}], "columns": [9]}),
+                json!([{"line": 4, "column": 9}]),
+            ),
+            (
+                json!({"lines": [{"line": 1, "column": null}]}),
+                json!([{"line": 1, "column": null}]),
+            ),
+        ];
+        for (input, expected) in accepted {
+            let built = breakpoints_from_lines(&args(input.clone()));
+            assert_eq!(built.ok(), Some(expected), "{input}");
+        }
+
+        let rejected = [
+            (json!({"lines": 3}), "Field 'lines' must be an array"),
+            (
+                json!({"lines": [1, 2], "columns": [1]}),
+                "Field 'columns' must have the same length as 'lines'",
+            ),
+            (
+                json!({"lines": [1], "columns": 1}),
+                "Field 'columns' must be an array",
+            ),
+            (json!({"lines": [0]}), "Breakpoint line must be >= 1"),
+            (json!({"lines": [-2]}), "Breakpoint line must be >= 1"),
+            (
+                json!({"lines": ["7"]}),
+                "Breakpoint line must be an integer",
+            ),
+            (
+                json!({"lines": [1.5]}),
+                "Breakpoint line must be an integer",
+            ),
+            (
+                json!({"lines": [{"column": 1}]}),
+                "Breakpoint line must be an integer",
+            ),
+            (
+                json!({"lines": [1], "columns": [0]}),
+                "Breakpoint column must be >= 1",
+            ),
+            (
+                json!({"lines": [{"line": 1, "column": "2"}]}),
+                "Breakpoint column must be an integer",
+            ),
+        ];
+        for (input, message) in rejected {
+            let err =
+                breakpoints_from_lines(&args(input.clone())).expect_err("invalid breakpoints");
+            assert_eq!(err.message, message, "{input}");
+        }
+    }
+}
@@ -9,12 +9,22 @@ use rmcp::{
 };
 use serde_json::json;
 use tokio::task;
-use std::sync::{Arc, Mutex};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, TryLockError};
 
+use crate::da::CancelHandle;
 use crate::{handle_structured_call, DapAdapterManager};
-use crate::list_tools_impl;
+use crate::{list_tools_impl, optional_i64, optional_strings, optional_u64, unsupported_report};
+use crate::{DEFAULT_CONTINUE_ITERATIONS, DEFAULT_STATUS_OUTPUT, MAX_CONTINUE_ITERATIONS};
 
-fn call_tool_impl(request: CallToolRequestParam, manager: &mut DapAdapterManager) -> Result<CallToolResult, ErrorData> {
+/// Session used by tool calls without a `sessionId`.
+const DEFAULT_SESSION: &str = "default";
+
+fn call_tool_impl(
+    request: CallToolRequestParam,
+    session_id: &str,
+    manager: &mut DapAdapterManager,
+) -> Result<CallToolResult, ErrorData> {
     let CallToolRequestParam { name, arguments } = request;
     if !name.starts_with("dap_") {
         return Err(ErrorData::method_not_found::<
@@ -26,12 +36,25 @@ fn call_tool_impl(request: CallToolRequestParam, manager: &mut DapAdapterManager
 
     match name.as_ref() {
         "dap_initialize" => {
+            match args.get("initializeArguments") {
+                None | Some(serde_json::Value::Null) => {}
+                Some(overrides @ serde_json::Value::Object(_)) => {
+                    manager.set_initialize_overrides(overrides.clone());
+                }
+                Some(_) => {
+                    return Err(ErrorData::invalid_params(
+                        "Field 'initializeArguments' must be an object",
+                        None,
+                    ))
+                }
+            }
             let res = manager
                 .capabilities(adapter_cmd)
                 .map_err(|e| ErrorData::internal_error(format!("dap init error: {e}"), None))?;
             Ok(CallToolResult::structured(json!({
                 "tool": "dap_initialize",
                 "status": "ok",
+                "sessionId": session_id,
                 "capabilities": res
             })))
         }
@@ -52,10 +75,283 @@ fn call_tool_impl(request: CallToolRequestParam, manager: &mut DapAdapterManager
                 "result": result
             })))
         }
+        "dap_output" => {
+            let category = args.get("category").and_then(|v| v.as_str());
+            let limit = optional_u64(&args, "limit", u64::MAX)?.map(|n| n as usize);
+            let events = manager.output_events(category, limit);
+            let text: String = events
+                .iter()
+                .filter_map(|e| e.get("output").and_then(|o| o.as_str()))
+                .collect();
+            Ok(CallToolResult::structured(json!({
+                "tool": "dap_output",
+                "status": "ok",
+                "count": events.len(),
+                "text": text,
+                "events": events
+            })))
+        }
+        "dap_set_event_filter" => {
+            let allow = optional_strings(&args, "allow")?;
+            let deny = optional_strings(&args, "deny")?.map(Option::unwrap_or_default);
+            let reset = args.get("reset").and_then(|v| v.as_bool()).unwrap_or(false);
+            Ok(CallToolResult::structured(json!({
+                "tool": "dap_set_event_filter",
+                "status": "ok",
+                "filter": manager.set_event_filter(allow, deny, reset)
+            })))
+        }
+        "dap_thread_states" => {
+            let threads = manager
+                .thread_states(adapter_cmd)
+                .map_err(|e| ErrorData::internal_error(format!("dap error: {e}"), None))?;
+            Ok(CallToolResult::structured(json!({
+                "tool": "dap_thread_states",
+                "status": "ok",
+                "threads": threads
+            })))
+        }
+        "dap_stop_summary" => {
+            let thread_id = args.get("threadId").and_then(|v| v.as_i64());
+            let summary = manager
+                .stop_summary(thread_id, adapter_cmd)
+                .map_err(|e| ErrorData::internal_error(format!("dap error: {e}"), None))?;
+            Ok(CallToolResult::structured(json!({
+                "tool": "dap_stop_summary",
+                "status": "ok",
+                "summary": summary
+            })))
+        }
+        "dap_select_frame" => {
+            if args.get("clear").and_then(|v| v.as_bool()) == Some(true) {
+                return Ok(CallToolResult::structured(json!({
+                    "tool": "dap_select_frame",
+                    "status": "ok",
+                    "result": {"cleared": manager.clear_frame_selection()}
+                })));
+            }
+            let integer = |key: &str| match args.get(key) {
+                None | Some(serde_json::Value::Null) => Ok(None),
+                Some(v) => v.as_i64().map(Some).ok_or_else(|| {
+                    ErrorData::invalid_params(format!("Field '{key}' must be an integer"), None)
+                }),
+            };
+            let thread_id = integer("threadId")?;
+            let frame_id = integer("frameId")?;
+            let level = match integer("level")? {
+                Some(level) if level < 0 => {
+                    return Err(ErrorData::invalid_params(
+                        "Field 'level' must not be negative",
+                        None,
+                    ))
+                }
+                level => level.map(|l| l as usize),
+            };
+            if frame_id.is_some() && level.is_some() {
+                return Err(ErrorData::invalid_params(
+                    "Pass either frameId or level, not both",
+                    None,
+                ));
+            }
+            let result = manager
+                .select_frame(thread_id, frame_id, level, adapter_cmd)
+                .map_err(|e| ErrorData::internal_error(format!("dap error: {e}"), None))?;
+            Ok(CallToolResult::structured(json!({
+                "tool": "dap_select_frame",
+                "status": "ok",
+                "result": result
+            })))
+        }
+        "dap_watch" => {
+            let expressions: Vec<String> = match args.get("expressions") {
+                Some(serde_json::Value::Array(items)) => items
+                    .iter()
+                    .map(|v| v.as_str().map(str::to_string))
+                    .collect::<Option<_>>()
+                    .ok_or_else(|| {
+                        ErrorData::invalid_params(
+                            "Field 'expressions' must contain only strings",
+                            None,
+                        )
+                    })?,
+                _ => {
+                    return Err(ErrorData::invalid_params(
+                        "Missing required field: expressions (array of strings)",
+                        None,
+                    ))
+                }
+            };
+            let frame_id = optional_i64(&args, "frameId")?;
+            let result = manager
+                .watch(&expressions, frame_id, adapter_cmd)
+                .map_err(|e| ErrorData::internal_error(format!("dap error: {e}"), None))?;
+            Ok(CallToolResult::structured(json!({
+                "tool": "dap_watch",
+                "status": "ok",
+                "result": result
+            })))
+        }
+        "dap_get_variable" => {
+            let name = args
+                .get("name")
+                .and_then(|v| v.as_str())
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .ok_or_else(|| ErrorData::invalid_params("Missing required field: name", None))?;
+            if name.split('.').any(|segment| segment.trim().is_empty()) {
+                return Err(ErrorData::invalid_params(
+                    "Field 'name' must not contain empty segments",
+                    None,
+                ));
+            }
+            let frame_id = optional_i64(&args, "frameId")?;
+            let thread_id = optional_i64(&args, "threadId")?;
+            let result = manager
+                .get_variable(name, frame_id, thread_id, adapter_cmd)
+                .map_err(|e| ErrorData::internal_error(format!("dap error: {e}"), None))?;
+            Ok(CallToolResult::structured(json!({
+                "tool": "dap_get_variable",
+                "status": "ok",
+                "result": result
+            })))
+        }
+        "dap_set_breakpoint_hit_condition" => {
+            let path = args
+                .get("source")
+                .and_then(|s| s.get("path"))
+                .and_then(|p| p.as_str())
+                .ok_or_else(|| {
+                    ErrorData::invalid_params("Missing required field: source.path", None)
+                })?;
+            let line = args
+                .get("line")
+                .and_then(|v| v.as_i64())
+                .filter(|n| *n >= 1)
+                .ok_or_else(|| {
+                    ErrorData::invalid_params("Field 'line' must be an integer >= 1", None)
+                })?;
+            let hit_condition = match args.get("hitCondition") {
+                None | Some(serde_json::Value::Null) => None,
+                Some(serde_json::Value::String(s)) if s.trim().is_empty() => None,
+                Some(serde_json::Value::String(s)) => Some(s.as_str()),
+                Some(_) => {
+                    return Err(ErrorData::invalid_params(
+                        "Field 'hitCondition' must be a string or null",
+                        None,
+                    ))
+                }
+            };
+            let result = manager
+                .set_breakpoint_hit_condition(path, line, hit_condition, adapter_cmd)
+                .map_err(|e| ErrorData::internal_error(format!("dap error: {e}"), None))?;
+            Ok(CallToolResult::structured(json!({
+                "tool": "dap_set_breakpoint_hit_condition",
+                "status": "ok",
+                "result": result
+            })))
+        }
+        "dap_continue_until" => {
+            let thread_id = args
+                .get("threadId")
+                .and_then(|v| v.as_i64())
+                .ok_or_else(|| {
+                    ErrorData::invalid_params("Missing required field: threadId", None)
+                })?;
+            let expression = args
+                .get("expression")
+                .and_then(|v| v.as_str())
+                .filter(|s| !s.trim().is_empty())
+                .ok_or_else(|| {
+                    ErrorData::invalid_params("Missing required field: expression", None)
+                })?;
+            let max_iterations = match args.get("maxIterations") {
+                None | Some(serde_json::Value::Null) => DEFAULT_CONTINUE_ITERATIONS,
+                Some(v) => v
+                    .as_u64()
+                    .filter(|n| (1..=MAX_CONTINUE_ITERATIONS).contains(n))
+                    .ok_or_else(|| {
+                        ErrorData::invalid_params(
+                            format!(
+                                "Field 'maxIterations' must be an integer between 1 and {MAX_CONTINUE_ITERATIONS}"
+                            ),
+                            None,
+                        )
+                    })?,
+            };
+            let result = manager
+                .continue_until(thread_id, expression, max_iterations, adapter_cmd)
+                .map_err(|e| ErrorData::internal_error(format!("dap error: {e}"), None))?;
+            Ok(CallToolResult::structured(json!({
+                "tool": "dap_continue_until",
+                "status": "ok",
+                "result": result
+            })))
+        }
+        "dap_status" => {
+            let limit = optional_u64(&args, "outputLimit", u64::MAX)?
+                .unwrap_or(DEFAULT_STATUS_OUTPUT) as usize;
+            Ok(CallToolResult::structured(json!({
+                "tool": "dap_status",
+                "status": "ok",
+                "session": manager.status(limit, adapter_cmd)
+            })))
+        }
+        "dap_list_breakpoints" => {
+            let path = args.get("path").and_then(|v| v.as_str());
+            Ok(CallToolResult::structured(json!({
+                "tool": "dap_list_breakpoints",
+                "status": "ok",
+                "result": manager.list_breakpoints(path, adapter_cmd)
+            })))
+        }
+        "dap_exit_status" => Ok(CallToolResult::structured(json!({
+            "tool": "dap_exit_status",
+            "status": "ok",
+            "result": manager.exit_status()
+        }))),
+        "dap_shutdown" => Ok(CallToolResult::structured(json!({
+            "tool": "dap_shutdown",
+            "status": "ok",
+            "result": manager.shutdown()
+        }))),
+        "dap_unsupported" => {
+            let caps = manager
+                .capabilities(adapter_cmd)
+                .map_err(|e| ErrorData::internal_error(format!("dap init error: {e}"), None))?;
+            Ok(CallToolResult::structured(json!({
+                "tool": "dap_unsupported",
+                "status": "ok",
+                "unsupported": unsupported_report(caps.as_ref())
+            })))
+        }
         other => handle_structured_call(other, &args, adapter_cmd, manager),
     }
 }
 
+/// `dap_cancel` while another tool call holds the manager: send `cancel` straight to the adapter
+/// so the blocked call returns once the adapter answers, instead of queueing behind it.
+fn cancel_in_flight(
+    request: CallToolRequestParam,
+    cancel: &CancelHandle,
+) -> Result<CallToolResult, ErrorData> {
+    let args = request.arguments.unwrap_or_default();
+    let request_id = match args.get("requestId") {
+        None | Some(serde_json::Value::Null) => None,
+        Some(v) => Some(v.as_i64().ok_or_else(|| {
+            ErrorData::invalid_params("Field 'requestId' must be an integer", None)
+        })?),
+    };
+    let progress_id = args.get("progressId").and_then(|v| v.as_str());
+    let sent = cancel
+        .cancel(request_id, progress_id)
+        .map_err(|e| ErrorData::internal_error(format!("dap error: {e}"), None))?;
+    Ok(CallToolResult::structured(json!({
+        "tool": "dap_cancel",
+        "status": "sent",
+        "result": sent
+    })))
+}
+
 fn server_info() -> ServerInfo {
     ServerInfo {
         instructions: Some(
@@ -66,9 +362,92 @@ fn server_info() -> ServerInfo {
     }
 }
 
+/// One debug session: its own adapter process, breakpoints, and event state.
 #[derive(Clone)]
-struct CodexDapServer {
+struct Session {
     manager: Arc<Mutex<DapAdapterManager>>,
+    cancel: CancelHandle,
+}
+
+impl Session {
+    fn new() -> Self {
+        let manager = DapAdapterManager::new();
+        Self {
+            cancel: manager.cancel_handle(),
+            manager: Arc::new(Mutex::new(manager)),
+        }
+    }
+}
+
+/// Debug sessions by `sessionId`. The default session always exists; named ones are created by
+/// `dap_initialize` and dropped by `dap_shutdown`.
+struct Sessions {
+    by_id: BTreeMap<String, Session>,
+    next_id: u64,
+}
+
+impl Sessions {
+    fn new() -> Self {
+        let mut by_id = BTreeMap::new();
+        by_id.insert(DEFAULT_SESSION.to_string(), Session::new());
+        Self { by_id, next_id: 1 }
+    }
+
+    /// Pick the session a tool call targets. Only `dap_initialize` creates sessions: for an
+    /// unknown `sessionId`, or under a generated id with `newSession: true`.
+    fn resolve(&mut self, request: &CallToolRequestParam) -> Result<(String, Session), ErrorData> {
+        let args = request.arguments.as_ref();
+        let requested = match args.and_then(|a| a.get("sessionId")) {
+            None | Some(serde_json::Value::Null) => None,
+            Some(serde_json::Value::String(s)) if !s.trim().is_empty() => Some(s.clone()),
+            Some(_) => {
+                return Err(ErrorData::invalid_params(
+                    "Field 'sessionId' must be a non-empty string",
+                    None,
+                ))
+            }
+        };
+        let initialize = request.name == "dap_initialize";
+        let new_session = initialize
+            && args
+                .and_then(|a| a.get("newSession"))
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+        let id = match requested {
+            Some(id) => id,
+            None if new_session => loop {
+                let id = format!("session-{}", self.next_id);
+                self.next_id += 1;
+                if !self.by_id.contains_key(&id) {
+                    break id;
+                }
+            },
+            None => DEFAULT_SESSION.to_string(),
+        };
+        if let Some(session) = self.by_id.get(&id) {
+            return Ok((id, session.clone()));
+        }
+        if !initialize {
+            return Err(ErrorData::invalid_params(
+                format!("Unknown sessionId '{id}'; create it with dap_initialize"),
+                Some(json!({"sessionId": id, "sessions": self.by_id.keys().collect::<Vec<_>>()})),
+            ));
+        }
+        let session = Session::new();
+        self.by_id.insert(id.clone(), session.clone());
+        Ok((id, session))
+    }
+}
+
+#[derive(Clone)]
+struct CodexDapServer {
+    sessions: Arc<Mutex<Sessions>>,
+}
+
+impl CodexDapServer {
+    fn default_session(&self) -> Session {
+        self.sessions.lock().unwrap().by_id[DEFAULT_SESSION].clone()
+    }
 }
 
 impl ServerHandler for CodexDapServer {
@@ -81,7 +460,7 @@ impl ServerHandler for CodexDapServer {
         _request: Option<PaginatedRequestParam>,
         _context: RequestContext<RoleServer>,
     ) -> Result<ListToolsResult, ErrorData> {
-        let manager = self.manager.clone();
+        let manager = self.default_session().manager;
         let tools = task::spawn_blocking(move || {
             let mut guard = manager.lock().unwrap();
             list_tools_impl(&mut guard)
@@ -96,18 +475,33 @@ impl ServerHandler for CodexDapServer {
         request: CallToolRequestParam,
         _context: RequestContext<RoleServer>,
     ) -> Result<CallToolResult, ErrorData> {
-        let manager = self.manager.clone();
-        task::spawn_blocking(move || {
+        let (session_id, Session { manager, cancel }) =
+            self.sessions.lock().unwrap().resolve(&request)?;
+        // A named session ends with its adapter; the default one is reused after a shutdown.
+        let forget = (request.name == "dap_shutdown" && session_id != DEFAULT_SESSION)
+            .then(|| session_id.clone());
+        let result = task::spawn_blocking(move || {
+            if request.name == "dap_cancel" {
+                if let Err(TryLockError::WouldBlock) = manager.try_lock() {
+                    return cancel_in_flight(request, &cancel);
+                }
+            }
             let mut guard = manager.lock().unwrap();
-            call_tool_impl(request, &mut guard)
+            call_tool_impl(request, &session_id, &mut guard)
         })
             .await
-            .map_err(|e| ErrorData::internal_error(format!("call tool task panicked: {e}"), None))?
+            .map_err(|e| ErrorData::internal_error(format!("call tool task panicked: {e}"), None))?;
+        if let Some(id) = forget {
+            self.sessions.lock().unwrap().by_id.remove(&id);
+        }
+        result
     }
 }
 
 pub async fn run() -> Result<()> {
-    let server = CodexDapServer { manager: Arc::new(Mutex::new(DapAdapterManager::new())) };
+    let server = CodexDapServer {
+        sessions: Arc::new(Mutex::new(Sessions::new())),
+    };
     let running = server.serve(rmcp::transport::stdio()).await?;
     running.waiting().await?;
     Ok(())
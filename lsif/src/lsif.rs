@@ -29,21 +29,79 @@ fn contains(span: Span, p: Pos) -> bool {
 
 pub struct LSIFIndex {
     // vertices
-    documents: HashMap<i64, String>,  // id -> uri
-    doc_by_uri: HashMap<String, i64>, // uri -> id
-    ranges: HashMap<i64, Span>,       // id -> span
-    range_doc: HashMap<i64, i64>,     // range id -> doc id
-    result_sets: HashSet<i64>,        // ids that are resultSet vertices
+    documents: HashMap<i64, String>, // id -> uri (resolved against its project root)
+    doc_by_uri: HashMap<String, Vec<i64>>, // resolved uri -> ids, one per project that has it
+    projects: HashMap<i64, Project>, // id -> project vertex
+    doc_project: HashMap<i64, i64>,  // document id -> project id
+    project_root: Option<String>,    // metaData.projectRoot, for documents outside projects
+    ranges: HashMap<i64, Span>,      // id -> span
+    range_doc: HashMap<i64, i64>,    // range id -> doc id
+    result_sets: HashSet<i64>,       // ids that are resultSet vertices
     // edges
     range_to_resultset: HashMap<i64, i64>, // range id -> resultSet id
     rset_to_def: HashMap<i64, i64>,        // resultSet id -> definitionResult id
     rset_to_ref: HashMap<i64, i64>,        // resultSet id -> referenceResult id
     range_to_def: HashMap<i64, i64>,       // fallback: range id -> definitionResult id
     range_to_ref: HashMap<i64, i64>,       // fallback: range id -> referenceResult id
+    rset_to_hover: HashMap<i64, i64>,      // resultSet id -> hoverResult id
+    range_to_hover: HashMap<i64, i64>,     // fallback: range id -> hoverResult id
     // results
     def_items: HashMap<i64, Vec<i64>>, // definitionResult id -> [range ids]
     ref_items: HashMap<i64, RefItems>, // referenceResult id -> split items
     hover_results: HashMap<i64, Value>, // hoverResult id -> result payload
+    // load metadata
+    source: Option<String>, // path of the loaded dump
+    dangling: Dangling,     // filled by finalize()
+}
+
+/// Edge endpoints that point at vertices missing from the dump. Any non-zero count means some
+/// queries will come back empty because of the data, not the position asked about.
+#[derive(Default, Clone, Copy)]
+struct Dangling {
+    result_ids: usize, // definition/reference/hover edges to a result never ingested
+    range_ids: usize,  // edges or items naming a range id never ingested
+    result_set_ids: usize, // `next` edges to a resultSet id never ingested
+    orphan_ranges: usize, // ranges no document `contains`
+}
+
+impl Dangling {
+    fn total(&self) -> usize {
+        self.result_ids + self.range_ids + self.result_set_ids + self.orphan_ranges
+    }
+
+    fn to_json(self) -> Value {
+        json!({
+            "resultIds": self.result_ids,
+            "rangeIds": self.range_ids,
+            "resultSetIds": self.result_set_ids,
+            "orphanRanges": self.orphan_ranges,
+            "total": self.total()
+        })
+    }
+}
+
+struct Project {
+    name: Option<String>,
+    root: Option<String>,
+}
+
+impl Project {
+    /// Whether `selector` names this project, by `name` or by vertex id.
+    fn matches(&self, id: i64, selector: &str) -> bool {
+        self.name.as_deref() == Some(selector) || id.to_string() == selector
+    }
+}
+
+/// Resolve a document URI against a project root. Absolute URIs are returned unchanged.
+fn resolve_uri(root: Option<&str>, uri: &str) -> String {
+    match root {
+        Some(root) if !uri.contains("://") && !uri.starts_with('/') => format!(
+            "{}/{}",
+            root.trim_end_matches('/'),
+            uri.trim_start_matches("./")
+        ),
+        _ => uri.to_string(),
+    }
 }
 
 #[derive(Default)]
@@ -58,6 +116,9 @@ impl LSIFIndex {
         Self {
             documents: HashMap::new(),
             doc_by_uri: HashMap::new(),
+            projects: HashMap::new(),
+            doc_project: HashMap::new(),
+            project_root: None,
             ranges: HashMap::new(),
             range_doc: HashMap::new(),
             result_sets: HashSet::new(),
@@ -66,9 +127,13 @@ impl LSIFIndex {
             rset_to_ref: HashMap::new(),
             range_to_def: HashMap::new(),
             range_to_ref: HashMap::new(),
+            rset_to_hover: HashMap::new(),
+            range_to_hover: HashMap::new(),
             def_items: HashMap::new(),
             ref_items: HashMap::new(),
             hover_results: HashMap::new(),
+            source: None,
+            dangling: Dangling::default(),
         }
     }
 
@@ -81,10 +146,27 @@ impl LSIFIndex {
                     {
                         if let Some(id) = idv.as_i64() {
                             self.documents.insert(id, uri.clone());
-                            self.doc_by_uri.insert(uri.clone(), id);
                         }
                     }
                 }
+                "project" => {
+                    if let Some(id) = v.get("id").and_then(|x| x.as_i64()) {
+                        let text =
+                            |key: &str| v.get(key).and_then(|x| x.as_str()).map(String::from);
+                        self.projects.insert(
+                            id,
+                            Project {
+                                name: text("name"),
+                                root: text("projectRoot").or_else(|| text("resource")),
+                            },
+                        );
+                    }
+                }
+                "metaData" => {
+                    if let Some(root) = v.get("projectRoot").and_then(|x| x.as_str()) {
+                        self.project_root = Some(root.to_string());
+                    }
+                }
                 "range" => {
                     if let Some(Value::Number(idv)) = v.get("id") {
                         if let Some(id) = idv.as_i64() {
@@ -145,7 +227,15 @@ impl LSIFIndex {
         match label {
             "contains" => {
                 let out = e.get("outV").and_then(|v| v.as_i64());
-                if let Some(doc_id) = out {
+                if let Some(project_id) = out.filter(|id| self.projects.contains_key(id)) {
+                    if let Some(Value::Array(invs)) = e.get("inVs") {
+                        for iv in invs {
+                            if let Some(did) = iv.as_i64() {
+                                self.doc_project.insert(did, project_id);
+                            }
+                        }
+                    }
+                } else if let Some(doc_id) = out {
                     if self.documents.contains_key(&doc_id) {
                         if let Some(Value::Array(invs)) = e.get("inVs") {
                             for iv in invs {
@@ -190,8 +280,16 @@ impl LSIFIndex {
                 }
             }
             "textDocument/hover" => {
-                // Note: minimal ingester doesn't wire hover edges; extend if needed.
-                let _ = e; // silence unused warning if not used
+                if let (Some(ov), Some(iv)) = (
+                    e.get("outV").and_then(|v| v.as_i64()),
+                    e.get("inV").and_then(|v| v.as_i64()),
+                ) {
+                    if self.result_sets.contains(&ov) {
+                        self.rset_to_hover.insert(ov, iv);
+                    } else {
+                        self.range_to_hover.insert(ov, iv);
+                    }
+                }
             }
             "item" => {
                 let outv = e.get("outV").and_then(|v| v.as_i64());
@@ -222,10 +320,126 @@ impl LSIFIndex {
         }
     }
 
-    fn finalize(&mut self) {}
+    /// Resolve document URIs against their project roots and index them, then count edge
+    /// endpoints that do not resolve to ingested vertices.
+    fn finalize(&mut self) {
+        for (did, uri) in self.documents.iter_mut() {
+            let root = match self.doc_project.get(did) {
+                Some(pid) => self.projects.get(pid).and_then(|p| p.root.as_deref()),
+                None => self.project_root.as_deref(),
+            };
+            *uri = resolve_uri(root, uri);
+            self.doc_by_uri.entry(uri.clone()).or_default().push(*did);
+        }
+        for ids in self.doc_by_uri.values_mut() {
+            ids.sort();
+        }
+
+        let mut d = Dangling::default();
+        let is_range_or_rset =
+            |id: &i64| self.ranges.contains_key(id) || self.result_sets.contains(id);
+
+        for (out, rset) in &self.range_to_resultset {
+            if !is_range_or_rset(out) {
+                d.range_ids += 1;
+            }
+            if !self.result_sets.contains(rset) {
+                d.result_set_ids += 1;
+            }
+        }
+        let fallback_edges = [&self.range_to_def, &self.range_to_ref, &self.range_to_hover];
+        d.range_ids += fallback_edges
+            .iter()
+            .flat_map(|m| m.keys())
+            .filter(|id| !self.ranges.contains_key(id))
+            .count();
+
+        let def_targets = self.rset_to_def.values().chain(self.range_to_def.values());
+        d.result_ids += def_targets
+            .filter(|id| !self.def_items.contains_key(id))
+            .count();
+        let ref_targets = self.rset_to_ref.values().chain(self.range_to_ref.values());
+        d.result_ids += ref_targets
+            .filter(|id| !self.ref_items.contains_key(id))
+            .count();
+        let hover_targets = self
+            .rset_to_hover
+            .values()
+            .chain(self.range_to_hover.values());
+        d.result_ids += hover_targets
+            .filter(|id| !self.hover_results.contains_key(id))
+            .count();
+
+        let item_ranges =
+            self.def_items
+                .values()
+                .flatten()
+                .chain(self.ref_items.values().flat_map(|r| {
+                    r.definitions
+                        .iter()
+                        .chain(&r.references)
+                        .chain(&r.declarations)
+                }));
+        d.range_ids += item_ranges
+            .filter(|id| !self.ranges.contains_key(id))
+            .count();
+
+        d.range_ids += self
+            .range_doc
+            .keys()
+            .filter(|id| !self.ranges.contains_key(id))
+            .count();
+        d.orphan_ranges = self
+            .ranges
+            .keys()
+            .filter(|id| !self.range_doc.contains_key(id))
+            .count();
+        self.dangling = d;
+    }
+
+    /// Document id for `uri`, narrowed to `project` (name or id) when given. A URI present in
+    /// several projects is an error unless `project` picks one.
+    fn document_for(&self, uri: &str, project: Option<&str>) -> Result<i64> {
+        let ids = self
+            .doc_by_uri
+            .get(uri)
+            .ok_or_else(|| anyhow!("document not in LSIF dump: {uri}"))?;
+        let project_of = |did: &i64| {
+            self.doc_project
+                .get(did)
+                .and_then(|pid| self.projects.get(pid).map(|p| (*pid, p)))
+        };
+        let candidates: Vec<i64> = match project {
+            Some(sel) => ids
+                .iter()
+                .copied()
+                .filter(|did| project_of(did).is_some_and(|(pid, p)| p.matches(pid, sel)))
+                .collect(),
+            None => ids.clone(),
+        };
+        match candidates.as_slice() {
+            [did] => Ok(*did),
+            [] => Err(anyhow!(
+                "document {uri} is not part of LSIF project {}",
+                project.unwrap_or_default()
+            )),
+            _ => {
+                let names: Vec<String> = candidates
+                    .iter()
+                    .map(|did| match project_of(did) {
+                        Some((pid, p)) => p.name.clone().unwrap_or_else(|| pid.to_string()),
+                        None => "<none>".to_string(),
+                    })
+                    .collect();
+                Err(anyhow!(
+                    "document {uri} appears in several LSIF projects ({}); pass project to choose one",
+                    names.join(", ")
+                ))
+            }
+        }
+    }
 
-    fn find_best_range(&self, uri: &str, pos: Pos) -> Option<i64> {
-        let did = *self.doc_by_uri.get(uri)?;
+    fn find_best_range(&self, did: i64, pos: Pos) -> Option<i64> {
         let mut best: Option<(i64, Span)> = None;
         for (rid, span) in self.ranges.iter() {
             if let Some(doc_id) = self.range_doc.get(rid) {
@@ -329,10 +543,35 @@ pub fn load_from_path(path: &str) -> Result<()> {
             }
         }
         idx.finalize();
+        idx.source = Some(path.to_string());
+        let d = idx.dangling;
+        if d.total() > 0 {
+            eprintln!(
+                "mcp-lsif: warning: {path} has dangling references ({} result ids, {} range ids, {} resultSet ids, {} ranges outside any document); affected queries will return empty results",
+                d.result_ids, d.range_ids, d.result_set_ids, d.orphan_ranges
+            );
+        }
         Ok(())
     })
 }
 
+/// Vertex counts and dangling-reference counts for the loaded dump.
+pub fn stats() -> Result<Value> {
+    with_index(|idx| {
+        Ok(json!({
+            "path": idx.source,
+            "projects": idx.projects.len(),
+            "documents": idx.documents.len(),
+            "ranges": idx.ranges.len(),
+            "resultSets": idx.result_sets.len(),
+            "definitionResults": idx.def_items.len(),
+            "referenceResults": idx.ref_items.len(),
+            "hoverResults": idx.hover_results.len(),
+            "dangling": idx.dangling.to_json()
+        }))
+    })
+}
+
 fn loc_json(uri: &str, span: Span) -> Value {
     json!({
         "uri": uri,
@@ -343,11 +582,17 @@ fn loc_json(uri: &str, span: Span) -> Value {
     })
 }
 
-pub fn query_definition(uri: &str, line: u32, character: u32) -> Result<Value> {
+pub fn query_definition(
+    uri: &str,
+    project: Option<&str>,
+    line: u32,
+    character: u32,
+) -> Result<Value> {
     with_index(|idx| {
         let pos = Pos { line, character };
+        let did = idx.document_for(uri, project)?;
         let rid = idx
-            .find_best_range(uri, pos)
+            .find_best_range(did, pos)
             .ok_or_else(|| anyhow!("no LSIF range at position"))?;
         let rset = idx.resultset_for_range(rid);
         let def_res = rset
@@ -371,14 +616,16 @@ pub fn query_definition(uri: &str, line: u32, character: u32) -> Result<Value> {
 
 pub fn query_references(
     uri: &str,
+    project: Option<&str>,
     line: u32,
     character: u32,
     include_declarations: bool,
 ) -> Result<Value> {
     with_index(|idx| {
         let pos = Pos { line, character };
+        let did = idx.document_for(uri, project)?;
         let rid = idx
-            .find_best_range(uri, pos)
+            .find_best_range(did, pos)
             .ok_or_else(|| anyhow!("no LSIF range at position"))?;
         let rset = idx.resultset_for_range(rid);
         let ref_res = rset
@@ -392,7 +639,176 @@ pub fn query_references(
     })
 }
 
-pub fn query_hover(uri: &str, line: u32, character: u32) -> Result<Value> {
-    let _ = (uri, line, character);
-    Err(anyhow!("hover not available in minimal ingester"))
+/// Convert a `file://` URI from the dump into a filesystem path, decoding percent escapes.
+fn path_from_uri(uri: &str) -> std::path::PathBuf {
+    let raw = uri.strip_prefix("file://").unwrap_or(uri);
+    let bytes = raw.as_bytes();
+    let hex = |b: u8| (b as char).to_digit(16);
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            if let (Some(hi), Some(lo)) = (hex(bytes[i + 1]), hex(bytes[i + 2])) {
+                decoded.push((hi * 16 + lo) as u8);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    std::path::PathBuf::from(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+enum HoverLookup {
+    Found(Value),
+    Definition(String, Span),
+    Missing,
+}
+
+/// Return the hover result recorded in the dump. When the dump has no hover data and
+/// `source_fallback` is set, read the definition's first line from disk and return it as a
+/// minimal `{ contents }` hover.
+pub fn query_hover(
+    uri: &str,
+    project: Option<&str>,
+    line: u32,
+    character: u32,
+    source_fallback: bool,
+) -> Result<Value> {
+    let lookup = with_index(|idx| {
+        let pos = Pos { line, character };
+        let did = idx.document_for(uri, project)?;
+        let rid = idx
+            .find_best_range(did, pos)
+            .ok_or_else(|| anyhow!("no LSIF range at position"))?;
+        let rset = idx.resultset_for_range(rid);
+        let hover = rset
+            .and_then(|rs| idx.rset_to_hover.get(&rs).copied())
+            .or_else(|| idx.range_to_hover.get(&rid).copied())
+            .and_then(|hid| idx.hover_results.get(&hid).cloned());
+        if let Some(result) = hover {
+            return Ok(HoverLookup::Found(result));
+        }
+        let def = rset
+            .and_then(|rs| idx.rset_to_def.get(&rs).copied())
+            .or_else(|| idx.range_to_def.get(&rid).copied())
+            .and_then(|def_id| idx.ranges_for_result(def_id).into_iter().next());
+        Ok(match def {
+            Some((def_uri, span)) => HoverLookup::Definition(def_uri, span),
+            None => HoverLookup::Missing,
+        })
+    })?;
+
+    match lookup {
+        HoverLookup::Found(result) => Ok(result),
+        HoverLookup::Definition(def_uri, span) if source_fallback => {
+            let path = path_from_uri(&def_uri);
+            let text = std::fs::read_to_string(&path)
+                .with_context(|| format!("read definition source {:?}", path))?;
+            let signature = text
+                .lines()
+                .nth(span.start.line as usize)
+                .map(|l| l.trim().to_string())
+                .ok_or_else(|| anyhow!("definition line {} not found in {:?}", span.start.line, path))?;
+            Ok(json!({
+                "contents": signature,
+                "range": loc_json(&def_uri, span).get("range").cloned(),
+                "synthesized": true
+            }))
+        }
+        HoverLookup::Definition(..) => Err(anyhow!(
+            "no hover result in LSIF dump; pass sourceFallback: true to synthesize one from the definition source"
+        )),
+        HoverLookup::Missing => Err(anyhow!("no hover result or definition for symbol")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn index_from(lines: &[Value]) -> LSIFIndex {
+        let mut idx = LSIFIndex::new();
+        for line in lines {
+            let map = line.as_object().expect("dump lines are objects");
+            match map.get("type").and_then(|t| t.as_str()) {
+                Some("vertex") => idx.add_vertex(map),
+                Some("edge") => idx.add_edge(map),
+                _ => {}
+            }
+        }
+        idx.finalize();
+        idx
+    }
+
+    /// Two projects that each contain `src/lib.rs`, with a definition inside each copy.
+    fn two_project_dump(doc_uri: &str) -> LSIFIndex {
+        let range = |id: i64, line: u32| {
+            json!({"id": id, "type": "vertex", "label": "range",
+                   "start": {"line": line, "character": 4}, "end": {"line": line, "character": 9}})
+        };
+        index_from(&[
+            json!({"id": 1, "type": "vertex", "label": "project", "name": "alpha", "projectRoot": "file:///ws/alpha"}),
+            json!({"id": 2, "type": "vertex", "label": "project", "name": "beta", "projectRoot": "file:///ws/beta"}),
+            json!({"id": 10, "type": "vertex", "label": "document", "uri": doc_uri}),
+            json!({"id": 20, "type": "vertex", "label": "document", "uri": doc_uri}),
+            range(11, 3),
+            range(21, 7),
+            json!({"id": 12, "type": "vertex", "label": "definitionResult"}),
+            json!({"id": 22, "type": "vertex", "label": "definitionResult"}),
+            json!({"id": 30, "type": "edge", "label": "contains", "outV": 1, "inVs": [10]}),
+            json!({"id": 31, "type": "edge", "label": "contains", "outV": 2, "inVs": [20]}),
+            json!({"id": 32, "type": "edge", "label": "contains", "outV": 10, "inVs": [11]}),
+            json!({"id": 33, "type": "edge", "label": "contains", "outV": 20, "inVs": [21]}),
+            json!({"id": 34, "type": "edge", "label": "textDocument/definition", "outV": 11, "inV": 12}),
+            json!({"id": 35, "type": "edge", "label": "textDocument/definition", "outV": 21, "inV": 22}),
+            json!({"id": 36, "type": "edge", "label": "item", "outV": 12, "inVs": [11], "document": 10}),
+            json!({"id": 37, "type": "edge", "label": "item", "outV": 22, "inVs": [21], "document": 20}),
+        ])
+    }
+
+    fn definition_line(idx: &LSIFIndex, did: i64, line: u32) -> Option<(String, u32)> {
+        let rid = idx.find_best_range(did, Pos { line, character: 5 })?;
+        let def = idx.range_to_def.get(&rid)?;
+        idx.ranges_for_result(*def)
+            .into_iter()
+            .next()
+            .map(|(uri, span)| (uri, span.start.line))
+    }
+
+    #[test]
+    fn relative_documents_resolve_against_their_project_root() -> Result<()> {
+        let idx = two_project_dump("src/lib.rs");
+
+        let alpha = idx.document_for("file:///ws/alpha/src/lib.rs", None)?;
+        let beta = idx.document_for("file:///ws/beta/src/lib.rs", None)?;
+        assert_eq!((alpha, beta), (10, 20));
+        assert!(idx.document_for("src/lib.rs", None).is_err());
+
+        assert_eq!(
+            definition_line(&idx, alpha, 3),
+            Some(("file:///ws/alpha/src/lib.rs".to_string(), 3))
+        );
+        assert_eq!(
+            definition_line(&idx, beta, 7),
+            Some(("file:///ws/beta/src/lib.rs".to_string(), 7))
+        );
+        assert_eq!(definition_line(&idx, alpha, 7), None);
+        Ok(())
+    }
+
+    #[test]
+    fn shared_uri_needs_a_project_selector() -> Result<()> {
+        let idx = two_project_dump("file:///ws/shared/src/lib.rs");
+        let uri = "file:///ws/shared/src/lib.rs";
+
+        let err = idx.document_for(uri, None).unwrap_err().to_string();
+        assert!(err.contains("several LSIF projects (alpha, beta)"), "{err}");
+
+        assert_eq!(idx.document_for(uri, Some("alpha"))?, 10);
+        assert_eq!(idx.document_for(uri, Some("2"))?, 20);
+        assert!(idx.document_for(uri, Some("gamma")).is_err());
+        Ok(())
+    }
 }
@@ -65,6 +65,10 @@ fn tools() -> Vec<McpTool> {
                 "type": "string",
                 "description": "Document URI (file:// or path)"
             },
+            "project": {
+                "type": "string",
+                "description": "LSIF project name or vertex id; required when the URI exists in several projects"
+            },
             "position": {
                 "type": "object",
                 "properties": {
@@ -83,16 +87,38 @@ fn tools() -> Vec<McpTool> {
         .cloned()
         .expect("position schema");
 
+    let project_schema = positional
+        .get("properties")
+        .and_then(|p| p.get("project"))
+        .cloned()
+        .expect("project schema");
+
     let references_schema = json!({
         "type": "object",
         "properties": {
             "uri": {"type": "string"},
-            "position": position_schema,
+            "project": project_schema.clone(),
+            "position": position_schema.clone(),
             "includeDeclarations": {"type": "boolean", "default": false}
         },
         "required": ["uri", "position"]
     });
 
+    let hover_schema = json!({
+        "type": "object",
+        "properties": {
+            "uri": {"type": "string"},
+            "project": project_schema.clone(),
+            "position": position_schema,
+            "sourceFallback": {
+                "type": "boolean",
+                "default": false,
+                "description": "When the dump has no hover data, read the definition's source line from disk"
+            }
+        },
+        "required": ["uri", "position"]
+    });
+
     vec![
         McpTool::new(
             "lsif_load",
@@ -103,10 +129,15 @@ fn tools() -> Vec<McpTool> {
                 "required": ["path"]
             })),
         ),
+        McpTool::new(
+            "lsif_stats",
+            "Counts for the loaded LSIF dump, including dangling edge references",
+            schema(json!({"type": "object", "properties": {}})),
+        ),
         McpTool::new(
             "lsif_definition",
             "Definition via LSIF index",
-            schema(positional.clone()),
+            schema(positional),
         ),
         McpTool::new(
             "lsif_references",
@@ -115,8 +146,8 @@ fn tools() -> Vec<McpTool> {
         ),
         McpTool::new(
             "lsif_hover",
-            "Hover via LSIF index (if available)",
-            schema(positional),
+            "Hover via LSIF index; optionally falls back to the definition's source line",
+            schema(hover_schema),
         ),
     ]
 }
@@ -156,28 +187,39 @@ fn call_tool_impl(request: CallToolRequestParam) -> Result<CallToolResult, Error
                 "status": "ok"
             })))
         }
+        "lsif_stats" => {
+            let result = lsif::stats().map_err(|err| to_internal_error("lsif stats error", err))?;
+            Ok(CallToolResult::structured(result))
+        }
         "lsif_definition" => {
             let uri = require_string(&args, "uri")?;
+            let project = args.get("project").and_then(|v| v.as_str());
             let (line, character) = require_position(&args)?;
-            let result = lsif::query_definition(&uri, line, character)
+            let result = lsif::query_definition(&uri, project, line, character)
                 .map_err(|err| to_internal_error("lsif definition error", err))?;
             Ok(CallToolResult::structured(result))
         }
         "lsif_references" => {
             let uri = require_string(&args, "uri")?;
+            let project = args.get("project").and_then(|v| v.as_str());
             let (line, character) = require_position(&args)?;
             let include = args
                 .get("includeDeclarations")
                 .and_then(|v| v.as_bool())
                 .unwrap_or(false);
-            let result = lsif::query_references(&uri, line, character, include)
+            let result = lsif::query_references(&uri, project, line, character, include)
                 .map_err(|err| to_internal_error("lsif references error", err))?;
             Ok(CallToolResult::structured(result))
         }
         "lsif_hover" => {
             let uri = require_string(&args, "uri")?;
+            let project = args.get("project").and_then(|v| v.as_str());
             let (line, character) = require_position(&args)?;
-            let result = lsif::query_hover(&uri, line, character)
+            let source_fallback = args
+                .get("sourceFallback")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            let result = lsif::query_hover(&uri, project, line, character, source_fallback)
                 .map_err(|err| to_internal_error("lsif hover error", err))?;
             Ok(CallToolResult::structured(result))
         }
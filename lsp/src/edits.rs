@@ -0,0 +1,445 @@
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+/// Lines of unchanged context around each unified diff hunk.
+const DIFF_CONTEXT: usize = 3;
+/// Above this many cells the line diff skips the LCS table and emits one replacement hunk.
+const MAX_LCS_CELLS: usize = 4_000_000;
+
+/// Byte offset of an LSP position (UTF-16 `character`) in `text`, clamped to the line end.
+fn offset_at(text: &str, line_starts: &[usize], position: &Value) -> Option<usize> {
+    let line = position.get("line")?.as_u64()? as usize;
+    let character = position.get("character")?.as_u64()? as usize;
+    let Some(&start) = line_starts.get(line) else {
+        return Some(text.len());
+    };
+    let line_text = &text[start..];
+    let line_end = line_text
+        .find('\n')
+        .map(|i| {
+            if i > 0 && line_text.as_bytes()[i - 1] == b'\r' {
+                i - 1
+            } else {
+                i
+            }
+        })
+        .unwrap_or(line_text.len());
+    let mut units = 0;
+    for (idx, ch) in line_text[..line_end].char_indices() {
+        if units >= character {
+            return Some(start + idx);
+        }
+        units += ch.len_utf16();
+    }
+    Some(start + line_end)
+}
+
+/// Apply LSP `TextEdit[]` to `text`. Edits are applied in descending range order so earlier
+/// offsets stay valid; inserts at the same position keep their array order. Overlapping ranges
+/// are rejected.
+pub(crate) fn apply_text_edits(text: &str, edits: &Value) -> Result<String> {
+    let items = match edits {
+        Value::Null => return Ok(text.to_string()),
+        Value::Array(items) => items,
+        _ => return Err(anyhow!("formatting result is not a TextEdit array")),
+    };
+    let mut line_starts = vec![0];
+    line_starts.extend(text.match_indices('\n').map(|(i, _)| i + 1));
+
+    let mut resolved = Vec::with_capacity(items.len());
+    for (index, edit) in items.iter().enumerate() {
+        let range = edit
+            .get("range")
+            .ok_or_else(|| anyhow!("TextEdit missing range: {edit}"))?;
+        let start = range
+            .get("start")
+            .and_then(|p| offset_at(text, &line_starts, p))
+            .ok_or_else(|| anyhow!("TextEdit has invalid start: {edit}"))?;
+        let end = range
+            .get("end")
+            .and_then(|p| offset_at(text, &line_starts, p))
+            .ok_or_else(|| anyhow!("TextEdit has invalid end: {edit}"))?;
+        let new_text = edit.get("newText").and_then(|v| v.as_str()).unwrap_or("");
+        resolved.push((start, end.max(start), index, new_text));
+    }
+    resolved.sort_by(|a, b| b.0.cmp(&a.0).then(b.1.cmp(&a.1)).then(b.2.cmp(&a.2)));
+    for pair in resolved.windows(2) {
+        let (later, earlier) = (&pair[0], &pair[1]);
+        if earlier.1 > later.0 {
+            return Err(anyhow!(
+                "TextEdits {} and {} overlap",
+                earlier.2.min(later.2),
+                earlier.2.max(later.2)
+            ));
+        }
+    }
+
+    let mut out = text.to_string();
+    for (start, end, _, new_text) in resolved {
+        out.replace_range(start..end, new_text);
+    }
+    Ok(out)
+}
+
+#[derive(Clone, Copy)]
+enum DiffOp {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Line-level edit script from `a` to `b`: common prefix/suffix are trimmed and the middle is
+/// aligned with an LCS table (or replaced wholesale when it would be too large).
+fn diff_lines(a: &[&str], b: &[&str]) -> Vec<DiffOp> {
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let suffix = a[prefix..]
+        .iter()
+        .rev()
+        .zip(b[prefix..].iter().rev())
+        .take_while(|(x, y)| x == y)
+        .count();
+    let am = &a[prefix..a.len() - suffix];
+    let bm = &b[prefix..b.len() - suffix];
+
+    let mut ops = vec![DiffOp::Equal; prefix];
+    if am.len().saturating_mul(bm.len()) > MAX_LCS_CELLS {
+        ops.extend(std::iter::repeat_n(DiffOp::Delete, am.len()));
+        ops.extend(std::iter::repeat_n(DiffOp::Insert, bm.len()));
+    } else {
+        let (n, m) = (am.len(), bm.len());
+        let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i * (m + 1) + j] = if am[i] == bm[j] {
+                    lcs[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && am[i] == bm[j] {
+                ops.push(DiffOp::Equal);
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lcs[(i + 1) * (m + 1) + j] >= lcs[i * (m + 1) + j + 1]) {
+                ops.push(DiffOp::Delete);
+                i += 1;
+            } else {
+                ops.push(DiffOp::Insert);
+                j += 1;
+            }
+        }
+    }
+    ops.extend(std::iter::repeat_n(DiffOp::Equal, suffix));
+    ops
+}
+
+fn push_diff_line(out: &mut String, marker: char, line: &str) {
+    out.push(marker);
+    out.push_str(line);
+    if !line.ends_with('\n') {
+        out.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+/// Unified diff between `old` and `new` labelled with `path`; empty when they are identical.
+pub(crate) fn unified_diff(old: &str, new: &str, path: &str) -> String {
+    let a: Vec<&str> = old.split_inclusive('\n').collect();
+    let b: Vec<&str> = new.split_inclusive('\n').collect();
+    let ops = diff_lines(&a, &b);
+
+    // Line cursors in `a` and `b` before each op.
+    let mut cursors = Vec::with_capacity(ops.len() + 1);
+    let (mut ai, mut bi) = (0, 0);
+    for op in &ops {
+        cursors.push((ai, bi));
+        match op {
+            DiffOp::Equal => {
+                ai += 1;
+                bi += 1;
+            }
+            DiffOp::Delete => ai += 1,
+            DiffOp::Insert => bi += 1,
+        }
+    }
+    cursors.push((ai, bi));
+
+    let changes: Vec<usize> = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, op)| !matches!(op, DiffOp::Equal))
+        .map(|(i, _)| i)
+        .collect();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut out = format!("--- a/{path}\n+++ b/{path}\n");
+    let mut group_start = 0;
+    while group_start < changes.len() {
+        let mut group_end = group_start;
+        while group_end + 1 < changes.len()
+            && changes[group_end + 1] - changes[group_end] <= 2 * DIFF_CONTEXT + 1
+        {
+            group_end += 1;
+        }
+        let from = changes[group_start].saturating_sub(DIFF_CONTEXT);
+        let to = (changes[group_end] + DIFF_CONTEXT + 1).min(ops.len());
+        let (a_from, b_from) = cursors[from];
+        let (a_to, b_to) = cursors[to];
+        let (a_len, b_len) = (a_to - a_from, b_to - b_from);
+        let a_start = if a_len == 0 { a_from } else { a_from + 1 };
+        let b_start = if b_len == 0 { b_from } else { b_from + 1 };
+        out.push_str(&format!("@@ -{a_start},{a_len} +{b_start},{b_len} @@\n"));
+        for (op, &(ai, bi)) in ops[from..to].iter().zip(&cursors[from..to]) {
+            match op {
+                DiffOp::Equal => push_diff_line(&mut out, ' ', a[ai]),
+                DiffOp::Delete => push_diff_line(&mut out, '-', a[ai]),
+                DiffOp::Insert => push_diff_line(&mut out, '+', b[bi]),
+            }
+        }
+        group_start = group_end + 1;
+    }
+    out
+}
+
+/// Plain text a snippet (`InsertTextFormat.Snippet`) inserts when accepted without further
+/// editing: tabstops and variables vanish, placeholders keep their default, choices keep their
+/// first option, and `\$`, `\}`, `\\` escapes are unescaped.
+pub(crate) fn snippet_text(snippet: &str) -> String {
+    let chars: Vec<char> = snippet.chars().collect();
+    let mut i = 0;
+    expand_snippet(&chars, &mut i, false)
+}
+
+fn expand_snippet(chars: &[char], i: &mut usize, nested: bool) -> String {
+    let mut out = String::new();
+    while *i < chars.len() {
+        let c = chars[*i];
+        *i += 1;
+        match c {
+            '\\' if *i < chars.len() && matches!(chars[*i], '$' | '}' | '\\' | ',' | '|') => {
+                out.push(chars[*i]);
+                *i += 1;
+            }
+            '}' if nested => return out,
+            '$' if *i < chars.len() && chars[*i] == '{' => {
+                *i += 1;
+                // Tabstop number or variable name.
+                while *i < chars.len() && (chars[*i].is_alphanumeric() || chars[*i] == '_') {
+                    *i += 1;
+                }
+                match chars.get(*i) {
+                    Some(':') => {
+                        *i += 1;
+                        out.push_str(&expand_snippet(chars, i, true));
+                    }
+                    Some('|') => {
+                        *i += 1;
+                        let mut first = String::new();
+                        let mut in_first = true;
+                        while *i < chars.len()
+                            && !(chars[*i] == '|' && chars.get(*i + 1) == Some(&'}'))
+                        {
+                            match chars[*i] {
+                                '\\' if *i + 1 < chars.len() => {
+                                    *i += 1;
+                                    if in_first {
+                                        first.push(chars[*i]);
+                                    }
+                                }
+                                ',' => in_first = false,
+                                ch if in_first => first.push(ch),
+                                _ => {}
+                            }
+                            *i += 1;
+                        }
+                        *i = (*i + 2).min(chars.len());
+                        out.push_str(&first);
+                    }
+                    _ => {
+                        // `${1}`, `${name}`, or a transform we do not evaluate: skip to `}`.
+                        let mut depth = 1;
+                        while *i < chars.len() && depth > 0 {
+                            match chars[*i] {
+                                '\\' => *i += 1,
+                                '{' => depth += 1,
+                                '}' => depth -= 1,
+                                _ => {}
+                            }
+                            *i += 1;
+                        }
+                    }
+                }
+            }
+            '$' if *i < chars.len() && (chars[*i].is_alphanumeric() || chars[*i] == '_') => {
+                while *i < chars.len() && (chars[*i].is_alphanumeric() || chars[*i] == '_') {
+                    *i += 1;
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn edit(start: (u64, u64), end: (u64, u64), new_text: &str) -> Value {
+        json!({
+            "range": {
+                "start": {"line": start.0, "character": start.1},
+                "end": {"line": end.0, "character": end.1}
+            },
+            "newText": new_text
+        })
+    }
+
+    #[test]
+    fn text_edits_apply_to_lines_and_utf16_columns() {
+        let cases = [
+            (
+                "single line",
+                "let x = 1;\n",
+                vec![edit((0, 4), (0, 5), "y")],
+                "let y = 1;\n",
+            ),
+            (
+                "multi-line",
+                "a\nb\nc\n",
+                vec![edit((0, 1), (2, 0), "X\n")],
+                "aX\nc\n",
+            ),
+            (
+                "insert at end",
+                "abc",
+                vec![edit((0, 3), (0, 3), "d")],
+                "abcd",
+            ),
+            (
+                "line past end",
+                "abc\n",
+                vec![edit((5, 0), (5, 0), "z")],
+                "abc\nz",
+            ),
+            (
+                "column past line end",
+                "ab\ncd\n",
+                vec![edit((0, 9), (0, 9), "!")],
+                "ab!\ncd\n",
+            ),
+            (
+                "crlf keeps line ending",
+                "ab\r\ncd\r\n",
+                vec![edit((0, 0), (0, 99), "xy")],
+                "xy\r\ncd\r\n",
+            ),
+            (
+                "crlf second line",
+                "ab\r\ncd\r\n",
+                vec![edit((1, 1), (1, 2), "D")],
+                "ab\r\ncD\r\n",
+            ),
+            // `é` is one UTF-16 unit and `😀` two, so column 3 is the `x`.
+            (
+                "utf-16 columns",
+                "é😀x\n",
+                vec![edit((0, 3), (0, 4), "y")],
+                "é😀y\n",
+            ),
+            (
+                "same-position inserts keep order",
+                "c",
+                vec![edit((0, 0), (0, 0), "a"), edit((0, 0), (0, 0), "b")],
+                "abc",
+            ),
+            (
+                "adjacent ranges",
+                "abcd",
+                vec![edit((0, 2), (0, 4), "CD"), edit((0, 0), (0, 2), "AB")],
+                "ABCD",
+            ),
+        ];
+        for (name, text, edits, expected) in cases {
+            let applied = apply_text_edits(text, &Value::Array(edits));
+            assert_eq!(applied.ok().as_deref(), Some(expected), "{name}");
+        }
+    }
+
+    #[test]
+    fn text_edits_reject_overlaps_and_malformed_input() {
+        let overlapping = json!([edit((0, 0), (0, 3), "x"), edit((0, 2), (0, 4), "y")]);
+        let err = apply_text_edits("abcdef", &overlapping).unwrap_err();
+        assert_eq!(err.to_string(), "TextEdits 0 and 1 overlap");
+
+        let across_lines = json!([edit((1, 0), (1, 1), ""), edit((0, 1), (1, 1), "")]);
+        assert!(apply_text_edits("ab\ncd\n", &across_lines).is_err());
+
+        assert_eq!(
+            apply_text_edits("same", &Value::Null).ok().as_deref(),
+            Some("same")
+        );
+        assert!(apply_text_edits("x", &json!({"newText": "y"})).is_err());
+        assert!(apply_text_edits("x", &json!([{"newText": "y"}])).is_err());
+        let bad_position =
+            json!([{"range": {"start": {"line": 0}, "end": {"line": 0, "character": 0}}}]);
+        assert!(apply_text_edits("x", &bad_position).is_err());
+    }
+
+    #[test]
+    fn unified_diff_hunks() {
+        let cases = [
+            ("identical", "a\nb\n", "a\nb\n", ""),
+            (
+                "changed line",
+                "a\nb\nc\n",
+                "a\nB\nc\n",
+                "--- a/f.rs\n+++ b/f.rs\n@@ -1,3 +1,3 @@\n a\n-b\n+B\n c\n",
+            ),
+            (
+                "insert into empty",
+                "",
+                "x\n",
+                "--- a/f.rs\n+++ b/f.rs\n@@ -0,0 +1,1 @@\n+x\n",
+            ),
+            (
+                "missing final newline",
+                "a",
+                "b",
+                "--- a/f.rs\n+++ b/f.rs\n@@ -1,1 +1,1 @@\n-a\n\\ No newline at end of file\n+b\n\\ No newline at end of file\n",
+            ),
+            (
+                "distant changes split hunks",
+                "1\n2\n3\n4\n5\n6\n7\n8\n9\n10\n",
+                "one\n2\n3\n4\n5\n6\n7\n8\n9\nten\n",
+                "--- a/f.rs\n+++ b/f.rs\n@@ -1,4 +1,4 @@\n-1\n+one\n 2\n 3\n 4\n@@ -7,4 +7,4 @@\n 7\n 8\n 9\n-10\n+ten\n",
+            ),
+        ];
+        for (name, old, new, expected) in cases {
+            assert_eq!(unified_diff(old, new, "f.rs"), expected, "{name}");
+        }
+    }
+
+    #[test]
+    fn snippet_text_expansion() {
+        let cases = [
+            ("plain", "println!()", "println!()"),
+            ("tabstops", "foo($1, $2)$0", "foo(, )"),
+            ("placeholder", "foo(${1:bar})", "foo(bar)"),
+            ("nested placeholder", "${1:outer ${2:inner}}", "outer inner"),
+            ("choice", "${1|one,two|}", "one"),
+            ("variables", "$TM_FILENAME and ${TM_LINE_NUMBER}", " and "),
+            ("transform", "x${1/(.*)/$1/}y", "xy"),
+            ("escapes", r"\$x \} \\", r"$x } \"),
+            ("multibyte", "${1:héllo} 😀", "héllo 😀"),
+        ];
+        for (name, snippet, expected) in cases {
+            assert_eq!(snippet_text(snippet), expected, "{name}");
+        }
+    }
+}
@@ -1,11 +1,20 @@
 use anyhow::{anyhow, Context, Result};
 use serde_json::{json, Value};
+use std::collections::HashMap;
 use std::fmt::Write as _;
 use std::io::{BufRead, Read, Write};
 use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
-use std::time::Duration;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant};
 use url::Url;
 
+/// Upper bound for `LSP_START_RETRIES`.
+const MAX_START_RETRIES: u32 = 10;
+/// Default for `LSP_REQUEST_TIMEOUT_MS`.
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// Delay before the first launch retry; doubled per attempt up to 16x.
+const START_RETRY_BASE_DELAY: Duration = Duration::from_millis(250);
+
 /// Minimal LSP client manager that speaks Content-Length framed JSON-RPC.
 
 #[derive(Clone, Copy, Debug)]
@@ -14,6 +23,15 @@ enum Framing {
     Newline,
 }
 
+impl Framing {
+    fn as_str(self) -> &'static str {
+        match self {
+            Framing::ContentLength => "content-length",
+            Framing::Newline => "newline",
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
 enum FramingPreference {
     Auto,
@@ -56,20 +74,315 @@ pub struct LanguageServerManager {
     current_cmd: Option<String>,
     child: Option<Child>,
     stdin: Option<ChildStdin>,
-    stdout: Option<std::io::BufReader<ChildStdout>>,
+    /// Framed messages read from the server's stdout by its reader thread, which exits after
+    /// sending the first read error (EOF included).
+    incoming: Option<Receiver<Result<(String, Framing)>>>,
     next_id: i64,
     server_capabilities: Option<Value>,
+    /// Registrations received via `client/registerCapability` as `(id, method, registerOptions)`.
+    dynamic_registrations: Vec<(String, String, Value)>,
+    /// `semanticTokensProvider.legend` of the merged capabilities, filled on first use and
+    /// dropped whenever the capabilities change.
+    semantic_tokens_legend: Option<Value>,
+    /// Latest `textDocument/publishDiagnostics` diagnostics per normalized document uri,
+    /// collected from notifications read while awaiting responses.
+    published_diagnostics: HashMap<String, Value>,
     write_pref: FramingPreference,
     read_mode: Option<Framing>,
+    /// JSONL file receiving every message exchanged with the server (`LSP_TRACE_FILE`).
+    trace_path: Option<std::path::PathBuf>,
+    /// Command of the most recently spawned server, recorded in trace entries.
+    trace_server: Option<String>,
+    /// `{ uri, name }` folders from `LSP_WORKSPACE_FOLDERS`, as sent at initialize; empty when
+    /// none are configured.
+    workspace_folders: Vec<Value>,
+}
+
+/// Exit status of a language server that died while a request was in flight. Attached as
+/// context to the transport error so tool errors can report how the server went away.
+#[derive(Debug)]
+pub(crate) struct ServerExited {
+    command: String,
+    code: Option<i32>,
+    signal: Option<i32>,
+}
+
+impl ServerExited {
+    fn from_status(command: &str, status: std::process::ExitStatus) -> Self {
+        #[cfg(unix)]
+        let signal = std::os::unix::process::ExitStatusExt::signal(&status);
+        #[cfg(not(unix))]
+        let signal = None;
+        Self {
+            command: command.to_string(),
+            code: status.code(),
+            signal,
+        }
+    }
+
+    fn signal_name(&self) -> Option<&'static str> {
+        Some(match self.signal? {
+            1 => "SIGHUP",
+            2 => "SIGINT",
+            4 => "SIGILL",
+            6 => "SIGABRT",
+            7 => "SIGBUS",
+            8 => "SIGFPE",
+            9 => "SIGKILL",
+            11 => "SIGSEGV",
+            13 => "SIGPIPE",
+            15 => "SIGTERM",
+            _ => return None,
+        })
+    }
+
+    /// Structured form for tool error `data`.
+    pub(crate) fn data(&self) -> Value {
+        json!({
+            "command": self.command,
+            "exitCode": self.code,
+            "signal": self.signal,
+            "signalName": self.signal_name(),
+        })
+    }
+}
+
+impl std::fmt::Display for ServerExited {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "language server '{}' ", self.command)?;
+        match (self.code, self.signal) {
+            (Some(code), _) => write!(f, "exited with code {code}"),
+            (None, Some(signal)) => {
+                write!(f, "was killed by signal {signal}")?;
+                if let Some(name) = self.signal_name() {
+                    write!(f, " ({name})")?;
+                }
+                if signal == 9 {
+                    write!(f, " — likely out of memory")?;
+                }
+                Ok(())
+            }
+            (None, None) => write!(f, "exited"),
+        }
+    }
+}
+
+/// A request that got no response within `LSP_REQUEST_TIMEOUT_MS`. The server is killed when
+/// this is returned, so the next call starts a fresh one.
+#[derive(Debug)]
+pub(crate) struct RequestTimedOut {
+    method: String,
+    timeout: Duration,
+}
+
+impl RequestTimedOut {
+    /// Structured form for tool error `data`.
+    pub(crate) fn data(&self) -> Value {
+        json!({
+            "method": self.method,
+            "timeoutMs": self.timeout.as_millis() as u64,
+        })
+    }
+}
+
+impl std::fmt::Display for RequestTimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "LSP request {} timed out after ", self.method)?;
+        let millis = self.timeout.as_millis();
+        if millis.is_multiple_of(1000) {
+            write!(f, "{}s", millis / 1000)
+        } else {
+            write!(f, "{millis}ms")
+        }
+    }
+}
+
+impl std::error::Error for RequestTimedOut {}
+
+/// Internal marker for a bounded read whose deadline passed; `request` turns it into
+/// `RequestTimedOut`.
+#[derive(Debug)]
+struct ReadDeadlineElapsed;
+
+impl std::fmt::Display for ReadDeadlineElapsed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("no message from language server before the deadline")
+    }
+}
+
+impl std::error::Error for ReadDeadlineElapsed {}
+
+/// Mutable slot at `path` inside a capabilities object, creating intermediate objects.
+fn capability_slot<'a>(caps: &'a mut Value, path: &[&str]) -> Option<&'a mut Value> {
+    let (first, rest) = path.split_first()?;
+    let obj = caps.as_object_mut()?;
+    if rest.is_empty() {
+        return Some(obj.entry(first.to_string()).or_insert(Value::Null));
+    }
+    capability_slot(
+        obj.entry(first.to_string()).or_insert_with(|| json!({})),
+        rest,
+    )
+}
+
+/// Flat summary of `ServerCapabilities` for `lsp_server_features`. A provider given as `true`
+/// or an options object counts as supported; `*Resolve` flags read `resolveProvider`.
+fn summarize_capabilities(caps: &Value) -> Value {
+    let supported = |key: &str| match caps.get(key) {
+        Some(Value::Bool(b)) => *b,
+        Some(Value::Object(_)) => true,
+        _ => false,
+    };
+    let resolve = |key: &str| {
+        caps.get(key)
+            .and_then(|v| v.get("resolveProvider"))
+            .and_then(|b| b.as_bool())
+            .unwrap_or(false)
+    };
+    let flag = |v: Option<&Value>| match v {
+        Some(Value::Bool(b)) => *b,
+        Some(Value::Object(_)) => true,
+        _ => false,
+    };
+
+    // `textDocumentSync` is either a TextDocumentSyncKind number or TextDocumentSyncOptions.
+    let sync = caps.get("textDocumentSync");
+    let (sync_kind, open_close) = match sync {
+        Some(Value::Number(n)) => (n.as_u64().unwrap_or(0), n.as_u64().unwrap_or(0) > 0),
+        Some(Value::Object(o)) => (
+            o.get("change").and_then(|c| c.as_u64()).unwrap_or(0),
+            o.get("openClose")
+                .and_then(|b| b.as_bool())
+                .unwrap_or(false),
+        ),
+        _ => (0, false),
+    };
+    let sync_option = |key: &str| flag(sync.and_then(|s| s.get(key)));
+    let save_include_text = sync
+        .and_then(|s| s.get("save"))
+        .and_then(|s| s.get("includeText"))
+        .and_then(|b| b.as_bool())
+        .unwrap_or(false);
+
+    let diagnostics = caps.get("diagnosticProvider").filter(|v| !v.is_null());
+    let semantic = caps.get("semanticTokensProvider");
+    let semantic_full = semantic.and_then(|s| s.get("full"));
+    let trigger_characters = |key: &str| {
+        caps.get(key)
+            .and_then(|v| v.get("triggerCharacters"))
+            .cloned()
+            .unwrap_or_else(|| json!([]))
+    };
+
+    json!({
+        "textDocumentSync": match sync_kind {
+            1 => "full",
+            2 => "incremental",
+            _ => "none",
+        },
+        "incremental": sync_kind == 2,
+        "openClose": open_close,
+        "willSave": sync_option("willSave"),
+        "willSaveWaitUntil": sync_option("willSaveWaitUntil"),
+        "save": sync_option("save"),
+        "saveIncludeText": save_include_text,
+        "positionEncoding": caps.get("positionEncoding").and_then(|v| v.as_str()).unwrap_or("utf-16"),
+        "pullDiagnostics": diagnostics.is_some(),
+        "workspaceDiagnostics": diagnostics
+            .and_then(|d| d.get("workspaceDiagnostics"))
+            .and_then(|b| b.as_bool())
+            .unwrap_or(false),
+        "pushDiagnostics": diagnostics.is_none(),
+        "completion": caps.get("completionProvider").is_some_and(|v| !v.is_null()),
+        "completionResolve": resolve("completionProvider"),
+        "completionTriggerCharacters": trigger_characters("completionProvider"),
+        "signatureHelpTriggerCharacters": trigger_characters("signatureHelpProvider"),
+        "rename": supported("renameProvider"),
+        "renamePrepare": caps
+            .get("renameProvider")
+            .and_then(|v| v.get("prepareProvider"))
+            .and_then(|b| b.as_bool())
+            .unwrap_or(false),
+        "codeActionResolve": resolve("codeActionProvider"),
+        "codeLensResolve": resolve("codeLensProvider"),
+        "documentLinkResolve": resolve("documentLinkProvider"),
+        "inlayHintResolve": resolve("inlayHintProvider"),
+        "workspaceSymbolResolve": resolve("workspaceSymbolProvider"),
+        "formatting": supported("documentFormattingProvider"),
+        "rangeFormatting": supported("documentRangeFormattingProvider"),
+        "onTypeFormatting": caps.get("documentOnTypeFormattingProvider").is_some_and(|v| !v.is_null()),
+        "semanticTokens": {
+            "full": flag(semantic_full),
+            "delta": semantic_full
+                .and_then(|f| f.get("delta"))
+                .and_then(|b| b.as_bool())
+                .unwrap_or(false),
+            "range": flag(semantic.and_then(|s| s.get("range"))),
+        },
+        "executeCommands": caps
+            .get("executeCommandProvider")
+            .and_then(|v| v.get("commands"))
+            .cloned()
+            .unwrap_or_else(|| json!([])),
+    })
+}
+
+/// Capability path a dynamically registered method maps onto in `ServerCapabilities`.
+fn capability_path_for_method(method: &str) -> Option<&'static [&'static str]> {
+    let path: &'static [&'static str] = match method {
+        "textDocument/hover" => &["hoverProvider"],
+        "textDocument/declaration" => &["declarationProvider"],
+        "textDocument/definition" => &["definitionProvider"],
+        "textDocument/typeDefinition" => &["typeDefinitionProvider"],
+        "textDocument/implementation" => &["implementationProvider"],
+        "textDocument/references" => &["referencesProvider"],
+        "textDocument/completion" => &["completionProvider"],
+        "textDocument/signatureHelp" => &["signatureHelpProvider"],
+        "textDocument/documentHighlight" => &["documentHighlightProvider"],
+        "textDocument/documentSymbol" => &["documentSymbolProvider"],
+        "textDocument/codeAction" => &["codeActionProvider"],
+        "textDocument/codeLens" => &["codeLensProvider"],
+        "textDocument/documentLink" => &["documentLinkProvider"],
+        "textDocument/documentColor" => &["colorProvider"],
+        "textDocument/formatting" => &["documentFormattingProvider"],
+        "textDocument/rangeFormatting" => &["documentRangeFormattingProvider"],
+        "textDocument/onTypeFormatting" => &["documentOnTypeFormattingProvider"],
+        "textDocument/rename" => &["renameProvider"],
+        "textDocument/foldingRange" => &["foldingRangeProvider"],
+        "textDocument/selectionRange" => &["selectionRangeProvider"],
+        "textDocument/linkedEditingRange" => &["linkedEditingRangeProvider"],
+        "textDocument/moniker" => &["monikerProvider"],
+        "textDocument/inlineValue" => &["inlineValueProvider"],
+        "textDocument/inlayHint" => &["inlayHintProvider"],
+        "textDocument/prepareCallHierarchy" => &["callHierarchyProvider"],
+        "textDocument/prepareTypeHierarchy" => &["typeHierarchyProvider"],
+        "textDocument/semanticTokens" => &["semanticTokensProvider"],
+        "textDocument/diagnostic" => &["diagnosticProvider"],
+        "textDocument/willSave" => &["textDocumentSync", "willSave"],
+        "textDocument/willSaveWaitUntil" => &["textDocumentSync", "willSaveWaitUntil"],
+        "workspace/symbol" => &["workspaceSymbolProvider"],
+        "workspace/executeCommand" => &["executeCommandProvider"],
+        "workspace/willCreateFiles" => &["workspace", "fileOperations", "willCreate"],
+        "workspace/willRenameFiles" => &["workspace", "fileOperations", "willRename"],
+        "workspace/willDeleteFiles" => &["workspace", "fileOperations", "willDelete"],
+        "workspace/textDocumentContent" => &["workspace", "textDocumentContentProvider"],
+        _ => return None,
+    };
+    Some(path)
 }
 
 impl LanguageServerManager {
     fn client_capabilities() -> Value {
         json!({
             "workspace": {
-                "configuration": true
+                "configuration": true,
+                "workspaceFolders": true
             },
             "textDocument": {
+                "synchronization": {
+                    "willSave": true,
+                    "willSaveWaitUntil": true
+                },
                 "hover": {
                     "contentFormat": ["markdown", "plaintext"]
                 },
@@ -120,11 +433,17 @@ impl LanguageServerManager {
             current_cmd: None,
             child: None,
             stdin: None,
-            stdout: None,
+            incoming: None,
             next_id: 1,
             server_capabilities: None,
+            dynamic_registrations: Vec::new(),
+            semantic_tokens_legend: None,
+            published_diagnostics: HashMap::new(),
             write_pref: FramingPreference::Auto,
             read_mode: None,
+            trace_path: Self::trace_path_from_env(),
+            trace_server: None,
+            workspace_folders: Vec::new(),
         }
     }
 
@@ -134,11 +453,17 @@ impl LanguageServerManager {
             current_cmd: None,
             child: None,
             stdin: None,
-            stdout: None,
+            incoming: None,
             next_id: 1,
             server_capabilities: None,
+            dynamic_registrations: Vec::new(),
+            semantic_tokens_legend: None,
+            published_diagnostics: HashMap::new(),
             write_pref: FramingPreference::Auto,
             read_mode: None,
+            trace_path: Self::trace_path_from_env(),
+            trace_server: None,
+            workspace_folders: Vec::new(),
         }
     }
 
@@ -195,6 +520,100 @@ impl LanguageServerManager {
         Ok(parts)
     }
 
+    /// Folders listed in `LSP_WORKSPACE_FOLDERS`, separated like `PATH` entries; relative paths
+    /// resolve against the current directory and each folder is named after its last component.
+    fn workspace_folders_from_env() -> Result<Vec<Value>> {
+        let Some(raw) = std::env::var_os("LSP_WORKSPACE_FOLDERS").filter(|v| !v.is_empty()) else {
+            return Ok(Vec::new());
+        };
+        std::env::split_paths(&raw)
+            .filter(|path| !path.as_os_str().is_empty())
+            .map(|path| {
+                let uri = Self::path_to_file_uri(&path)
+                    .with_context(|| format!("LSP_WORKSPACE_FOLDERS entry {:?}", path))?;
+                let name = path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| uri.clone());
+                Ok(json!({"uri": uri, "name": name}))
+            })
+            .collect()
+    }
+
+    /// Extra launch attempts from `LSP_START_RETRIES` (default 0); invalid values are ignored.
+    fn start_retries_from_env() -> u32 {
+        match std::env::var("LSP_START_RETRIES") {
+            Ok(value) if !value.trim().is_empty() => match value.trim().parse::<u32>() {
+                Ok(n) => n.min(MAX_START_RETRIES),
+                Err(_) => {
+                    eprintln!(
+                        "mcp-lsp: invalid LSP_START_RETRIES value '{}'; not retrying",
+                        value
+                    );
+                    0
+                }
+            },
+            _ => 0,
+        }
+    }
+
+    /// Response deadline from `LSP_REQUEST_TIMEOUT_MS` (default 30 s); `0` waits forever and
+    /// invalid values fall back to the default.
+    fn request_timeout_from_env() -> Option<Duration> {
+        match std::env::var("LSP_REQUEST_TIMEOUT_MS") {
+            Ok(value) if !value.trim().is_empty() => match value.trim().parse::<u64>() {
+                Ok(0) => None,
+                Ok(ms) => Some(Duration::from_millis(ms)),
+                Err(_) => {
+                    eprintln!(
+                        "mcp-lsp: invalid LSP_REQUEST_TIMEOUT_MS value '{}'; using {}ms",
+                        value,
+                        DEFAULT_REQUEST_TIMEOUT.as_millis()
+                    );
+                    Some(DEFAULT_REQUEST_TIMEOUT)
+                }
+            },
+            _ => Some(DEFAULT_REQUEST_TIMEOUT),
+        }
+    }
+
+    fn trace_path_from_env() -> Option<std::path::PathBuf> {
+        std::env::var_os("LSP_TRACE_FILE")
+            .filter(|v| !v.is_empty())
+            .map(std::path::PathBuf::from)
+    }
+
+    /// Append one traffic record to `LSP_TRACE_FILE`. Bodies that are not valid JSON are
+    /// logged as strings. Write failures go to stderr and never fail the exchange.
+    fn trace(&self, direction: &str, framing: Framing, body: &str) {
+        let Some(path) = self.trace_path.as_ref() else {
+            return;
+        };
+        let ts_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        let message = serde_json::from_str::<Value>(body).unwrap_or_else(|_| json!(body));
+        let record = json!({
+            "ts": ts_ms,
+            "direction": direction,
+            "server": self.trace_server,
+            "framing": framing.as_str(),
+            "message": message,
+        });
+        let written = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut f| writeln!(f, "{record}"));
+        if let Err(err) = written {
+            eprintln!(
+                "mcp-lsp: failed to write LSP_TRACE_FILE {}: {err}",
+                path.display()
+            );
+        }
+    }
+
     fn current_write_mode(&self) -> Framing {
         match self.write_pref {
             FramingPreference::ContentLength => Framing::ContentLength,
@@ -222,6 +641,7 @@ impl LanguageServerManager {
     fn write_jsonrpc(&mut self, value: &Value) -> Result<()> {
         let payload = serde_json::to_string(value)?;
         let framing = self.current_write_mode();
+        self.trace("send", framing, &payload);
         let stdin = self
             .stdin
             .as_mut()
@@ -250,6 +670,39 @@ impl LanguageServerManager {
         self.write_jsonrpc(&response)
     }
 
+    /// Track `client/registerCapability` registrations and drop them again on
+    /// `client/unregisterCapability` (whose list is spelled `unregisterations` in the spec).
+    fn record_registrations(&mut self, method: &str, params: &Value) {
+        self.semantic_tokens_legend = None;
+        if method == "client/registerCapability" {
+            let items = params.get("registrations").and_then(|r| r.as_array());
+            for reg in items.into_iter().flatten() {
+                let (Some(id), Some(reg_method)) = (
+                    reg.get("id").and_then(|v| v.as_str()),
+                    reg.get("method").and_then(|v| v.as_str()),
+                ) else {
+                    continue;
+                };
+                let options = reg.get("registerOptions").cloned().unwrap_or(Value::Null);
+                self.dynamic_registrations
+                    .retain(|(existing, _, _)| existing != id);
+                self.dynamic_registrations
+                    .push((id.to_string(), reg_method.to_string(), options));
+            }
+        } else {
+            let items = params
+                .get("unregisterations")
+                .or_else(|| params.get("unregistrations"))
+                .and_then(|r| r.as_array());
+            for unreg in items.into_iter().flatten() {
+                if let Some(id) = unreg.get("id").and_then(|v| v.as_str()) {
+                    self.dynamic_registrations
+                        .retain(|(existing, _, _)| existing != id);
+                }
+            }
+        }
+    }
+
     fn handle_server_request(
         &mut self,
         id: Value,
@@ -272,6 +725,9 @@ impl LanguageServerManager {
                 self.send_jsonrpc_response(id, result)
             }
             "client/registerCapability" | "client/unregisterCapability" => {
+                if let Some(params) = params {
+                    self.record_registrations(method, params);
+                }
                 eprintln!(
                     "mcp-lsp: acknowledging server request '{}' with null result",
                     method
@@ -286,11 +742,15 @@ impl LanguageServerManager {
                 self.send_jsonrpc_response(id, Value::Null)
             }
             "workspace/workspaceFolders" => {
-                eprintln!(
-                    "mcp-lsp: responding to server request '{}' with no workspace folders",
-                    method
-                );
-                self.send_jsonrpc_response(id, Value::Null)
+                if self.workspace_folders.is_empty() {
+                    eprintln!(
+                        "mcp-lsp: responding to server request '{}' with no workspace folders",
+                        method
+                    );
+                    return self.send_jsonrpc_response(id, Value::Null);
+                }
+                let folders = Value::Array(self.workspace_folders.clone());
+                self.send_jsonrpc_response(id, folders)
             }
             "workspace/applyEdit" => {
                 eprintln!(
@@ -388,28 +848,22 @@ impl LanguageServerManager {
         Ok(line.trim_end_matches(['\r', '\n']).to_string())
     }
 
-    fn read_detected_message(&mut self, first_line: Option<String>) -> Result<(String, Framing)> {
+    fn read_detected_message(
+        stdout: &mut std::io::BufReader<ChildStdout>,
+        first_line: Option<String>,
+    ) -> Result<(String, Framing)> {
         if let Some(line) = first_line {
             let trimmed = line.trim_end_matches(['\r', '\n']);
             if trimmed.is_empty() {
-                return self.read_detected_message(None);
+                return Self::read_detected_message(stdout, None);
             }
             if trimmed.starts_with('{') || trimmed.starts_with('[') {
                 return Ok((trimmed.to_string(), Framing::Newline));
             }
-            let stdout = self
-                .stdout
-                .as_mut()
-                .ok_or_else(|| anyhow!("language server stdout closed"))?;
             let body = Self::read_content_length_message(stdout, Some(line))?;
             return Ok((body, Framing::ContentLength));
         }
 
-        let stdout = self
-            .stdout
-            .as_mut()
-            .ok_or_else(|| anyhow!("language server stdout closed"))?;
-
         let mut line = String::new();
         loop {
             line.clear();
@@ -429,37 +883,94 @@ impl LanguageServerManager {
         }
     }
 
-    fn read_message(&mut self) -> Result<Value> {
-        let mode = self.read_mode;
+    fn read_framed_message(
+        stdout: &mut std::io::BufReader<ChildStdout>,
+        mode: Option<Framing>,
+    ) -> Result<(String, Framing)> {
         match mode {
             Some(Framing::ContentLength) => {
-                let stdout = self
-                    .stdout
-                    .as_mut()
-                    .ok_or_else(|| anyhow!("language server stdout closed"))?;
                 let body = Self::read_content_length_message(stdout, None)?;
-                serde_json::from_str(&body).context("parse lsp response")
+                Ok((body, Framing::ContentLength))
             }
             Some(Framing::Newline) => {
-                let stdout = self
-                    .stdout
-                    .as_mut()
-                    .ok_or_else(|| anyhow!("language server stdout closed"))?;
                 let body = Self::read_newline_message(stdout, None)?;
-                serde_json::from_str(&body).context("parse lsp response")
+                Ok((body, Framing::Newline))
             }
-            None => {
-                let (body, framing) = self.read_detected_message(None)?;
-                self.read_mode = Some(framing);
-                serde_json::from_str(&body).context("parse lsp response")
+            None => Self::read_detected_message(stdout, None),
+        }
+    }
+
+    /// Spawn the thread that owns `stdout` for the server's lifetime and forwards each framed
+    /// message; `mode` is the initial framing, detected from the first message when `None`.
+    fn spawn_reader(
+        stdout: ChildStdout,
+        mut mode: Option<Framing>,
+    ) -> Receiver<Result<(String, Framing)>> {
+        let (tx, rx) = mpsc::channel();
+        std::thread::spawn(move || {
+            let mut stdout = std::io::BufReader::new(stdout);
+            loop {
+                let read = Self::read_framed_message(&mut stdout, mode);
+                let failed = read.is_err();
+                if let Ok((_, framing)) = &read {
+                    mode.get_or_insert(*framing);
+                }
+                if tx.send(read).is_err() || failed {
+                    break;
+                }
             }
+        });
+        rx
+    }
+
+    fn read_message(&mut self) -> Result<Value> {
+        self.read_message_before(None)
+    }
+
+    /// Read the next message, failing with `ReadDeadlineElapsed` once `deadline` passes. The
+    /// reader thread keeps stdout either way, so a late message is still delivered in order
+    /// to the next read.
+    fn read_message_before(&mut self, deadline: Option<Instant>) -> Result<Value> {
+        let incoming = self
+            .incoming
+            .as_ref()
+            .ok_or_else(|| anyhow!("language server stdout closed"))?;
+        let received = match deadline {
+            None => incoming.recv().ok(),
+            Some(deadline) => {
+                match incoming.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(read) => Some(read),
+                    Err(RecvTimeoutError::Timeout) => return Err(anyhow!(ReadDeadlineElapsed)),
+                    Err(RecvTimeoutError::Disconnected) => None,
+                }
+            }
+        };
+        let (body, framing) =
+            received.ok_or_else(|| anyhow!("language server stdout closed"))??;
+        if self.read_mode.is_none() {
+            self.read_mode = Some(framing);
+        }
+        self.trace("recv", framing, &body);
+        serde_json::from_str(&body).context("parse lsp response")
+    }
+
+    /// Kill a server that stopped answering and reset the session, without the graceful
+    /// `shutdown` exchange it would not answer either.
+    fn abandon_server(&mut self) {
+        self.stdin = None;
+        self.incoming = None;
+        if let Some(child) = self.child.as_mut() {
+            let _ = child.kill();
+        }
+        if let Err(err) = self.stop_child() {
+            eprintln!("mcp-lsp: failed to stop unresponsive language server: {err:#}");
         }
     }
 
     fn stop_child(&mut self) -> Result<()> {
         if self.child.is_some() {
             // Attempt graceful shutdown if streams are still available.
-            if self.stdin.is_some() && self.stdout.is_some() {
+            if self.stdin.is_some() && self.incoming.is_some() {
                 let shutdown = json!({
                     "jsonrpc": "2.0",
                     "id": self.alloc_id(),
@@ -473,7 +984,7 @@ impl LanguageServerManager {
 
             // Drop streams so EOF propagates.
             self.stdin = None;
-            self.stdout = None;
+            self.incoming = None;
 
             if let Some(mut child) = self.child.take() {
                 // Give the server a moment to exit cleanly after the shutdown handshake.
@@ -496,10 +1007,13 @@ impl LanguageServerManager {
             }
         } else {
             self.stdin = None;
-            self.stdout = None;
+            self.incoming = None;
         }
 
         self.server_capabilities = None;
+        self.dynamic_registrations.clear();
+        self.semantic_tokens_legend = None;
+        self.published_diagnostics.clear();
         self.next_id = 1;
         self.read_mode = self.write_pref.initial_read_mode();
         Ok(())
@@ -507,6 +1021,7 @@ impl LanguageServerManager {
 
     fn start_server(&mut self, cmd: &str) -> Result<()> {
         let parts = Self::command_parts(cmd)?;
+        self.trace_server = Some(cmd.to_string());
         let mut command = Command::new(&parts[0]);
         if parts.len() > 1 {
             command.args(&parts[1..]);
@@ -519,20 +1034,38 @@ impl LanguageServerManager {
             .with_context(|| format!("spawn lsp server '{}'", cmd))?;
         let stdin = child.stdin.take().ok_or_else(|| anyhow!("no stdin"))?;
         let stdout = child.stdout.take().ok_or_else(|| anyhow!("no stdout"))?;
+        self.write_pref = FramingPreference::from_env();
+        self.read_mode = self.write_pref.initial_read_mode();
         self.stdin = Some(stdin);
-        self.stdout = Some(std::io::BufReader::new(stdout));
+        self.incoming = Some(Self::spawn_reader(stdout, self.read_mode));
         self.child = Some(child);
         self.server_capabilities = None;
+        self.dynamic_registrations.clear();
+        self.semantic_tokens_legend = None;
+        self.published_diagnostics.clear();
         self.next_id = 1;
-        self.write_pref = FramingPreference::from_env();
-        self.read_mode = self.write_pref.initial_read_mode();
 
         let init_result = (|| -> Result<()> {
-            // Minimal initialize handshake. Use current working directory as the workspace root
-            // so servers like rust-analyzer can locate files on disk without an explicit didOpen.
-            let cwd = std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
-            let root_uri = Self::path_to_file_uri(&cwd)
-                .with_context(|| format!("build rootUri for workspace at {:?}", cwd))?;
+            // Minimal initialize handshake. Without configured workspace folders, use the current
+            // working directory as the workspace root so servers like rust-analyzer can locate
+            // files on disk without an explicit didOpen; otherwise the first folder is the root.
+            self.workspace_folders = Self::workspace_folders_from_env()?;
+            let (root_uri, folders) = match self.workspace_folders.first() {
+                Some(first) => (
+                    first["uri"].clone(),
+                    Value::Array(self.workspace_folders.clone()),
+                ),
+                None => {
+                    let cwd =
+                        std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
+                    let root_uri = Self::path_to_file_uri(&cwd)
+                        .with_context(|| format!("build rootUri for workspace at {:?}", cwd))?;
+                    (
+                        json!(root_uri),
+                        json!([{"uri": root_uri, "name": "workspace"}]),
+                    )
+                }
+            };
             let id = self.alloc_id();
             let init = json!({
                 "jsonrpc":"2.0",
@@ -542,7 +1075,7 @@ impl LanguageServerManager {
                     "processId": null,
                     "capabilities": Self::client_capabilities(),
                     "rootUri": root_uri,
-                    "workspaceFolders": [{"uri": root_uri, "name": "workspace"}]
+                    "workspaceFolders": folders
                 }
             });
             self.write_jsonrpc(&init)?;
@@ -567,6 +1100,10 @@ impl LanguageServerManager {
                         }
                         continue;
                     }
+                    if method_name == "textDocument/publishDiagnostics" {
+                        self.record_published_diagnostics(value.get("params"));
+                        continue;
+                    }
                     eprintln!(
                         "mcp-lsp: dropping notification '{}' received during initialize",
                         method_name
@@ -595,6 +1132,7 @@ impl LanguageServerManager {
         })();
 
         if let Err(e) = init_result {
+            let e = self.annotate_exit(e, cmd);
             let _ = self.stop_child();
             return Err(e);
         }
@@ -639,22 +1177,55 @@ impl LanguageServerManager {
                 )
             })?;
 
-        if let Err(err) = self.start_server(&cmd) {
+        // A malformed command never becomes valid by waiting, so only spawn/initialize
+        // failures are retried.
+        Self::command_parts(&cmd)?;
+        let retries = Self::start_retries_from_env();
+        let mut attempt = 0;
+        while let Err(err) = self.start_server(&cmd) {
+            if attempt >= retries {
+                eprintln!(
+                    "mcp-lsp: failed to launch language server '{}': {err:#}",
+                    cmd
+                );
+                return Err(anyhow!(
+                    "failed to launch language server '{}': {:#}",
+                    cmd,
+                    err
+                ));
+            }
+            attempt += 1;
+            let delay = START_RETRY_BASE_DELAY * 2u32.pow(attempt - 1).min(16);
             eprintln!(
-                "mcp-lsp: failed to launch language server '{}': {err:#}",
-                cmd
-            );
-            return Err(anyhow!(
-                "failed to launch language server '{}': {:#}",
+                "mcp-lsp: failed to launch language server '{}': {err:#}; retry {}/{} in {}ms",
                 cmd,
-                err
-            ));
+                attempt,
+                retries,
+                delay.as_millis()
+            );
+            std::thread::sleep(delay);
         }
 
         self.current_cmd = Some(cmd);
         Ok(())
     }
 
+    /// Attach the server's exit status to `err` when the child has terminated. A crash is
+    /// usually seen as EOF on stdout slightly before the process is reaped, so poll briefly.
+    fn annotate_exit(&mut self, err: anyhow::Error, command: &str) -> anyhow::Error {
+        let Some(child) = self.child.as_mut() else {
+            return err;
+        };
+        for _ in 0..10 {
+            match child.try_wait() {
+                Ok(Some(status)) => return err.context(ServerExited::from_status(command, status)),
+                Ok(None) => std::thread::sleep(Duration::from_millis(20)),
+                Err(_) => break,
+            }
+        }
+        err
+    }
+
     fn alloc_id(&mut self) -> i64 {
         let id = self.next_id;
         self.next_id += 1;
@@ -668,11 +1239,29 @@ impl LanguageServerManager {
         server_cmd: Option<&str>,
     ) -> Result<Value> {
         self.ensure_started(server_cmd)?;
+        let command = self.current_cmd.clone().unwrap_or_default();
         let id = self.alloc_id();
         let req = json!({"jsonrpc":"2.0","id":id,"method":method,"params":params});
-        self.write_jsonrpc(&req)?;
+        self.write_jsonrpc(&req)
+            .map_err(|e| self.annotate_exit(e, &command))?;
+        let timeout = Self::request_timeout_from_env();
+        let deadline = timeout.map(|t| Instant::now() + t);
         loop {
-            let value = self.read_message().context("parse lsp response")?;
+            let value = match self.read_message_before(deadline) {
+                Ok(value) => value,
+                Err(err) if err.is::<ReadDeadlineElapsed>() => {
+                    let timed_out = RequestTimedOut {
+                        method: method.to_string(),
+                        timeout: timeout.unwrap_or_default(),
+                    };
+                    eprintln!("mcp-lsp: {timed_out}; restarting '{}'", command);
+                    self.abandon_server();
+                    return Err(timed_out.into());
+                }
+                Err(err) => {
+                    return Err(self.annotate_exit(err.context("parse lsp response"), &command))
+                }
+            };
 
             if value.get("id") == Some(&json!(id)) {
                 if let Some(err) = value.get("error") {
@@ -698,6 +1287,10 @@ impl LanguageServerManager {
                     }
                     continue;
                 }
+                if method_name == "textDocument/publishDiagnostics" {
+                    self.record_published_diagnostics(value.get("params"));
+                    continue;
+                }
                 eprintln!(
                     "mcp-lsp: dropping unsolicited notification '{}' while awaiting '{}'",
                     method_name, method
@@ -731,13 +1324,130 @@ impl LanguageServerManager {
 
     pub fn notify(&mut self, method: &str, params: Value, server_cmd: Option<&str>) -> Result<()> {
         self.ensure_started(server_cmd)?;
+        let command = self.current_cmd.clone().unwrap_or_default();
         let notif = json!({"jsonrpc":"2.0","method": method, "params": params});
         self.write_jsonrpc(&notif)
+            .map_err(|e| self.annotate_exit(e, &command))
+    }
+
+    /// Static capabilities from `initialize` merged with dynamic registrations. A registered
+    /// method only fills in its provider when the static value is absent or `false`.
+    fn effective_capabilities(&self) -> Option<Value> {
+        if self.server_capabilities.is_none() && self.dynamic_registrations.is_empty() {
+            return None;
+        }
+        let mut caps = self
+            .server_capabilities
+            .clone()
+            .unwrap_or_else(|| json!({}));
+        for (_, method, options) in &self.dynamic_registrations {
+            let Some(path) = capability_path_for_method(method) else {
+                continue;
+            };
+            let value = if options.is_object() {
+                options.clone()
+            } else {
+                Value::Bool(true)
+            };
+            if let Some(slot) = capability_slot(&mut caps, path) {
+                if matches!(slot, Value::Null | Value::Bool(false)) {
+                    *slot = value;
+                }
+            }
+        }
+        Some(caps)
+    }
+
+    /// Static capabilities, dynamic registrations, and their merged view for reporting.
+    pub fn capability_report(&mut self, server_cmd: Option<&str>) -> Result<Value> {
+        self.ensure_started(server_cmd)?;
+        let registrations: Vec<Value> = self
+            .dynamic_registrations
+            .iter()
+            .map(|(id, method, options)| {
+                json!({ "id": id, "method": method, "registerOptions": options })
+            })
+            .collect();
+        Ok(json!({
+            "capabilities": self.effective_capabilities(),
+            "staticCapabilities": self.server_capabilities,
+            "dynamicRegistrations": registrations
+        }))
+    }
+
+    /// The merged capabilities distilled into flat flags (sync kind, diagnostics model,
+    /// resolve providers, ...) for callers that only need to pick the right tools.
+    pub fn feature_summary(&mut self, server_cmd: Option<&str>) -> Result<Value> {
+        self.ensure_started(server_cmd)?;
+        let caps = self.effective_capabilities().unwrap_or_else(|| json!({}));
+        Ok(summarize_capabilities(&caps))
+    }
+
+    /// Keep the diagnostics of a `textDocument/publishDiagnostics` notification; each push
+    /// replaces the previous one for its document.
+    fn record_published_diagnostics(&mut self, params: Option<&Value>) {
+        let Some(params) = params else {
+            return;
+        };
+        let Some(uri) = params.get("uri").and_then(|u| u.as_str()) else {
+            return;
+        };
+        let diagnostics = params
+            .get("diagnostics")
+            .cloned()
+            .unwrap_or_else(|| json!([]));
+        self.published_diagnostics
+            .insert(crate::LanguageServerPool::normalize_uri(uri), diagnostics);
+    }
+
+    /// Diagnostics last pushed by the server, keyed by normalized document uri.
+    pub fn published_diagnostics(&self) -> &HashMap<String, Value> {
+        &self.published_diagnostics
+    }
+
+    /// Command identifiers from `executeCommandProvider.commands` of the merged capabilities;
+    /// empty when the server exposes none.
+    pub fn execute_commands(&mut self, server_cmd: Option<&str>) -> Result<Vec<String>> {
+        self.ensure_started(server_cmd)?;
+        let commands = self
+            .effective_capabilities()
+            .and_then(|caps| caps.get("executeCommandProvider")?.get("commands").cloned())
+            .and_then(|v| v.as_array().cloned())
+            .unwrap_or_default();
+        Ok(commands
+            .iter()
+            .filter_map(|c| c.as_str().map(str::to_string))
+            .collect())
+    }
+
+    /// `{ tokenTypes, tokenModifiers }` from the server's semantic tokens legend, needed to
+    /// decode `lsp_semantic_tokens_*` data. Cached until the server restarts or (un)registers
+    /// capabilities.
+    pub fn semantic_tokens_legend(&mut self, server_cmd: Option<&str>) -> Result<Value> {
+        self.ensure_started(server_cmd)?;
+        if let Some(legend) = &self.semantic_tokens_legend {
+            return Ok(legend.clone());
+        }
+        let legend = self
+            .effective_capabilities()
+            .and_then(|caps| caps.get("semanticTokensProvider")?.get("legend").cloned())
+            .ok_or_else(|| {
+                anyhow!(
+                    "server '{}' does not advertise semanticTokensProvider.legend",
+                    self.current_cmd.as_deref().unwrap_or_default()
+                )
+            })?;
+        let legend = json!({
+            "tokenTypes": legend.get("tokenTypes").cloned().unwrap_or_else(|| json!([])),
+            "tokenModifiers": legend.get("tokenModifiers").cloned().unwrap_or_else(|| json!([])),
+        });
+        self.semantic_tokens_legend = Some(legend.clone());
+        Ok(legend)
     }
 
     pub fn capabilities(&mut self, server_cmd: Option<&str>) -> Result<Option<Value>> {
         match self.ensure_started(server_cmd) {
-            Ok(()) => Ok(self.server_capabilities.clone()),
+            Ok(()) => Ok(self.effective_capabilities()),
             Err(e) => {
                 // If no server is configured, treat as no capabilities available.
                 let msg = format!("{}", e);
@@ -750,6 +1460,20 @@ impl LanguageServerManager {
         }
     }
 
+    /// Start the server (running the full initialize handshake) without sending a request.
+    /// Returns whether this call launched it, as opposed to finding it already running.
+    pub fn warm_up(&mut self, server_cmd: Option<&str>) -> Result<bool> {
+        let running = match self.child.as_mut() {
+            Some(child) => {
+                matches!(child.try_wait(), Ok(None))
+                    && server_cmd.is_none_or(|cmd| self.current_cmd.as_deref() == Some(cmd))
+            }
+            None => false,
+        };
+        self.ensure_started(server_cmd)?;
+        Ok(!running)
+    }
+
     pub fn shutdown(&mut self) -> Result<()> {
         self.stop_child()
     }
@@ -827,3 +1551,34 @@ impl LanguageServerManager {
         anyhow!(msg)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A response that misses the deadline must still reach the next read: the reader thread
+    // keeps stdout, so the manager does not lose the server's output to a timed-out read.
+    #[test]
+    fn late_message_is_read_after_a_missed_deadline() -> Result<()> {
+        let mut child = Command::new("sh")
+            .args([
+                "-c",
+                r#"sleep 0.3; printf '{"jsonrpc":"2.0","id":1,"result":7}\n'"#,
+            ])
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdout = child.stdout.take().ok_or_else(|| anyhow!("no stdout"))?;
+        let mut manager = LanguageServerManager::with_command("sh".to_string());
+        manager.incoming = Some(LanguageServerManager::spawn_reader(stdout, None));
+
+        let missed = manager.read_message_before(Some(Instant::now() + Duration::from_millis(20)));
+        assert!(missed.is_err_and(|err| err.is::<ReadDeadlineElapsed>()));
+        let value = manager.read_message_before(Some(Instant::now() + Duration::from_secs(10)))?;
+        assert_eq!(value["result"], json!(7));
+        assert!(matches!(manager.read_mode, Some(Framing::Newline)));
+
+        assert!(manager.read_message().is_err(), "EOF once the server exits");
+        child.wait()?;
+        Ok(())
+    }
+}
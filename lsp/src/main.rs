@@ -1,9 +1,10 @@
+mod edits;
 mod ls;
 mod mcp;
 use anyhow::{anyhow, Context, Result};
 use ls::LanguageServerManager;
 use serde_json::{json, Map, Value};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::io::ErrorKind;
 use std::sync::{Mutex, OnceLock};
 use tokio::task;
@@ -134,6 +135,9 @@ fn build_lsp_invocation(
     match tool {
         "lsp_hover"
         | "lsp_definition"
+        | "lsp_definition_with_hover"
+        | "lsp_definition_resolved"
+        | "lsp_usages"
         | "lsp_type_definition"
         | "lsp_implementation"
         | "lsp_document_highlight"
@@ -145,7 +149,10 @@ fn build_lsp_invocation(
             let position = require_object_field(args, "position")?;
             let method = match tool {
                 "lsp_hover" => "textDocument/hover",
-                "lsp_definition" => "textDocument/definition",
+                "lsp_definition"
+                | "lsp_definition_with_hover"
+                | "lsp_definition_resolved"
+                | "lsp_usages" => "textDocument/definition",
                 "lsp_type_definition" => "textDocument/typeDefinition",
                 "lsp_implementation" => "textDocument/implementation",
                 "lsp_document_highlight" => "textDocument/documentHighlight",
@@ -237,7 +244,7 @@ fn build_lsp_invocation(
                 Some(uri),
             ))
         }
-        "lsp_document_symbol" => {
+        "lsp_document_symbol" | "lsp_document_symbols_flat" => {
             let uri = canonical_uri(args)?;
             Ok(make_invocation(
                 "textDocument/documentSymbol",
@@ -245,8 +252,20 @@ fn build_lsp_invocation(
                 Some(uri),
             ))
         }
-        "lsp_workspace_symbol" => {
+        "lsp_workspace_symbol" | "lsp_locate_symbol" => {
             let query = require_string_field(args, "query")?;
+            let query = if tool == "lsp_locate_symbol" {
+                if query.trim().is_empty() {
+                    return Err(invalid_params_error("Field 'query' must not be empty"));
+                }
+                if args.get("uri").is_some_and(|v| !v.is_string()) {
+                    return Err(invalid_params_error("Field 'uri' must be a string"));
+                }
+                // Servers match names, not qualified paths; the container is checked afterwards.
+                split_qualified_query(&query).1.to_string()
+            } else {
+                query
+            };
             Ok(make_invocation(
                 "workspace/symbol",
                 json!({ "query": query }),
@@ -339,7 +358,7 @@ fn build_lsp_invocation(
                 Some(uri),
             ))
         }
-        "lsp_formatting" => {
+        "lsp_formatting" | "lsp_format_preview" => {
             let uri = canonical_uri(args)?;
             let options = require_object_field(args, "options")?;
             Ok(make_invocation(
@@ -365,7 +384,7 @@ fn build_lsp_invocation(
                 Some(uri),
             ))
         }
-        "lsp_on_type_formatting" => {
+        "lsp_on_type_formatting" | "lsp_on_type_format_preview" => {
             let uri = canonical_uri(args)?;
             let position = require_object_field(args, "position")?;
             let ch = require_string_field(args, "ch")?;
@@ -461,13 +480,14 @@ fn build_lsp_invocation(
         }
         "lsp_semantic_tokens_full_delta" => {
             let uri = canonical_uri(args)?;
-            let prev = require_string_field(args, "previousResultId")?;
+            let mut params = json!({ "textDocument": {"uri": uri} });
+            // Without `previousResultId` the pool fills in the document's last `resultId`.
+            if let Some(prev) = args.get("previousResultId").and_then(Value::as_str) {
+                params["previousResultId"] = json!(prev);
+            }
             Ok(make_invocation(
                 "textDocument/semanticTokens/full/delta",
-                json!({
-                    "textDocument": {"uri": uri},
-                    "previousResultId": prev
-                }),
+                params,
                 Some(uri),
             ))
         }
@@ -492,6 +512,32 @@ fn build_lsp_invocation(
             };
             Ok(make_invocation("workspace/executeCommand", params, None))
         }
+        "lsp_will_save" | "lsp_will_save_wait_until" => {
+            let uri = canonical_uri(args)?;
+            let reason =
+                match args.get("reason") {
+                    None => 1,
+                    Some(v) => match v.as_u64() {
+                        Some(n @ 1..=3) => n,
+                        _ => return Err(invalid_params_error(
+                            "Field 'reason' must be 1 (Manual), 2 (AfterDelay), or 3 (FocusOut)",
+                        )),
+                    },
+                };
+            let method = if tool == "lsp_will_save" {
+                "textDocument/willSave"
+            } else {
+                "textDocument/willSaveWaitUntil"
+            };
+            Ok(make_invocation(
+                method,
+                json!({
+                    "textDocument": {"uri": uri},
+                    "reason": reason
+                }),
+                Some(uri),
+            ))
+        }
         "lsp_will_create_files" => {
             let files = require_array_field(args, "files")?;
             Ok(make_invocation(
@@ -543,7 +589,7 @@ fn build_lsp_invocation(
                 Some(uri),
             ))
         }
-        "lsp_workspace_diagnostic" => {
+        "lsp_workspace_diagnostic" | "lsp_workspace_problems" => {
             let mut payload = json!({});
             if let Some(prev) = args.get("previousResultIds") {
                 if let Some(obj) = payload.as_object_mut() {
@@ -594,6 +640,7 @@ async fn handle_lsp_call(
         None
     };
     let is_open = method == "textDocument/didOpen";
+    let is_change = method == "textDocument/didChange";
     let is_close = method == "textDocument/didClose";
 
     let method_for_request = method.clone();
@@ -643,6 +690,15 @@ async fn handle_lsp_call(
                     pool.associate_document(uri, &cmd);
                 }
             }
+            if is_change {
+                if let Some(payload) = open_params.as_ref() {
+                    // Keep the text just opened implicitly so ranged changes apply to it.
+                    pool.record_document_text("textDocument/didOpen", payload);
+                }
+            }
+            if is_open || is_change {
+                pool.record_document_text(&method_for_request, &params_for_request);
+            }
             if is_close {
                 if let Some(uri) = uri_hint_for_request.as_ref() {
                     pool.release_document(uri);
@@ -692,6 +748,7 @@ async fn handle_lsp_call(
 }
 
 async fn handle_lsp_notify(
+    tool: &str,
     mut args: Map<String, Value>,
     server_cmd: Option<String>,
 ) -> JsonRpcResponse {
@@ -719,6 +776,7 @@ async fn handle_lsp_notify(
         None
     };
     let is_open = method == "textDocument/didOpen";
+    let is_change = method == "textDocument/didChange";
     let is_close = method == "textDocument/didClose";
 
     let method_for_request = method.clone();
@@ -746,6 +804,9 @@ async fn handle_lsp_notify(
                     pool.associate_document(uri, &cmd);
                 }
             }
+            if is_open || is_change {
+                pool.record_document_text(&method_for_request, &params_for_request);
+            }
             if is_close {
                 if let Some(uri) = uri_hint_for_request.as_ref() {
                     pool.release_document(uri);
@@ -758,41 +819,330 @@ async fn handle_lsp_notify(
 
     match result {
         Ok(Ok(())) => JsonRpcResponse::result(json!({
-            "tool": "lsp_notify",
+            "tool": tool,
             "status": "ok"
         })),
         Ok(Err(e)) => {
             let data = build_error_data(
-                "lsp_notify",
+                tool,
                 Some(&method),
                 uri_hint.as_deref(),
                 server_cmd.as_deref(),
                 &e,
             );
             if let Ok(json_data) = serde_json::to_string(&data) {
-                eprintln!("mcp-lsp: tool 'lsp_notify' failed -> {}", json_data);
+                eprintln!("mcp-lsp: tool '{}' failed -> {}", tool, json_data);
             }
-            let message = format_tool_error_message("lsp_notify", Some(&method), &e);
+            let message = format_tool_error_message(tool, Some(&method), &e);
             JsonRpcResponse::error(ErrorObject::new(-32050, &message, Some(data)))
         }
         Err(join_err) => {
             let err = anyhow::Error::new(join_err);
             let data = build_error_data(
-                "lsp_notify",
+                tool,
                 Some(&method),
                 uri_hint.as_deref(),
                 server_cmd.as_deref(),
                 &err,
             );
             if let Ok(json_data) = serde_json::to_string(&data) {
-                eprintln!("mcp-lsp: tool 'lsp_notify' failed -> {}", json_data);
+                eprintln!("mcp-lsp: tool '{}' failed -> {}", tool, json_data);
+            }
+            let message = format_tool_error_message(tool, Some(&method), &err);
+            JsonRpcResponse::error(ErrorObject::new(-32050, &message, Some(data)))
+        }
+    }
+}
+
+/// Report the resolved server's static capabilities, dynamic registrations, and the merged
+/// set used for tool filtering (`lsp_capabilities`), the merged set distilled into flat
+/// feature flags (`lsp_server_features`), or the semantic tokens legend
+/// (`lsp_semantic_tokens_legend`).
+async fn handle_lsp_capabilities(
+    tool: &'static str,
+    args: Map<String, Value>,
+    server_cmd: Option<String>,
+) -> JsonRpcResponse {
+    let uri = args
+        .get("uri")
+        .and_then(|v| v.as_str())
+        .map(LanguageServerPool::normalize_uri);
+    let uri_for_request = uri.clone();
+    let server_cmd_for_request = server_cmd.clone();
+
+    let result = task::spawn_blocking(move || {
+        with_language_pool(|pool| {
+            let cmd = pool.resolve_command(
+                server_cmd_for_request.as_deref(),
+                uri_for_request.as_deref(),
+                None,
+            )?;
+            let report = pool.with_manager(&cmd, |lsm| match tool {
+                "lsp_server_features" => lsm.feature_summary(Some(&cmd)),
+                "lsp_semantic_tokens_legend" => lsm.semantic_tokens_legend(Some(&cmd)),
+                "lsp_list_commands" => lsm
+                    .execute_commands(Some(&cmd))
+                    .map(|commands| json!({ "commands": commands })),
+                _ => lsm.capability_report(Some(&cmd)),
+            })?;
+            Ok((cmd, report))
+        })
+    })
+    .await
+    .map_err(anyhow::Error::new)
+    .and_then(|res| res);
+
+    match result {
+        Ok((cmd, report)) => JsonRpcResponse::result(json!({
+            "tool": tool,
+            "status": "ok",
+            "serverCommand": cmd,
+            "result": report
+        })),
+        Err(e) => {
+            let data = build_error_data(tool, None, uri.as_deref(), server_cmd.as_deref(), &e);
+            if let Ok(json_data) = serde_json::to_string(&data) {
+                eprintln!("mcp-lsp: tool '{}' failed -> {}", tool, json_data);
+            }
+            let message = format_tool_error_message(tool, None, &e);
+            JsonRpcResponse::error(ErrorObject::new(-32050, &message, Some(data)))
+        }
+    }
+}
+
+/// Start the server selected by `serverCommand`/`languageId` ahead of real requests, reporting
+/// whether this call launched it and how long the initialize handshake took.
+async fn handle_lsp_warmup(
+    args: Map<String, Value>,
+    server_cmd: Option<String>,
+) -> JsonRpcResponse {
+    let language_id = args
+        .get("languageId")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string());
+    let server_cmd_for_request = server_cmd.clone();
+
+    let result = task::spawn_blocking(move || {
+        with_language_pool(|pool| {
+            let cmd = pool.resolve_command(
+                server_cmd_for_request.as_deref(),
+                None,
+                language_id.as_deref(),
+            )?;
+            let started_at = std::time::Instant::now();
+            let launched = pool.with_manager(&cmd, |lsm| lsm.warm_up(Some(&cmd)))?;
+            Ok((cmd, launched, started_at.elapsed()))
+        })
+    })
+    .await
+    .map_err(anyhow::Error::new)
+    .and_then(|res| res);
+
+    match result {
+        Ok((cmd, launched, elapsed)) => JsonRpcResponse::result(json!({
+            "tool": "lsp_warmup",
+            "status": "ok",
+            "serverCommand": cmd,
+            "result": {
+                "started": true,
+                "alreadyRunning": !launched,
+                "initializeMs": if launched { json!(elapsed.as_millis() as u64) } else { Value::Null }
+            }
+        })),
+        Err(e) => {
+            let method = "initialize";
+            let data =
+                build_error_data("lsp_warmup", Some(method), None, server_cmd.as_deref(), &e);
+            if let Ok(json_data) = serde_json::to_string(&data) {
+                eprintln!("mcp-lsp: tool 'lsp_warmup' failed -> {}", json_data);
+            }
+            let message = format_tool_error_message("lsp_warmup", Some(method), &e);
+            JsonRpcResponse::error(ErrorObject::new(-32050, &message, Some(data)))
+        }
+    }
+}
+
+/// Close a document that mcp-lsp opened (implicitly or via `lsp_notify`) and drop its routing
+/// and cached diagnostics. Closing a document that is not open is a no-op.
+async fn handle_lsp_did_close(
+    args: Map<String, Value>,
+    server_cmd: Option<String>,
+) -> JsonRpcResponse {
+    let uri = match canonical_uri(&args) {
+        Ok(uri) => uri,
+        Err(err) => return JsonRpcResponse::error(err),
+    };
+    let uri_for_request = uri.clone();
+    let server_cmd_for_request = server_cmd.clone();
+
+    let result = task::spawn_blocking(move || {
+        with_language_pool(|pool| {
+            if !pool.has_document(&uri_for_request) {
+                return Ok(false);
+            }
+            let cmd = pool.resolve_command(
+                server_cmd_for_request.as_deref(),
+                Some(uri_for_request.as_str()),
+                None,
+            )?;
+            let params = json!({ "textDocument": { "uri": uri_for_request } });
+            pool.with_manager(&cmd, |lsm| {
+                lsm.notify("textDocument/didClose", params, Some(cmd.as_str()))
+            })?;
+            pool.release_document(&uri_for_request);
+            Ok(true)
+        })
+    })
+    .await
+    .map_err(anyhow::Error::new)
+    .and_then(|res| res);
+
+    match result {
+        Ok(was_open) => JsonRpcResponse::result(json!({
+            "tool": "lsp_did_close",
+            "status": "ok",
+            "uri": uri,
+            "wasOpen": was_open
+        })),
+        Err(e) => {
+            let method = "textDocument/didClose";
+            let data = build_error_data(
+                "lsp_did_close",
+                Some(method),
+                Some(uri.as_str()),
+                server_cmd.as_deref(),
+                &e,
+            );
+            if let Ok(json_data) = serde_json::to_string(&data) {
+                eprintln!("mcp-lsp: tool 'lsp_did_close' failed -> {}", json_data);
+            }
+            let message = format_tool_error_message("lsp_did_close", Some(method), &e);
+            JsonRpcResponse::error(ErrorObject::new(-32050, &message, Some(data)))
+        }
+    }
+}
+
+async fn handle_lsp_diagnostics_summary(
+    args: Map<String, Value>,
+    server_cmd: Option<String>,
+) -> JsonRpcResponse {
+    let tool = "lsp_diagnostics_summary";
+    let uri = match canonical_uri(&args) {
+        Ok(uri) => uri,
+        Err(err) => return JsonRpcResponse::error(err),
+    };
+    let uri_for_request = uri.clone();
+    let server_cmd_for_request = server_cmd.clone();
+
+    let result = task::spawn_blocking(move || {
+        with_language_pool(|pool| {
+            let cmd = pool.resolve_command(
+                server_cmd_for_request.as_deref(),
+                Some(uri_for_request.as_str()),
+                None,
+            )?;
+            let need_open = !pool.has_document(&uri_for_request);
+            let open_params = if need_open {
+                Some(pool.build_did_open_params(&uri_for_request, None)?)
+            } else {
+                None
+            };
+            let summary = pool.diagnostics_summary(&cmd, &uri_for_request, open_params.as_ref())?;
+            if need_open {
+                pool.associate_document(&uri_for_request, &cmd);
+            }
+            Ok(summary)
+        })
+    })
+    .await
+    .map_err(anyhow::Error::new)
+    .and_then(|res| res);
+
+    match result {
+        Ok(summary) => JsonRpcResponse::result(json!({
+            "tool": tool,
+            "status": "ok",
+            "uri": uri,
+            "result": summary
+        })),
+        Err(e) => {
+            let method = "textDocument/diagnostic";
+            let data = build_error_data(
+                tool,
+                Some(method),
+                Some(uri.as_str()),
+                server_cmd.as_deref(),
+                &e,
+            );
+            if let Ok(json_data) = serde_json::to_string(&data) {
+                eprintln!("mcp-lsp: tool '{}' failed -> {}", tool, json_data);
+            }
+            let message = format_tool_error_message(tool, Some(method), &e);
+            JsonRpcResponse::error(ErrorObject::new(-32050, &message, Some(data)))
+        }
+    }
+}
+
+async fn handle_lsp_poll_diagnostics(
+    args: Map<String, Value>,
+    server_cmd: Option<String>,
+) -> JsonRpcResponse {
+    let tool = "lsp_poll_diagnostics";
+    let uri = if args.contains_key("uri") {
+        match canonical_uri(&args) {
+            Ok(uri) => Some(uri),
+            Err(err) => return JsonRpcResponse::error(err),
+        }
+    } else {
+        None
+    };
+    let uri_for_request = uri.clone();
+    let server_cmd_for_request = server_cmd.clone();
+
+    let result = task::spawn_blocking(move || {
+        with_language_pool(|pool| {
+            Ok(pool.polled_diagnostics(
+                uri_for_request.as_deref(),
+                server_cmd_for_request.as_deref(),
+            ))
+        })
+    })
+    .await
+    .map_err(anyhow::Error::new)
+    .and_then(|res| res);
+
+    match result {
+        Ok(documents) => {
+            let mut response = json!({
+                "tool": tool,
+                "status": "ok",
+                "result": { "documents": documents }
+            });
+            if let Some(uri) = uri {
+                response["uri"] = json!(uri);
+                response["result"]["available"] = json!(!documents.is_empty());
+            }
+            JsonRpcResponse::result(response)
+        }
+        Err(e) => {
+            let data = build_error_data(tool, None, uri.as_deref(), server_cmd.as_deref(), &e);
+            if let Ok(json_data) = serde_json::to_string(&data) {
+                eprintln!("mcp-lsp: tool '{}' failed -> {}", tool, json_data);
             }
-            let message = format_tool_error_message("lsp_notify", Some(&method), &err);
+            let message = format_tool_error_message(tool, None, &e);
             JsonRpcResponse::error(ErrorObject::new(-32050, &message, Some(data)))
         }
     }
 }
 
+/// Buffer contents supplied by the caller through `didOpen`/`didChange`, preferred over the
+/// on-disk file whenever the bridge opens the document itself.
+struct StoredDocument {
+    text: String,
+    language_id: Option<String>,
+    version: i64,
+}
+
 /// Tracks running language servers and routes requests based on languageId/extension,
 /// falling back to the most recently used server or environment overrides when
 /// document hints are unavailable.
@@ -804,13 +1154,29 @@ pub(crate) struct LanguageServerPool {
     ext_map: HashMap<String, String>,
     ext_language_map: HashMap<String, String>,
     last_server: Option<String>,
+    diagnostic_reports: HashMap<String, Value>,
+    /// Last semantic tokens `resultId` per document, used as `previousResultId` for deltas.
+    semantic_token_result_ids: HashMap<String, String>,
+    document_texts: HashMap<String, StoredDocument>,
 }
 
 impl LanguageServerPool {
     fn new() -> Self {
+        Self::with_server_map(std::env::var("LSP_SERVER_MAP").ok().as_deref())
+    }
+
+    /// Pool whose built-in routing is overridden by `server_map`, an `LSP_SERVER_MAP` value.
+    fn with_server_map(server_map: Option<&str>) -> Self {
         let default_cmd = std::env::var("LSP_SERVER_CMD").ok();
         let (mut lang_map, mut ext_map, mut ext_language_map) = Self::built_in_server_map();
-        Self::load_server_map_overrides(&mut lang_map, &mut ext_map, &mut ext_language_map);
+        if let Some(raw) = server_map {
+            Self::load_server_map_overrides(
+                raw,
+                &mut lang_map,
+                &mut ext_map,
+                &mut ext_language_map,
+            );
+        }
         Self {
             default_cmd,
             managers: HashMap::new(),
@@ -819,6 +1185,9 @@ impl LanguageServerPool {
             ext_map,
             ext_language_map,
             last_server: None,
+            diagnostic_reports: HashMap::new(),
+            semantic_token_result_ids: HashMap::new(),
+            document_texts: HashMap::new(),
         }
     }
 
@@ -923,16 +1292,14 @@ impl LanguageServerPool {
     }
 
     fn load_server_map_overrides(
+        raw: &str,
         lang_map: &mut HashMap<String, String>,
         ext_map: &mut HashMap<String, String>,
         ext_language_map: &mut HashMap<String, String>,
     ) {
-        if let Ok(raw) = std::env::var("LSP_SERVER_MAP") {
-            if let Ok(value) = serde_json::from_str::<Value>(&raw) {
-                Self::populate_server_map(&value, lang_map, ext_map, ext_language_map);
-            } else {
-                eprintln!("warning: failed to parse LSP_SERVER_MAP as JSON");
-            }
+        match parse_relaxed_json(raw) {
+            Ok(value) => Self::populate_server_map(&value, lang_map, ext_map, ext_language_map),
+            Err(err) => eprintln!("warning: failed to parse LSP_SERVER_MAP: {err}"),
         }
     }
 
@@ -942,55 +1309,95 @@ impl LanguageServerPool {
         ext_map: &mut HashMap<String, String>,
         ext_language_map: &mut HashMap<String, String>,
     ) {
-        if let Value::Object(obj) = value {
-            for (key, val) in obj {
-                if key.eq_ignore_ascii_case("languages") || key.eq_ignore_ascii_case("language") {
-                    if let Value::Object(inner) = val {
-                        for (lang, cmd) in inner {
-                            if let Some(cmd_str) = cmd.as_str() {
-                                lang_map.insert(lang.to_ascii_lowercase(), cmd_str.to_string());
-                            }
-                        }
-                    }
-                    continue;
+        let Value::Object(obj) = value else {
+            return;
+        };
+        // Extensions mapped without an explicit `language`; resolved once every language entry
+        // is known.
+        let mut unlabeled = Vec::new();
+        let mut add_extension = |ext: &str, entry: &Value| {
+            let Some((cmd, language)) = Self::server_map_entry(entry) else {
+                return;
+            };
+            let canonical = ext.trim_start_matches('.').to_ascii_lowercase();
+            ext_map.insert(canonical.clone(), cmd);
+            match language {
+                Some(language) => {
+                    ext_language_map.insert(canonical, language);
                 }
-                if key.eq_ignore_ascii_case("extensions") || key.eq_ignore_ascii_case("extension") {
-                    if let Value::Object(inner) = val {
-                        for (ext, cmd) in inner {
-                            if let Some(cmd_str) = cmd.as_str() {
-                                let canonical = ext.trim_start_matches('.').to_ascii_lowercase();
-                                ext_map.insert(canonical.clone(), cmd_str.to_string());
-                                ext_language_map
-                                    .entry(canonical.clone())
-                                    .or_insert(canonical.clone());
-                            }
+                None => unlabeled.push(canonical),
+            }
+        };
+        for (key, val) in obj {
+            if key.eq_ignore_ascii_case("languages") || key.eq_ignore_ascii_case("language") {
+                if let Value::Object(inner) = val {
+                    for (lang, entry) in inner {
+                        if let Some((cmd, _)) = Self::server_map_entry(entry) {
+                            lang_map.insert(lang.to_ascii_lowercase(), cmd);
                         }
                     }
-                    continue;
                 }
-                if let Some(cmd_str) = val.as_str() {
-                    if let Some(rest) = key.strip_prefix("lang:") {
-                        lang_map.insert(rest.to_ascii_lowercase(), cmd_str.to_string());
-                    } else if let Some(rest) = key.strip_prefix("ext:") {
-                        let canonical = rest.trim_start_matches('.').to_ascii_lowercase();
-                        ext_map.insert(canonical.clone(), cmd_str.to_string());
-                        ext_language_map
-                            .entry(canonical.clone())
-                            .or_insert(canonical.clone());
-                    } else if key.starts_with('.') {
-                        let canonical = key.trim_start_matches('.').to_ascii_lowercase();
-                        ext_map.insert(canonical.clone(), cmd_str.to_string());
-                        ext_language_map
-                            .entry(canonical.clone())
-                            .or_insert(canonical.clone());
-                    } else {
-                        lang_map.insert(key.to_ascii_lowercase(), cmd_str.to_string());
+                continue;
+            }
+            if key.eq_ignore_ascii_case("extensions") || key.eq_ignore_ascii_case("extension") {
+                if let Value::Object(inner) = val {
+                    for (ext, entry) in inner {
+                        add_extension(ext, entry);
                     }
                 }
+                continue;
+            }
+            if let Some(rest) = key.strip_prefix("lang:") {
+                if let Some((cmd, _)) = Self::server_map_entry(val) {
+                    lang_map.insert(rest.to_ascii_lowercase(), cmd);
+                }
+            } else if let Some(rest) = key.strip_prefix("ext:") {
+                add_extension(rest, val);
+            } else if key.starts_with('.') {
+                add_extension(key, val);
+            } else if let Some((cmd, _)) = Self::server_map_entry(val) {
+                lang_map.insert(key.to_ascii_lowercase(), cmd);
+            }
+        }
+        for ext in unlabeled {
+            if ext_language_map.contains_key(&ext) {
+                continue;
+            }
+            let language =
+                Self::language_served_by(lang_map, &ext_map[&ext]).unwrap_or(ext.clone());
+            ext_language_map.insert(ext, language);
+        }
+    }
+
+    /// A server map value: a command string, or `{ command, language? }` where `language`
+    /// (alias `languageId`) is the languageId sent in `didOpen` for an extension entry.
+    fn server_map_entry(entry: &Value) -> Option<(String, Option<String>)> {
+        match entry {
+            Value::String(cmd) => Some((cmd.clone(), None)),
+            Value::Object(obj) => {
+                let cmd = obj.get("command").and_then(|v| v.as_str())?;
+                let language = obj
+                    .get("language")
+                    .or_else(|| obj.get("languageId"))
+                    .and_then(|v| v.as_str())
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string);
+                Some((cmd.to_string(), language))
             }
+            _ => None,
         }
     }
 
+    /// The language (alphabetically first, for determinism) mapped to exactly `cmd`, so an
+    /// extension routed to e.g. `rust-analyzer` opens as `rust` rather than as its extension.
+    fn language_served_by(lang_map: &HashMap<String, String>, cmd: &str) -> Option<String> {
+        lang_map
+            .iter()
+            .filter(|(_, c)| c.as_str() == cmd)
+            .map(|(lang, _)| lang.clone())
+            .min()
+    }
+
     fn resolve_command(
         &mut self,
         explicit: Option<&str>,
@@ -1006,8 +1413,12 @@ impl LanguageServerPool {
                 return Ok(cmd.clone());
             }
         }
-        if let Some(lang) = language {
-            let key = lang.to_ascii_lowercase();
+        // Without an explicit languageId, use the one an implicit didOpen would send for this
+        // extension so requests before and after the open reach the same server.
+        let language = language
+            .map(|lang| lang.to_ascii_lowercase())
+            .or_else(|| uri.and_then(|uri| self.language_for_uri(uri)));
+        if let Some(key) = language {
             if let Some(cmd) = self.lang_map.get(&key) {
                 return Ok(cmd.clone());
             }
@@ -1029,6 +1440,14 @@ impl LanguageServerPool {
         }
     }
 
+    /// languageId derived from the document's extension via the extension→language table.
+    fn language_for_uri(&self, uri: &str) -> Option<String> {
+        let ext = Self::extension_from_uri(&Self::normalize_uri(uri))?;
+        self.ext_language_map
+            .get(&ext)
+            .map(|lang| lang.to_ascii_lowercase())
+    }
+
     fn with_manager<F, T>(&mut self, cmd: &str, f: F) -> Result<T>
     where
         F: FnOnce(&mut LanguageServerManager) -> Result<T>,
@@ -1049,6 +1468,9 @@ impl LanguageServerPool {
 
     fn release_document(&mut self, uri: &str) {
         let key = Self::normalize_uri(uri);
+        self.diagnostic_reports.remove(&key);
+        self.semantic_token_result_ids.remove(&key);
+        self.document_texts.remove(&key);
         let removed = self.doc_servers.remove(&key);
         if let Some(command) = removed {
             if self.doc_servers.values().any(|c| c == &command) {
@@ -1065,72 +1487,588 @@ impl LanguageServerPool {
         }
         self.managers.clear();
         self.doc_servers.clear();
+        self.diagnostic_reports.clear();
+        self.semantic_token_result_ids.clear();
+        self.document_texts.clear();
         self.last_server = None;
         Ok(())
     }
 
-    fn probe_default_capabilities(&mut self) -> Result<Option<Value>> {
-        let Some(cmd) = self.default_cmd.clone() else {
-            return Ok(None);
+    /// Keep the caller's buffer text from a `didOpen` or `didChange` notification so later
+    /// implicit opens use it instead of the file on disk. Ranged changes are applied to the
+    /// stored text; they are ignored for documents whose text was never supplied.
+    fn record_document_text(&mut self, method: &str, params: &Value) {
+        let Some(doc) = params.get("textDocument") else {
+            return;
         };
-        self.with_manager(&cmd, |lsm| lsm.capabilities(Some(&cmd)))
-    }
-
-    fn extension_from_uri(uri: &str) -> Option<String> {
-        let path_part = uri.strip_prefix("file://").unwrap_or(uri);
-        let path = std::path::Path::new(path_part);
-        if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
-            return Some(ext.to_ascii_lowercase());
-        }
-        let segment = path_part
-            .rsplit(|c: char| std::path::is_separator(c))
-            .next()
-            .unwrap_or(path_part);
-        segment
-            .rsplit_once('.')
-            .map(|(_, ext)| ext.to_ascii_lowercase())
-    }
-
-    fn path_from_uri(uri: &str) -> std::path::PathBuf {
-        if let Ok(url) = Url::parse(uri) {
-            if url.scheme() == "file" {
-                if let Ok(path) = url.to_file_path() {
-                    return path;
+        let Some(uri) = doc.get("uri").and_then(|v| v.as_str()) else {
+            return;
+        };
+        let key = Self::normalize_uri(uri);
+        let version = doc.get("version").and_then(|v| v.as_i64());
+        match method {
+            "textDocument/didOpen" => {
+                if let Some(text) = doc.get("text").and_then(|v| v.as_str()) {
+                    self.document_texts.insert(
+                        key,
+                        StoredDocument {
+                            text: text.to_string(),
+                            language_id: doc
+                                .get("languageId")
+                                .and_then(|v| v.as_str())
+                                .map(|s| s.to_string()),
+                            version: version.unwrap_or(1),
+                        },
+                    );
                 }
             }
-        }
-        if let Some(stripped) = uri.strip_prefix("file://") {
-            #[cfg(windows)]
-            {
-                let trimmed = if let Some(rest) = stripped.strip_prefix('/') {
-                    let mut chars = rest.chars();
-                    match (chars.next(), chars.next()) {
-                        (Some(drive), Some(':')) if drive.is_ascii_alphabetic() => rest,
-                        _ => stripped,
+            "textDocument/didChange" => {
+                let changes = params.get("contentChanges").and_then(|v| v.as_array());
+                for change in changes.into_iter().flatten() {
+                    let Some(text) = change.get("text").and_then(|v| v.as_str()) else {
+                        continue;
+                    };
+                    match (change.get("range"), self.document_texts.get_mut(&key)) {
+                        (None, Some(stored)) => stored.text = text.to_string(),
+                        (None, None) => {
+                            self.document_texts.insert(
+                                key.clone(),
+                                StoredDocument {
+                                    text: text.to_string(),
+                                    language_id: None,
+                                    version: 1,
+                                },
+                            );
+                        }
+                        (Some(range), Some(stored)) => {
+                            let edit = json!([{ "range": range, "newText": text }]);
+                            match edits::apply_text_edits(&stored.text, &edit) {
+                                Ok(updated) => stored.text = updated,
+                                Err(err) => {
+                                    eprintln!("mcp-lsp: dropping stored text for {key}: {err:#}");
+                                    self.document_texts.remove(&key);
+                                    return;
+                                }
+                            }
+                        }
+                        (Some(_), None) => {
+                            eprintln!(
+                                "mcp-lsp: ignoring ranged didChange for {key}: no stored text to apply it to"
+                            );
+                        }
                     }
-                } else {
-                    stripped
-                };
-                return std::path::PathBuf::from(trimmed);
-            }
-            #[cfg(not(windows))]
-            {
-                return std::path::PathBuf::from(stripped);
+                }
+                if let (Some(stored), Some(version)) = (self.document_texts.get_mut(&key), version)
+                {
+                    stored.version = version;
+                }
             }
+            _ => {}
         }
-        std::path::PathBuf::from(uri)
     }
 
-    fn language_from_extension(&self, ext: &str) -> Option<String> {
-        self.ext_language_map.get(ext).cloned()
+    fn stored_text(&self, uri: &str) -> Option<&str> {
+        self.document_texts
+            .get(&Self::normalize_uri(uri))
+            .map(|d| d.text.as_str())
     }
 
-    fn has_document(&self, uri: &str) -> bool {
+    /// What the bridge tracks for `uri`: the server it was opened on, if any, and the buffer
+    /// text and version last supplied through `didOpen`/`didChange`.
+    fn document_state(&self, uri: &str) -> Value {
         let key = Self::normalize_uri(uri);
-        self.doc_servers.contains_key(&key)
+        let server = self.doc_servers.get(&key);
+        let stored = self.document_texts.get(&key);
+        json!({
+            "uri": key,
+            "open": server.is_some(),
+            "serverCommand": server,
+            "tracked": stored.is_some(),
+            "version": stored.map(|d| d.version),
+            "languageId": stored.and_then(|d| d.language_id.as_deref()),
+            "textLength": stored.map(|d| d.text.chars().count()),
+        })
+    }
+
+    /// Remember full `textDocument/diagnostic` reports per document and substitute the cached
+    /// items when the server answers a follow-up pull with an `unchanged` report.
+    fn reconcile_document_diagnostic(&mut self, uri: &str, report: Value) -> Value {
+        let key = Self::normalize_uri(uri);
+        match report.get("kind").and_then(Value::as_str) {
+            Some("full") => {
+                self.diagnostic_reports.insert(key, report.clone());
+                report
+            }
+            Some("unchanged") => {
+                let Some(cached) = self.diagnostic_reports.get_mut(&key) else {
+                    return report;
+                };
+                if let (Some(obj), Some(result_id)) =
+                    (cached.as_object_mut(), report.get("resultId"))
+                {
+                    obj.insert("resultId".into(), result_id.clone());
+                }
+                let mut merged = cached.clone();
+                if let Some(obj) = merged.as_object_mut() {
+                    obj.insert("fromCache".into(), Value::Bool(true));
+                }
+                merged
+            }
+            _ => report,
+        }
+    }
+
+    /// Diagnostics for `uri` counted by severity. Servers with a `diagnosticProvider` are pulled
+    /// with `textDocument/diagnostic`; otherwise the last `publishDiagnostics` push is used, which
+    /// is absent (`available: false`) until the server has published for the document.
+    fn diagnostics_summary(
+        &mut self,
+        cmd: &str,
+        uri: &str,
+        open_params: Option<&Value>,
+    ) -> Result<Value> {
+        let key = Self::normalize_uri(uri);
+        let (source, report) = self.with_manager(cmd, |lsm| {
+            if let Some(payload) = open_params {
+                lsm.notify("textDocument/didOpen", payload.clone(), Some(cmd))?;
+            }
+            let pull = lsm
+                .capabilities(Some(cmd))?
+                .is_some_and(|caps| caps.get("diagnosticProvider").is_some());
+            if pull {
+                let params = json!({ "textDocument": { "uri": uri } });
+                let report = lsm.request("textDocument/diagnostic", params, Some(cmd))?;
+                return Ok(("pull", Some(report)));
+            }
+            let pushed = lsm
+                .published_diagnostics()
+                .get(&key)
+                .map(|diagnostics| json!({ "items": diagnostics }));
+            Ok(("push", pushed))
+        })?;
+        let report = match (source, report) {
+            ("pull", Some(report)) => Some(self.reconcile_document_diagnostic(uri, report)),
+            (_, report) => report,
+        };
+        let items = report
+            .as_ref()
+            .and_then(|r| r.get("items"))
+            .and_then(Value::as_array)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mut summary = count_diagnostics_by_severity(items);
+        if let Some(obj) = summary.as_object_mut() {
+            obj.insert("uri".into(), json!(uri));
+            obj.insert("source".into(), json!(source));
+            obj.insert("available".into(), json!(report.is_some()));
+        }
+        Ok(summary)
+    }
+
+    /// Diagnostics the running servers last pushed via `publishDiagnostics`, as
+    /// `{ uri, serverCommand, diagnostics }` sorted by uri, limited to `uri` and `server_cmd`
+    /// when given. No server is started or queried.
+    fn polled_diagnostics(&self, uri: Option<&str>, server_cmd: Option<&str>) -> Vec<Value> {
+        let key = uri.map(Self::normalize_uri);
+        let mut documents: Vec<Value> = self
+            .managers
+            .iter()
+            .filter(|(cmd, _)| server_cmd.is_none_or(|wanted| wanted == cmd.as_str()))
+            .flat_map(|(cmd, manager)| {
+                manager
+                    .published_diagnostics()
+                    .iter()
+                    .filter(|(published, _)| key.as_ref().is_none_or(|key| key == *published))
+                    .map(move |(published, diagnostics)| {
+                        json!({
+                            "uri": published,
+                            "serverCommand": cmd,
+                            "diagnostics": diagnostics,
+                        })
+                    })
+            })
+            .collect();
+        documents.sort_by(|a, b| {
+            (a["uri"].as_str(), a["serverCommand"].as_str())
+                .cmp(&(b["uri"].as_str(), b["serverCommand"].as_str()))
+        });
+        documents
+    }
+
+    /// Fill in a `semanticTokens/full/delta` request's missing `previousResultId` with the last
+    /// `resultId` seen for the document; other requests are returned unchanged.
+    fn with_semantic_tokens_result_id(
+        &self,
+        method: &str,
+        uri: Option<&str>,
+        mut params: Value,
+    ) -> Result<Value> {
+        if method != "textDocument/semanticTokens/full/delta"
+            || params.get("previousResultId").is_some()
+        {
+            return Ok(params);
+        }
+        let uri = uri.unwrap_or_default();
+        let result_id = self
+            .semantic_token_result_ids
+            .get(&Self::normalize_uri(uri))
+            .ok_or_else(|| {
+                anyhow!(
+                    "no semantic tokens resultId cached for {uri}; call lsp_semantic_tokens_full first or pass previousResultId"
+                )
+            })?;
+        params["previousResultId"] = json!(result_id);
+        Ok(params)
+    }
+
+    /// Remember the `resultId` of a full or delta semantic tokens response for the next delta
+    /// request on the document, forgetting it when the server sends none.
+    fn record_semantic_tokens_result_id(&mut self, method: &str, uri: &str, outcome: &Value) {
+        if !matches!(
+            method,
+            "textDocument/semanticTokens/full" | "textDocument/semanticTokens/full/delta"
+        ) {
+            return;
+        }
+        let key = Self::normalize_uri(uri);
+        match outcome.get("resultId").and_then(Value::as_str) {
+            Some(result_id) => {
+                self.semantic_token_result_ids
+                    .insert(key, result_id.to_string());
+            }
+            None => {
+                self.semantic_token_result_ids.remove(&key);
+            }
+        }
+    }
+
+    /// Documents already open on the server from the caller's point of view: those tracked in
+    /// `doc_servers` plus the source document about to be opened via `open_params`.
+    fn open_documents(
+        &self,
+        open_params: Option<&Value>,
+        definition_params: &Value,
+    ) -> HashSet<String> {
+        let mut open_docs: HashSet<String> = self.doc_servers.keys().cloned().collect();
+        if open_params.is_some() {
+            if let Some(uri) = definition_params
+                .pointer("/textDocument/uri")
+                .and_then(|v| v.as_str())
+            {
+                open_docs.insert(Self::normalize_uri(uri));
+            }
+        }
+        open_docs
+    }
+
+    /// Implicitly open a definition target that is not yet tracked. Returns true when a didOpen
+    /// was sent. Targets outside the workspace (for example vendored sources) may not be
+    /// readable; those are skipped so servers that track them internally can still answer.
+    fn open_definition_target(
+        manager: &mut LanguageServerManager,
+        ext_language_map: &HashMap<String, String>,
+        document_texts: &HashMap<String, StoredDocument>,
+        open_docs: &mut HashSet<String>,
+        cmd: &str,
+        uri: &str,
+    ) -> Result<bool> {
+        let key = Self::normalize_uri(uri);
+        if open_docs.contains(&key) {
+            return Ok(false);
+        }
+        match Self::did_open_params_for(ext_language_map, document_texts, uri, None) {
+            Ok(payload) => {
+                manager.notify("textDocument/didOpen", payload, Some(cmd))?;
+                open_docs.insert(key);
+                Ok(true)
+            }
+            Err(err) => {
+                eprintln!("mcp-lsp: skipping didOpen for definition target {uri}: {err:#}");
+                Ok(false)
+            }
+        }
+    }
+
+    /// Run `textDocument/definition`, then `textDocument/hover` at the start of the first
+    /// returned location, within a single manager acquisition. `open_params` opens the source
+    /// document first; the target document is opened implicitly when it is not yet tracked.
+    /// Returns the combined result plus the target URIs opened here.
+    fn definition_with_hover(
+        &mut self,
+        cmd: &str,
+        open_params: Option<&Value>,
+        definition_params: Value,
+    ) -> Result<(Value, Vec<String>)> {
+        let mut open_docs = self.open_documents(open_params, &definition_params);
+        let manager = self
+            .managers
+            .entry(cmd.to_string())
+            .or_insert_with(|| LanguageServerManager::with_command(cmd.to_string()));
+        self.last_server = Some(cmd.to_string());
+
+        if let Some(payload) = open_params {
+            manager.notify("textDocument/didOpen", payload.clone(), Some(cmd))?;
+        }
+        let definition =
+            manager.request("textDocument/definition", definition_params, Some(cmd))?;
+        let Some((target_uri, range)) = first_definition_location(&definition) else {
+            return Ok((
+                json!({ "definition": definition, "location": Value::Null, "hover": Value::Null }),
+                Vec::new(),
+            ));
+        };
+
+        let mut opened = Vec::new();
+        if Self::open_definition_target(
+            manager,
+            &self.ext_language_map,
+            &self.document_texts,
+            &mut open_docs,
+            cmd,
+            &target_uri,
+        )? {
+            opened.push(target_uri.clone());
+        }
+
+        let location = json!({ "uri": target_uri, "range": range });
+        let hover_params = json!({
+            "textDocument": {"uri": target_uri},
+            "position": range.get("start").cloned().unwrap_or(Value::Null)
+        });
+        let combined = match manager.request("textDocument/hover", hover_params, Some(cmd)) {
+            Ok(hover) => json!({ "definition": definition, "location": location, "hover": hover }),
+            Err(err) => json!({
+                "definition": definition,
+                "location": location,
+                "hover": Value::Null,
+                "hoverError": format!("{:#}", err)
+            }),
+        };
+        Ok((combined, opened))
+    }
+
+    /// Follow `textDocument/definition` from the requested position, re-running it at each
+    /// result's start until the target stops moving, a location repeats, or `max_hops` is
+    /// reached. Runs within a single manager acquisition and opens hop targets implicitly.
+    /// Returns `{ location, hops, stopReason }` plus the URIs opened here.
+    fn definition_chain(
+        &mut self,
+        cmd: &str,
+        open_params: Option<&Value>,
+        definition_params: Value,
+        max_hops: usize,
+    ) -> Result<(Value, Vec<String>)> {
+        let mut open_docs = self.open_documents(open_params, &definition_params);
+        let manager = self
+            .managers
+            .entry(cmd.to_string())
+            .or_insert_with(|| LanguageServerManager::with_command(cmd.to_string()));
+        self.last_server = Some(cmd.to_string());
+
+        if let Some(payload) = open_params {
+            manager.notify("textDocument/didOpen", payload.clone(), Some(cmd))?;
+        }
+
+        let position_key = |params: &Value| {
+            let uri = params
+                .pointer("/textDocument/uri")
+                .and_then(|v| v.as_str())
+                .map(Self::normalize_uri)
+                .unwrap_or_default();
+            let line = params.pointer("/position/line").and_then(|v| v.as_u64());
+            let character = params
+                .pointer("/position/character")
+                .and_then(|v| v.as_u64());
+            (uri, line, character)
+        };
+
+        let mut current = definition_params;
+        let mut visited = HashSet::new();
+        visited.insert(position_key(&current));
+        let mut hops: Vec<Value> = Vec::new();
+        let mut opened = Vec::new();
+        let stop_reason = loop {
+            if hops.len() >= max_hops {
+                break "limit";
+            }
+            let definition =
+                manager.request("textDocument/definition", current.clone(), Some(cmd))?;
+            let Some((target_uri, range)) = first_definition_location(&definition) else {
+                break if hops.is_empty() {
+                    "noDefinition"
+                } else {
+                    "stable"
+                };
+            };
+            let hop = json!({ "uri": target_uri, "range": range });
+            // A definition whose range covers the queried position is the declaration itself.
+            let (current_uri, _, _) = position_key(&current);
+            if Self::normalize_uri(&target_uri) == current_uri
+                && range_contains(&range, current.get("position").unwrap_or(&Value::Null))
+            {
+                if hops.is_empty() {
+                    hops.push(hop);
+                }
+                break "stable";
+            }
+            let next = json!({
+                "textDocument": {"uri": target_uri},
+                "position": range.get("start").cloned().unwrap_or(Value::Null)
+            });
+            if !visited.insert(position_key(&next)) {
+                break "cycle";
+            }
+            hops.push(hop);
+            if Self::open_definition_target(
+                manager,
+                &self.ext_language_map,
+                &self.document_texts,
+                &mut open_docs,
+                cmd,
+                &target_uri,
+            )? {
+                opened.push(target_uri);
+            }
+            current = next;
+        };
+
+        let location = hops.last().cloned().unwrap_or(Value::Null);
+        Ok((
+            json!({ "location": location, "hops": hops, "stopReason": stop_reason }),
+            opened,
+        ))
+    }
+
+    /// Run `textDocument/definition` and `textDocument/references` (declarations included) at
+    /// the same position within a single manager acquisition. Returns
+    /// `{ definitions, references }` as de-duplicated `{ uri, range }` arrays.
+    fn usages(
+        &mut self,
+        cmd: &str,
+        open_params: Option<&Value>,
+        position_params: Value,
+    ) -> Result<(Value, Vec<String>)> {
+        let manager = self
+            .managers
+            .entry(cmd.to_string())
+            .or_insert_with(|| LanguageServerManager::with_command(cmd.to_string()));
+        self.last_server = Some(cmd.to_string());
+
+        if let Some(payload) = open_params {
+            manager.notify("textDocument/didOpen", payload.clone(), Some(cmd))?;
+        }
+        let definition = manager.request(
+            "textDocument/definition",
+            position_params.clone(),
+            Some(cmd),
+        )?;
+        let mut references_params = position_params;
+        references_params["context"] = json!({ "includeDeclaration": true });
+        let references =
+            manager.request("textDocument/references", references_params, Some(cmd))?;
+        Ok((
+            json!({
+                "definitions": normalized_locations(&definition),
+                "references": normalized_locations(&references)
+            }),
+            Vec::new(),
+        ))
+    }
+
+    fn probe_default_capabilities(&mut self) -> Result<Option<Value>> {
+        let Some(cmd) = self.default_cmd.clone() else {
+            return Ok(None);
+        };
+        self.with_manager(&cmd, |lsm| lsm.capabilities(Some(&cmd)))
+    }
+
+    fn extension_from_uri(uri: &str) -> Option<String> {
+        let path_part = uri.strip_prefix("file://").unwrap_or(uri);
+        let path = std::path::Path::new(path_part);
+        if let Some(ext) = path.extension().and_then(|s| s.to_str()) {
+            return Some(ext.to_ascii_lowercase());
+        }
+        let segment = path_part
+            .rsplit(|c: char| std::path::is_separator(c))
+            .next()
+            .unwrap_or(path_part);
+        segment
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_ascii_lowercase())
+    }
+
+    fn path_from_uri(uri: &str) -> std::path::PathBuf {
+        if let Ok(url) = Url::parse(uri) {
+            if url.scheme() == "file" {
+                if let Ok(path) = url.to_file_path() {
+                    return path;
+                }
+            }
+        }
+        if let Some(stripped) = uri.strip_prefix("file://") {
+            #[cfg(windows)]
+            {
+                let trimmed = if let Some(rest) = stripped.strip_prefix('/') {
+                    let mut chars = rest.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(drive), Some(':')) if drive.is_ascii_alphabetic() => rest,
+                        _ => stripped,
+                    }
+                } else {
+                    stripped
+                };
+                return std::path::PathBuf::from(trimmed);
+            }
+            #[cfg(not(windows))]
+            {
+                return std::path::PathBuf::from(stripped);
+            }
+        }
+        std::path::PathBuf::from(uri)
+    }
+
+    fn has_document(&self, uri: &str) -> bool {
+        let key = Self::normalize_uri(uri);
+        self.doc_servers.contains_key(&key)
     }
 
     fn normalize_uri(uri: &str) -> String {
+        Self::canonicalize_drive_letter(Self::file_uri_string(uri))
+    }
+
+    /// Windows clients disagree on drive-letter spelling (`C:`, `c:`, `c%3A`) and separators (`/`,
+    /// `\`, `%5C`); fold them all to `file:///C:/...` so one file never ends up under two
+    /// `doc_servers` keys.
+    fn canonicalize_drive_letter(uri: String) -> String {
+        let Some(rest) = uri.strip_prefix("file:///") else {
+            return uri;
+        };
+        let mut chars = rest.chars();
+        let Some(drive) = chars.next().filter(|c| c.is_ascii_alphabetic()) else {
+            return uri;
+        };
+        let tail = chars.as_str();
+        let tail = if let Some(t) = tail.strip_prefix(':') {
+            t
+        } else if let Some(t) = tail
+            .strip_prefix("%3A")
+            .or_else(|| tail.strip_prefix("%3a"))
+        {
+            t
+        } else {
+            return uri;
+        };
+        let tail = tail
+            .replace('\\', "/")
+            .replace("%5C", "/")
+            .replace("%5c", "/");
+        if !(tail.is_empty() || tail.starts_with('/')) {
+            return uri;
+        }
+        format!("file:///{}:{tail}", drive.to_ascii_uppercase())
+    }
+
+    fn file_uri_string(uri: &str) -> String {
         if let Ok(url) = Url::parse(uri) {
             if url.scheme() == "file" {
                 return url.to_string();
@@ -1165,8 +2103,45 @@ impl LanguageServerPool {
     }
 
     fn build_did_open_params(&self, uri: &str, language_hint: Option<&str>) -> Result<Value> {
+        Self::did_open_params_for(
+            &self.ext_language_map,
+            &self.document_texts,
+            uri,
+            language_hint,
+        )
+    }
+
+    /// Field-level variant of `build_did_open_params` so callers holding a manager borrow can
+    /// still construct didOpen payloads. Text supplied earlier via didOpen/didChange wins over
+    /// the file on disk.
+    fn did_open_params_for(
+        ext_language_map: &HashMap<String, String>,
+        document_texts: &HashMap<String, StoredDocument>,
+        uri: &str,
+        language_hint: Option<&str>,
+    ) -> Result<Value> {
         let canonical_uri = Self::normalize_uri(uri);
         let path = Self::path_from_uri(&canonical_uri);
+        if let Some(stored) = document_texts.get(&canonical_uri) {
+            let language_id = language_hint
+                .map(|s| s.to_string())
+                .or_else(|| stored.language_id.clone())
+                .or_else(|| {
+                    path.extension()
+                        .and_then(|e| e.to_str())
+                        .map(|ext| ext.to_ascii_lowercase())
+                        .and_then(|ext| ext_language_map.get(&ext).cloned())
+                })
+                .unwrap_or_else(|| "plaintext".to_string());
+            return Ok(json!({
+                "textDocument": {
+                    "uri": canonical_uri,
+                    "languageId": language_id,
+                    "version": stored.version,
+                    "text": stored.text
+                }
+            }));
+        }
         let metadata = std::fs::metadata(&path)
             .with_context(|| format!("stat document content for {:?}", path))?;
         const MAX_INLINE_DOC_BYTES: u64 = 2 * 1024 * 1024;
@@ -1196,7 +2171,7 @@ impl LanguageServerPool {
                 path.extension()
                     .and_then(|e| e.to_str())
                     .map(|ext| ext.to_ascii_lowercase())
-                    .and_then(|ext| self.language_from_extension(&ext))
+                    .and_then(|ext| ext_language_map.get(&ext).cloned())
             })
             .unwrap_or_else(|| "plaintext".to_string());
         Ok(json!({
@@ -1227,6 +2202,10 @@ pub(crate) fn tools() -> Vec<Tool> {
     const SERVER_CMD_DESC: &str = "Optional override for the language server command. When omitted, mcp-lsp chooses based on languageId/extension or falls back to LSP_SERVER_CMD.";
     const SERVER_NOTE: &str =
         "Use `serverCommand` to override the configured language server for a single request.";
+    const PAGING_LIMIT_DESC: &str =
+        "When the result is an array, return at most this many items (see `paging` in the result).";
+    const PAGING_OFFSET_DESC: &str =
+        "When the result is an array, skip this many items first (default 0).";
 
     let lsp_positional_schema = json!({
         "type": "object",
@@ -1298,6 +2277,15 @@ pub(crate) fn tools() -> Vec<Tool> {
         "additionalProperties": false
     });
 
+    let lsp_poll_diagnostics_schema = json!({
+        "type": "object",
+        "properties": {
+            "uri": {"type": "string", "description": "Only return diagnostics for this document (file path or file:// URI). Omit for every document."},
+            "serverCommand": {"type": "string", "description": "Only return diagnostics pushed by this running server."}
+        },
+        "additionalProperties": false
+    });
+
     let lsp_positions_array_schema = json!({
         "type": "object",
         "properties": {
@@ -1365,6 +2353,17 @@ pub(crate) fn tools() -> Vec<Tool> {
         "additionalProperties": false
     });
 
+    let lsp_locate_symbol_schema = json!({
+        "type": "object",
+        "properties": {
+            "query": {"type": "string", "minLength": 1, "description": "Symbol name, optionally qualified by its container (`Outer.inner`, `module::item`)."},
+            "uri": {"type": "string", "description": "Only consider symbols in this file. A `file://` URI or a path; relative paths resolve against the working directory, and a symbol whose path merely ends with the given relative path also matches."},
+            "serverCommand": {"type": "string", "description": SERVER_CMD_DESC}
+        },
+        "required": ["query"],
+        "additionalProperties": false
+    });
+
     let lsp_rename_schema = json!({
         "type": "object",
         "properties": {
@@ -1528,6 +2527,38 @@ pub(crate) fn tools() -> Vec<Tool> {
         });
     }
 
+    tools.push(Tool {
+        name: "lsp_definition_with_hover".to_string(),
+        description: Some(format!(
+            "Navigate to the definition of the symbol at the given position and return its hover documentation in one call. Runs LSP `textDocument/definition`, then `textDocument/hover` at the start of the first returned location (opening the target document implicitly). Returns `{{ definition, location, hover }}`; `location` and `hover` are null when no definition is found. {SERVER_NOTE}"
+        )),
+        input_schema: lsp_positional_schema.clone(),
+    });
+
+    let mut lsp_definition_resolved_schema = lsp_positional_schema.clone();
+    lsp_definition_resolved_schema["properties"]["maxHops"] = json!({
+        "type": "integer",
+        "minimum": 1,
+        "maximum": MAX_DEFINITION_HOPS,
+        "default": DEFAULT_DEFINITION_HOPS,
+        "description": "Maximum number of definition hops to follow."
+    });
+    tools.push(Tool {
+        name: "lsp_definition_resolved".to_string(),
+        description: Some(format!(
+            "Follow the definition chain through aliases and re-exports (TS `export {{ x }} from`, Rust `pub use`) to the original declaration. Re-runs LSP `textDocument/definition` at each result until it stabilizes, a location repeats, or `maxHops` is reached. Returns `{{ location, hops, stopReason }}` where `stopReason` is `stable`, `cycle`, `limit`, or `noDefinition`. {SERVER_NOTE}"
+        )),
+        input_schema: lsp_definition_resolved_schema,
+    });
+
+    tools.push(Tool {
+        name: "lsp_usages".to_string(),
+        description: Some(format!(
+            "Declaration and all uses of the symbol at the given position in one call. Runs LSP `textDocument/definition` and `textDocument/references` (with `includeDeclaration: true`) against the same server. Returns `{{ definitions, references }}` as `{{ uri, range }}` arrays with canonical URIs and duplicates removed. {SERVER_NOTE}"
+        )),
+        input_schema: lsp_positional_schema.clone(),
+    });
+
     tools.push(Tool {
         name: "lsp_references".to_string(),
         description: Some(format!(
@@ -1560,6 +2591,14 @@ pub(crate) fn tools() -> Vec<Tool> {
         input_schema: lsp_doc_only_schema.clone(),
     });
 
+    tools.push(Tool {
+        name: "lsp_document_symbols_flat".to_string(),
+        description: Some(format!(
+            "List a document's symbols as a flat array of `{{ name, kind, range, containerName }}` regardless of whether the server answers `textDocument/documentSymbol` with `SymbolInformation[]` or a `DocumentSymbol[]` hierarchy; nested symbols get the dotted path of their parents as `containerName`. Provide the document `uri`. {SERVER_NOTE}"
+        )),
+        input_schema: lsp_doc_only_schema.clone(),
+    });
+
     tools.push(Tool {
         name: "lsp_workspace_symbol".to_string(),
         description: Some(format!(
@@ -1568,6 +2607,14 @@ pub(crate) fn tools() -> Vec<Tool> {
         input_schema: lsp_query_schema.clone(),
     });
 
+    tools.push(Tool {
+        name: "lsp_locate_symbol".to_string(),
+        description: Some(format!(
+            "Find where a symbol is defined when you know its name but not its position: runs `workspace/symbol` for `query`, keeps only symbols in `uri` when given, and returns the best match as `{{ name, kind, containerName, uri, range, position }}` (exact name first, then case-insensitive, prefix, and substring matches) plus up to {LOCATE_SYMBOL_ALTERNATIVES} runners-up. `position` can be passed straight to positional tools such as `lsp_hover` or `lsp_references`. `match` is null when nothing fits. {SERVER_NOTE}"
+        )),
+        input_schema: lsp_locate_symbol_schema,
+    });
+
     tools.push(Tool {
         name: "lsp_workspace_symbol_resolve".to_string(),
         description: Some(format!(
@@ -1618,6 +2665,41 @@ pub(crate) fn tools() -> Vec<Tool> {
         input_schema: lsp_item_resolve_schema.clone(),
     });
 
+    tools.push(Tool {
+        name: "lsp_completion_apply_preview".to_string(),
+        description: Some(
+            "Preview accepting a completion item, including edits such as auto-imports, without touching the file. Applies the item's `textEdit` (or `insertText`/`label` at `position`) plus its `additionalTextEdits` to the document (buffer text from didOpen/didChange, else the file on disk) and returns `{ changed, text, diff, edits }` where `diff` is a unified diff. Snippets are inserted as plain text. Pass an item resolved with `lsp_completion_item_resolve` when the server fills `additionalTextEdits` lazily. No server request is made.".to_string(),
+        ),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "uri": {"type": "string", "description": URI_DESC},
+                "item": {"type": "object", "description": "Completion item, ideally resolved."},
+                "position": {"type": "object", "description": "Completion position; required when the item has no textEdit.", "properties": {"line": {"type": "integer"}, "character": {"type": "integer"}}},
+                "replace": {"type": "boolean", "description": "For InsertReplaceEdit items, use the replace range instead of the insert range (default false)."}
+            },
+            "required": ["uri", "item"],
+            "additionalProperties": true
+        }),
+    });
+
+    tools.push(Tool {
+        name: "lsp_code_action_preview".to_string(),
+        description: Some(format!(
+            "Preview what a code action from `lsp_code_action` would change without touching disk. An action without an `edit` is first resolved with `codeAction/resolve`; its `WorkspaceEdit` is then applied to in-memory copies of the affected files (buffer text from didOpen/didChange, else the file on disk). Returns `{{ title, kind, resolved, files: [{{ uri, before, after, diff }}], operations, command }}`. Create, rename, and delete operations in `documentChanges` are listed in `operations` and reflected in `files` but never executed; a rename whose source does not exist carries an `error` instead; a `command` the action would run is reported, not run. Pass `uri` to route resolution to that document's server. {SERVER_NOTE}"
+        )),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "action": {"type": "object", "description": "Code action (or command) returned by `lsp_code_action`."},
+                "uri": {"type": "string", "description": "Document the action was requested for; selects the server used to resolve it."},
+                "serverCommand": {"type": "string", "description": SERVER_CMD_DESC}
+            },
+            "required": ["action"],
+            "additionalProperties": false
+        }),
+    });
+
     tools.push(Tool {
         name: "lsp_code_lens".to_string(),
         description: Some(format!(
@@ -1693,6 +2775,23 @@ pub(crate) fn tools() -> Vec<Tool> {
         }),
     });
 
+    tools.push(Tool {
+        name: "lsp_format_preview".to_string(),
+        description: Some(format!(
+            "Preview whole-document formatting without touching the file. Runs `textDocument/formatting`, applies the returned edits to the document's stored buffer text (or the file on disk when mcp-lsp holds no text for it), and returns `{{ changed, formatted, diff, edits }}` where `diff` is a unified diff. Provide `uri` and the LSP formatting `options`. {SERVER_NOTE}"
+        )),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "uri": {"type": "string", "description": URI_DESC},
+                "options": {"type": "object", "description": "Formatting options (tabSize, insertSpaces, etc.)."},
+                "serverCommand": {"type": "string", "description": SERVER_CMD_DESC}
+            },
+            "required": ["uri", "options"],
+            "additionalProperties": true
+        }),
+    });
+
     tools.push(Tool {
         name: "lsp_range_formatting".to_string(),
         description: Some(format!(
@@ -1731,17 +2830,36 @@ pub(crate) fn tools() -> Vec<Tool> {
     });
 
     tools.push(Tool {
-        name: "lsp_inline_value".to_string(),
+        name: "lsp_on_type_format_preview".to_string(),
         description: Some(format!(
-            "Compute inline values (debug views) for a range via `textDocument/inlineValue`. Provide `uri`, target `range`, and inline value `context`. {SERVER_NOTE}"
+            "Preview the effect of typing a character without touching the file. Runs `textDocument/onTypeFormatting` and applies the returned edits to the document content: the buffer text last sent through `didOpen`/`didChange` when there is one (which should already contain the typed `ch`), otherwise the file on disk. Returns `{{ changed, formatted, diff, edits }}` like `lsp_format_preview`. Provide `uri`, the cursor `position` after the typed character, `ch`, and formatting `options`. {SERVER_NOTE}"
         )),
         input_schema: json!({
             "type": "object",
             "properties": {
                 "uri": {"type": "string", "description": URI_DESC},
-                "range": range_property.clone(),
-                "context": {"description": "Inline value context (see LSP spec)."},
-                "serverCommand": {"type": "string", "description": SERVER_CMD_DESC}
+                "position": position_property.clone(),
+                "ch": {"type": "string", "description": "Single character that triggered formatting."},
+                "options": {"type": "object", "description": "Formatting options."},
+                "serverCommand": {"type": "string", "description": SERVER_CMD_DESC}
+            },
+            "required": ["uri", "position", "ch", "options"],
+            "additionalProperties": true
+        }),
+    });
+
+    tools.push(Tool {
+        name: "lsp_inline_value".to_string(),
+        description: Some(format!(
+            "Compute inline values (debug views) for a range via `textDocument/inlineValue`. Provide `uri`, target `range`, and inline value `context`. {SERVER_NOTE}"
+        )),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "uri": {"type": "string", "description": URI_DESC},
+                "range": range_property.clone(),
+                "context": {"description": "Inline value context (see LSP spec)."},
+                "serverCommand": {"type": "string", "description": SERVER_CMD_DESC}
             },
             "required": ["uri", "range", "context"],
             "additionalProperties": true
@@ -1804,19 +2922,49 @@ pub(crate) fn tools() -> Vec<Tool> {
         input_schema: lsp_doc_only_schema.clone(),
     });
 
+    tools.push(Tool {
+        name: "lsp_semantic_tokens_legend".to_string(),
+        description: Some(format!(
+            "Return the server's semantic tokens legend as `{{ tokenTypes, tokenModifiers }}`, the arrays that token type indexes and modifier bitsets in `lsp_semantic_tokens_*` data refer to. Cached per server until it restarts. Optionally pass `uri` to select the server for that document. {SERVER_NOTE}"
+        )),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "uri": {"type": "string", "description": URI_DESC},
+                "serverCommand": {"type": "string", "description": SERVER_CMD_DESC}
+            },
+            "additionalProperties": false
+        }),
+    });
+
+    tools.push(Tool {
+        name: "lsp_list_commands".to_string(),
+        description: Some(format!(
+            "List the command identifiers the server accepts for `lsp_execute_command`, from `executeCommandProvider.commands`, as `{{ commands }}`. Optionally pass `uri` to select the server for that document. {SERVER_NOTE}"
+        )),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "uri": {"type": "string", "description": URI_DESC},
+                "serverCommand": {"type": "string", "description": SERVER_CMD_DESC}
+            },
+            "additionalProperties": false
+        }),
+    });
+
     tools.push(Tool {
         name: "lsp_semantic_tokens_full_delta".to_string(),
         description: Some(format!(
-            "Request semantic token deltas with respect to a previous result using `textDocument/semanticTokens/full/delta`. Provide `uri` and `previousResultId`. {SERVER_NOTE}"
+            "Request semantic token deltas with respect to a previous result using `textDocument/semanticTokens/full/delta`. Provide `uri`; `previousResultId` defaults to the `resultId` of the document's last `lsp_semantic_tokens_full` or delta response. {SERVER_NOTE}"
         )),
         input_schema: json!({
             "type": "object",
             "properties": {
                 "uri": {"type": "string", "description": URI_DESC},
-                "previousResultId": {"type": "string", "description": "Previous semantic tokens result identifier."},
+                "previousResultId": {"type": "string", "description": "Previous semantic tokens result identifier (default: the last one returned for this document)."},
                 "serverCommand": {"type": "string", "description": SERVER_CMD_DESC}
             },
-            "required": ["uri", "previousResultId"],
+            "required": ["uri"],
             "additionalProperties": false
         }),
     });
@@ -1841,7 +2989,7 @@ pub(crate) fn tools() -> Vec<Tool> {
     tools.push(Tool {
         name: "lsp_execute_command".to_string(),
         description: Some(format!(
-            "Execute a workspace command exposed by the server via `workspace/executeCommand`. Provide the command identifier and optional `arguments` array. {SERVER_NOTE}"
+            "Execute a workspace command exposed by the server via `workspace/executeCommand`. Provide the command identifier and optional `arguments` array; `lsp_list_commands` lists the identifiers. If the server rejects a command it does not advertise, the error names the closest advertised commands. {SERVER_NOTE}"
         )),
         input_schema: lsp_execute_command_schema,
     });
@@ -1881,16 +3029,40 @@ pub(crate) fn tools() -> Vec<Tool> {
     tools.push(Tool {
         name: "lsp_text_document_diagnostic".to_string(),
         description: Some(format!(
-            "Pull diagnostics for a single document using `textDocument/diagnostic`. Provide `uri` and optionally carry `identifier`/`previousResultId` tokens. {SERVER_NOTE}"
+            "Pull diagnostics for a single document using `textDocument/diagnostic`. Provide `uri` and optionally carry `identifier`/`previousResultId` tokens. When the server reports `unchanged`, the previously cached full report for the document is returned with `fromCache: true`. {SERVER_NOTE}"
         )),
         input_schema: lsp_text_document_diagnostic_schema,
     });
 
+    tools.push(Tool {
+        name: "lsp_diagnostics_summary".to_string(),
+        description: Some(format!(
+            "Count a document's diagnostics by severity. Pulls `textDocument/diagnostic` when the server has a `diagnosticProvider`, otherwise reads the diagnostics the server last pushed via `publishDiagnostics` (`available: false` until it has published for the document). Returns `{{ counts: {{ error, warning, information, hint }}, unspecified, total, hasErrors, source, available }}`, where `source` is `pull` or `push`. {SERVER_NOTE}"
+        )),
+        input_schema: lsp_doc_only_schema.clone(),
+    });
+
+    tools.push(Tool {
+        name: "lsp_poll_diagnostics".to_string(),
+        description: Some(
+            "Return the diagnostics language servers last pushed via `textDocument/publishDiagnostics`, for servers (such as rust-analyzer or clangd) that never answer pull requests. Returns `{ documents: [{ uri, serverCommand, diagnostics }] }` for every document, or only `uri` (adding `available`). Pushes are picked up while mcp-lsp awaits responses, so a new push appears after the next request to that server; no server is started or queried.".to_string(),
+        ),
+        input_schema: lsp_poll_diagnostics_schema,
+    });
+
     tools.push(Tool {
         name: "lsp_workspace_diagnostic".to_string(),
         description: Some(format!(
             "Pull workspace diagnostics via `workspace/diagnostic`. Optionally include `identifier` and `previousResultIds` to maintain state. {SERVER_NOTE}"
         )),
+        input_schema: lsp_workspace_diagnostic_schema.clone(),
+    });
+
+    tools.push(Tool {
+        name: "lsp_workspace_problems".to_string(),
+        description: Some(format!(
+            "List every workspace problem as a flat array by pulling `workspace/diagnostic` and flattening the per-document reports into `{{ uri, severity, message, range, source, code }}` entries, de-duplicated and sorted by severity then uri. {SERVER_NOTE}"
+        )),
         input_schema: lsp_workspace_diagnostic_schema,
     });
 
@@ -1902,6 +3074,114 @@ pub(crate) fn tools() -> Vec<Tool> {
         input_schema: lsp_call_schema,
     });
 
+    tools.push(Tool {
+        name: "lsp_capabilities".to_string(),
+        description: Some(format!(
+            "Report the language server's capabilities: `staticCapabilities` from `initialize`, `dynamicRegistrations` received via `client/registerCapability`, and the merged `capabilities` used to filter `tools/list`. Optionally pass `uri` to select the server for that document. {SERVER_NOTE}"
+        )),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "uri": {"type": "string", "description": URI_DESC},
+                "serverCommand": {"type": "string", "description": SERVER_CMD_DESC}
+            },
+            "additionalProperties": false
+        }),
+    });
+
+    tools.push(Tool {
+        name: "lsp_server_features".to_string(),
+        description: Some(format!(
+            "Summarize the language server's merged capabilities as flat flags: `textDocumentSync` (`none`, `full`, `incremental`) and `incremental` for `didChange`, `pullDiagnostics`/`workspaceDiagnostics` vs `pushDiagnostics`, resolve providers (`completionResolve`, `codeActionResolve`, ...), `renamePrepare`, `positionEncoding`, semantic token modes, trigger characters, and `executeCommands`. Simpler than `lsp_capabilities` for choosing which tools to use. Optionally pass `uri` to select the server for that document. {SERVER_NOTE}"
+        )),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "uri": {"type": "string", "description": URI_DESC},
+                "serverCommand": {"type": "string", "description": SERVER_CMD_DESC}
+            },
+            "additionalProperties": false
+        }),
+    });
+
+    tools.push(Tool {
+        name: "lsp_warmup".to_string(),
+        description: Some(format!(
+            "Start a language server ahead of time by running its `initialize` handshake without issuing a request, so the first real tool call does not pay the startup cost. Select the server with `languageId` (e.g. `rust`) or `serverCommand`; with neither, the default server is used. Returns `{{ started, alreadyRunning, initializeMs }}`, where `initializeMs` is null when the server was already running. {SERVER_NOTE}"
+        )),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "languageId": {"type": "string", "description": "Language whose configured server should be started (e.g. `rust`, `python`)."},
+                "serverCommand": {"type": "string", "description": SERVER_CMD_DESC}
+            },
+            "additionalProperties": false
+        }),
+    });
+
+    tools.push(Tool {
+        name: "lsp_did_close".to_string(),
+        description: Some(format!(
+            "Close a document by sending LSP `textDocument/didClose` and release mcp-lsp's tracking for it (server routing and cached diagnostics). Documents are opened implicitly by position/document tools; closing one that is not open succeeds with `wasOpen: false`. {SERVER_NOTE}"
+        )),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "uri": {"type": "string", "description": URI_DESC},
+                "serverCommand": {"type": "string", "description": SERVER_CMD_DESC}
+            },
+            "required": ["uri"],
+            "additionalProperties": false
+        }),
+    });
+
+    tools.push(Tool {
+        name: "lsp_document_state".to_string(),
+        description: Some(
+            "Inspect mcp-lsp's tracking of a document without contacting a server: whether it is open and on which `serverCommand`, and the `version`, `languageId`, and `textLength` (characters) of the buffer text last supplied via `didOpen`/`didChange` (`tracked: false` when none was, so the file on disk is used). Use it to check that a `didChange` registered."
+                .to_string(),
+        ),
+        input_schema: json!({
+            "type": "object",
+            "properties": {
+                "uri": {"type": "string", "description": URI_DESC}
+            },
+            "required": ["uri"],
+            "additionalProperties": false
+        }),
+    });
+
+    let will_save_schema = json!({
+        "type": "object",
+        "properties": {
+            "uri": {"type": "string", "description": URI_DESC},
+            "reason": {
+                "type": "integer",
+                "enum": [1, 2, 3],
+                "description": "TextDocumentSaveReason: 1 = Manual (default), 2 = AfterDelay, 3 = FocusOut."
+            },
+            "serverCommand": {"type": "string", "description": SERVER_CMD_DESC}
+        },
+        "required": ["uri"],
+        "additionalProperties": false
+    });
+
+    tools.push(Tool {
+        name: "lsp_will_save".to_string(),
+        description: Some(format!(
+            "Notify the server that a document is about to be saved via `textDocument/willSave`. Provide `uri` and optionally the save `reason`. {SERVER_NOTE}"
+        )),
+        input_schema: will_save_schema.clone(),
+    });
+
+    tools.push(Tool {
+        name: "lsp_will_save_wait_until".to_string(),
+        description: Some(format!(
+            "Ask the server for edits to apply before saving via `textDocument/willSaveWaitUntil`; returns `TextEdit[]` (or null). Provide `uri` and optionally the save `reason`. {SERVER_NOTE}"
+        )),
+        input_schema: will_save_schema,
+    });
+
     tools.push(Tool {
         name: "lsp_notify".to_string(),
         description: Some(format!(
@@ -1910,6 +3190,25 @@ pub(crate) fn tools() -> Vec<Tool> {
         input_schema: lsp_notify_schema,
     });
 
+    // List-returning tools accept the paging arguments `handle_tools_call` applies to their
+    // array results.
+    for tool in tools.iter_mut().filter(|t| is_paged_tool(&t.name)) {
+        if let Some(properties) = tool
+            .input_schema
+            .get_mut("properties")
+            .and_then(Value::as_object_mut)
+        {
+            properties.insert(
+                "limit".into(),
+                json!({"type": "integer", "minimum": 1, "description": PAGING_LIMIT_DESC}),
+            );
+            properties.insert(
+                "offset".into(),
+                json!({"type": "integer", "minimum": 0, "description": PAGING_OFFSET_DESC}),
+            );
+        }
+    }
+
     tools
 }
 
@@ -1960,108 +3259,1279 @@ fn language_from_did_open(params: &Value) -> Option<String> {
         .map(|s| s.to_ascii_lowercase())
 }
 
-fn parse_params_value(raw: Value) -> Value {
-    match raw {
-        Value::String(s) => serde_json::from_str(&s).unwrap_or(Value::String(s)),
-        other => other,
-    }
+/// Apply formatting edits to the content of `uri` (the stored buffer text when the caller
+/// supplied one, otherwise the file on disk) and return the formatted text, a unified diff
+/// against the original, and the raw edits.
+fn format_preview(uri: &str, edits: &Value, stored_text: Option<&str>) -> Result<Value> {
+    let path = LanguageServerPool::path_from_uri(uri);
+    let original = match stored_text {
+        Some(text) => text.to_string(),
+        None => std::fs::read_to_string(&path)
+            .with_context(|| format!("read document content for {:?}", path))?,
+    };
+    let formatted = edits::apply_text_edits(&original, edits)?;
+    let label = path.to_string_lossy();
+    let diff = edits::unified_diff(&original, &formatted, label.trim_start_matches('/'));
+    Ok(json!({
+        "changed": formatted != original,
+        "formatted": formatted,
+        "diff": diff,
+        "edits": edits
+    }))
 }
 
-fn build_error_data(
-    tool: &str,
-    method: Option<&str>,
-    uri: Option<&str>,
-    server_cmd: Option<&str>,
-    err: &anyhow::Error,
-) -> Value {
-    let mut map = serde_json::Map::new();
-    map.insert("tool".into(), Value::String(tool.to_string()));
-    if let Some(method) = method {
-        map.insert("method".into(), Value::String(method.to_string()));
-    }
-    if let Some(uri) = uri {
-        map.insert("uri".into(), Value::String(uri.to_string()));
-    }
-    if let Some(cmd) = server_cmd {
-        map.insert("serverCommand".into(), Value::String(cmd.to_string()));
+/// `TextEdit`s that accepting completion `item` applies: its main edit (the `insert` range of an
+/// `InsertReplaceEdit` unless `replace`, or `insertText`/`label` at `position` when the item has
+/// no `textEdit`) followed by its `additionalTextEdits`. Snippets are reduced to plain text.
+fn completion_edits(item: &Value, position: Option<&Value>, replace: bool) -> Result<Vec<Value>> {
+    let is_snippet = item.get("insertTextFormat").and_then(Value::as_u64) == Some(2);
+    let plain = |text: &str| {
+        if is_snippet {
+            edits::snippet_text(text)
+        } else {
+            text.to_string()
+        }
+    };
+    let mut out = Vec::new();
+    match item.get("textEdit") {
+        Some(edit) => {
+            let new_text = edit.get("newText").and_then(Value::as_str).unwrap_or("");
+            let range = edit
+                .get("range")
+                .or_else(|| edit.get(if replace { "replace" } else { "insert" }))
+                .ok_or_else(|| anyhow!("completion textEdit has no range: {edit}"))?;
+            out.push(json!({ "range": range, "newText": plain(new_text) }));
+        }
+        None => {
+            let position = position.ok_or_else(|| {
+                anyhow!("completion item has no textEdit; pass the completion `position`")
+            })?;
+            let text = item
+                .get("insertText")
+                .or_else(|| item.get("label"))
+                .and_then(Value::as_str)
+                .ok_or_else(|| anyhow!("completion item has no insertText or label"))?;
+            out.push(json!({
+                "range": { "start": position, "end": position },
+                "newText": plain(text)
+            }));
+        }
     }
-    map.insert("details".into(), Value::String(format!("{:#}", err)));
-    Value::Object(map)
-}
-
-fn format_tool_error_message(tool: &str, method: Option<&str>, err: &anyhow::Error) -> String {
-    match method {
-        Some(method) => format!("LSP tool '{tool}' invoking '{method}' failed: {:#}", err),
-        None => format!("Tool '{tool}' failed: {:#}", err),
+    if let Some(Value::Array(extra)) = item.get("additionalTextEdits") {
+        out.extend(extra.iter().cloned());
     }
+    Ok(out)
 }
 
-pub(crate) async fn handle_tools_call(params: Option<Value>) -> JsonRpcResponse {
-    let err_resp = |code: i64, msg: &str| JsonRpcResponse::error(ErrorObject::new(code, msg, None));
-    let params = match params {
-        Some(Value::Object(map)) => map,
-        _ => return err_resp(-32602, "Invalid params: expected object"),
-    };
-    let tool_name = match params.get("name") {
-        Some(Value::String(s)) => s.clone(),
-        _ => return err_resp(-32602, "Missing 'name' in params"),
+/// Preview accepting a (resolved) completion item in `uri` without touching the file.
+async fn handle_lsp_completion_apply_preview(args: Map<String, Value>) -> JsonRpcResponse {
+    let tool = "lsp_completion_apply_preview";
+    let uri = match canonical_uri(&args) {
+        Ok(uri) => uri,
+        Err(err) => return JsonRpcResponse::error(err),
     };
-    let tool_name = match tool_name.as_str() {
-        "hover" => "lsp_hover".to_string(),
-        "definition" => "lsp_definition".to_string(),
-        "type_definition" => "lsp_type_definition".to_string(),
-        "implementation" => "lsp_implementation".to_string(),
-        "references" => "lsp_references".to_string(),
-        "completion" => "lsp_completion".to_string(),
-        "call" => "lsp_call".to_string(),
-        other => other.to_string(),
+    let item = match require_object_field(&args, "item") {
+        Ok(item) => item,
+        Err(err) => return JsonRpcResponse::error(err),
     };
+    let position = args.get("position").filter(|v| v.is_object()).cloned();
+    let replace = args
+        .get("replace")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    let uri_for_preview = uri.clone();
 
-    let arguments_value = params
-        .get("arguments")
-        .cloned()
-        .unwrap_or_else(|| json!({}));
-
-    match tool_name.as_str() {
-        "lsp_call" => {
-            let mut args_map = match arguments_value.as_object() {
-                Some(m) => m.clone(),
-                None => return err_resp(-32602, "Invalid arguments: expected object"),
-            };
-            let server_cmd = args_map
-                .remove("serverCommand")
-                .and_then(|v| v.as_str().map(|s| s.to_string()));
-            return handle_lsp_call(args_map, server_cmd).await;
+    let result = task::spawn_blocking(move || {
+        let edits = Value::Array(completion_edits(&item, position.as_ref(), replace)?);
+        let stored =
+            with_language_pool(|pool| Ok(pool.stored_text(&uri_for_preview).map(str::to_string)))?;
+        let mut preview = format_preview(&uri_for_preview, &edits, stored.as_deref())?;
+        if let Some(obj) = preview.as_object_mut() {
+            if let Some(text) = obj.remove("formatted") {
+                obj.insert("text".into(), text);
+            }
         }
-        "lsp_notify" => {
-            let mut args_map = match arguments_value.as_object() {
-                Some(m) => m.clone(),
-                None => Map::new(),
-            };
-            let server_cmd = args_map
-                .remove("serverCommand")
-                .and_then(|v| v.as_str().map(|s| s.to_string()));
-            return handle_lsp_notify(args_map, server_cmd).await;
+        Ok::<_, anyhow::Error>(preview)
+    })
+    .await
+    .map_err(anyhow::Error::new)
+    .and_then(|res| res);
+
+    match result {
+        Ok(preview) => JsonRpcResponse::result(json!({
+            "tool": tool,
+            "status": "ok",
+            "uri": uri,
+            "result": preview
+        })),
+        Err(e) => {
+            let data = build_error_data(tool, None, Some(uri.as_str()), None, &e);
+            let message = format_tool_error_message(tool, None, &e);
+            JsonRpcResponse::error(ErrorObject::new(-32050, &message, Some(data)))
         }
-        _ => {}
     }
+}
 
-    let mut args_map = match arguments_value.as_object() {
-        Some(m) => m.clone(),
-        None => return err_resp(-32602, "Invalid arguments: expected object"),
-    };
+/// In-memory state of one file touched by a `WorkspaceEdit` preview: its content before the
+/// edit (`None` when the file did not exist) and after it (`None` once deleted).
+struct PreviewFile {
+    before: Option<String>,
+    after: Option<String>,
+}
 
-    let server_cmd = args_map
-        .remove("serverCommand")
-        .and_then(|v| v.as_str().map(|s| s.to_string()));
+/// Apply `edit` (a `WorkspaceEdit`) to in-memory copies of the files it touches, loading each
+/// one through `load` on first use. `documentChanges` are taken in order, with `create`,
+/// `rename`, and `delete` operations tracked in memory and described in `operations`;
+/// otherwise `changes` is used. Returns per-file `before`/`after` text and a unified diff.
+fn workspace_edit_preview(
+    edit: &Value,
+    load: &mut dyn FnMut(&str) -> Result<Option<String>>,
+) -> Result<Value> {
+    let mut files: BTreeMap<String, PreviewFile> = BTreeMap::new();
+    let mut operations = Vec::new();
+
+    fn entry<'a>(
+        files: &'a mut BTreeMap<String, PreviewFile>,
+        uri: &str,
+        load: &mut dyn FnMut(&str) -> Result<Option<String>>,
+    ) -> Result<&'a mut PreviewFile> {
+        Ok(match files.entry(uri.to_string()) {
+            std::collections::btree_map::Entry::Occupied(e) => e.into_mut(),
+            std::collections::btree_map::Entry::Vacant(e) => {
+                let before = load(uri)?;
+                let after = before.clone();
+                e.insert(PreviewFile { before, after })
+            }
+        })
+    }
 
-    if !tool_name.starts_with("lsp_") {
-        return JsonRpcResponse::error(unsupported_tool_error(&tool_name));
+    fn apply(file: &mut PreviewFile, uri: &str, edits: &[Value]) -> Result<()> {
+        // `SnippetTextEdit`s carry a snippet instead of `newText`; insert its plain text.
+        let edits: Vec<Value> = edits
+            .iter()
+            .map(
+                |e| match e.pointer("/snippet/value").and_then(Value::as_str) {
+                    Some(snippet) => {
+                        json!({ "range": e["range"], "newText": edits::snippet_text(snippet) })
+                    }
+                    None => e.clone(),
+                },
+            )
+            .collect();
+        let text = file
+            .after
+            .as_deref()
+            .ok_or_else(|| anyhow!("edit targets {uri}, which does not exist at that point"))?;
+        file.after = Some(edits::apply_text_edits(text, &Value::Array(edits))?);
+        Ok(())
     }
 
-    let invocation = match build_lsp_invocation(&tool_name, &args_map, server_cmd.clone()) {
-        Ok(inv) => inv,
-        Err(err) => return JsonRpcResponse::error(err),
+    if let Some(changes) = edit.get("documentChanges").and_then(Value::as_array) {
+        for change in changes {
+            let text = |key: &str| change.get(key).and_then(Value::as_str).unwrap_or_default();
+            match change.get("kind").and_then(Value::as_str) {
+                None => {
+                    let uri = change
+                        .pointer("/textDocument/uri")
+                        .and_then(Value::as_str)
+                        .ok_or_else(|| anyhow!("TextDocumentEdit without textDocument.uri"))?;
+                    let edits = change
+                        .get("edits")
+                        .and_then(Value::as_array)
+                        .cloned()
+                        .unwrap_or_default();
+                    apply(entry(&mut files, uri, load)?, uri, &edits)?;
+                }
+                Some("create") => {
+                    let uri = text("uri");
+                    let overwrite = change.pointer("/options/overwrite") == Some(&json!(true));
+                    let file = entry(&mut files, uri, load)?;
+                    if file.after.is_none() || overwrite {
+                        file.after = Some(String::new());
+                    }
+                    operations.push(
+                        json!({"kind": "create", "uri": uri, "options": change.get("options")}),
+                    );
+                }
+                Some("rename") => {
+                    let (old_uri, new_uri) = (text("oldUri"), text("newUri"));
+                    let mut operation = json!({
+                        "kind": "rename",
+                        "oldUri": old_uri,
+                        "newUri": new_uri,
+                        "options": change.get("options")
+                    });
+                    // A missing source is reported on the operation; the target is left untouched.
+                    match entry(&mut files, old_uri, load)?.after.take() {
+                        Some(content) => entry(&mut files, new_uri, load)?.after = Some(content),
+                        None => {
+                            operation["error"] = json!(format!(
+                                "rename source {old_uri} does not exist at that point"
+                            ))
+                        }
+                    }
+                    operations.push(operation);
+                }
+                Some("delete") => {
+                    let uri = text("uri");
+                    entry(&mut files, uri, load)?.after = None;
+                    operations.push(
+                        json!({"kind": "delete", "uri": uri, "options": change.get("options")}),
+                    );
+                }
+                Some(other) => {
+                    return Err(anyhow!("unknown documentChanges operation kind '{other}'"))
+                }
+            }
+        }
+    } else if let Some(changes) = edit.get("changes").and_then(Value::as_object) {
+        for (uri, edits) in changes {
+            let edits = edits.as_array().cloned().unwrap_or_default();
+            apply(entry(&mut files, uri, load)?, uri, &edits)?;
+        }
+    }
+
+    let files: Vec<Value> = files
+        .into_iter()
+        .map(|(uri, file)| {
+            let path = LanguageServerPool::path_from_uri(&uri);
+            let label = path.to_string_lossy();
+            let diff = edits::unified_diff(
+                file.before.as_deref().unwrap_or_default(),
+                file.after.as_deref().unwrap_or_default(),
+                label.trim_start_matches('/'),
+            );
+            json!({
+                "uri": uri,
+                "changed": file.before != file.after,
+                "before": file.before,
+                "after": file.after,
+                "diff": diff
+            })
+        })
+        .collect();
+    Ok(json!({ "files": files, "operations": operations }))
+}
+
+/// Preview a code action's `WorkspaceEdit`, resolving the action first when it has no `edit`.
+async fn handle_lsp_code_action_preview(
+    args: Map<String, Value>,
+    server_cmd: Option<String>,
+) -> JsonRpcResponse {
+    let tool = "lsp_code_action_preview";
+    let action = match require_object_field(&args, "action") {
+        Ok(action) => action,
+        Err(err) => return JsonRpcResponse::error(err),
+    };
+    let uri = match args.get("uri") {
+        None => None,
+        Some(_) => match canonical_uri(&args) {
+            Ok(uri) => Some(uri),
+            Err(err) => return JsonRpcResponse::error(err),
+        },
+    };
+    let uri_for_request = uri.clone();
+    let server_cmd_for_request = server_cmd.clone();
+
+    let result = task::spawn_blocking(move || {
+        // A bare `Command` has a string `command`; a `CodeAction` may carry one as an object.
+        let is_command = action.get("command").is_some_and(Value::is_string);
+        let needs_resolve = !is_command && action.get("edit").is_none();
+        let action = if needs_resolve {
+            with_language_pool(|pool| {
+                let cmd = pool.resolve_command(
+                    server_cmd_for_request.as_deref(),
+                    uri_for_request.as_deref(),
+                    None,
+                )?;
+                pool.with_manager(&cmd, |lsm| {
+                    lsm.request("codeAction/resolve", action.clone(), Some(cmd.as_str()))
+                })
+            })?
+        } else {
+            action
+        };
+        // The edit comes from the caller or the server, so it is applied outside the pool lock;
+        // a malformed edit then only fails this call.
+        let mut load = |uri: &str| -> Result<Option<String>> {
+            let stored = with_language_pool(|pool| Ok(pool.stored_text(uri).map(str::to_string)))?;
+            if stored.is_some() {
+                return Ok(stored);
+            }
+            let path = LanguageServerPool::path_from_uri(uri);
+            match std::fs::read_to_string(&path) {
+                Ok(text) => Ok(Some(text)),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+                Err(e) => Err(anyhow!("read document content for {:?}: {e}", path)),
+            }
+        };
+        let mut preview = match action.get("edit") {
+            Some(edit) => workspace_edit_preview(edit, &mut load)?,
+            None => json!({ "files": [], "operations": [] }),
+        };
+        let command = if is_command {
+            Some(action.clone())
+        } else {
+            action.get("command").cloned()
+        };
+        if let Some(obj) = preview.as_object_mut() {
+            obj.insert(
+                "title".into(),
+                action.get("title").cloned().unwrap_or(Value::Null),
+            );
+            obj.insert(
+                "kind".into(),
+                action.get("kind").cloned().unwrap_or(Value::Null),
+            );
+            obj.insert("resolved".into(), json!(needs_resolve));
+            obj.insert("command".into(), command.unwrap_or(Value::Null));
+        }
+        Ok::<_, anyhow::Error>(preview)
+    })
+    .await
+    .map_err(anyhow::Error::new)
+    .and_then(|res| res);
+
+    match result {
+        Ok(preview) => JsonRpcResponse::result(json!({
+            "tool": tool,
+            "status": "ok",
+            "result": preview
+        })),
+        Err(e) => {
+            let data = build_error_data(tool, None, uri.as_deref(), server_cmd.as_deref(), &e);
+            let message = format_tool_error_message(tool, None, &e);
+            JsonRpcResponse::error(ErrorObject::new(-32050, &message, Some(data)))
+        }
+    }
+}
+
+async fn handle_lsp_document_state(args: Map<String, Value>) -> JsonRpcResponse {
+    let tool = "lsp_document_state";
+    let uri = match canonical_uri(&args) {
+        Ok(uri) => uri,
+        Err(err) => return JsonRpcResponse::error(err),
+    };
+    let uri_for_state = uri.clone();
+
+    let result = task::spawn_blocking(move || {
+        with_language_pool(|pool| Ok(pool.document_state(&uri_for_state)))
+    })
+    .await
+    .map_err(anyhow::Error::new)
+    .and_then(|res| res);
+
+    match result {
+        Ok(state) => JsonRpcResponse::result(json!({
+            "tool": tool,
+            "status": "ok",
+            "uri": uri,
+            "result": state
+        })),
+        Err(e) => {
+            let data = build_error_data(tool, None, Some(uri.as_str()), None, &e);
+            let message = format_tool_error_message(tool, None, &e);
+            JsonRpcResponse::error(ErrorObject::new(-32050, &message, Some(data)))
+        }
+    }
+}
+
+/// Default and upper bound for `lsp_definition_resolved` hops.
+const DEFAULT_DEFINITION_HOPS: usize = 5;
+const MAX_DEFINITION_HOPS: u64 = 20;
+
+/// Whether an LSP `range` covers `position` (inclusive of both ends).
+fn range_contains(range: &Value, position: &Value) -> bool {
+    let point = |v: Option<&Value>| {
+        let v = v?;
+        Some((v.get("line")?.as_u64()?, v.get("character")?.as_u64()?))
+    };
+    match (
+        point(range.get("start")),
+        point(range.get("end")),
+        point(Some(position)),
+    ) {
+        (Some(start), Some(end), Some(pos)) => start <= pos && pos <= end,
+        _ => false,
+    }
+}
+
+/// First target of a definition result (`Location`, `Location[]`, or `LocationLink[]`) as
+/// `(uri, range)`. For links, the selection range is preferred over the full target range.
+fn first_definition_location(result: &Value) -> Option<(String, Value)> {
+    let first = match result {
+        Value::Array(items) => items.first()?,
+        Value::Object(_) => result,
+        _ => return None,
+    };
+    location_parts(first)
+}
+
+/// `(uri, range)` of a `Location` or `LocationLink` (preferring its selection range).
+fn location_parts(item: &Value) -> Option<(String, Value)> {
+    if let Some(uri) = item.get("targetUri").and_then(|v| v.as_str()) {
+        let range = item
+            .get("targetSelectionRange")
+            .or_else(|| item.get("targetRange"))?;
+        return Some((uri.to_string(), range.clone()));
+    }
+    let uri = item.get("uri").and_then(|v| v.as_str())?;
+    Some((uri.to_string(), item.get("range")?.clone()))
+}
+
+/// Flatten a `Location | Location[] | LocationLink[]` result into `{ uri, range }` entries with
+/// canonical URIs, dropping duplicates while keeping server order.
+fn normalized_locations(result: &Value) -> Vec<Value> {
+    let items = match result {
+        Value::Array(items) => items.as_slice(),
+        Value::Object(_) => std::slice::from_ref(result),
+        _ => &[],
+    };
+    let mut seen = HashSet::new();
+    items
+        .iter()
+        .filter_map(location_parts)
+        .filter_map(|(uri, range)| {
+            let uri = LanguageServerPool::normalize_uri(&uri);
+            seen.insert((uri.clone(), range.to_string()))
+                .then(|| json!({ "uri": uri, "range": range }))
+        })
+        .collect()
+}
+
+/// `{ counts: { error, warning, information, hint }, unspecified, total, hasErrors }` for a list
+/// of LSP diagnostics; `unspecified` counts entries without a `severity`.
+fn count_diagnostics_by_severity(items: &[Value]) -> Value {
+    let mut counts = [0u64; 4];
+    let mut unspecified = 0u64;
+    for item in items {
+        match item.get("severity").and_then(Value::as_u64) {
+            Some(sev @ 1..=4) => counts[sev as usize - 1] += 1,
+            _ => unspecified += 1,
+        }
+    }
+    json!({
+        "counts": {
+            "error": counts[0],
+            "warning": counts[1],
+            "information": counts[2],
+            "hint": counts[3],
+        },
+        "unspecified": unspecified,
+        "total": items.len(),
+        "hasErrors": counts[0] > 0,
+    })
+}
+
+/// Maximum number of close matches suggested for an unknown `lsp_execute_command` command.
+const MAX_COMMAND_SUGGESTIONS: usize = 5;
+
+/// Levenshtein distance between two strings, by characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                prev
+            } else {
+                1 + prev.min(cur).min(row[j])
+            };
+            prev = cur;
+        }
+    }
+    row[b.len()]
+}
+
+/// Advertised commands resembling `command`: case-insensitive substring matches either way, or
+/// an edit distance within a third of its length (at least 2), nearest first.
+fn close_command_matches<'a>(command: &str, commands: &'a [String]) -> Vec<&'a str> {
+    let needle = command.to_lowercase();
+    let limit = (needle.chars().count() / 3).max(2);
+    let mut scored: Vec<(usize, &str)> = commands
+        .iter()
+        .filter_map(|candidate| {
+            let lower = candidate.to_lowercase();
+            let distance = edit_distance(&needle, &lower);
+            (distance <= limit || lower.contains(&needle) || needle.contains(&lower))
+                .then_some((distance, candidate.as_str()))
+        })
+        .collect();
+    scored.sort();
+    scored
+        .into_iter()
+        .take(MAX_COMMAND_SUGGESTIONS)
+        .map(|(_, c)| c)
+        .collect()
+}
+
+/// Add the closest advertised commands to a failed `workspace/executeCommand` whose command
+/// the server does not advertise; failures of advertised commands are returned unchanged.
+fn explain_execute_command_error(
+    lsm: &mut LanguageServerManager,
+    server_cmd: &str,
+    command: &str,
+    err: anyhow::Error,
+) -> anyhow::Error {
+    let Ok(commands) = lsm.execute_commands(Some(server_cmd)) else {
+        return err;
+    };
+    if commands.iter().any(|c| c == command) {
+        return err;
+    }
+    let matches = close_command_matches(command, &commands);
+    let hint = if matches.is_empty() {
+        format!(
+            "no advertised command is close (the server lists {}; see lsp_list_commands)",
+            commands.len()
+        )
+    } else {
+        format!("close matches: {}", matches.join(", "))
+    };
+    err.context(format!(
+        "command '{command}' is not advertised by the server; {hint}"
+    ))
+}
+
+fn severity_rank(entry: &Value) -> u64 {
+    // Diagnostics without a severity sort after hints.
+    entry.get("severity").and_then(Value::as_u64).unwrap_or(5)
+}
+
+/// Flatten a `workspace/diagnostic` report into a de-duplicated list of problems.
+/// Unchanged document reports carry no items and contribute nothing.
+fn flatten_workspace_problems(report: &Value) -> Value {
+    let mut seen = HashSet::new();
+    let mut problems = Vec::new();
+    let documents = report
+        .get("items")
+        .and_then(Value::as_array)
+        .cloned()
+        .unwrap_or_default();
+    for doc in documents {
+        if doc.get("kind").and_then(Value::as_str) == Some("unchanged") {
+            continue;
+        }
+        let Some(uri) = doc.get("uri").and_then(Value::as_str) else {
+            continue;
+        };
+        let diagnostics = doc
+            .get("items")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        for diag in diagnostics {
+            let entry = json!({
+                "uri": uri,
+                "severity": diag.get("severity").cloned().unwrap_or(Value::Null),
+                "message": diag.get("message").cloned().unwrap_or(Value::Null),
+                "range": diag.get("range").cloned().unwrap_or(Value::Null),
+                "source": diag.get("source").cloned().unwrap_or(Value::Null),
+                "code": diag.get("code").cloned().unwrap_or(Value::Null),
+            });
+            if seen.insert(entry.to_string()) {
+                problems.push(entry);
+            }
+        }
+    }
+    problems.sort_by(|a, b| {
+        severity_rank(a).cmp(&severity_rank(b)).then_with(|| {
+            let ua = a.get("uri").and_then(Value::as_str).unwrap_or_default();
+            let ub = b.get("uri").and_then(Value::as_str).unwrap_or_default();
+            ua.cmp(ub)
+        })
+    });
+    json!({ "count": problems.len(), "problems": problems })
+}
+
+/// Normalize a `textDocument/documentSymbol` result into a flat symbol list. `SymbolInformation`
+/// entries keep their own `containerName`; `DocumentSymbol` trees are walked depth-first in
+/// document order, naming each child's container by the dotted path of its ancestors.
+fn flatten_document_symbols(result: &Value) -> Value {
+    fn walk(symbols: &[Value], container: Option<&str>, out: &mut Vec<Value>) {
+        for symbol in symbols {
+            let Some(name) = symbol.get("name").and_then(Value::as_str) else {
+                continue;
+            };
+            let kind = symbol.get("kind").cloned().unwrap_or(Value::Null);
+            if let Some(location) = symbol.get("location") {
+                out.push(json!({
+                    "name": name,
+                    "kind": kind,
+                    "range": location.get("range").cloned().unwrap_or(Value::Null),
+                    "containerName": symbol.get("containerName").cloned().unwrap_or(Value::Null),
+                }));
+                continue;
+            }
+            out.push(json!({
+                "name": name,
+                "kind": kind,
+                "range": symbol.get("range").cloned().unwrap_or(Value::Null),
+                "containerName": container,
+            }));
+            if let Some(children) = symbol.get("children").and_then(Value::as_array) {
+                let path = match container {
+                    Some(parent) => format!("{parent}.{name}"),
+                    None => name.to_string(),
+                };
+                walk(children, Some(&path), out);
+            }
+        }
+    }
+
+    let mut symbols = Vec::new();
+    if let Some(items) = result.as_array() {
+        walk(items, None, &mut symbols);
+    }
+    json!({ "count": symbols.len(), "symbols": symbols })
+}
+
+/// Number of next-best candidates `lsp_locate_symbol` reports next to its match.
+const LOCATE_SYMBOL_ALTERNATIVES: usize = 5;
+
+/// Split `Outer.inner` or `mod::item` into its container and trailing name.
+fn split_qualified_query(query: &str) -> (Option<&str>, &str) {
+    let query = query.trim();
+    match query.rsplit_once("::").or_else(|| query.rsplit_once('.')) {
+        Some((container, name)) if !name.is_empty() && !container.is_empty() => {
+            (Some(container), name)
+        }
+        _ => (None, query),
+    }
+}
+
+/// Pick the symbol a `workspace/symbol` result most likely means by `query`. A qualified query
+/// (`Outer.inner`, `mod::item`) matches its last segment against the name and requires the rest
+/// in `containerName`. Names rank exact, case-insensitive, prefix, then substring; anything the
+/// server returned that matches none of these is dropped. With `uri`, only symbols in that file
+/// are kept: the normalized URI must match, or the symbol's URI must end with the given path.
+fn locate_symbol(result: &Value, query: &str, uri: Option<&str>) -> Value {
+    let query = query.trim();
+    let (container, name) = split_qualified_query(query);
+    let name_lower = name.to_lowercase();
+    let container_lower = container.map(str::to_lowercase);
+    let file = uri.map(|u| {
+        let suffix = u.trim_start_matches("./").replace('\\', "/");
+        (
+            LanguageServerPool::normalize_uri(u),
+            format!("/{}", suffix.trim_start_matches('/')),
+        )
+    });
+
+    let mut ranked: Vec<(u8, usize, Value)> = Vec::new();
+    for (index, symbol) in result.as_array().into_iter().flatten().enumerate() {
+        let Some(symbol_name) = symbol.get("name").and_then(Value::as_str) else {
+            continue;
+        };
+        let location = symbol.get("location").cloned().unwrap_or(Value::Null);
+        let symbol_uri = location
+            .get("uri")
+            .and_then(Value::as_str)
+            .unwrap_or_default();
+        if let Some((normalized, suffix)) = &file {
+            if LanguageServerPool::normalize_uri(symbol_uri) != *normalized
+                && !symbol_uri.ends_with(suffix.as_str())
+            {
+                continue;
+            }
+        }
+        let symbol_container = symbol.get("containerName").and_then(Value::as_str);
+        if let Some(wanted) = &container_lower {
+            let found = symbol_container.unwrap_or_default().to_lowercase();
+            if !found.contains(wanted.as_str()) {
+                continue;
+            }
+        }
+        let lower = symbol_name.to_lowercase();
+        let rank = if symbol_name == name {
+            0
+        } else if lower == name_lower {
+            1
+        } else if lower.starts_with(&name_lower) {
+            2
+        } else if lower.contains(&name_lower) {
+            3
+        } else {
+            continue;
+        };
+        let range = location.get("range").cloned().unwrap_or(Value::Null);
+        let position = range.get("start").cloned().unwrap_or(Value::Null);
+        ranked.push((
+            rank,
+            index,
+            json!({
+                "name": symbol_name,
+                "kind": symbol.get("kind").cloned().unwrap_or(Value::Null),
+                "containerName": symbol_container,
+                "uri": symbol_uri,
+                "range": range,
+                "position": position,
+            }),
+        ));
+    }
+    ranked.sort_by_key(|(rank, index, _)| (*rank, *index));
+    let candidates = ranked.len();
+    let mut matches = ranked.into_iter().map(|(_, _, entry)| entry);
+    let best = matches.next();
+    let alternatives: Vec<Value> = matches.take(LOCATE_SYMBOL_ALTERNATIVES).collect();
+    json!({
+        "query": query,
+        "uri": file.as_ref().map(|(normalized, _)| normalized),
+        "match": best,
+        "candidates": candidates,
+        "alternatives": alternatives,
+    })
+}
+
+/// Parse a hand-maintained JSON config, tolerating `//` and `/* */` comments and trailing
+/// commas. Comments and dropped commas are blanked in place (newlines kept), so errors report
+/// the line and column of the original text.
+fn parse_relaxed_json(raw: &str) -> Result<Value, serde_json::Error> {
+    if let Ok(value) = serde_json::from_str(raw) {
+        return Ok(value);
+    }
+    let chars: Vec<char> = raw.chars().collect();
+    let mut out: Vec<char> = Vec::with_capacity(chars.len());
+    // Index in `out` of a comma that is trailing if the next significant char closes a container.
+    let mut pending_comma: Option<usize> = None;
+    // Last significant char emitted; a comma only counts as trailing when it follows a value.
+    let mut last = ' ';
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '"' => {
+                pending_comma = None;
+                last = c;
+                out.push(c);
+                i += 1;
+                while i < chars.len() {
+                    out.push(chars[i]);
+                    if chars[i] == '\\' && i + 1 < chars.len() {
+                        out.push(chars[i + 1]);
+                        i += 2;
+                        continue;
+                    }
+                    i += 1;
+                    if chars[i - 1] == '"' {
+                        break;
+                    }
+                }
+                continue;
+            }
+            '/' if chars.get(i + 1) == Some(&'/') => {
+                while i < chars.len() && chars[i] != '\n' {
+                    out.push(' ');
+                    i += 1;
+                }
+                continue;
+            }
+            '/' if chars.get(i + 1) == Some(&'*') => {
+                out.push(' ');
+                out.push(' ');
+                i += 2;
+                while i < chars.len() && !(chars[i] == '*' && chars.get(i + 1) == Some(&'/')) {
+                    out.push(if chars[i] == '\n' { '\n' } else { ' ' });
+                    i += 1;
+                }
+                if i < chars.len() {
+                    out.push(' ');
+                    out.push(' ');
+                    i += 2;
+                }
+                continue;
+            }
+            ',' if !matches!(last, ',' | ':' | '[' | '{' | ' ') => pending_comma = Some(out.len()),
+            '}' | ']' => {
+                if let Some(idx) = pending_comma.take() {
+                    out[idx] = ' ';
+                }
+            }
+            c if c.is_whitespace() => {
+                out.push(c);
+                i += 1;
+                continue;
+            }
+            _ => pending_comma = None,
+        }
+        last = c;
+        out.push(c);
+        i += 1;
+    }
+    serde_json::from_str(&out.into_iter().collect::<String>())
+}
+
+fn parse_params_value(raw: Value) -> Value {
+    match raw {
+        Value::String(s) => serde_json::from_str(&s).unwrap_or(Value::String(s)),
+        other => other,
+    }
+}
+
+fn build_error_data(
+    tool: &str,
+    method: Option<&str>,
+    uri: Option<&str>,
+    server_cmd: Option<&str>,
+    err: &anyhow::Error,
+) -> Value {
+    let mut map = serde_json::Map::new();
+    map.insert("tool".into(), Value::String(tool.to_string()));
+    if let Some(method) = method {
+        map.insert("method".into(), Value::String(method.to_string()));
+    }
+    if let Some(uri) = uri {
+        map.insert("uri".into(), Value::String(uri.to_string()));
+    }
+    if let Some(cmd) = server_cmd {
+        map.insert("serverCommand".into(), Value::String(cmd.to_string()));
+    }
+    map.insert("details".into(), Value::String(format!("{:#}", err)));
+    if let Some(exited) = err.downcast_ref::<ls::ServerExited>() {
+        map.insert("serverExit".into(), exited.data());
+    }
+    if let Some(timed_out) = err.downcast_ref::<ls::RequestTimedOut>() {
+        map.insert("timeout".into(), timed_out.data());
+    }
+    Value::Object(map)
+}
+
+fn format_tool_error_message(tool: &str, method: Option<&str>, err: &anyhow::Error) -> String {
+    match method {
+        Some(method) => format!("LSP tool '{tool}' invoking '{method}' failed: {:#}", err),
+        None => format!("Tool '{tool}' failed: {:#}", err),
+    }
+}
+
+/// Extra tool-name aliases from `LSP_TOOL_ALIASES`, a JSON object mapping an alias to a tool
+/// name (e.g. `{"goto_def": "lsp_definition"}`), consulted before the built-in short names.
+/// Entries that target an unknown tool, or that would shadow a real tool, are dropped with a
+/// warning when the table is loaded.
+pub(crate) fn tool_aliases() -> &'static HashMap<String, String> {
+    static ALIASES: OnceLock<HashMap<String, String>> = OnceLock::new();
+    ALIASES.get_or_init(|| {
+        let Ok(raw) = std::env::var("LSP_TOOL_ALIASES") else {
+            return HashMap::new();
+        };
+        let entries = match parse_relaxed_json(&raw) {
+            Ok(Value::Object(entries)) => entries,
+            Ok(_) => {
+                eprintln!("warning: LSP_TOOL_ALIASES must be a JSON object; ignoring it");
+                return HashMap::new();
+            }
+            Err(err) => {
+                eprintln!("warning: failed to parse LSP_TOOL_ALIASES: {err}");
+                return HashMap::new();
+            }
+        };
+        let known: HashSet<String> = tools().into_iter().map(|tool| tool.name).collect();
+        let mut aliases = HashMap::new();
+        for (alias, target) in entries {
+            let Some(target) = target.as_str() else {
+                eprintln!("warning: LSP_TOOL_ALIASES: alias '{alias}' must map to a tool name; ignoring it");
+                continue;
+            };
+            if known.contains(&alias) {
+                eprintln!("warning: LSP_TOOL_ALIASES: alias '{alias}' is already a tool name; ignoring it");
+                continue;
+            }
+            if !known.contains(target) {
+                eprintln!("warning: LSP_TOOL_ALIASES: alias '{alias}' targets unknown tool '{target}'; ignoring it");
+                continue;
+            }
+            aliases.insert(alias, target.to_string());
+        }
+        aliases
+    })
+}
+
+fn canonical_tool_name(name: &str) -> String {
+    if let Some(target) = tool_aliases().get(name) {
+        return target.clone();
+    }
+    match name {
+        "hover" => "lsp_hover".to_string(),
+        "definition" => "lsp_definition".to_string(),
+        "type_definition" => "lsp_type_definition".to_string(),
+        "implementation" => "lsp_implementation".to_string(),
+        "references" => "lsp_references".to_string(),
+        "completion" => "lsp_completion".to_string(),
+        "call" => "lsp_call".to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Maximum serialized size of a tool result, read from `LSP_MAX_RESPONSE_BYTES`.
+/// Defaults to 4 MiB; `0` disables the guard.
+fn max_response_bytes() -> Option<usize> {
+    static LIMIT: OnceLock<Option<usize>> = OnceLock::new();
+    *LIMIT.get_or_init(|| {
+        parse_max_response_bytes(std::env::var("LSP_MAX_RESPONSE_BYTES").ok().as_deref())
+    })
+}
+
+fn parse_max_response_bytes(raw: Option<&str>) -> Option<usize> {
+    const DEFAULT_MAX_RESPONSE_BYTES: usize = 4 * 1024 * 1024;
+    let limit = match raw {
+        Some(raw) => match raw.trim().parse::<usize>() {
+            Ok(n) => n,
+            Err(_) => {
+                eprintln!(
+                    "mcp-lsp: invalid LSP_MAX_RESPONSE_BYTES value '{}'; using default",
+                    raw
+                );
+                DEFAULT_MAX_RESPONSE_BYTES
+            }
+        },
+        None => DEFAULT_MAX_RESPONSE_BYTES,
+    };
+    (limit > 0).then_some(limit)
+}
+
+/// Tools whose result is an uncapped list; only these take `limit`/`offset`. Passthrough tools
+/// such as `lsp_call` keep their arguments untouched.
+const PAGED_TOOLS: &[&str] = &[
+    "lsp_references",
+    "lsp_implementation",
+    "lsp_document_highlight",
+    "lsp_document_symbol",
+    "lsp_document_symbols_flat",
+    "lsp_workspace_symbol",
+    "lsp_folding_range",
+    "lsp_code_action",
+    "lsp_code_lens",
+    "lsp_document_link",
+    "lsp_document_color",
+    "lsp_inlay_hint",
+    "lsp_inline_value",
+    "lsp_call_hierarchy_incoming_calls",
+    "lsp_call_hierarchy_outgoing_calls",
+    "lsp_type_hierarchy_supertypes",
+    "lsp_type_hierarchy_subtypes",
+    "lsp_workspace_problems",
+];
+
+fn is_paged_tool(tool: &str) -> bool {
+    PAGED_TOOLS.contains(&tool)
+}
+
+/// `limit`/`offset` arguments of a paged tool, taken off the call before dispatch and applied
+/// to its array result.
+#[derive(Default)]
+struct Paging {
+    offset: usize,
+    limit: Option<usize>,
+}
+
+impl Paging {
+    fn requested(&self) -> bool {
+        self.offset > 0 || self.limit.is_some()
+    }
+}
+
+fn take_paging_args(params: &mut Option<Value>) -> Result<Paging, ErrorObject> {
+    let Some(args) = params
+        .as_mut()
+        .and_then(|p| p.get_mut("arguments"))
+        .and_then(Value::as_object_mut)
+    else {
+        return Ok(Paging::default());
+    };
+    let mut field = |name: &str| match args.remove(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(value) => value.as_u64().map(|n| Some(n as usize)).ok_or_else(|| {
+            invalid_params_error(&format!("`{name}` must be a non-negative integer"))
+        }),
+    };
+    let offset = field("offset")?.unwrap_or(0);
+    let limit = field("limit")?;
+    if limit == Some(0) {
+        return Err(invalid_params_error("`limit` must be at least 1"));
+    }
+    Ok(Paging { offset, limit })
+}
+
+/// Cut an array `result` down to the requested page and describe it as
+/// `paging: { offset, limit, total, returned, nextOffset }`; other results are unchanged.
+fn paginate(mut response: JsonRpcResponse, paging: &Paging) -> JsonRpcResponse {
+    if !paging.requested() {
+        return response;
+    }
+    let Some(envelope) = response.result.as_mut().and_then(Value::as_object_mut) else {
+        return response;
+    };
+    let Some(Value::Array(items)) = envelope.get_mut("result") else {
+        return response;
+    };
+    let total = items.len();
+    let start = paging.offset.min(total);
+    let end = paging
+        .limit
+        .map_or(total, |limit| start.saturating_add(limit).min(total));
+    items.truncate(end);
+    items.drain(..start);
+    envelope.insert(
+        "paging".into(),
+        json!({
+            "offset": paging.offset,
+            "limit": paging.limit,
+            "total": total,
+            "returned": end - start,
+            "nextOffset": (end < total).then_some(end),
+        }),
+    );
+    response
+}
+
+fn oversized_response_hint(tool: &str) -> &'static str {
+    match tool {
+        "lsp_semantic_tokens_full" | "lsp_semantic_tokens_full_delta" => {
+            "Request a smaller slice with `lsp_semantic_tokens_range`."
+        }
+        "lsp_workspace_symbol" => "Narrow the `query` to match fewer symbols.",
+        "lsp_workspace_diagnostic" | "lsp_workspace_problems" => {
+            "Pull diagnostics per document with `lsp_text_document_diagnostic`."
+        }
+        "lsp_inlay_hint" | "lsp_inline_value" => "Request a smaller `range`.",
+        "lsp_call" => "Narrow the request `params` (for example a smaller range or query).",
+        _ => "Narrow the request (smaller range, more specific query) or raise LSP_MAX_RESPONSE_BYTES.",
+    }
+}
+
+/// Replace results whose serialized size exceeds `limit` with a structured error so clients
+/// never receive a silently truncated payload. For paged tools the error suggests a `limit` that
+/// fits, starting from the current `offset`.
+fn enforce_response_limit(
+    tool: &str,
+    paging: &Paging,
+    limit: Option<usize>,
+    response: JsonRpcResponse,
+) -> JsonRpcResponse {
+    let (Some(limit), Some(result)) = (limit, response.result.as_ref()) else {
+        return response;
+    };
+    let size = match serde_json::to_vec(result) {
+        Ok(bytes) => bytes.len(),
+        Err(_) => return response,
+    };
+    if size <= limit {
+        return response;
+    }
+    eprintln!(
+        "mcp-lsp: tool '{}' result is {} bytes, exceeding the {} byte limit",
+        tool, size, limit
+    );
+    let mut message = format!(
+        "Tool '{tool}' produced a {size} byte result, exceeding the {limit} byte response limit"
+    );
+    let mut data = json!({
+        "tool": tool,
+        "bytes": size,
+        "limit": limit,
+        "hint": oversized_response_hint(tool),
+    });
+    let items = result
+        .get("result")
+        .and_then(Value::as_array)
+        .filter(|_| is_paged_tool(tool));
+    if let Some(items) = items {
+        // Assume evenly sized items and leave some headroom for the envelope.
+        let fitting = (items.len().saturating_mul(limit) / size * 9 / 10).max(1);
+        message.push_str(&format!(
+            "; retry with `limit: {fitting}` and `offset: {}`, then continue from `paging.nextOffset`",
+            paging.offset
+        ));
+        data["hint"] = json!(
+            "Page through the result with `limit`/`offset`; each page reports `paging.nextOffset`."
+        );
+        data["paging"] = json!({ "limit": fitting, "offset": paging.offset });
+    }
+    JsonRpcResponse::error(ErrorObject::new(-32050, &message, Some(data)))
+}
+
+pub(crate) async fn handle_tools_call(mut params: Option<Value>) -> JsonRpcResponse {
+    let tool_name = params
+        .as_ref()
+        .and_then(|p| p.get("name"))
+        .and_then(Value::as_str)
+        .map(canonical_tool_name);
+    let paging = if tool_name.as_deref().is_some_and(is_paged_tool) {
+        match take_paging_args(&mut params) {
+            Ok(paging) => paging,
+            Err(err) => return JsonRpcResponse::error(err),
+        }
+    } else {
+        Paging::default()
+    };
+    let response = paginate(dispatch_tools_call(params).await, &paging);
+    match tool_name {
+        Some(tool) => enforce_response_limit(&tool, &paging, max_response_bytes(), response),
+        None => response,
+    }
+}
+
+async fn dispatch_tools_call(params: Option<Value>) -> JsonRpcResponse {
+    let err_resp = |code: i64, msg: &str| JsonRpcResponse::error(ErrorObject::new(code, msg, None));
+    let params = match params {
+        Some(Value::Object(map)) => map,
+        _ => return err_resp(-32602, "Invalid params: expected object"),
+    };
+    let tool_name = match params.get("name") {
+        Some(Value::String(s)) => canonical_tool_name(s),
+        _ => return err_resp(-32602, "Missing 'name' in params"),
+    };
+
+    let arguments_value = params
+        .get("arguments")
+        .cloned()
+        .unwrap_or_else(|| json!({}));
+
+    match tool_name.as_str() {
+        "lsp_call" => {
+            let mut args_map = match arguments_value.as_object() {
+                Some(m) => m.clone(),
+                None => return err_resp(-32602, "Invalid arguments: expected object"),
+            };
+            let server_cmd = args_map
+                .remove("serverCommand")
+                .and_then(|v| v.as_str().map(|s| s.to_string()));
+            return handle_lsp_call(args_map, server_cmd).await;
+        }
+        "lsp_notify" => {
+            let mut args_map = match arguments_value.as_object() {
+                Some(m) => m.clone(),
+                None => Map::new(),
+            };
+            let server_cmd = args_map
+                .remove("serverCommand")
+                .and_then(|v| v.as_str().map(|s| s.to_string()));
+            return handle_lsp_notify("lsp_notify", args_map, server_cmd).await;
+        }
+        "lsp_capabilities"
+        | "lsp_server_features"
+        | "lsp_semantic_tokens_legend"
+        | "lsp_list_commands" => {
+            let tool = match tool_name.as_str() {
+                "lsp_capabilities" => "lsp_capabilities",
+                "lsp_server_features" => "lsp_server_features",
+                "lsp_list_commands" => "lsp_list_commands",
+                _ => "lsp_semantic_tokens_legend",
+            };
+            let mut args_map = match arguments_value.as_object() {
+                Some(m) => m.clone(),
+                None => Map::new(),
+            };
+            let server_cmd = args_map
+                .remove("serverCommand")
+                .and_then(|v| v.as_str().map(|s| s.to_string()));
+            return handle_lsp_capabilities(tool, args_map, server_cmd).await;
+        }
+        "lsp_warmup" => {
+            let mut args_map = match arguments_value.as_object() {
+                Some(m) => m.clone(),
+                None => Map::new(),
+            };
+            let server_cmd = args_map
+                .remove("serverCommand")
+                .and_then(|v| v.as_str().map(|s| s.to_string()));
+            return handle_lsp_warmup(args_map, server_cmd).await;
+        }
+        "lsp_did_close" => {
+            let mut args_map = match arguments_value.as_object() {
+                Some(m) => m.clone(),
+                None => return err_resp(-32602, "Invalid arguments: expected object"),
+            };
+            let server_cmd = args_map
+                .remove("serverCommand")
+                .and_then(|v| v.as_str().map(|s| s.to_string()));
+            return handle_lsp_did_close(args_map, server_cmd).await;
+        }
+        "lsp_diagnostics_summary" => {
+            let mut args_map = match arguments_value.as_object() {
+                Some(m) => m.clone(),
+                None => return err_resp(-32602, "Invalid arguments: expected object"),
+            };
+            let server_cmd = args_map
+                .remove("serverCommand")
+                .and_then(|v| v.as_str().map(|s| s.to_string()));
+            return handle_lsp_diagnostics_summary(args_map, server_cmd).await;
+        }
+        "lsp_poll_diagnostics" => {
+            let mut args_map = match arguments_value.as_object() {
+                Some(m) => m.clone(),
+                None => Map::new(),
+            };
+            let server_cmd = args_map
+                .remove("serverCommand")
+                .and_then(|v| v.as_str().map(|s| s.to_string()));
+            return handle_lsp_poll_diagnostics(args_map, server_cmd).await;
+        }
+        "lsp_completion_apply_preview" => {
+            let args_map = match arguments_value.as_object() {
+                Some(m) => m.clone(),
+                None => return err_resp(-32602, "Invalid arguments: expected object"),
+            };
+            return handle_lsp_completion_apply_preview(args_map).await;
+        }
+        "lsp_code_action_preview" => {
+            let mut args_map = match arguments_value.as_object() {
+                Some(m) => m.clone(),
+                None => return err_resp(-32602, "Invalid arguments: expected object"),
+            };
+            let server_cmd = args_map
+                .remove("serverCommand")
+                .and_then(|v| v.as_str().map(|s| s.to_string()));
+            return handle_lsp_code_action_preview(args_map, server_cmd).await;
+        }
+        "lsp_document_state" => {
+            let args_map = match arguments_value.as_object() {
+                Some(m) => m.clone(),
+                None => return err_resp(-32602, "Invalid arguments: expected object"),
+            };
+            return handle_lsp_document_state(args_map).await;
+        }
+        "lsp_will_save" => {
+            let mut args_map = match arguments_value.as_object() {
+                Some(m) => m.clone(),
+                None => return err_resp(-32602, "Invalid arguments: expected object"),
+            };
+            let server_cmd = args_map
+                .remove("serverCommand")
+                .and_then(|v| v.as_str().map(|s| s.to_string()));
+            let invocation = match build_lsp_invocation(&tool_name, &args_map, server_cmd.clone()) {
+                Ok(inv) => inv,
+                Err(err) => return JsonRpcResponse::error(err),
+            };
+            let mut notify_args = Map::new();
+            notify_args.insert("method".into(), json!(invocation.method));
+            notify_args.insert("params".into(), invocation.params);
+            if let Some(uri) = invocation.uri_hint {
+                notify_args.insert("uri".into(), json!(uri));
+            }
+            return handle_lsp_notify("lsp_will_save", notify_args, server_cmd).await;
+        }
+        _ => {}
+    }
+
+    let mut args_map = match arguments_value.as_object() {
+        Some(m) => m.clone(),
+        None => return err_resp(-32602, "Invalid arguments: expected object"),
+    };
+
+    let server_cmd = args_map
+        .remove("serverCommand")
+        .and_then(|v| v.as_str().map(|s| s.to_string()));
+
+    if !tool_name.starts_with("lsp_") {
+        return JsonRpcResponse::error(unsupported_tool_error(&tool_name));
+    }
+
+    let invocation = match build_lsp_invocation(&tool_name, &args_map, server_cmd.clone()) {
+        Ok(inv) => inv,
+        Err(err) => return JsonRpcResponse::error(err),
     };
 
     let method = invocation.method;
@@ -2072,6 +4542,12 @@ pub(crate) async fn handle_tools_call(params: Option<Value>) -> JsonRpcResponse
     let params_for_closure = params_for_request.clone();
     let server_cmd_for_closure = server_cmd_for_request.clone();
     let uri_hint_for_closure = uri_hint_for_request.clone();
+    let tool_name_for_closure = tool_name.clone();
+    let max_hops = args_map
+        .get("maxHops")
+        .and_then(|v| v.as_u64())
+        .map(|n| n.clamp(1, MAX_DEFINITION_HOPS) as usize)
+        .unwrap_or(DEFAULT_DEFINITION_HOPS);
 
     let result = task::spawn_blocking(move || {
         with_language_pool(|pool| {
@@ -2093,28 +4569,102 @@ pub(crate) async fn handle_tools_call(params: Option<Value>) -> JsonRpcResponse
             } else {
                 None
             };
+            let composite = match tool_name_for_closure.as_str() {
+                "lsp_definition_with_hover" => Some(pool.definition_with_hover(
+                    &cmd,
+                    open_params.as_ref(),
+                    params_for_closure.clone(),
+                )?),
+                "lsp_definition_resolved" => Some(pool.definition_chain(
+                    &cmd,
+                    open_params.as_ref(),
+                    params_for_closure.clone(),
+                    max_hops,
+                )?),
+                "lsp_usages" => {
+                    Some(pool.usages(&cmd, open_params.as_ref(), params_for_closure.clone())?)
+                }
+                _ => None,
+            };
+            if let Some((combined, opened_targets)) = composite {
+                if need_open {
+                    if let Some(uri) = uri_hint_for_closure.as_ref() {
+                        pool.associate_document(uri, &cmd);
+                    }
+                }
+                for target in opened_targets {
+                    pool.associate_document(&target, &cmd);
+                }
+                return Ok(combined);
+            }
+            let request_params = pool.with_semantic_tokens_result_id(
+                method,
+                uri_hint_for_closure.as_deref(),
+                params_for_closure.clone(),
+            )?;
             let outcome = pool.with_manager(&cmd, |lsm| {
                 if let Some(payload) = open_params.as_ref() {
                     lsm.notify("textDocument/didOpen", payload.clone(), Some(cmd.as_str()))?;
                 }
-                lsm.request(method, params_for_closure.clone(), Some(cmd.as_str()))
+                let command = request_params
+                    .get("command")
+                    .and_then(Value::as_str)
+                    .map(str::to_string);
+                let outcome = lsm.request(method, request_params, Some(cmd.as_str()));
+                match command {
+                    Some(command) if method == "workspace/executeCommand" => {
+                        outcome.map_err(|e| explain_execute_command_error(lsm, &cmd, &command, e))
+                    }
+                    _ => outcome,
+                }
             })?;
             if need_open {
                 if let Some(uri) = uri_hint_for_closure.as_ref() {
                     pool.associate_document(uri, &cmd);
                 }
             }
+            if let Some(uri) = uri_hint_for_closure.as_deref() {
+                pool.record_semantic_tokens_result_id(method, uri, &outcome);
+            }
+            if method == "textDocument/diagnostic" {
+                if let Some(uri) = uri_hint_for_closure.as_deref() {
+                    return Ok(pool.reconcile_document_diagnostic(uri, outcome));
+                }
+            }
+            if matches!(
+                tool_name_for_closure.as_str(),
+                "lsp_format_preview" | "lsp_on_type_format_preview"
+            ) {
+                if let Some(uri) = uri_hint_for_closure.as_deref() {
+                    return format_preview(uri, &outcome, pool.stored_text(uri));
+                }
+            }
             Ok(outcome)
         })
     })
     .await;
 
     match result {
-        Ok(Ok(value)) => JsonRpcResponse::result(json!({
-            "tool": tool_name,
-            "status": "ok",
-            "result": value
-        })),
+        Ok(Ok(value)) => {
+            let value = match tool_name.as_str() {
+                "lsp_workspace_problems" => flatten_workspace_problems(&value),
+                "lsp_document_symbols_flat" => flatten_document_symbols(&value),
+                "lsp_locate_symbol" => locate_symbol(
+                    &value,
+                    args_map
+                        .get("query")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default(),
+                    args_map.get("uri").and_then(Value::as_str),
+                ),
+                _ => value,
+            };
+            JsonRpcResponse::result(json!({
+                "tool": tool_name,
+                "status": "ok",
+                "result": value
+            }))
+        }
         Ok(Err(e)) => {
             let data = build_error_data(
                 &tool_name,
@@ -2159,3 +4709,617 @@ impl Drop for LanguageServerPool {
 async fn main() -> Result<()> {
     mcp::run().await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `lsp_call` resolves an unopened document's server from its uri alone, then opens it
+    // implicitly with `build_did_open_params`; both must agree on the mapped language.
+    #[test]
+    fn lsp_call_routes_unopened_document_by_extension_language() -> Result<()> {
+        let mut pool = LanguageServerPool::with_server_map(Some(
+            r#"{
+                ".novl": {"command": "novl-extension-server", "language": "novel"},
+                "languages": {"novel": "novel-language-server"},
+            }"#,
+        ));
+        let path =
+            std::env::temp_dir().join(format!("mcp-lsp-routing-{}.novl", std::process::id()));
+        std::fs::write(&path, "greet world\n")?;
+        let uri = Url::from_file_path(&path)
+            .map_err(|()| anyhow!("no file URI for {path:?}"))?
+            .to_string();
+
+        // A raw hover carries no languageId, yet goes to the server an implicit didOpen of a
+        // `.novl` file would pick.
+        let cmd = pool.resolve_command(None, Some(&uri), None)?;
+        let open = pool.build_did_open_params(&uri, None);
+        std::fs::remove_file(&path)?;
+        assert_eq!(cmd, "novel-language-server");
+        assert_eq!(open?["textDocument"]["languageId"], json!("novel"));
+        assert_eq!(pool.language_for_uri(&uri).as_deref(), Some("novel"));
+        Ok(())
+    }
+
+    #[test]
+    fn did_change_applies_incremental_changes_to_stored_text() {
+        let uri = "file:///work/notes.txt";
+        let mut pool = LanguageServerPool::with_server_map(None);
+        let change = |version: i64, changes: Value| json!({"textDocument": {"uri": uri, "version": version}, "contentChanges": changes});
+        let range = |start: (u64, u64), end: (u64, u64)| {
+            json!({
+                "start": {"line": start.0, "character": start.1},
+                "end": {"line": end.0, "character": end.1}
+            })
+        };
+
+        // Without stored text a ranged change has nothing to apply to and is ignored.
+        pool.record_document_text(
+            "textDocument/didChange",
+            &change(2, json!([{"range": range((0, 0), (0, 0)), "text": "x"}])),
+        );
+        assert_eq!(pool.stored_text(uri), None);
+
+        pool.record_document_text(
+            "textDocument/didOpen",
+            &json!({"textDocument": {"uri": uri, "languageId": "plaintext", "version": 1, "text": "one\ntwo\n"}}),
+        );
+        // Changes in one notification apply in order, each to the result of the previous.
+        pool.record_document_text(
+            "textDocument/didChange",
+            &change(
+                2,
+                json!([
+                    {"range": range((0, 0), (0, 3)), "text": "ONE"},
+                    {"range": range((1, 3), (1, 3)), "text": "!\nthree"},
+                    {"range": range((2, 0), (2, 5)), "text": "3"},
+                ]),
+            ),
+        );
+        assert_eq!(pool.stored_text(uri), Some("ONE\ntwo!\n3\n"));
+        assert_eq!(pool.document_texts[uri].version, 2);
+
+        // A change without a range replaces the whole text.
+        pool.record_document_text(
+            "textDocument/didChange",
+            &change(3, json!([{"text": "fresh\n"}])),
+        );
+        assert_eq!(pool.stored_text(uri), Some("fresh\n"));
+        assert_eq!(pool.document_texts[uri].version, 3);
+
+        // A ranged change that cannot be applied drops the text rather than keeping a stale copy.
+        pool.record_document_text(
+            "textDocument/didChange",
+            &change(4, json!([{"range": {"start": {"line": 0}}, "text": "?"}])),
+        );
+        assert_eq!(pool.stored_text(uri), None);
+    }
+
+    #[test]
+    fn drive_letter_uris_share_one_document_key() {
+        let spellings = [
+            "file:///c:/work/a.rs",
+            "file:///C:/work/a.rs",
+            "file:///c%3A/work/a.rs",
+            "file:///c%3a/work/a.rs",
+            "file:///c:\\work\\a.rs",
+            "file:///C:/work\\a.rs",
+            "file:///c%3A%5Cwork%5Ca.rs",
+            "file:///C:%5cwork/a.rs",
+        ];
+        for uri in spellings {
+            assert_eq!(
+                LanguageServerPool::normalize_uri(uri),
+                "file:///C:/work/a.rs",
+                "{uri}"
+            );
+        }
+
+        let mut pool = LanguageServerPool::new();
+        pool.associate_document("file:///c%3A/work/a.rs", "rust-analyzer");
+        assert!(spellings.iter().all(|uri| pool.has_document(uri)));
+        assert!(!pool.has_document("file:///D:/work/a.rs"));
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_paths_match_their_drive_letter_uris() {
+        for path in [
+            "c:\\work\\a.rs",
+            "C:\\work\\a.rs",
+            "c:/work/a.rs",
+            "C:/work\\a.rs",
+        ] {
+            assert_eq!(
+                LanguageServerPool::normalize_uri(path),
+                "file:///C:/work/a.rs",
+                "{path}"
+            );
+        }
+
+        let mut pool = LanguageServerPool::new();
+        pool.associate_document("c:\\work\\a.rs", "rust-analyzer");
+        assert!(pool.has_document("file:///C:/work/a.rs"));
+        assert!(pool.has_document("C:/work/a.rs"));
+    }
+
+    fn references_response(items: Vec<Value>) -> JsonRpcResponse {
+        JsonRpcResponse::result(json!({
+            "tool": "lsp_references",
+            "status": "ok",
+            "result": items
+        }))
+    }
+
+    #[test]
+    fn paginate_returns_the_requested_page() {
+        let items: Vec<Value> = (0..5).map(|i| json!(i)).collect();
+        let cases = [
+            (0, Some(2), json!([0, 1]), json!(2)),
+            (2, Some(2), json!([2, 3]), json!(4)),
+            (4, Some(2), json!([4]), Value::Null),
+            (3, None, json!([3, 4]), Value::Null),
+            (9, Some(2), json!([]), Value::Null),
+        ];
+        for (offset, limit, page, next) in cases {
+            let response = paginate(
+                references_response(items.clone()),
+                &Paging { offset, limit },
+            );
+            let result = response.result.expect("paginated result");
+            assert_eq!(result["result"], page, "offset {offset}, limit {limit:?}");
+            assert_eq!(result["paging"]["total"], 5);
+            assert_eq!(result["paging"]["nextOffset"], next);
+        }
+
+        let unpaged = paginate(references_response(items), &Paging::default());
+        assert!(unpaged.result.expect("result").get("paging").is_none());
+
+        let object = json!({ "result": { "items": [1, 2, 3] } });
+        let response = paginate(
+            JsonRpcResponse::result(object.clone()),
+            &Paging {
+                offset: 1,
+                limit: Some(1),
+            },
+        );
+        assert_eq!(response.result, Some(object));
+    }
+
+    #[test]
+    fn paging_args_are_validated_and_removed() {
+        let mut params = Some(json!({
+            "name": "lsp_references",
+            "arguments": { "uri": "file:///a.rs", "limit": 10, "offset": 20 }
+        }));
+        let paging = take_paging_args(&mut params).expect("valid paging");
+        assert_eq!((paging.offset, paging.limit), (20, Some(10)));
+        assert_eq!(
+            params.expect("params")["arguments"],
+            json!({ "uri": "file:///a.rs" })
+        );
+
+        for arguments in [
+            json!({ "limit": 0 }),
+            json!({ "limit": "10" }),
+            json!({ "offset": -1 }),
+            json!({ "offset": 1.5 }),
+        ] {
+            let mut params = Some(json!({ "name": "lsp_references", "arguments": arguments }));
+            let err = take_paging_args(&mut params).err().expect("invalid paging");
+            assert_eq!(err.code, -32602, "{arguments}");
+        }
+    }
+
+    #[test]
+    fn max_response_bytes_parsing() {
+        let default = Some(4 * 1024 * 1024);
+        let cases = [
+            (None, default),
+            (Some("1024"), Some(1024)),
+            (Some(" 2048 "), Some(2048)),
+            (Some("0"), None),
+            (Some("-1"), default),
+            (Some("4MiB"), default),
+        ];
+        for (raw, expected) in cases {
+            assert_eq!(parse_max_response_bytes(raw), expected, "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn oversized_results_become_errors_with_paging_advice() {
+        let items: Vec<Value> = (0..100).map(|i| json!(format!("item-{i:04}"))).collect();
+        let size = serde_json::to_vec(&references_response(items.clone()).result)
+            .expect("serializable")
+            .len();
+
+        let fits = enforce_response_limit(
+            "lsp_references",
+            &Paging::default(),
+            Some(size),
+            references_response(items.clone()),
+        );
+        assert!(fits.error.is_none());
+        let unlimited = enforce_response_limit(
+            "lsp_references",
+            &Paging::default(),
+            None,
+            references_response(items.clone()),
+        );
+        assert!(unlimited.error.is_none());
+
+        let limit = size / 4;
+        let paging = Paging {
+            offset: 30,
+            limit: None,
+        };
+        let error = enforce_response_limit(
+            "lsp_references",
+            &paging,
+            Some(limit),
+            references_response(items.clone()),
+        )
+        .error
+        .expect("oversized paged result");
+        assert_eq!(error.code, -32050);
+        let data = error.data.expect("error data");
+        assert_eq!(data["bytes"], size);
+        assert_eq!(data["paging"]["offset"], 30);
+        let suggested = data["paging"]["limit"].as_u64().expect("suggested limit") as usize;
+        assert!(error
+            .message
+            .contains(&format!("retry with `limit: {suggested}` and `offset: 30`")));
+        let page = references_response(items[..suggested].to_vec());
+        assert!(
+            serde_json::to_vec(&page.result)
+                .expect("serializable")
+                .len()
+                <= limit
+        );
+
+        let error = enforce_response_limit(
+            "lsp_call",
+            &Paging::default(),
+            Some(limit),
+            references_response(items),
+        )
+        .error
+        .expect("oversized passthrough result");
+        let data = error.data.expect("error data");
+        assert!(data.get("paging").is_none());
+        assert_eq!(data["hint"], oversized_response_hint("lsp_call"));
+        assert!(!error.message.contains("limit:"));
+    }
+
+    #[test]
+    fn relaxed_json_tolerates_comments_and_trailing_commas() {
+        let cases = [
+            ("strict", r#"{"a": [1, 2]}"#, json!({"a": [1, 2]})),
+            (
+                "line comments",
+                "{\n  // routing\n  \"a\": 1 // trailing note\n}",
+                json!({"a": 1}),
+            ),
+            (
+                "block comments",
+                "{ /* multi\n line */ \"a\": [1, /* inline */ 2] }",
+                json!({"a": [1, 2]}),
+            ),
+            (
+                "comment markers in strings",
+                "{\"url\": \"http://host//x\", \"glob\": \"src/**/*.rs\", // real\n}",
+                json!({"url": "http://host//x", "glob": "src/**/*.rs"}),
+            ),
+            (
+                "escaped quotes",
+                r#"{"q": "say \"hi\" // still text", "p": "\\", }"#,
+                json!({"q": "say \"hi\" // still text", "p": "\\"}),
+            ),
+            (
+                "trailing commas",
+                r#"{"a": [1, 2, ], "b": {"c": 1, }, }"#,
+                json!({"a": [1, 2], "b": {"c": 1}}),
+            ),
+            (
+                "trailing comma before comment",
+                "[1, // last\n]",
+                json!([1]),
+            ),
+            ("comma in string", r#"{"a": ",}", }"#, json!({"a": ",}"})),
+        ];
+        for (name, raw, expected) in cases {
+            let parsed = parse_relaxed_json(raw);
+            assert_eq!(parsed.ok(), Some(expected), "{name}");
+        }
+    }
+
+    #[test]
+    fn relaxed_json_still_rejects_invalid_input() {
+        for raw in ["[1,,]", "[,]", "{\"a\": }", "{\"a\" /* open"] {
+            assert!(parse_relaxed_json(raw).is_err(), "{raw}");
+        }
+        // Blanked comments keep line numbers, so errors point into the original text.
+        let err = parse_relaxed_json("{\n  // note\n  \"a\": ,\n}").unwrap_err();
+        assert_eq!(err.line(), 3);
+    }
+
+    #[test]
+    fn qualified_queries_split_at_the_last_separator() {
+        let cases = [
+            ("a::b", (Some("a"), "b")),
+            ("a.b", (Some("a"), "b")),
+            ("std::fmt::Display", (Some("std::fmt"), "Display")),
+            ("Outer.Inner.method", (Some("Outer.Inner"), "method")),
+            // `::` wins over `.`, so a dotted container stays whole.
+            ("a.b::c", (Some("a.b"), "c")),
+            ("plain", (None, "plain")),
+            ("  spaced  ", (None, "spaced")),
+            ("a::", (None, "a::")),
+            ("::b", (None, "::b")),
+            (".hidden", (None, ".hidden")),
+        ];
+        for (query, expected) in cases {
+            assert_eq!(split_qualified_query(query), expected, "{query}");
+        }
+    }
+
+    #[test]
+    fn locate_symbol_ranks_names_and_filters_by_container_and_uri() {
+        let symbol = |name: &str, container: Option<&str>, uri: &str, line: u64| {
+            json!({
+                "name": name,
+                "kind": 12,
+                "containerName": container,
+                "location": {
+                    "uri": uri,
+                    "range": {
+                        "start": {"line": line, "character": 4},
+                        "end": {"line": line, "character": 10}
+                    }
+                }
+            })
+        };
+        let ui = "file:///work/src/ui.rs";
+        let lib = "file:///work/src/lib.rs";
+        let result = json!([
+            symbol("MyWidget", None, lib, 1),
+            symbol("widget", None, lib, 2),
+            symbol("Widget", Some("ui"), ui, 3),
+            symbol("WidgetBuilder", Some("ui::builder"), ui, 4),
+            symbol("new", Some("Widget"), ui, 5),
+            symbol("new", Some("Gadget"), lib, 6),
+            symbol("Other", None, lib, 7),
+        ]);
+        let names = |located: &Value| -> Vec<String> {
+            std::iter::once(&located["match"])
+                .chain(located["alternatives"].as_array().into_iter().flatten())
+                .filter_map(|entry| entry["name"].as_str().map(str::to_string))
+                .collect()
+        };
+
+        // Exact, case-insensitive, prefix, then substring; unrelated names are dropped.
+        let located = locate_symbol(&result, " Widget ", None);
+        assert_eq!(located["query"], "Widget");
+        assert_eq!(
+            names(&located),
+            ["Widget", "widget", "WidgetBuilder", "MyWidget"]
+        );
+        assert_eq!(located["candidates"], 4);
+        assert_eq!(
+            located["match"]["position"],
+            json!({"line": 3, "character": 4})
+        );
+        assert_eq!(located["uri"], Value::Null);
+
+        for query in ["Widget::new", "Widget.new", "widget::new"] {
+            let located = locate_symbol(&result, query, None);
+            assert_eq!(located["match"]["containerName"], "Widget", "{query}");
+            assert_eq!(located["candidates"], 1, "{query}");
+        }
+        let located = locate_symbol(&result, "ui::Widget", None);
+        assert_eq!(names(&located), ["Widget", "WidgetBuilder"]);
+
+        // A full uri must match after normalization; a relative path matches as a suffix.
+        let located = locate_symbol(&result, "Widget", Some(ui));
+        assert_eq!(names(&located), ["Widget", "WidgetBuilder"]);
+        assert_eq!(located["uri"], ui);
+        let located = locate_symbol(&result, "new", Some("./src/lib.rs"));
+        assert_eq!(located["match"]["containerName"], "Gadget");
+        assert_eq!(located["candidates"], 1);
+        let located = locate_symbol(&result, "Widget", Some("src/other.rs"));
+        assert_eq!(located["match"], Value::Null);
+        assert_eq!(located["candidates"], 0);
+    }
+
+    #[test]
+    fn edit_distance_counts_characters() {
+        let cases = [
+            ("", "", 0),
+            ("", "abc", 3),
+            ("abc", "", 3),
+            ("same", "same", 0),
+            ("kitten", "sitting", 3),
+            ("flaw", "lawn", 2),
+            ("héllo", "hello", 1),
+            ("日本語", "日本", 1),
+            ("😀", "😁", 1),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(edit_distance(a, b), expected, "{a:?} -> {b:?}");
+        }
+    }
+
+    #[test]
+    fn close_command_matches_rank_nearest_first() {
+        let commands: Vec<String> = [
+            "rust-analyzer.runSingle",
+            "rust-analyzer.debugSingle",
+            "editor.action.format",
+            "café.open",
+            "x",
+        ]
+        .map(String::from)
+        .to_vec();
+        let cases: [(&str, &[&str]); 6] = [
+            (
+                "rust-analyzer.runsingle",
+                &["rust-analyzer.runSingle", "rust-analyzer.debugSingle"],
+            ),
+            ("format", &["editor.action.format"]),
+            ("cafe.open", &["café.open"]),
+            ("CAFÉ.OPEN", &["café.open"]),
+            // Short commands still allow a distance of 2.
+            ("zz", &["x"]),
+            ("zzz", &[]),
+        ];
+        for (command, expected) in cases {
+            assert_eq!(
+                close_command_matches(command, &commands),
+                expected,
+                "{command}"
+            );
+        }
+        // An empty command is a substring of everything: the shortest names come first.
+        let empty = close_command_matches("", &commands);
+        assert_eq!(empty.len(), MAX_COMMAND_SUGGESTIONS);
+        assert_eq!(empty[..2], ["x", "café.open"]);
+    }
+
+    #[test]
+    fn workspace_problems_flatten_full_reports_only() {
+        let range = |line: u64| {
+            json!({
+                "start": {"line": line, "character": 0},
+                "end": {"line": line, "character": 5}
+            })
+        };
+        let unused = json!({"severity": 2, "message": "unused variable", "range": range(4), "source": "rustc"});
+        let report = json!({
+            "items": [
+                {
+                    "kind": "full",
+                    "uri": "file:///work/b.rs",
+                    "items": [
+                        unused,
+                        {"message": "no severity", "range": range(9)},
+                        unused,
+                    ]
+                },
+                {"kind": "unchanged", "uri": "file:///work/c.rs", "resultId": "7"},
+                {
+                    "kind": "full",
+                    "uri": "file:///work/a.rs",
+                    "items": [
+                        unused,
+                        {"severity": 1, "message": "mismatched types", "range": range(2), "code": "E0308"},
+                    ]
+                },
+                {"kind": "full", "items": [{"severity": 1, "message": "no uri"}]},
+            ]
+        });
+
+        let flat = flatten_workspace_problems(&report);
+        assert_eq!(flat["count"], 4);
+        // Errors first, then by uri within a severity; a missing severity sorts last.
+        let order: Vec<(Value, &str)> = flat["problems"]
+            .as_array()
+            .expect("problems array")
+            .iter()
+            .map(|p| (p["severity"].clone(), p["uri"].as_str().unwrap_or_default()))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (json!(1), "file:///work/a.rs"),
+                (json!(2), "file:///work/a.rs"),
+                (json!(2), "file:///work/b.rs"),
+                (Value::Null, "file:///work/b.rs"),
+            ]
+        );
+        assert_eq!(
+            flat["problems"][0],
+            json!({
+                "uri": "file:///work/a.rs",
+                "severity": 1,
+                "message": "mismatched types",
+                "range": range(2),
+                "source": null,
+                "code": "E0308",
+            })
+        );
+
+        let unchanged =
+            json!({"items": [{"kind": "unchanged", "uri": "file:///work/c.rs", "resultId": "7"}]});
+        assert_eq!(
+            flatten_workspace_problems(&unchanged),
+            json!({"count": 0, "problems": []})
+        );
+        assert_eq!(flatten_workspace_problems(&Value::Null)["count"], 0);
+    }
+
+    #[test]
+    fn diagnostics_are_counted_by_severity() {
+        let items = [
+            json!({"severity": 1, "message": "error"}),
+            json!({"severity": 2, "message": "warning"}),
+            json!({"severity": 2, "message": "another warning"}),
+            json!({"severity": 4, "message": "hint"}),
+            json!({"message": "no severity"}),
+            json!({"severity": 9, "message": "out of range"}),
+            json!({"severity": "1", "message": "not a number"}),
+        ];
+        assert_eq!(
+            count_diagnostics_by_severity(&items),
+            json!({
+                "counts": {"error": 1, "warning": 2, "information": 0, "hint": 1},
+                "unspecified": 3,
+                "total": 7,
+                "hasErrors": true,
+            })
+        );
+        assert_eq!(
+            count_diagnostics_by_severity(&items[1..4]),
+            json!({
+                "counts": {"error": 0, "warning": 2, "information": 0, "hint": 1},
+                "unspecified": 0,
+                "total": 3,
+                "hasErrors": false,
+            })
+        );
+        assert_eq!(count_diagnostics_by_severity(&[])["total"], 0);
+    }
+
+    // A rename whose source is missing is flagged on its operation instead of clearing or
+    // creating the target; a valid rename still moves the content.
+    #[test]
+    fn workspace_edit_preview_flags_rename_of_missing_source() -> Result<()> {
+        let mut load = |uri: &str| -> Result<Option<String>> {
+            Ok((uri == "file:///a.rs").then(|| "fn a() {}\n".to_string()))
+        };
+        let edit = json!({"documentChanges": [
+            {"kind": "rename", "oldUri": "file:///gone.rs", "newUri": "file:///a.rs"},
+            {"kind": "rename", "oldUri": "file:///a.rs", "newUri": "file:///b.rs"},
+        ]});
+        let preview = workspace_edit_preview(&edit, &mut load)?;
+        let operations = preview["operations"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        assert_eq!(operations.len(), 2);
+        let error = operations[0]["error"].as_str().unwrap_or_default();
+        assert!(error.contains("file:///gone.rs"), "error: {error:?}");
+        assert!(operations[1].get("error").is_none());
+
+        let files = preview["files"].as_array().cloned().unwrap_or_default();
+        let file = |uri: &str| files.iter().find(|f| f["uri"] == uri).cloned();
+        let a = file("file:///a.rs").ok_or_else(|| anyhow!("a.rs missing from files"))?;
+        assert_eq!(a["before"], "fn a() {}\n");
+        assert!(a["after"].is_null());
+        let b = file("file:///b.rs").ok_or_else(|| anyhow!("b.rs missing from files"))?;
+        assert!(b["before"].is_null());
+        assert_eq!(b["after"], "fn a() {}\n");
+        Ok(())
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{handle_tools_call, tools, with_language_pool, LanguageServerPool, Tool};
+use crate::{handle_tools_call, tool_aliases, tools, with_language_pool, LanguageServerPool, Tool};
 use anyhow::{anyhow, Result};
 use rmcp::{
     model::{
@@ -31,7 +31,11 @@ fn lsp_capability_truthy(v: &Value) -> bool {
     }
 }
 
-fn filter_tools_by_capabilities(all: Vec<Tool>, caps: Option<Value>) -> Vec<Tool> {
+fn filter_tools_by_capabilities(
+    all: Vec<Tool>,
+    caps: Option<Value>,
+    forced: &HashSet<String>,
+) -> Vec<Tool> {
     let Some(caps) = caps else {
         return all;
     };
@@ -69,6 +73,13 @@ fn filter_tools_by_capabilities(all: Vec<Tool>, caps: Option<Value>) -> Vec<Tool
         .and_then(|v| v.get("workspaceDiagnostics"))
         .and_then(|b| b.as_bool())
         .unwrap_or(false);
+    let sync_flag = |k: &str| {
+        caps_obj
+            .get("textDocumentSync")
+            .and_then(|v| v.get(k))
+            .and_then(|b| b.as_bool())
+            .unwrap_or(false)
+    };
     let workspace = caps_obj.get("workspace").cloned();
     let ws_obj = workspace.as_ref().and_then(|v| v.as_object());
     let file_ops = ws_obj.and_then(|w| w.get("fileOperations"));
@@ -86,6 +97,10 @@ fn filter_tools_by_capabilities(all: Vec<Tool>, caps: Option<Value>) -> Vec<Tool
     }
     if has("definitionProvider") {
         allowed.insert("lsp_definition".into());
+        allowed.insert("lsp_definition_resolved".into());
+    }
+    if has("definitionProvider") && has("hoverProvider") {
+        allowed.insert("lsp_definition_with_hover".into());
     }
     if has("typeDefinitionProvider") {
         allowed.insert("lsp_type_definition".into());
@@ -95,9 +110,13 @@ fn filter_tools_by_capabilities(all: Vec<Tool>, caps: Option<Value>) -> Vec<Tool
     }
     if has("referencesProvider") {
         allowed.insert("lsp_references".into());
+        if has("definitionProvider") {
+            allowed.insert("lsp_usages".into());
+        }
     }
     if caps_obj.get("completionProvider").is_some() {
         allowed.insert("lsp_completion".into());
+        allowed.insert("lsp_completion_apply_preview".into());
         if resolve_flag("completionProvider") {
             allowed.insert("lsp_completion_item_resolve".into());
         }
@@ -110,9 +129,11 @@ fn filter_tools_by_capabilities(all: Vec<Tool>, caps: Option<Value>) -> Vec<Tool
     }
     if has("documentSymbolProvider") {
         allowed.insert("lsp_document_symbol".into());
+        allowed.insert("lsp_document_symbols_flat".into());
     }
     if has("codeActionProvider") {
         allowed.insert("lsp_code_action".into());
+        allowed.insert("lsp_code_action_preview".into());
         if resolve_flag("codeActionProvider") {
             allowed.insert("lsp_code_action_resolve".into());
         }
@@ -135,12 +156,20 @@ fn filter_tools_by_capabilities(all: Vec<Tool>, caps: Option<Value>) -> Vec<Tool
     }
     if has("documentFormattingProvider") {
         allowed.insert("lsp_formatting".into());
+        allowed.insert("lsp_format_preview".into());
     }
     if has("documentRangeFormattingProvider") {
         allowed.insert("lsp_range_formatting".into());
     }
     if caps_obj.get("documentOnTypeFormattingProvider").is_some() {
         allowed.insert("lsp_on_type_formatting".into());
+        allowed.insert("lsp_on_type_format_preview".into());
+    }
+    if sync_flag("willSave") {
+        allowed.insert("lsp_will_save".into());
+    }
+    if sync_flag("willSaveWaitUntil") {
+        allowed.insert("lsp_will_save_wait_until".into());
     }
     if has("renameProvider") {
         allowed.insert("lsp_rename".into());
@@ -180,6 +209,7 @@ fn filter_tools_by_capabilities(all: Vec<Tool>, caps: Option<Value>) -> Vec<Tool
         allowed.insert("lsp_type_hierarchy_subtypes".into());
     }
     if caps_obj.get("semanticTokensProvider").is_some() {
+        allowed.insert("lsp_semantic_tokens_legend".into());
         if matches!(
             semantic_full,
             Some(Value::Bool(true)) | Some(Value::Object(_))
@@ -195,12 +225,14 @@ fn filter_tools_by_capabilities(all: Vec<Tool>, caps: Option<Value>) -> Vec<Tool
     }
     if has("workspaceSymbolProvider") {
         allowed.insert("lsp_workspace_symbol".into());
+        allowed.insert("lsp_locate_symbol".into());
         if resolve_flag("workspaceSymbolProvider") {
             allowed.insert("lsp_workspace_symbol_resolve".into());
         }
     }
     if caps_obj.get("executeCommandProvider").is_some() {
         allowed.insert("lsp_execute_command".into());
+        allowed.insert("lsp_list_commands".into());
     }
     if let Some(fops) = file_ops.and_then(|v| v.as_object()) {
         if fops.get("willCreate").is_some() {
@@ -220,13 +252,25 @@ fn filter_tools_by_capabilities(all: Vec<Tool>, caps: Option<Value>) -> Vec<Tool
         allowed.insert("lsp_text_document_diagnostic".into());
         if diag_workspace {
             allowed.insert("lsp_workspace_diagnostic".into());
+            allowed.insert("lsp_workspace_problems".into());
         }
     }
 
     all.into_iter()
         .filter(|t| {
             let n = t.name.as_str();
-            if n == "lsp_call" {
+            if matches!(
+                n,
+                "lsp_call"
+                    | "lsp_did_close"
+                    | "lsp_diagnostics_summary"
+                    | "lsp_poll_diagnostics"
+                    | "lsp_document_state"
+                    | "lsp_capabilities"
+                    | "lsp_server_features"
+                    | "lsp_warmup"
+            ) || forced.contains(n)
+            {
                 return true;
             }
             if n.starts_with("lsp_") {
@@ -253,10 +297,38 @@ fn convert_tool_to_mcp(tool: Tool) -> McpTool {
     }
 }
 
+/// `LSP_DISABLE_CAPABILITY_FILTER` set to `1`/`true`/`yes`/`on` lists every tool without
+/// probing the default server.
+fn capability_filter_disabled() -> bool {
+    std::env::var("LSP_DISABLE_CAPABILITY_FILTER").is_ok_and(|v| {
+        matches!(
+            v.trim().to_ascii_lowercase().as_str(),
+            "1" | "true" | "yes" | "on"
+        )
+    })
+}
+
+/// Tool names from `LSP_FORCE_TOOLS` (comma-separated) that stay listed even when the server
+/// does not advertise the capability behind them.
+fn forced_tools() -> HashSet<String> {
+    std::env::var("LSP_FORCE_TOOLS")
+        .map(|v| {
+            v.split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .map(str::to_string)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 async fn list_available_tools() -> Result<Vec<McpTool>> {
     let all = tools();
+    if capability_filter_disabled() {
+        return Ok(all.into_iter().map(convert_tool_to_mcp).collect());
+    }
     let caps = with_language_pool_async(|pool| pool.probe_default_capabilities()).await?;
-    let filtered = filter_tools_by_capabilities(all, caps);
+    let filtered = filter_tools_by_capabilities(all, caps, &forced_tools());
     Ok(filtered.into_iter().map(convert_tool_to_mcp).collect())
 }
 
@@ -326,6 +398,8 @@ impl ServerHandler for CodexLspServer {
 }
 
 pub async fn run() -> Result<()> {
+    // Load `LSP_TOOL_ALIASES` up front so bad entries are reported at startup.
+    tool_aliases();
     let server = CodexLspServer;
     let running = server.serve(rmcp::transport::stdio()).await?;
     running.waiting().await?;
@@ -0,0 +1,345 @@
+//! Stand-in for `codex mcp` used by the integration tests. It speaks newline-delimited
+//! JSON-RPC on stdio, keeps conversations in memory, and plays out each turn as a fixed
+//! sequence of `codex/event` notifications.
+//!
+//! Turn events are written before the turn's response, so by the time a `sendUserTurn` or
+//! `sendUserMessage` call returns, the orchestrator has already seen every event of the turn.
+//! A turn sent with `"testApproval": true` instead asks the client for an
+//! `execCommandApproval` and is only answered once that approval is decided, which keeps the
+//! turn (and its conversation) in flight for as long as a test needs.
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const MODEL: &str = "gpt-5";
+
+struct Conversation {
+    id: String,
+    path: String,
+    preview: String,
+    timestamp: String,
+}
+
+impl Conversation {
+    fn to_json(&self) -> Value {
+        json!({
+            "conversationId": self.id,
+            "path": self.path,
+            "preview": self.preview,
+            "timestamp": self.timestamp,
+        })
+    }
+}
+
+/// A `sendUserTurn` held back until the client answers the approval request it triggered.
+struct DeferredTurn {
+    request_id: Value,
+    conversation_id: Option<String>,
+    text: String,
+}
+
+struct Stub<W: Write> {
+    out: W,
+    /// Conversations not archived yet, oldest first.
+    conversations: Vec<Conversation>,
+    created: u64,
+    next_request_id: i64,
+    /// Turns waiting on an approval, by the id of the approval request.
+    deferred: HashMap<i64, DeferredTurn>,
+}
+
+type Reply = Result<Value, (i64, String)>;
+
+impl<W: Write> Stub<W> {
+    fn new(out: W) -> Self {
+        Self {
+            out,
+            conversations: Vec::new(),
+            created: 0,
+            next_request_id: 1,
+            deferred: HashMap::new(),
+        }
+    }
+
+    fn handle_line(&mut self, line: &str) -> io::Result<()> {
+        let Ok(Value::Object(msg)) = serde_json::from_str::<Value>(line) else {
+            return Ok(());
+        };
+        let id = msg.get("id").cloned();
+        let params = msg.get("params").cloned().unwrap_or(Value::Null);
+        match (msg.get("method").and_then(Value::as_str), id) {
+            (Some(method), Some(id)) => self.handle_request(method, id, &params),
+            // `notifications/initialized` and friends need no answer.
+            (Some(_), None) => Ok(()),
+            (None, Some(id)) => self.handle_response(&id, msg.get("result")),
+            (None, None) => Ok(()),
+        }
+    }
+
+    fn handle_request(&mut self, method: &str, id: Value, params: &Value) -> io::Result<()> {
+        let reply = match method {
+            "initialize" => Ok(json!({
+                "protocolVersion": params
+                    .get("protocolVersion")
+                    .cloned()
+                    .unwrap_or_else(|| json!("2025-06-18")),
+                "capabilities": { "tools": {} },
+                "serverInfo": { "name": "stub_codex", "version": env!("CARGO_PKG_VERSION") },
+            })),
+            "newConversation" => Ok(self.new_conversation(params)),
+            "listConversations" => Ok(self.list_conversations(params)),
+            "resumeConversation" => self.resume_conversation(params),
+            "archiveConversation" => self.archive_conversation(params),
+            "sendUserMessage" => {
+                self.play_turn(conversation_id(params), &turn_text(params))?;
+                Ok(json!({}))
+            }
+            "sendUserTurn" => {
+                if params.get("testApproval").and_then(Value::as_bool) == Some(true) {
+                    return self.request_approval(id, params);
+                }
+                self.play_turn(conversation_id(params), &turn_text(params))?;
+                Ok(json!({}))
+            }
+            "interruptConversation" => {
+                let cid = conversation_id(params);
+                self.event(cid.as_deref(), json!({ "type": "turn_aborted", "reason": "interrupted" }))?;
+                Ok(json!({ "abortReason": "interrupted" }))
+            }
+            "model/list" => Ok(json!({ "data": [{ "id": MODEL }, { "id": "o3" }] })),
+            _ => Err((METHOD_NOT_FOUND, format!("Method not found: {method}"))),
+        };
+        self.reply(id, reply)
+    }
+
+    /// The client's answer to one of our approval requests: finish the turn that asked.
+    fn handle_response(&mut self, id: &Value, result: Option<&Value>) -> io::Result<()> {
+        let Some(turn) = id.as_i64().and_then(|id| self.deferred.remove(&id)) else {
+            return Ok(());
+        };
+        let decision = result
+            .and_then(|r| r.get("decision"))
+            .and_then(Value::as_str)
+            .unwrap_or("deny");
+        let cid = turn.conversation_id.as_deref();
+        if decision == "allow" {
+            self.event(cid, json!({ "type": "exec_command_end", "exit_code": 0 }))?;
+        }
+        self.event(
+            cid,
+            json!({ "type": "agent_message", "message": format!("{decision}: {}", turn.text) }),
+        )?;
+        self.event(cid, json!({ "type": "task_complete" }))?;
+        self.reply(turn.request_id, Ok(json!({})))
+    }
+
+    fn new_conversation(&mut self, params: &Value) -> Value {
+        self.created += 1;
+        // The pid keeps ids (and so rollout paths) apart between concurrently running stubs.
+        let id = format!("stub-{}-{}", std::process::id(), self.created);
+        let path = std::env::temp_dir()
+            .join(format!("rollout-{id}.jsonl"))
+            .to_string_lossy()
+            .into_owned();
+        let preview = ["value", "prompt", "text"]
+            .iter()
+            .find_map(|key| params.get(*key).and_then(Value::as_str))
+            .unwrap_or_default()
+            .to_string();
+        let response = json!({ "conversationId": id, "model": MODEL, "rolloutPath": path });
+        self.conversations.push(Conversation {
+            id,
+            path,
+            preview,
+            timestamp: rfc3339_now(),
+        });
+        response
+    }
+
+    /// Newest first, `pageSize` at a time; the cursor is the offset of the next page.
+    fn list_conversations(&self, params: &Value) -> Value {
+        let page_size = params
+            .get("pageSize")
+            .and_then(Value::as_u64)
+            .map_or(usize::MAX, |n| n as usize);
+        let start = params
+            .get("cursor")
+            .and_then(Value::as_str)
+            .and_then(|c| c.parse::<usize>().ok())
+            .unwrap_or(0);
+        let newest_first: Vec<&Conversation> = self.conversations.iter().rev().collect();
+        let end = start.saturating_add(page_size).min(newest_first.len());
+        let items: Vec<Value> = newest_first
+            .get(start..end)
+            .unwrap_or_default()
+            .iter()
+            .map(|c| c.to_json())
+            .collect();
+        let next_cursor = (end < newest_first.len()).then(|| end.to_string());
+        json!({ "items": items, "nextCursor": next_cursor })
+    }
+
+    /// Resumes by rollout path. Archived conversations and rollouts written by another stub
+    /// process resolve too: the conversation id is recovered from the file name.
+    fn resume_conversation(&mut self, params: &Value) -> Reply {
+        let path = params
+            .get("path")
+            .and_then(Value::as_str)
+            .ok_or((INVALID_PARAMS, "resumeConversation needs a path".to_string()))?;
+        let id = match self.conversations.iter().find(|c| c.path == path) {
+            Some(c) => c.id.clone(),
+            None => Path::new(path)
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.strip_prefix("rollout-"))
+                .ok_or((INVALID_PARAMS, format!("not a rollout path: {path}")))?
+                .to_string(),
+        };
+        let model = params
+            .get("overrides")
+            .and_then(|o| o.get("model"))
+            .and_then(Value::as_str)
+            .unwrap_or(MODEL);
+        Ok(json!({
+            "conversationId": id,
+            "model": model,
+            "rolloutPath": path,
+            "initialMessages": [],
+        }))
+    }
+
+    fn archive_conversation(&mut self, params: &Value) -> Reply {
+        let cid = conversation_id(params)
+            .ok_or((INVALID_PARAMS, "archiveConversation needs a conversationId".to_string()))?;
+        let before = self.conversations.len();
+        self.conversations.retain(|c| c.id != cid);
+        if self.conversations.len() == before {
+            return Err((INVALID_PARAMS, format!("unknown conversation: {cid}")));
+        }
+        Ok(json!({ "ok": true }))
+    }
+
+    /// Start a turn that waits on an `execCommandApproval`; `handle_response` finishes it.
+    fn request_approval(&mut self, turn_id: Value, params: &Value) -> io::Result<()> {
+        let cid = conversation_id(params);
+        self.event(cid.as_deref(), json!({ "type": "task_started" }))?;
+        let approval_id = self.next_request_id;
+        self.next_request_id += 1;
+        self.deferred.insert(
+            approval_id,
+            DeferredTurn {
+                request_id: turn_id,
+                conversation_id: cid.clone(),
+                text: turn_text(params),
+            },
+        );
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "id": approval_id,
+            "method": "execCommandApproval",
+            "params": {
+                "conversationId": cid,
+                "callId": format!("call-{approval_id}"),
+                "command": ["echo", "approved"],
+                "cwd": params.get("cwd").cloned().unwrap_or_else(|| json!("/tmp")),
+                "reason": "stub approval request",
+            },
+        }))
+    }
+
+    fn play_turn(&mut self, cid: Option<String>, text: &str) -> io::Result<()> {
+        let cid = cid.as_deref();
+        let reply = format!("stub reply: {text}");
+        self.event(cid, json!({ "type": "task_started" }))?;
+        self.event(cid, json!({ "type": "agent_message_delta", "delta": reply }))?;
+        self.event(cid, json!({ "type": "agent_message", "message": reply }))?;
+        self.event(cid, json!({ "type": "task_complete", "last_agent_message": reply }))
+    }
+
+    fn event(&mut self, cid: Option<&str>, msg: Value) -> io::Result<()> {
+        self.send(json!({
+            "jsonrpc": "2.0",
+            "method": "codex/event",
+            "params": { "conversationId": cid, "msg": msg },
+        }))
+    }
+
+    fn reply(&mut self, id: Value, reply: Reply) -> io::Result<()> {
+        match reply {
+            Ok(result) => self.send(json!({ "jsonrpc": "2.0", "id": id, "result": result })),
+            Err((code, message)) => self.send(json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            })),
+        }
+    }
+
+    fn send(&mut self, msg: Value) -> io::Result<()> {
+        writeln!(self.out, "{msg}")?;
+        self.out.flush()
+    }
+}
+
+fn conversation_id(params: &Value) -> Option<String> {
+    params
+        .get("conversationId")
+        .or_else(|| params.get("conversation_id"))
+        .and_then(Value::as_str)
+        .map(str::to_string)
+}
+
+/// Text of the first text item, or of a bare `value` (the orchestrator wraps strings so).
+fn turn_text(params: &Value) -> String {
+    params
+        .get("items")
+        .and_then(Value::as_array)
+        .and_then(|items| {
+            items
+                .iter()
+                .find_map(|item| item.get("data")?.get("text")?.as_str())
+        })
+        .or_else(|| params.get("value").and_then(Value::as_str))
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// Current UTC time as `YYYY-MM-DDTHH:MM:SSZ`.
+fn rfc3339_now() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or_default();
+    let (days, rem) = (secs.div_euclid(86_400), secs.rem_euclid(86_400));
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut stub = Stub::new(io::stdout().lock());
+    for line in stdin.lock().lines() {
+        stub.handle_line(&line?)?;
+    }
+    Ok(())
+}
@@ -1,13 +1,17 @@
 use std::{
-    collections::HashMap,
-    path::PathBuf,
-    sync::Arc,
-    time::{SystemTime, UNIX_EPOCH},
+    collections::{HashMap, HashSet, VecDeque},
+    fmt,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use anyhow::{anyhow, Result};
 use rmcp::model::{
-    InitializeRequestParam, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest,
+    InitializeRequestParam, JsonRpcError, JsonRpcMessage, JsonRpcNotification, JsonRpcRequest,
     JsonRpcResponse, JsonRpcVersion2_0, Notification, Request, RequestId,
 };
 use rmcp::transport::async_rw::JsonRpcMessageCodec;
@@ -15,8 +19,9 @@ use tokio_util::codec::{FramedRead, FramedWrite};
 use futures_util::{sink::SinkExt, stream::StreamExt};
 use serde_json::{json, Value};
 use tokio::{
+    io::{AsyncBufReadExt, BufReader},
     process::Command,
-    sync::{Mutex, RwLock, oneshot},
+    sync::{Mutex, Notify, OnceCell, RwLock, mpsc, oneshot},
 };
 
 use crate::mcp;
@@ -25,19 +30,800 @@ use crate::mcp;
 #[derive(Default, Clone)]
 pub struct Manager {
     agents: Arc<RwLock<HashMap<String, Arc<Agent>>>>,
-    approvals: Arc<Mutex<HashMap<String, oneshot::Sender<String>>>>,
+    approvals: Arc<Mutex<HashMap<String, PendingApproval>>>,
+    /// Woken whenever an approval request is registered, for `wait_and_decide_approval`.
+    approval_added: Arc<Notify>,
+    /// Spawns past the `CODEX_MAX_AGENTS` check but not yet registered, so concurrent spawns
+    /// cannot overshoot the limit.
+    starting: Arc<AtomicUsize>,
+}
+
+/// Holds one `Manager::starting` slot for the duration of a spawn.
+struct StartSlot(Arc<AtomicUsize>);
+
+impl Drop for StartSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// An approval request from Codex awaiting a decision, keyed by "agentId:requestId".
+#[derive(Debug)]
+struct PendingApproval {
+    method: String,
+    params: Value,
+    received: Instant,
+    decide: oneshot::Sender<String>,
 }
 
 #[derive(Debug)]
 struct Agent {
     id: String,
-    #[allow(dead_code)]
     cwd: Option<PathBuf>,
+    /// OS process id of the agent, captured at spawn.
+    pid: Option<u32>,
+    /// Codex binary the agent was started from.
+    bin: String,
     child: Mutex<tokio::process::Child>,
     reader: Arc<Mutex<FramedRead<tokio::process::ChildStdout, JsonRpcMessageCodec<RawMsg>>>>,
     writer: Arc<Mutex<FramedWrite<tokio::process::ChildStdin, JsonRpcMessageCodec<RawMsg>>>>,
     pending: Arc<Mutex<HashMap<i64, oneshot::Sender<Result<Value, Value>>>>>,
     last_conversation_id: Mutex<Option<String>>, 
+    /// `rolloutPath` by conversation id, as reported by new/resume conversation responses.
+    rollout_paths: Mutex<HashMap<String, String>>,
+    /// Latest `agent_message` per conversation, as `{ text, requestId, receivedAtMs }`.
+    last_responses: Mutex<HashMap<String, Value>>,
+    /// When the last RPC to this agent started or finished, or an event was last forwarded
+    /// from it; drives idle reaping.
+    last_activity: Mutex<Instant>,
+    /// Bumped with every `last_activity` update, so the reaper can tell whether anything
+    /// happened between its idle check and the removal without awaiting under the registry lock.
+    activity: AtomicU64,
+    /// Caller-supplied labels from `spawn_agent`, fixed for the agent's lifetime.
+    labels: AgentLabels,
+    /// Turn state by conversation id, from Codex events and our own turn/interrupt requests.
+    run_states: Mutex<HashMap<String, ConversationState>>,
+    /// Model ids from Codex's model listing, fetched on the first validated turn; `None` when
+    /// this Codex build does not expose one.
+    models: OnceCell<Option<Vec<String>>>,
+    /// Result of the MCP `initialize` handshake (`protocolVersion`, `capabilities`, `serverInfo`).
+    init_result: OnceCell<Value>,
+    /// Methods this Codex build answered with "method not found"; later calls fail fast.
+    unsupported_methods: Mutex<HashSet<String>>,
+    /// Last `seq` stamped on an event forwarded for this agent.
+    event_seq: AtomicU64,
+    /// Id of the latest sendUserMessage/sendUserTurn request per conversation; events for the
+    /// conversation carry it as `requestId`.
+    turn_requests: Mutex<HashMap<String, i64>>,
+    /// Conversations with a streamed `send_user_turn` waiting on them; the read loop copies
+    /// their forwarded events here.
+    turn_streams: Mutex<HashMap<String, mpsc::UnboundedSender<Value>>>,
+    /// Recently forwarded events, indexed by conversation, for `recent_events`/`wait_for_event`.
+    events: Mutex<EventBuffer>,
+    /// Woken whenever an event is added to `events`.
+    event_added: Notify,
+    /// Set by `stop_agent_graceful`: new RPCs are rejected while in-flight work finishes.
+    draining: AtomicBool,
+}
+
+impl Agent {
+    /// Record activity: resets the idle clock and bumps the `activity` counter.
+    async fn touch(&self) {
+        *self.last_activity.lock().await = Instant::now();
+        self.activity.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// Events kept per conversation (and in the shared bucket) by an agent's event buffer.
+const EVENT_BUFFER_CAPACITY: usize = 500;
+
+/// Forwarded events of one agent: a bucket per `conversationId`, plus a shared bucket for
+/// events without one (approval requests, agent-wide notifications). Each bucket keeps the
+/// latest `EVENT_BUFFER_CAPACITY` events in `seq` order.
+#[derive(Debug, Default)]
+struct EventBuffer {
+    by_conversation: HashMap<String, VecDeque<Value>>,
+    shared: VecDeque<Value>,
+}
+
+impl EventBuffer {
+    fn push(&mut self, event: Value) {
+        let bucket = match buffered_conversation_id(&event) {
+            Some(cid) => self.by_conversation.entry(cid).or_default(),
+            None => &mut self.shared,
+        };
+        if bucket.len() == EVENT_BUFFER_CAPACITY {
+            bucket.pop_front();
+        }
+        bucket.push_back(event);
+    }
+
+    /// Events of one conversation (`Some`) or of every bucket merged by `seq` (`None`), after
+    /// `after_seq`, oldest first.
+    fn select(&self, conversation_id: Option<&str>, after_seq: u64) -> Vec<&Value> {
+        let newer = |e: &&Value| event_seq_of(e) > after_seq;
+        match conversation_id {
+            Some(cid) => self
+                .by_conversation
+                .get(cid)
+                .map(|bucket| bucket.iter().filter(newer).collect())
+                .unwrap_or_default(),
+            None => {
+                let mut all: Vec<&Value> = self
+                    .by_conversation
+                    .values()
+                    .flatten()
+                    .chain(&self.shared)
+                    .filter(newer)
+                    .collect();
+                all.sort_by_key(|e| event_seq_of(e));
+                all
+            }
+        }
+    }
+}
+
+fn event_seq_of(event: &Value) -> u64 {
+    event.get("seq").and_then(|s| s.as_u64()).unwrap_or(0)
+}
+
+/// Conversation an emitted event belongs to: its `conversationId`, else one in its `params`.
+fn buffered_conversation_id(event: &Value) -> Option<String> {
+    event
+        .get("conversationId")
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+        .or_else(|| event.get("params").and_then(conversation_id_of))
+}
+
+/// Type of an emitted event for `wait_for_event`: the Codex event type of a notification, or
+/// the `kind` of orchestrator events such as `approval_request`.
+fn buffered_event_type(event: &Value) -> Option<&str> {
+    match (event.get("method").and_then(|m| m.as_str()), event.get("params")) {
+        (Some(method), Some(params)) => codex_event_type(method, params),
+        _ => event.get("kind").and_then(|k| k.as_str()),
+    }
+}
+
+/// Whether a conversation has a turn in progress, as last observed by the orchestrator.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunState {
+    Idle,
+    Running,
+    /// No turn was sent and no task event seen for this conversation.
+    Unknown,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ConversationState {
+    #[serde(rename = "conversationId")]
+    pub conversation_id: String,
+    pub state: RunState,
+    /// What set `state`: a Codex event type (`task_started`, `task_complete`, ...) or the
+    /// request that implied it (`sendUserTurn`, `interruptConversation`, ...).
+    #[serde(rename = "lastEvent", skip_serializing_if = "Option::is_none")]
+    pub last_event: Option<String>,
+    #[serde(rename = "updatedAtMs", skip_serializing_if = "Option::is_none")]
+    pub updated_at_ms: Option<u64>,
+}
+
+impl ConversationState {
+    fn unknown(conversation_id: String) -> Self {
+        Self {
+            conversation_id,
+            state: RunState::Unknown,
+            last_event: None,
+            updated_at_ms: None,
+        }
+    }
+}
+
+/// Result of `Manager::interrupt_with_state`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct InterruptOutcome {
+    #[serde(rename = "conversationId", skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<String>,
+    /// Run state just before the interrupt; `idle` means there was likely nothing to stop.
+    #[serde(rename = "priorState")]
+    pub prior_state: RunState,
+    /// The agent's `interruptConversation` response.
+    pub response: Value,
+}
+
+/// Run state implied by a Codex event type; `None` for events that do not start or end a turn.
+fn run_state_after(event: &str) -> Option<RunState> {
+    match event {
+        "task_started" => Some(RunState::Running),
+        "task_complete" | "turn_aborted" | "error" | "shutdown_complete" => Some(RunState::Idle),
+        _ => None,
+    }
+}
+
+/// Event type of a Codex notification: `params.msg.type` for `codex/event`, or the suffix of a
+/// `codex/event/<type>` method.
+fn codex_event_type<'a>(method: &'a str, params: &'a Value) -> Option<&'a str> {
+    params
+        .get("msg")
+        .and_then(|m| m.get("type"))
+        .and_then(|t| t.as_str())
+        .or_else(|| method.strip_prefix("codex/event/"))
+}
+
+/// Text of the last assistant message in a rollout: an `agent_message` event or an assistant
+/// `message` item (`output_text` parts joined), whether wrapped in `payload` or not.
+fn last_assistant_message(rollout: &str) -> Option<String> {
+    rollout.lines().rev().find_map(|line| {
+        let entry: Value = serde_json::from_str(line).ok()?;
+        let item = entry.get("payload").unwrap_or(&entry);
+        match item.get("type").and_then(|t| t.as_str())? {
+            "agent_message" => item.get("message")?.as_str().map(str::to_string),
+            "message" if item.get("role").and_then(|r| r.as_str()) == Some("assistant") => {
+                let text: Vec<&str> = item
+                    .get("content")?
+                    .as_array()?
+                    .iter()
+                    .filter_map(|part| part.get("text").and_then(|t| t.as_str()))
+                    .collect();
+                (!text.is_empty()).then(|| text.concat())
+            }
+            _ => None,
+        }
+    })
+}
+
+/// A rollout line that is not valid JSON, by 1-based line number.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SkippedLine {
+    pub line: usize,
+    pub error: String,
+}
+
+/// Events parsed from a rollout file, with the lines that could not be parsed.
+#[derive(Debug, Clone, Default)]
+pub struct RolloutEvents {
+    pub events: Vec<Value>,
+    pub skipped: Vec<SkippedLine>,
+    /// The file ends in an unterminated line that does not parse yet, as when Codex is still
+    /// writing it. It is neither returned nor counted as skipped.
+    pub partial_line: bool,
+}
+
+/// Parse rollout JSONL, keeping track of corrupt lines instead of dropping them silently.
+/// Blank lines are ignored.
+pub fn parse_rollout(content: &str) -> RolloutEvents {
+    let mut parsed = RolloutEvents::default();
+    let terminated = content.ends_with('\n');
+    let line_count = content.lines().count();
+    for (idx, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(event) => parsed.events.push(event),
+            Err(_) if idx + 1 == line_count && !terminated => parsed.partial_line = true,
+            Err(e) => parsed.skipped.push(SkippedLine {
+                line: idx + 1,
+                error: e.to_string(),
+            }),
+        }
+    }
+    parsed
+}
+
+fn conversation_id_of(value: &Value) -> Option<String> {
+    value
+        .get("conversationId")
+        .or_else(|| value.get("conversation_id"))
+        .or_else(|| value.get("_meta").and_then(|m| m.get("conversationId")))
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+}
+
+/// Free-form tags and metadata attached to an agent at spawn time so clients can organize a
+/// fleet (by repo, task type, ...) without a side table.
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
+pub struct AgentLabels {
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub metadata: serde_json::Map<String, Value>,
+}
+
+impl AgentLabels {
+    /// True when every tag in `tags` is present and every `metadata` entry matches exactly.
+    pub fn matches(&self, tags: &[String], metadata: &serde_json::Map<String, Value>) -> bool {
+        tags.iter().all(|t| self.tags.contains(t))
+            && metadata.iter().all(|(k, v)| self.metadata.get(k) == Some(v))
+    }
+}
+
+/// Listing entry for a running agent.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize, rmcp::schemars::JsonSchema)]
+pub struct AgentInfo {
+    #[serde(rename = "agentId")]
+    pub agent_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cwd: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pid: Option<u32>,
+    /// Codex binary the agent was started from.
+    pub bin: String,
+    pub tags: Vec<String>,
+    pub metadata: serde_json::Map<String, Value>,
+}
+
+/// Seconds an agent may go without RPCs before the idle reaper kills it; unset or `0` disables
+/// reaping.
+const IDLE_SECS_ENV: &str = "CODEX_AGENT_IDLE_SECS";
+
+/// Canonical approval decision for `decision`: `allow` or `deny`, ignoring case and surrounding
+/// whitespace. Codex reads any other value as a denial, so unknown values are rejected.
+pub fn normalize_decision(decision: &str) -> Option<&'static str> {
+    let decision = decision.trim();
+    if decision.eq_ignore_ascii_case("allow") {
+        Some("allow")
+    } else if decision.eq_ignore_ascii_case("deny") {
+        Some("deny")
+    } else {
+        None
+    }
+}
+
+/// Most agents that may run at once; unset or `0` means no limit.
+const MAX_AGENTS_ENV: &str = "CODEX_MAX_AGENTS";
+
+/// Agent limit configured via `CODEX_MAX_AGENTS`, if any.
+pub fn max_agents_from_env() -> Option<usize> {
+    let max = std::env::var(MAX_AGENTS_ENV).ok()?.trim().parse::<usize>().ok()?;
+    (max > 0).then_some(max)
+}
+
+/// Idle threshold configured via `CODEX_AGENT_IDLE_SECS`, if reaping is enabled.
+pub fn idle_timeout_from_env() -> Option<Duration> {
+    let secs = std::env::var(IDLE_SECS_ENV).ok()?.trim().parse::<u64>().ok()?;
+    (secs > 0).then(|| Duration::from_secs(secs))
+}
+
+/// Number of trailing stderr lines kept from a starting agent for init-failure reports.
+const STDERR_TAIL_LINES: usize = 50;
+
+/// Why an agent failed to start. Carried inside the `anyhow::Error` returned by
+/// `spawn_agent` so the MCP layer can surface a machine-readable `kind`.
+#[derive(Debug)]
+pub enum SpawnError {
+    /// Neither `CODEX_BIN` nor `codex` on `PATH` resolved to a binary.
+    BinaryNotFound,
+    /// A per-agent `bin` is missing, not a file, or not executable.
+    InvalidBinary { bin: String, details: String },
+    /// The binary was resolved but the OS refused to start it.
+    SpawnFailed { bin: String, details: String },
+    /// `CODEX_MAX_AGENTS` agents are already running (or starting).
+    LimitReached { limit: usize, live: usize },
+    /// The process started but the MCP initialize handshake failed (usually a crash). `hint`
+    /// is set when the captured stderr matches a known misconfiguration.
+    InitFailed {
+        bin: String,
+        details: String,
+        stderr: String,
+        hint: Option<&'static str>,
+    },
+}
+
+/// Known init-failure signatures, matched case-insensitively against the captured stderr and
+/// the handshake error. The first entry with a matching needle supplies the hint.
+const INIT_FAILURE_HINTS: &[(&[&str], &str)] = &[
+    (
+        &["openai_api_key", "missing api key", "no api key", "api key not set", "api key is not set"],
+        "No API key is configured. Run `codex login` or set OPENAI_API_KEY for the orchestrator.",
+    ),
+    (
+        &[
+            "unauthorized",
+            "not logged in",
+            "authentication",
+            "invalid_api_key",
+            "incorrect api key",
+            "token expired",
+            "refresh token",
+        ],
+        "Codex could not authenticate. Run `codex login` (or refresh OPENAI_API_KEY) and retry.",
+    ),
+    (
+        &[
+            "unexpected argument",
+            "unrecognized subcommand",
+            "unrecognized option",
+            "unknown option",
+            "unknown flag",
+            "invalid value for",
+        ],
+        "This Codex build rejected the `codex mcp` command line. Upgrade Codex, or point CODEX_BIN or `bin` at a newer binary.",
+    ),
+];
+
+/// Hint for the first known misconfiguration signature found in an init failure, if any.
+pub fn init_failure_hint(stderr: &str, details: &str) -> Option<&'static str> {
+    let haystack = format!("{stderr}\n{details}").to_lowercase();
+    INIT_FAILURE_HINTS
+        .iter()
+        .find(|(needles, _)| needles.iter().any(|n| haystack.contains(n)))
+        .map(|(_, hint)| *hint)
+}
+
+impl SpawnError {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SpawnError::BinaryNotFound => "binary_not_found",
+            SpawnError::InvalidBinary { .. } => "invalid_binary",
+            SpawnError::SpawnFailed { .. } => "spawn_failed",
+            SpawnError::LimitReached { .. } => "agent_limit_reached",
+            SpawnError::InitFailed { .. } => "init_failed",
+        }
+    }
+
+    /// Structured payload for `McpError::data`.
+    pub fn data(&self) -> Value {
+        match self {
+            SpawnError::BinaryNotFound => json!({
+                "kind": self.kind(),
+                "hint": "Set CODEX_BIN or add 'codex' to PATH.",
+            }),
+            SpawnError::InvalidBinary { bin, details } => json!({
+                "kind": self.kind(),
+                "bin": bin,
+                "details": details,
+            }),
+            SpawnError::SpawnFailed { bin, details } => json!({
+                "kind": self.kind(),
+                "bin": bin,
+                "details": details,
+            }),
+            SpawnError::LimitReached { limit, live } => json!({
+                "kind": self.kind(),
+                "limit": limit,
+                "live": live,
+                "hint": "Kill agents that are no longer needed (kill_agent, kill_all_agents) or raise CODEX_MAX_AGENTS.",
+            }),
+            SpawnError::InitFailed { bin, details, stderr, hint } => json!({
+                "kind": self.kind(),
+                "bin": bin,
+                "details": details,
+                "stderr": stderr,
+                "hint": hint,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpawnError::BinaryNotFound => {
+                write!(f, "Unable to locate Codex binary. Set CODEX_BIN or add 'codex' to PATH.")
+            }
+            SpawnError::InvalidBinary { bin, details } => {
+                write!(f, "invalid Codex binary ({bin}): {details}")
+            }
+            SpawnError::SpawnFailed { bin, details } => {
+                write!(f, "spawn codex failed ({bin}): {details}")
+            }
+            SpawnError::LimitReached { limit, live } => {
+                write!(f, "agent limit reached: {live} of {limit} agents running (CODEX_MAX_AGENTS)")
+            }
+            SpawnError::InitFailed { bin, details, hint, .. } => {
+                write!(f, "codex ({bin}) failed during init: {details}")?;
+                match hint {
+                    Some(hint) => write!(f, ". {hint}"),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+impl std::error::Error for SpawnError {}
+
+/// Check a per-agent Codex binary before spawning it: a bare name is looked up on `PATH`, and
+/// anything else must be an existing executable file.
+fn validate_codex_bin(bin: &str) -> Result<String, SpawnError> {
+    let invalid = |details: String| SpawnError::InvalidBinary {
+        bin: bin.to_string(),
+        details,
+    };
+    let path = Path::new(bin);
+    if bin.trim().is_empty() {
+        return Err(invalid("empty path".into()));
+    }
+    if path.components().count() == 1 && !path.is_absolute() {
+        return which::which(bin)
+            .map(|p| p.to_string_lossy().into_owned())
+            .map_err(|e| invalid(e.to_string()));
+    }
+    let meta = std::fs::metadata(path).map_err(|e| invalid(e.to_string()))?;
+    if !meta.is_file() {
+        return Err(invalid("not a file".into()));
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if meta.permissions().mode() & 0o111 == 0 {
+            return Err(invalid("not executable".into()));
+        }
+    }
+    Ok(bin.to_string())
+}
+
+const VALIDATE_MODEL_ENV: &str = "CODEX_VALIDATE_MODEL";
+
+/// Codex method listing the models a turn may request.
+const MODEL_LIST_METHOD: &str = "model/list";
+
+/// How long to wait for the model listing before skipping validation.
+const MODEL_LIST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether `CODEX_VALIDATE_MODEL` asks for the `CODEX_TURN_MODEL` default to be checked
+/// against the agent's model listing before it is sent.
+pub fn validate_model_from_env() -> bool {
+    std::env::var(VALIDATE_MODEL_ENV)
+        .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+/// Model ids from a model listing response: `{ data: [...] }`, `{ models: [...] }`, or a bare
+/// array, whose entries are ids or objects carrying `id`, `model`, or `slug`.
+fn model_ids(listing: &Value) -> Vec<String> {
+    let entries = listing
+        .get("data")
+        .or_else(|| listing.get("models"))
+        .or_else(|| listing.get("items"))
+        .unwrap_or(listing);
+    let Some(entries) = entries.as_array() else { return Vec::new() };
+    entries
+        .iter()
+        .filter_map(|entry| match entry {
+            Value::String(id) => Some(id.clone()),
+            Value::Object(map) => ["id", "model", "slug"]
+                .iter()
+                .find_map(|key| map.get(*key).and_then(|v| v.as_str()))
+                .map(str::to_string),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The configured `CODEX_TURN_MODEL` is not among the models the agent reports.
+#[derive(Debug)]
+pub struct ModelSetupError {
+    pub model: String,
+    pub available: Vec<String>,
+}
+
+impl ModelSetupError {
+    /// Structured payload for `McpError::data`.
+    pub fn data(&self) -> Value {
+        json!({
+            "kind": "unknown_model",
+            "model": self.model,
+            "available": self.available,
+            "hint": "Set CODEX_TURN_MODEL to one of the available models, or unset it to use Codex's configured model.",
+        })
+    }
+}
+
+impl fmt::Display for ModelSetupError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "CODEX_TURN_MODEL '{}' is not a model this Codex agent offers (available: {})",
+            self.model,
+            self.available.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for ModelSetupError {}
+
+/// The agent is being stopped by `stop_agent_graceful` and takes no new requests.
+#[derive(Debug)]
+pub struct AgentDrainingError {
+    pub agent_id: String,
+}
+
+impl AgentDrainingError {
+    /// Structured payload for `McpError::data`.
+    pub fn data(&self) -> Value {
+        json!({
+            "kind": "agent_draining",
+            "agentId": self.agent_id,
+            "hint": "The agent stops once its in-flight requests and turns finish; spawn a new agent to continue.",
+        })
+    }
+}
+
+impl fmt::Display for AgentDrainingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "agent {} is stopping and accepts no new requests", self.agent_id)
+    }
+}
+
+impl std::error::Error for AgentDrainingError {}
+
+/// How often `stop_agent_graceful` re-checks whether an agent's work has finished.
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Result of `Manager::stop_agent_graceful`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct GracefulStopOutcome {
+    #[serde(rename = "agentId")]
+    pub agent_id: String,
+    /// Whether every in-flight request and running turn finished before the agent was killed.
+    pub drained: bool,
+    #[serde(rename = "timedOut")]
+    pub timed_out: bool,
+    #[serde(rename = "waitedMs")]
+    pub waited_ms: u64,
+    /// Requests still awaiting a response at the kill; their callers get "agent killed".
+    #[serde(rename = "abandonedRequests")]
+    pub abandoned_requests: usize,
+    /// Conversations still `running` at the kill.
+    #[serde(rename = "runningConversations")]
+    pub running_conversations: Vec<String>,
+}
+
+/// JSON-RPC "method not found", as Codex answers methods its build does not implement.
+const METHOD_NOT_FOUND: i64 = -32601;
+
+/// The agent's Codex build does not implement a method the orchestrator forwarded.
+#[derive(Debug)]
+pub struct UnsupportedMethodError {
+    pub agent_id: String,
+    pub method: String,
+}
+
+impl UnsupportedMethodError {
+    /// Structured payload for `McpError::data`.
+    pub fn data(&self) -> Value {
+        json!({
+            "kind": "unsupported_method",
+            "agentId": self.agent_id,
+            "method": self.method,
+            "hint": "Upgrade the Codex binary (CODEX_BIN) or avoid this tool for this agent; get_agent_capabilities lists what the agent reported.",
+        })
+    }
+}
+
+impl fmt::Display for UnsupportedMethodError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "this Codex build does not support {} (agent {})",
+            self.method, self.agent_id
+        )
+    }
+}
+
+impl std::error::Error for UnsupportedMethodError {}
+
+/// Move `images` and `files` from message params into `items`, after any existing items.
+/// Images become `image` items (for `data:`/`http(s)` URLs and `{ data, mimeType? }` base64
+/// objects) or `localImage` items (for paths); files become text items naming the path, which
+/// the agent reads itself. Relative paths resolve against `base_dir` and must exist.
+pub fn attach_inputs(params: &mut serde_json::Map<String, Value>, base_dir: &Path) -> Result<()> {
+    let images = params.remove("images");
+    let files = params.remove("files");
+    if images.is_none() && files.is_none() {
+        return Ok(());
+    }
+    let as_list = |field: &str, value: Option<Value>| -> Result<Vec<Value>> {
+        match value {
+            None | Some(Value::Null) => Ok(Vec::new()),
+            Some(Value::Array(list)) => Ok(list),
+            Some(single @ (Value::String(_) | Value::Object(_))) => Ok(vec![single]),
+            Some(_) => Err(anyhow!("{field} must be a string, an object, or an array of them")),
+        }
+    };
+    let existing_file = |field: &str, path: &str| -> Result<String> {
+        let path = base_dir.join(path);
+        if !path.is_file() {
+            return Err(anyhow!("{field}: no such file: {}", path.display()));
+        }
+        Ok(path.to_string_lossy().into_owned())
+    };
+
+    let mut attached = Vec::new();
+    for image in as_list("images", images)? {
+        let str_field = |key: &str| image.get(key).and_then(|v| v.as_str());
+        let item = if let Some(s) = image.as_str() {
+            if s.starts_with("data:") || s.starts_with("http://") || s.starts_with("https://") {
+                json!({"type": "image", "data": {"image_url": s}})
+            } else {
+                json!({"type": "localImage", "data": {"path": existing_file("images", s)?}})
+            }
+        } else if let Some(path) = str_field("path") {
+            json!({"type": "localImage", "data": {"path": existing_file("images", path)?}})
+        } else if let Some(url) = str_field("url") {
+            json!({"type": "image", "data": {"image_url": url}})
+        } else if let Some(data) = str_field("data") {
+            let mime = str_field("mimeType").unwrap_or("image/png");
+            json!({"type": "image", "data": {"image_url": format!("data:{mime};base64,{data}")}})
+        } else {
+            return Err(anyhow!("images entries need a path, url, or base64 data: {image}"));
+        };
+        attached.push(item);
+    }
+    for file in as_list("files", files)? {
+        let path = file
+            .as_str()
+            .or_else(|| file.get("path").and_then(|v| v.as_str()))
+            .ok_or_else(|| anyhow!("files entries must be paths or {{ path }} objects: {file}"))?;
+        let path = existing_file("files", path)?;
+        attached.push(json!({"type": "text", "data": {"text": format!("Attached file: {path}")}}));
+    }
+
+    match params.get_mut("items") {
+        Some(Value::Array(items)) => items.extend(attached),
+        _ => {
+            params.insert("items".to_string(), Value::Array(attached));
+        }
+    }
+    Ok(())
+}
+
+/// Fill the fields `sendUserTurn` requires when the caller omitted them. `model` is only
+/// injected when `CODEX_TURN_MODEL` is set; otherwise Codex uses its configured model.
+pub fn apply_turn_defaults(params: &mut Value) {
+    let Value::Object(map) = params else { return };
+    if !map.contains_key("cwd") {
+        map.insert("cwd".to_string(), json!(std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/tmp"))));
+    }
+    if !map.contains_key("approvalPolicy") {
+        map.insert("approvalPolicy".to_string(), json!("never"));
+    }
+    if !map.contains_key("sandboxPolicy") {
+        map.insert("sandboxPolicy".to_string(), json!({"mode": "read-only"}));
+    }
+    if !map.contains_key("model") {
+        if let Some(model) = std::env::var("CODEX_TURN_MODEL").ok().filter(|s| !s.is_empty()) {
+            map.insert("model".to_string(), json!(model));
+        }
+    }
+    if !map.contains_key("summary") {
+        map.insert("summary".to_string(), json!("auto"));
+    }
+}
+
+/// Fill turn defaults, then merge the caller's `overrides` object over the result so a single
+/// turn can change e.g. `approvalPolicy` without touching the conversation. Keys are replaced
+/// whole; `overrides` itself is removed and never sent to Codex.
+pub fn apply_turn_overrides(params: &mut Value) -> Result<()> {
+    let overrides = match params.as_object_mut().and_then(|map| map.remove("overrides")) {
+        None | Some(Value::Null) => serde_json::Map::new(),
+        Some(Value::Object(overrides)) => overrides,
+        Some(other) => return Err(anyhow!("params.overrides must be an object, got {other}")),
+    };
+    apply_turn_defaults(params);
+    if let Value::Object(map) = params {
+        map.extend(overrides);
+    }
+    Ok(())
+}
+
+/// Add the orchestrator's `requestId` for a turn to its response, matching the `requestId`
+/// on the events the turn produces. Non-object responses are returned unchanged.
+fn with_request_id(mut value: Value, request_id: i64) -> Value {
+    if let Value::Object(map) = &mut value {
+        map.entry("requestId").or_insert(json!(request_id));
+    }
+    value
+}
+
+/// Receiving end of a streamed turn, registered in `Agent::turn_streams`.
+struct TurnStream {
+    agent: Arc<Agent>,
+    conversation_id: String,
+    request_id: i64,
+    events: mpsc::UnboundedReceiver<Value>,
 }
 
 type RawReq = Request<String, Value>;
@@ -46,36 +832,128 @@ type RawMsg = JsonRpcMessage<RawReq, Value, RawNot>;
 
 impl Manager {
     pub async fn spawn_agent(&self, id: Option<String>, cwd: Option<PathBuf>) -> Result<String> {
+        self.spawn_labeled_agent(id, cwd, AgentLabels::default()).await
+    }
+
+    /// `spawn_agent` with tags and metadata recorded on the agent for `agent_infos`.
+    pub async fn spawn_labeled_agent(
+        &self,
+        id: Option<String>,
+        cwd: Option<PathBuf>,
+        labels: AgentLabels,
+    ) -> Result<String> {
+        self.spawn_agent_with_bin(id, cwd, labels, None).await
+    }
+
+    /// `spawn_labeled_agent` starting `bin` instead of the global `CODEX_BIN`/`PATH` binary, so
+    /// agents from different Codex builds can run side by side. `bin` is validated first.
+    pub async fn spawn_agent_with_bin(
+        &self,
+        id: Option<String>,
+        cwd: Option<PathBuf>,
+        labels: AgentLabels,
+        bin: Option<String>,
+    ) -> Result<String> {
+        let (agent_id, _) = self.start_agent(id, cwd, labels, bin, false).await?;
+        Ok(agent_id)
+    }
+
+    /// `spawn_agent_with_bin` for an id that may already be running. The new agent is started
+    /// and swapped into the registry before the old one is killed, so a spawn that fails (bad
+    /// `bin`, agent limit, failed handshake) leaves the running agent untouched. Returns the
+    /// agent id and whether a running agent was replaced.
+    pub async fn replace_agent_with_bin(
+        &self,
+        id: Option<String>,
+        cwd: Option<PathBuf>,
+        labels: AgentLabels,
+        bin: Option<String>,
+    ) -> Result<(String, bool)> {
+        self.start_agent(id, cwd, labels, bin, true).await
+    }
+
+    /// Start and register an agent. With `replace`, an agent already registered under the id
+    /// gives up its slot in the agent limit and is swapped out and killed once the new one is
+    /// up; otherwise a taken id is refused.
+    async fn start_agent(
+        &self,
+        id: Option<String>,
+        cwd: Option<PathBuf>,
+        labels: AgentLabels,
+        bin: Option<String>,
+        replace: bool,
+    ) -> Result<(String, bool)> {
         let agent_id = match id {
             Some(s) if !s.is_empty() => s,
+            // The counter keeps ids unique when several agents spawn within the same microsecond.
             _ => format!(
-                "agent-{}",
+                "agent-{}-{}",
                 SystemTime::now()
                     .duration_since(UNIX_EPOCH)
                     .unwrap_or_default()
-                    .as_micros()
+                    .as_micros(),
+                Self::next_id()
             ),
         };
 
-        // Resolve binary: env CODEX_BIN, else which("codex")
-        let bin = if let Some(v) = std::env::var("CODEX_BIN").ok().filter(|s| !s.is_empty()) {
+        // Refuse a taken id up front rather than starting a process only to discard it.
+        let existing = self.agents.read().await.get(&agent_id).cloned();
+        if existing.is_some() && !replace {
+            return Err(anyhow!("agent already exists: {agent_id}"));
+        }
+
+        // Resolve binary: per-agent bin, else env CODEX_BIN, else which("codex")
+        let bin = if let Some(b) = bin {
+            validate_codex_bin(&b)?
+        } else if let Some(v) = std::env::var("CODEX_BIN").ok().filter(|s| !s.is_empty()) {
             v
         } else if let Ok(path) = which::which("codex") {
             path.to_string_lossy().into_owned()
         } else {
-            return Err(anyhow!("Unable to locate Codex binary. Set CODEX_BIN or add 'codex' to PATH."));
+            return Err(SpawnError::BinaryNotFound.into());
         };
 
-        let mut cmd = Command::new(bin);
+        // Claim a start slot before counting, so spawns racing past this point see each other.
+        let starting = self.starting.fetch_add(1, Ordering::SeqCst);
+        let slot = StartSlot(self.starting.clone());
+        if let Some(limit) = max_agents_from_env() {
+            // The agent being replaced is counted as live but hands its slot to the newcomer.
+            let outgoing = usize::from(existing.as_deref().is_some_and(Self::process_live));
+            let live = self.live_agent_count().await.saturating_sub(outgoing);
+            if live + starting >= limit {
+                return Err(SpawnError::LimitReached { limit, live: live + starting }.into());
+            }
+        }
+
+        let mut cmd = Command::new(&bin);
         cmd.arg("mcp");
         if let Some(ref c) = cwd {
             cmd.current_dir(c);
         }
         cmd.stdin(std::process::Stdio::piped())
             .stdout(std::process::Stdio::piped())
-            .stderr(std::process::Stdio::inherit());
+            .stderr(std::process::Stdio::piped());
 
-        let mut child = cmd.spawn().map_err(|e| anyhow!("spawn codex failed: {e}"))?;
+        let mut child = cmd.spawn().map_err(|e| SpawnError::SpawnFailed {
+            bin: bin.clone(),
+            details: e.to_string(),
+        })?;
+        // Forward child stderr to ours, keeping a short tail for init-failure reports.
+        let stderr_tail = Arc::new(Mutex::new(VecDeque::<String>::new()));
+        let stderr_task = child.stderr.take().map(|stderr| {
+            let tail = stderr_tail.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    eprintln!("{line}");
+                    let mut tail = tail.lock().await;
+                    if tail.len() == STDERR_TAIL_LINES {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line);
+                }
+            })
+        });
         let stdout = child
             .stdout
             .take()
@@ -93,39 +971,329 @@ impl Manager {
         let agent = Arc::new(Agent {
             id: agent_id.clone(),
             cwd,
+            pid: child.id(),
+            bin: bin.clone(),
             child: Mutex::new(child),
             reader: Arc::new(Mutex::new(reader)),
             writer: Arc::new(Mutex::new(writer)),
             pending: Arc::new(Mutex::new(HashMap::new())),
             last_conversation_id: Mutex::new(None),
+            rollout_paths: Mutex::new(HashMap::new()),
+            last_responses: Mutex::new(HashMap::new()),
+            last_activity: Mutex::new(Instant::now()),
+            activity: AtomicU64::new(0),
+            labels,
+            run_states: Mutex::new(HashMap::new()),
+            models: OnceCell::new(),
+            init_result: OnceCell::new(),
+            unsupported_methods: Mutex::new(HashSet::new()),
+            event_seq: AtomicU64::new(0),
+            turn_requests: Mutex::new(HashMap::new()),
+            turn_streams: Mutex::new(HashMap::new()),
+            events: Mutex::new(EventBuffer::default()),
+            event_added: Notify::new(),
+            draining: AtomicBool::new(false),
         });
 
-        // Initialize MCP handshake
-        self.initialize(&agent).await?;
+        // Initialize MCP handshake. Ordering matters: `initialize` reads the initialize response
+        // straight from `agent.reader`, so the read loop must not start until the handshake has
+        // completed, and the agent is only registered afterwards so no `rpc_call` can race it.
+        if let Err(e) = self.initialize(&agent).await {
+            let _ = agent.child.lock().await.kill().await;
+            if let Some(task) = stderr_task {
+                // The child is gone, so stderr hits EOF shortly; bound the wait anyway.
+                let _ = tokio::time::timeout(Duration::from_millis(500), task).await;
+            }
+            let stderr = stderr_tail.lock().await.iter().cloned().collect::<Vec<_>>().join("\n");
+            let details = format!("{e:#}");
+            let hint = init_failure_hint(&stderr, &details);
+            return Err(SpawnError::InitFailed {
+                bin,
+                details,
+                stderr,
+                hint,
+            }
+            .into());
+        }
+        let replaced = {
+            // Register before starting the read loop; a concurrent spawn may have claimed the id
+            // while this agent was initializing, in which case the newcomer is discarded rather
+            // than silently orphaning the running agent. A replacement swaps in under the same
+            // lock, so the id never goes unregistered.
+            let mut agents = self.agents.write().await;
+            if !replace && agents.contains_key(&agent_id) {
+                drop(agents);
+                let _ = agent.child.lock().await.kill().await;
+                return Err(anyhow!("agent already exists: {agent_id}"));
+            }
+            agents.insert(agent_id.clone(), agent.clone())
+        };
+        drop(slot);
         // Start read loop
-        self.spawn_read_loop(agent.clone());
+        self.spawn_read_loop(agent);
+        if let Some(old) = &replaced {
+            Self::terminate(old).await;
+        }
 
-        self.agents.write().await.insert(agent_id.clone(), agent);
-        Ok(agent_id)
+        Ok((agent_id, replaced.is_some()))
     }
 
+    /// Whether the agent's process has not exited. A process handle that is busy (the agent is
+    /// being killed) counts as live.
+    fn process_live(agent: &Agent) -> bool {
+        match agent.child.try_lock() {
+            Ok(mut child) => matches!(child.try_wait(), Ok(None)),
+            Err(_) => true,
+        }
+    }
+
+    /// Registered agents whose process has not exited. An agent whose process handle is busy
+    /// (it is being killed) still counts.
+    async fn live_agent_count(&self) -> usize {
+        let agents: Vec<Arc<Agent>> = self.agents.read().await.values().cloned().collect();
+        agents.iter().filter(|agent| Self::process_live(agent)).count()
+    }
+
+    /// Ids of running agents; the MCP tools use `agent_infos` to include labels.
+    #[allow(dead_code)]
     pub async fn list_agents(&self) -> Vec<String> {
         self.agents.read().await.keys().cloned().collect()
     }
 
+    /// Running agents with their labels, sorted by id, keeping only those carrying all `tags`
+    /// and matching every `metadata` entry (both empty to list everything).
+    pub async fn agent_infos(
+        &self,
+        tags: &[String],
+        metadata: &serde_json::Map<String, Value>,
+    ) -> Vec<AgentInfo> {
+        let mut infos: Vec<AgentInfo> = self
+            .agents
+            .read()
+            .await
+            .values()
+            .filter(|agent| agent.labels.matches(tags, metadata))
+            .map(|agent| AgentInfo {
+                agent_id: agent.id.clone(),
+                cwd: agent.cwd.as_ref().map(|c| c.to_string_lossy().into_owned()),
+                pid: agent.pid,
+                bin: agent.bin.clone(),
+                tags: agent.labels.tags.clone(),
+                metadata: agent.labels.metadata.clone(),
+            })
+            .collect();
+        infos.sort_by(|a, b| a.agent_id.cmp(&b.agent_id));
+        infos
+    }
+
+    /// Per-agent liveness and queue depth, sorted by id. `alive` is null when the process handle
+    /// is busy (the agent is being killed) and cannot be polled without waiting.
+    pub async fn health(&self) -> Vec<Value> {
+        let agents: Vec<Arc<Agent>> = self.agents.read().await.values().cloned().collect();
+        let approval_keys = self.list_pending_approvals().await;
+        let mut report = Vec::with_capacity(agents.len());
+        for agent in agents {
+            let (alive, exit_code) = match agent.child.try_lock() {
+                Ok(mut child) => match child.try_wait() {
+                    Ok(None) => (Some(true), None),
+                    Ok(Some(status)) => (Some(false), status.code()),
+                    Err(_) => (Some(false), None),
+                },
+                Err(_) => (None, None),
+            };
+            let prefix = format!("{}:", agent.id);
+            let pending_approvals = approval_keys.iter().filter(|k| k.starts_with(&prefix)).count();
+            report.push(json!({
+                "agentId": agent.id,
+                "pid": agent.pid,
+                "alive": alive,
+                "exitCode": exit_code,
+                "pendingRequests": agent.pending.lock().await.len(),
+                "pendingApprovals": pending_approvals,
+                "idleMs": agent.last_activity.lock().await.elapsed().as_millis() as u64,
+                "draining": agent.draining.load(Ordering::SeqCst),
+            }));
+        }
+        report.sort_by(|a, b| a["agentId"].as_str().cmp(&b["agentId"].as_str()));
+        report
+    }
+
     pub async fn kill_agent(&self, agent_id: &str) -> Result<()> {
         let removed = self.agents.write().await.remove(agent_id);
         match removed {
             Some(agent) => {
-                if let Ok(mut child) = agent.child.try_lock() {
-                    let _ = child.kill().await;
-                }
+                Self::terminate(&agent).await;
                 Ok(())
             }
             None => Err(anyhow!("agent not found: {agent_id}")),
         }
     }
 
+    /// Stop an agent without cutting off its work: new RPCs fail with `AgentDrainingError`
+    /// while in-flight RPCs and `running` turns finish, then the agent is killed as by
+    /// `kill_agent`. After `timeout` it is killed regardless, abandoning what is left.
+    pub async fn stop_agent_graceful(
+        &self,
+        agent_id: &str,
+        timeout: Duration,
+    ) -> Result<GracefulStopOutcome> {
+        let agent = self.require_agent(agent_id).await?;
+        if agent.draining.swap(true, Ordering::SeqCst) {
+            return Err(AgentDrainingError { agent_id: agent_id.to_string() }.into());
+        }
+        let started = Instant::now();
+        let deadline = started + timeout;
+        let (abandoned_requests, running_conversations) = loop {
+            let in_flight = agent.pending.lock().await.len();
+            let mut running: Vec<String> = agent
+                .run_states
+                .lock()
+                .await
+                .values()
+                .filter(|s| s.state == RunState::Running)
+                .map(|s| s.conversation_id.clone())
+                .collect();
+            running.sort();
+            let now = Instant::now();
+            if (in_flight == 0 && running.is_empty()) || now >= deadline {
+                break (in_flight, running);
+            }
+            tokio::time::sleep(DRAIN_POLL_INTERVAL.min(deadline - now)).await;
+        };
+        // Only remove the agent we drained; the id may have been killed and respawned.
+        {
+            let mut agents = self.agents.write().await;
+            if agents.get(agent_id).is_some_and(|current| Arc::ptr_eq(current, &agent)) {
+                agents.remove(agent_id);
+            }
+        }
+        Self::terminate(&agent).await;
+        let drained = abandoned_requests == 0 && running_conversations.is_empty();
+        Ok(GracefulStopOutcome {
+            agent_id: agent_id.to_string(),
+            drained,
+            timed_out: !drained,
+            waited_ms: started.elapsed().as_millis() as u64,
+            abandoned_requests,
+            running_conversations,
+        })
+    }
+
+    /// Kill every managed agent and clear the registry. Returns the ids that were killed.
+    pub async fn kill_all_agents(&self) -> Vec<String> {
+        let drained = std::mem::take(&mut *self.agents.write().await);
+        let mut killed = Vec::with_capacity(drained.len());
+        for (id, agent) in drained {
+            Self::terminate(&agent).await;
+            killed.push(id);
+        }
+        killed.sort();
+        killed
+    }
+
+    /// Start the background idle reaper when `CODEX_AGENT_IDLE_SECS` is set. Must be called
+    /// from within a Tokio runtime.
+    pub fn spawn_idle_reaper(&self) -> Option<tokio::task::JoinHandle<()>> {
+        let max_idle = idle_timeout_from_env()?;
+        let period = (max_idle / 4).clamp(Duration::from_secs(1), Duration::from_secs(30));
+        tracing::info!("idle reaper: killing agents idle for more than {}s", max_idle.as_secs());
+        let mgr = self.clone();
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(period);
+            loop {
+                ticker.tick().await;
+                mgr.reap_idle(max_idle).await;
+            }
+        }))
+    }
+
+    /// Kill agents with no RPC or event activity for longer than `max_idle`. Agents with
+    /// in-flight requests, pending approvals, running turns, or streamed turns are never
+    /// reaped. Each reaped agent is reported upstream as a `codex/event` notification of kind
+    /// `agent_reaped`. Returns the reaped ids.
+    pub async fn reap_idle(&self, max_idle: Duration) -> Vec<String> {
+        let candidates: Vec<Arc<Agent>> = self.agents.read().await.values().cloned().collect();
+        let mut reaped = Vec::new();
+        for agent in candidates {
+            let Some((idle, activity)) = self.reapable_idle(&agent, max_idle).await else {
+                continue;
+            };
+            // Only reap the agent we inspected (the id may have been killed and respawned), and
+            // only if nothing touched it since the check: a request that arrived meanwhile keeps
+            // it alive.
+            {
+                let mut agents = self.agents.write().await;
+                match agents.get(&agent.id) {
+                    Some(current) if Arc::ptr_eq(current, &agent) && agent.activity.load(Ordering::SeqCst) == activity => {
+                        agents.remove(&agent.id);
+                    }
+                    _ => continue,
+                }
+            }
+            tracing::info!("idle reaper: killing agent {} after {}s idle", agent.id, idle.as_secs());
+            Self::terminate(&agent).await;
+            let payload = json!({
+                "kind": "agent_reaped",
+                "agentId": agent.id,
+                "idleSecs": idle.as_secs(),
+            });
+            Self::emit_event(&agent, payload).await;
+            reaped.push(agent.id.clone());
+        }
+        reaped.sort();
+        reaped
+    }
+
+    /// How long `agent` has been idle, with its `activity` count as of the check, when that
+    /// exceeds `max_idle` and nothing is in progress on it: no in-flight RPC, pending approval,
+    /// running turn, or streamed turn.
+    async fn reapable_idle(&self, agent: &Agent, max_idle: Duration) -> Option<(Duration, u64)> {
+        let activity = agent.activity.load(Ordering::SeqCst);
+        let idle = agent.last_activity.lock().await.elapsed();
+        if idle <= max_idle
+            || !agent.pending.lock().await.is_empty()
+            || !agent.turn_streams.lock().await.is_empty()
+        {
+            return None;
+        }
+        let running = agent
+            .run_states
+            .lock()
+            .await
+            .values()
+            .any(|s| s.state == RunState::Running);
+        if running {
+            return None;
+        }
+        let prefix = format!("{}:", agent.id);
+        if self.approvals.lock().await.keys().any(|k| k.starts_with(&prefix)) {
+            return None;
+        }
+        Some((idle, activity))
+    }
+
+    /// Kill an agent's process and fail its in-flight RPCs rather than leaving callers to wait
+    /// for the read loop to notice the closed stream.
+    async fn terminate(agent: &Agent) {
+        if let Ok(mut child) = agent.child.try_lock() {
+            let _ = child.kill().await;
+        }
+        Self::fail_pending(agent, json!({"error": "agent killed", "agentId": agent.id})).await;
+    }
+
+    /// Drain and fail any pending RPC waiters, and end streamed turns, so callers don't hang.
+    async fn fail_pending(agent: &Agent, error: Value) {
+        agent.turn_streams.lock().await.clear();
+        let drained: Vec<oneshot::Sender<Result<Value, Value>>> = {
+            let mut guard = agent.pending.lock().await;
+            let mut map = std::mem::take(&mut *guard);
+            map.drain().map(|(_, tx)| tx).collect()
+        };
+        for tx in drained {
+            let _ = tx.send(Err(error.clone()));
+        }
+    }
+
     pub async fn new_conversation(
         &self,
         agent_id: &str,
@@ -135,14 +1303,7 @@ impl Manager {
         let value = self
             .rpc_call(&agent, "newConversation", params)
             .await?;
-        if let Some(cid) = value
-            .get("conversationId")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string())
-            .or_else(|| value.get("conversation_id").and_then(|v| v.as_str()).map(|s| s.to_string()))
-        {
-            *agent.last_conversation_id.lock().await = Some(cid);
-        }
+        Self::remember_conversation(&agent, &value, None).await;
         Ok(value)
     }
 
@@ -153,10 +1314,16 @@ impl Manager {
     ) -> Result<Value> {
         let agent = self.require_agent(agent_id).await?;
         let params = self.prepare_message_params(&agent, params).await?;
+        let cid = conversation_id_of(&params);
+        let request_id = Self::next_id();
+        let prior = Self::begin_turn(&agent, cid.clone(), "sendUserMessage", request_id).await;
         let value = self
-            .rpc_call(&agent, "sendUserMessage", params)
-            .await?;
-        Ok(value)
+            .rpc_call_with_id(&agent, request_id, "sendUserMessage", params)
+            .await;
+        if value.is_err() {
+            Self::restore_run_state(&agent, cid, prior).await;
+        }
+        value.map(|v| with_request_id(v, request_id))
     }
 
     pub async fn send_user_turn(
@@ -164,39 +1331,222 @@ impl Manager {
         agent_id: &str,
         params: Value,
     ) -> Result<Value> {
+        Ok(self.send_turn(agent_id, params, false).await?.0)
+    }
+
+    /// `send_user_turn` that resolves only when the turn ends (`task_complete`, `turn_aborted`,
+    /// `error`, ...). Meanwhile each `agent_message_delta` and `agent_message` of the turn is
+    /// forwarded upstream as a `turn_stream` event. The turn response gains
+    /// `stream: { endEvent, message, deltas }`, where `message` is the final agent message
+    /// (or the concatenated deltas when none arrived).
+    pub async fn send_user_turn_streamed(
+        &self,
+        agent_id: &str,
+        params: Value,
+    ) -> Result<Value> {
+        let (mut value, stream) = self.send_turn(agent_id, params, true).await?;
+        let Some(TurnStream { agent, conversation_id, request_id, mut events }) = stream else {
+            return Ok(value);
+        };
+        let mut text = String::new();
+        let mut deltas = 0u64;
+        let mut final_message: Option<String> = None;
+        let end_event = loop {
+            let Some(event) = events.recv().await else {
+                return Err(anyhow!(
+                    "stream for conversation {conversation_id} ended before the turn completed (agent {} exited or another streamed turn replaced it)",
+                    agent.id
+                ));
+            };
+            let method = event.get("method").and_then(|m| m.as_str()).unwrap_or_default();
+            let params = event.get("params").cloned().unwrap_or(Value::Null);
+            let Some(kind) = codex_event_type(method, &params) else {
+                continue;
+            };
+            let msg = params.get("msg").unwrap_or(&params);
+            let chunk = match kind {
+                "agent_message_delta" => {
+                    let delta = msg.get("delta").and_then(|d| d.as_str()).unwrap_or_default();
+                    text.push_str(delta);
+                    deltas += 1;
+                    json!({ "delta": delta })
+                }
+                "agent_message" => {
+                    let message = msg.get("message").and_then(|d| d.as_str()).unwrap_or_default();
+                    final_message = Some(message.to_string());
+                    json!({ "message": message })
+                }
+                other if run_state_after(other) == Some(RunState::Idle) => break other.to_string(),
+                _ => continue,
+            };
+            let mut payload = json!({
+                "kind": "turn_stream",
+                "agentId": agent.id,
+                "conversationId": conversation_id,
+                "requestId": request_id,
+                "type": kind,
+            });
+            if let (Value::Object(map), Value::Object(extra)) = (&mut payload, chunk) {
+                map.extend(extra);
+            }
+            Self::emit_event(&agent, payload).await;
+        };
+        agent.turn_streams.lock().await.remove(&conversation_id);
+        if let Value::Object(map) = &mut value {
+            map.insert(
+                "stream".to_string(),
+                json!({
+                    "endEvent": end_event,
+                    "message": final_message.unwrap_or(text),
+                    "deltas": deltas,
+                }),
+            );
+        }
+        Ok(value)
+    }
+
+    /// Send `sendUserTurn`. With `stream`, the turn's conversation is subscribed to before the
+    /// request goes out, so no event of the turn is missed.
+    async fn send_turn(
+        &self,
+        agent_id: &str,
+        params: Value,
+        stream: bool,
+    ) -> Result<(Value, Option<TurnStream>)> {
         let agent = self.require_agent(agent_id).await?;
-        let mut params = self.prepare_message_params(&agent, params).await?;
+        let params = self.build_turn_params(&agent, params).await?;
 
-        // sendUserTurn requires additional fields - provide sensible defaults if missing
-        if let Value::Object(ref mut map) = params {
-            if !map.contains_key("cwd") {
-                map.insert("cwd".to_string(), json!(std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/tmp"))));
+        let cid = conversation_id_of(&params);
+        let request_id = Self::next_id();
+        let stream = match (stream, &cid) {
+            (false, _) => None,
+            (true, None) => {
+                return Err(anyhow!(
+                    "streaming a turn needs a conversation: pass params.conversationId or start one first"
+                ))
             }
-            if !map.contains_key("approvalPolicy") {
-                map.insert("approvalPolicy".to_string(), json!("never"));
+            (true, Some(cid)) => {
+                let (tx, rx) = mpsc::unbounded_channel();
+                agent.turn_streams.lock().await.insert(cid.clone(), tx);
+                Some(TurnStream {
+                    agent: agent.clone(),
+                    conversation_id: cid.clone(),
+                    request_id,
+                    events: rx,
+                })
             }
-            if !map.contains_key("sandboxPolicy") {
-                map.insert("sandboxPolicy".to_string(), json!({"mode": "read-only"}));
+        };
+        let prior = Self::begin_turn(&agent, cid.clone(), "sendUserTurn", request_id).await;
+        let value = self
+            .rpc_call_with_id(&agent, request_id, "sendUserTurn", params)
+            .await;
+        if value.is_err() {
+            if let (Some(cid), true) = (&cid, stream.is_some()) {
+                agent.turn_streams.lock().await.remove(cid);
             }
-            if !map.contains_key("model") {
-                map.insert("model".to_string(), json!("gpt-4"));
+            Self::restore_run_state(&agent, cid, prior).await;
+        }
+        value.map(|v| (with_request_id(v, request_id), stream))
+    }
+
+    /// The `sendUserTurn` params `send_user_turn` would send, without sending them or touching
+    /// run state. `defaulted` lists the keys the orchestrator filled in (defaults and the last
+    /// conversation) rather than the caller's `params` or `overrides`.
+    pub async fn validate_turn(&self, agent_id: &str, params: Value) -> Result<Value> {
+        let agent = self.require_agent(agent_id).await?;
+        let mut supplied: HashSet<String> = HashSet::new();
+        if let Value::Object(map) = &params {
+            supplied.extend(map.keys().cloned());
+            if let Some(Value::Object(overrides)) = map.get("overrides") {
+                supplied.extend(overrides.keys().cloned());
             }
-            if !map.contains_key("summary") {
-                map.insert("summary".to_string(), json!("auto"));
+        }
+        let params = self.build_turn_params(&agent, params).await?;
+        let defaulted: Vec<&String> = params
+            .as_object()
+            .map(|map| {
+                map.keys()
+                    .filter(|key| *key != "items" && !supplied.contains(*key))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Ok(json!({
+            "agentId": agent_id,
+            "method": "sendUserTurn",
+            "conversationId": conversation_id_of(&params),
+            "defaulted": defaulted,
+            "params": params,
+        }))
+    }
+
+    /// Normalize caller params into `sendUserTurn` params: text→items, attachments, the last
+    /// conversation, defaults, and overrides, then the `CODEX_VALIDATE_MODEL` check.
+    async fn build_turn_params(&self, agent: &Arc<Agent>, params: Value) -> Result<Value> {
+        let mut params = self.prepare_message_params(agent, params).await?;
+        let caller_model = params.get("model").is_some()
+            || params.get("overrides").and_then(|o| o.get("model")).is_some();
+
+        apply_turn_overrides(&mut params)?;
+        if !caller_model && validate_model_from_env() {
+            if let Some(model) = params.get("model").and_then(|v| v.as_str()) {
+                self.check_default_model(agent, model).await?;
             }
         }
+        Ok(params)
+    }
 
-        let value = self
-            .rpc_call(&agent, "sendUserTurn", params)
-            .await?;
-        Ok(value)
+    /// Reject an injected `CODEX_TURN_MODEL` the agent does not list. The listing is fetched
+    /// once per agent; builds without one, or with an empty one, skip the check.
+    async fn check_default_model(&self, agent: &Arc<Agent>, model: &str) -> Result<()> {
+        let models = agent
+            .models
+            .get_or_init(|| async {
+                let listing = tokio::time::timeout(
+                    MODEL_LIST_TIMEOUT,
+                    self.rpc_call(agent, MODEL_LIST_METHOD, json!({})),
+                )
+                .await;
+                match listing {
+                    Ok(Ok(listing)) => Some(model_ids(&listing)).filter(|ids| !ids.is_empty()),
+                    Ok(Err(e)) => {
+                        tracing::debug!("model listing unavailable on {}: {e}", agent.id);
+                        None
+                    }
+                    Err(_) => {
+                        tracing::debug!("model listing timed out on {}", agent.id);
+                        None
+                    }
+                }
+            })
+            .await;
+        match models {
+            Some(ids) if !ids.iter().any(|id| id == model) => Err(ModelSetupError {
+                model: model.to_string(),
+                available: ids.clone(),
+            }
+            .into()),
+            _ => Ok(()),
+        }
     }
 
+    /// `interruptConversation`, returning only the agent's response; the MCP tool uses
+    /// `interrupt_with_state`.
+    #[allow(dead_code)]
     pub async fn interrupt(
         &self,
         agent_id: &str,
         params: Value,
     ) -> Result<Value> {
+        Ok(self.interrupt_with_state(agent_id, params).await?.response)
+    }
+
+    /// `interrupt`, also reporting whether the conversation was running beforehand. A
+    /// successful interrupt leaves the conversation `idle`.
+    pub async fn interrupt_with_state(
+        &self,
+        agent_id: &str,
+        params: Value,
+    ) -> Result<InterruptOutcome> {
         let agent = self.require_agent(agent_id).await?;
         let mut params = params;
         if !params.get("conversationId").is_some() && !params.get("conversation_id").is_some() {
@@ -211,10 +1561,304 @@ impl Manager {
                 }
             }
         }
-        let value = self
+        let cid = conversation_id_of(&params);
+        let prior_state = match &cid {
+            Some(cid) => Self::run_state_of(&agent, cid).await.state,
+            None => RunState::Unknown,
+        };
+        let response = self
             .rpc_call(&agent, "interruptConversation", params)
             .await?;
-        Ok(value)
+        Self::set_run_state(&agent, cid.clone(), RunState::Idle, "interruptConversation").await;
+        Ok(InterruptOutcome {
+            conversation_id: cid,
+            prior_state,
+            response,
+        })
+    }
+
+    /// Run state of `conversation_id` on `agent_id` (default: the agent's last conversation).
+    pub async fn conversation_state(
+        &self,
+        agent_id: &str,
+        conversation_id: Option<&str>,
+    ) -> Result<ConversationState> {
+        let agent = self.require_agent(agent_id).await?;
+        let cid = match conversation_id {
+            Some(cid) => cid.to_string(),
+            None => agent
+                .last_conversation_id
+                .lock()
+                .await
+                .clone()
+                .ok_or_else(|| anyhow!("agent {agent_id} has no conversation yet; pass conversationId"))?,
+        };
+        Ok(Self::run_state_of(&agent, &cid).await)
+    }
+
+    async fn run_state_of(agent: &Agent, conversation_id: &str) -> ConversationState {
+        agent
+            .run_states
+            .lock()
+            .await
+            .get(conversation_id)
+            .cloned()
+            .unwrap_or_else(|| ConversationState::unknown(conversation_id.to_string()))
+    }
+
+    /// Record `state` for `conversation_id`, or for the agent's last conversation when the
+    /// id is unknown. Does nothing if neither is available.
+    async fn set_run_state(
+        agent: &Agent,
+        conversation_id: Option<String>,
+        state: RunState,
+        cause: &str,
+    ) {
+        let cid = match conversation_id {
+            Some(cid) => cid,
+            None => match agent.last_conversation_id.lock().await.clone() {
+                Some(cid) => cid,
+                None => return,
+            },
+        };
+        let updated_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .ok();
+        agent.run_states.lock().await.insert(
+            cid.clone(),
+            ConversationState {
+                conversation_id: cid,
+                state,
+                last_event: Some(cause.to_string()),
+                updated_at_ms,
+            },
+        );
+    }
+
+    /// Mark a conversation running before a turn is sent, so a fast `task_complete` cannot be
+    /// overwritten afterwards. Returns the previous state for `restore_run_state`.
+    async fn begin_turn(
+        agent: &Agent,
+        conversation_id: Option<String>,
+        cause: &str,
+        request_id: i64,
+    ) -> Option<ConversationState> {
+        let prior = match &conversation_id {
+            Some(cid) => agent.run_states.lock().await.get(cid).cloned(),
+            None => None,
+        };
+        let target = match &conversation_id {
+            Some(cid) => Some(cid.clone()),
+            None => agent.last_conversation_id.lock().await.clone(),
+        };
+        if let Some(cid) = target {
+            agent.turn_requests.lock().await.insert(cid, request_id);
+        }
+        Self::set_run_state(agent, conversation_id, RunState::Running, cause).await;
+        prior
+    }
+
+    /// Undo `begin_turn` after the turn request failed.
+    async fn restore_run_state(
+        agent: &Agent,
+        conversation_id: Option<String>,
+        prior: Option<ConversationState>,
+    ) {
+        let Some(cid) = conversation_id.or(agent.last_conversation_id.lock().await.clone()) else {
+            return;
+        };
+        let mut states = agent.run_states.lock().await;
+        match prior {
+            Some(prior) => {
+                states.insert(cid, prior);
+            }
+            None => {
+                states.remove(&cid);
+            }
+        }
+    }
+
+    /// Forward an event upstream stamped with the agent's next `seq`, so clients can order
+    /// events and notice gaps, and keep it in the agent's event buffer.
+    async fn emit_event(agent: &Agent, mut payload: Value) {
+        if let Value::Object(map) = &mut payload {
+            let seq = agent.event_seq.fetch_add(1, Ordering::Relaxed) + 1;
+            map.insert("seq".to_string(), json!(seq));
+        }
+        agent.events.lock().await.push(payload.clone());
+        agent.event_added.notify_waiters();
+        let _ = mcp::notify_codex_event(&agent.id, payload).await;
+    }
+
+    /// The last `limit` buffered events of an agent with `seq` above `after_seq`, oldest first,
+    /// from one conversation's bucket or, without `conversation_id`, from all of them.
+    pub async fn recent_events(
+        &self,
+        agent_id: &str,
+        conversation_id: Option<&str>,
+        after_seq: u64,
+        limit: usize,
+    ) -> Result<Vec<Value>> {
+        let agent = self.require_agent(agent_id).await?;
+        let events = agent.events.lock().await;
+        let selected = events.select(conversation_id, after_seq);
+        let skip = selected.len().saturating_sub(limit);
+        Ok(selected.into_iter().skip(skip).cloned().collect())
+    }
+
+    /// Wait for the first event of an agent with `seq` above `after_seq` (default: the latest
+    /// `seq` when the call starts, i.e. only new events), optionally limited to one
+    /// conversation and one event type. Buffered events count, so passing an earlier
+    /// `after_seq` returns an event that already arrived.
+    pub async fn wait_for_event(
+        &self,
+        agent_id: &str,
+        conversation_id: Option<&str>,
+        event_type: Option<&str>,
+        after_seq: Option<u64>,
+        timeout: Duration,
+    ) -> Result<Value> {
+        let agent = self.require_agent(agent_id).await?;
+        let after_seq = after_seq.unwrap_or_else(|| agent.event_seq.load(Ordering::Relaxed));
+        let started = Instant::now();
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // Register for wake-ups before looking, so an event added in between is not missed.
+            let added = agent.event_added.notified();
+            tokio::pin!(added);
+            added.as_mut().enable();
+            {
+                let events = agent.events.lock().await;
+                let found = events
+                    .select(conversation_id, after_seq)
+                    .into_iter()
+                    .find(|e| event_type.is_none_or(|t| buffered_event_type(e) == Some(t)));
+                if let Some(event) = found {
+                    return Ok(json!({
+                        "matched": true,
+                        "agentId": agent_id,
+                        "event": event,
+                        "waitedMs": started.elapsed().as_millis() as u64,
+                    }));
+                }
+            }
+            if tokio::time::timeout_at(deadline, added).await.is_err() {
+                return Ok(json!({
+                    "matched": false,
+                    "timedOut": true,
+                    "agentId": agent_id,
+                    "afterSeq": after_seq,
+                    "waitedMs": started.elapsed().as_millis() as u64,
+                }));
+            }
+        }
+    }
+
+    /// Update run state from a forwarded Codex notification that starts or ends a turn.
+    async fn track_run_state(agent: &Agent, method: &str, params: &Value) {
+        let Some(event) = codex_event_type(method, params) else {
+            return;
+        };
+        let Some(state) = run_state_after(event) else {
+            return;
+        };
+        let cid = conversation_id_of(params)
+            .or_else(|| params.get("msg").and_then(conversation_id_of));
+        Self::set_run_state(agent, cid, state, event).await;
+    }
+
+    /// Remember the text of an `agent_message` event as its conversation's last response.
+    async fn track_last_response(
+        agent: &Agent,
+        conversation_id: Option<&str>,
+        method: &str,
+        params: &Value,
+        request_id: Option<i64>,
+    ) {
+        if codex_event_type(method, params) != Some("agent_message") {
+            return;
+        }
+        let msg = params.get("msg").unwrap_or(params);
+        let Some(text) = msg.get("message").and_then(|m| m.as_str()) else {
+            return;
+        };
+        let cid = match conversation_id {
+            Some(cid) => cid.to_string(),
+            None => match agent.last_conversation_id.lock().await.clone() {
+                Some(cid) => cid,
+                None => return,
+            },
+        };
+        let received_at_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .ok();
+        agent.last_responses.lock().await.insert(
+            cid,
+            json!({ "text": text, "requestId": request_id, "receivedAtMs": received_at_ms }),
+        );
+    }
+
+    /// Most recent assistant message of `conversation_id` (default: the agent's last
+    /// conversation): from forwarded `agent_message` events, else from the tail of the
+    /// conversation's recorded rollout. `text` is null when neither has one.
+    pub async fn last_response(
+        &self,
+        agent_id: &str,
+        conversation_id: Option<&str>,
+    ) -> Result<Value> {
+        let agent = self.require_agent(agent_id).await?;
+        let cid = match conversation_id {
+            Some(cid) => cid.to_string(),
+            None => agent
+                .last_conversation_id
+                .lock()
+                .await
+                .clone()
+                .ok_or_else(|| anyhow!("agent {agent_id} has no conversation yet; pass conversationId"))?,
+        };
+        if let Some(seen) = agent.last_responses.lock().await.get(&cid).cloned() {
+            return Ok(json!({
+                "agentId": agent_id,
+                "conversationId": cid,
+                "text": seen["text"],
+                "source": "events",
+                "requestId": seen["requestId"],
+                "receivedAtMs": seen["receivedAtMs"],
+            }));
+        }
+        let path = agent.rollout_paths.lock().await.get(&cid).cloned();
+        let from_rollout = match path {
+            Some(path) => tokio::task::spawn_blocking(move || std::fs::read_to_string(path))
+                .await?
+                .ok()
+                .and_then(|content| last_assistant_message(&content)),
+            None => None,
+        };
+        Ok(json!({
+            "agentId": agent_id,
+            "conversationId": cid,
+            "source": from_rollout.as_ref().map(|_| "rollout"),
+            "text": from_rollout,
+        }))
+    }
+
+    /// What the agent reported at `initialize` (`protocolVersion`, `capabilities`,
+    /// `serverInfo`), plus the methods it has answered with "method not found" so far.
+    pub async fn agent_capabilities(&self, agent_id: &str) -> Result<Value> {
+        let agent = self.require_agent(agent_id).await?;
+        let init = agent.init_result.get().cloned().unwrap_or_else(|| json!({}));
+        let mut unsupported: Vec<String> =
+            agent.unsupported_methods.lock().await.iter().cloned().collect();
+        unsupported.sort();
+        Ok(json!({
+            "agentId": agent.id,
+            "protocolVersion": init.get("protocolVersion"),
+            "capabilities": init.get("capabilities"),
+            "serverInfo": init.get("serverInfo"),
+            "unsupportedMethods": unsupported,
+        }))
     }
 
     pub async fn list_conversations(
@@ -229,25 +1873,228 @@ impl Manager {
         Ok(value)
     }
 
+    /// `listConversations` on every running agent concurrently (or, with non-empty `cursors`,
+    /// only on the agents named there, continuing from each cursor). Items are tagged with
+    /// `agentId` and merged newest first by `timestamp`; `nextCursors` maps each agent with
+    /// more pages to its cursor, and agents whose listing failed are reported in `errors`.
+    pub async fn list_all_conversations(
+        &self,
+        page_size: Option<u64>,
+        cursors: &serde_json::Map<String, Value>,
+    ) -> Value {
+        let mut agents: Vec<Arc<Agent>> = self
+            .agents
+            .read()
+            .await
+            .values()
+            .filter(|agent| cursors.is_empty() || cursors.contains_key(&agent.id))
+            .cloned()
+            .collect();
+        agents.sort_by(|a, b| a.id.cmp(&b.id));
+        let listings = futures_util::future::join_all(agents.iter().map(|agent| {
+            let mut params = serde_json::Map::new();
+            if let Some(size) = page_size {
+                params.insert("pageSize".to_string(), json!(size));
+            }
+            if let Some(cursor) = cursors.get(&agent.id).filter(|c| !c.is_null()) {
+                params.insert("cursor".to_string(), cursor.clone());
+            }
+            self.rpc_call(agent, "listConversations", Value::Object(params))
+        }))
+        .await;
+
+        let mut items = Vec::new();
+        let mut next_cursors = serde_json::Map::new();
+        let mut errors = Vec::new();
+        for (agent, listing) in agents.iter().zip(listings) {
+            let listing = match listing {
+                Ok(listing) => listing,
+                Err(e) => {
+                    errors.push(json!({"agentId": agent.id, "error": e.to_string()}));
+                    continue;
+                }
+            };
+            for item in listing.get("items").and_then(|v| v.as_array()).into_iter().flatten() {
+                let mut item = item.clone();
+                if let Value::Object(map) = &mut item {
+                    map.insert("agentId".to_string(), json!(agent.id));
+                }
+                items.push(item);
+            }
+            if let Some(cursor) = listing.get("nextCursor").filter(|c| !c.is_null()) {
+                next_cursors.insert(agent.id.clone(), cursor.clone());
+            }
+        }
+        // Timestamps are RFC 3339, so string order is time order; undated items go last.
+        items.sort_by(|a, b| {
+            let ts = |v: &Value| v.get("timestamp").and_then(|t| t.as_str()).map(str::to_string);
+            ts(b).cmp(&ts(a))
+        });
+        json!({
+            "items": items,
+            "nextCursors": next_cursors,
+            "errors": errors,
+        })
+    }
+
     pub async fn resume_conversation(
         &self,
         agent_id: &str,
         params: Value,
     ) -> Result<Value> {
         let agent = self.require_agent(agent_id).await?;
+        // The resumed rollout is the file we were asked to load, should the response omit it.
+        let requested_path = params
+            .get("path")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string());
         let value = self
             .rpc_call(&agent, "resumeConversation", params)
             .await?;
-        // Update last_conversation_id if present in response
-        if let Some(cid) = value
+        Self::remember_conversation(&agent, &value, requested_path).await;
+        Ok(value)
+    }
+
+    /// Record the conversation id (and its rollout path, when known) from a new/resume
+    /// conversation response as the agent's last conversation.
+    async fn remember_conversation(agent: &Agent, value: &Value, fallback_path: Option<String>) {
+        let Some(cid) = value
             .get("conversationId")
             .and_then(|v| v.as_str())
             .map(|s| s.to_string())
             .or_else(|| value.get("conversation_id").and_then(|v| v.as_str()).map(|s| s.to_string()))
-        {
-            *agent.last_conversation_id.lock().await = Some(cid);
+        else {
+            return;
+        };
+        let path = value
+            .get("rolloutPath")
+            .or_else(|| value.get("rollout_path"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .or(fallback_path);
+        if let Some(path) = path {
+            agent.rollout_paths.lock().await.insert(cid.clone(), path);
         }
-        Ok(value)
+        *agent.last_conversation_id.lock().await = Some(cid);
+    }
+
+    /// Cached rollout path for `conversation_id` on `agent_id`, defaulting to the agent's last
+    /// conversation. Returns the resolved conversation id alongside the path.
+    pub async fn rollout_path(
+        &self,
+        agent_id: &str,
+        conversation_id: Option<&str>,
+    ) -> Result<(String, String)> {
+        let agent = self.require_agent(agent_id).await?;
+        let cid = match conversation_id {
+            Some(cid) => cid.to_string(),
+            None => agent
+                .last_conversation_id
+                .lock()
+                .await
+                .clone()
+                .ok_or_else(|| anyhow!("agent {agent_id} has no conversation yet; pass conversationId"))?,
+        };
+        let path = agent
+            .rollout_paths
+            .lock()
+            .await
+            .get(&cid)
+            .cloned()
+            .ok_or_else(|| anyhow!("no rollout path recorded for conversation {cid} on agent {agent_id}"))?;
+        Ok((cid, path))
+    }
+
+    /// Resume a rollout on `agent_id`, spawning a fresh agent (optionally in `cwd`) first when
+    /// no agent with that id is running. Returns the agent id used and whether it was spawned.
+    pub async fn resume_conversation_or_spawn(
+        &self,
+        agent_id: &str,
+        cwd: Option<PathBuf>,
+        params: Value,
+    ) -> Result<(String, bool, Value)> {
+        let running = self.agents.read().await.contains_key(agent_id);
+        let (agent_id, spawned) = if running {
+            (agent_id.to_string(), false)
+        } else {
+            let id = self.spawn_agent(Some(agent_id.to_string()), cwd).await?;
+            (id, true)
+        };
+        let value = self.resume_conversation(&agent_id, params).await?;
+        Ok((agent_id, spawned, value))
+    }
+
+    /// Spawn an agent, start a conversation, and send `prompt` as its first user turn. The
+    /// agent is killed again if either step after the spawn fails, so callers never inherit a
+    /// half-initialized agent. Returns the agent id, the conversation id, and the turn response.
+    #[allow(dead_code)]
+    pub async fn spawn_with_prompt(
+        &self,
+        id: Option<String>,
+        cwd: Option<PathBuf>,
+        labels: AgentLabels,
+        prompt: &str,
+    ) -> Result<(String, String, Value)> {
+        self.spawn_with_prompt_and_bin(id, cwd, labels, None, prompt).await
+    }
+
+    /// `spawn_with_prompt` starting a per-agent `bin`, as for `spawn_agent_with_bin`.
+    pub async fn spawn_with_prompt_and_bin(
+        &self,
+        id: Option<String>,
+        cwd: Option<PathBuf>,
+        labels: AgentLabels,
+        bin: Option<String>,
+        prompt: &str,
+    ) -> Result<(String, String, Value)> {
+        let agent_id = self
+            .spawn_agent_with_bin(id, cwd.clone(), labels, bin)
+            .await?;
+        let (cid, turn) = self.initial_turn(&agent_id, cwd, prompt).await?;
+        Ok((agent_id, cid, turn))
+    }
+
+    /// Start a conversation on a freshly spawned agent and send `prompt` as its first user
+    /// turn. If that fails the agent is killed, so a spawn with a prompt either fully works or
+    /// leaves nothing behind.
+    pub async fn initial_turn(
+        &self,
+        agent_id: &str,
+        cwd: Option<PathBuf>,
+        prompt: &str,
+    ) -> Result<(String, Value)> {
+        match self.start_first_turn(agent_id, cwd, prompt).await {
+            Ok(started) => Ok(started),
+            Err(e) => {
+                let _ = self.kill_agent(agent_id).await;
+                Err(e.context(format!("initial prompt failed; agent {agent_id} was killed")))
+            }
+        }
+    }
+
+    async fn start_first_turn(
+        &self,
+        agent_id: &str,
+        cwd: Option<PathBuf>,
+        prompt: &str,
+    ) -> Result<(String, Value)> {
+        let mut conv_params = json!({});
+        if let Some(ref c) = cwd {
+            conv_params["cwd"] = json!(c);
+        }
+        let conv = self.new_conversation(agent_id, conv_params).await?;
+        let cid = conv
+            .get("conversationId")
+            .or_else(|| conv.get("conversation_id"))
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .ok_or_else(|| anyhow!("newConversation response carried no conversationId"))?;
+        let mut turn_params = json!({"conversationId": cid, "text": prompt});
+        if let Some(c) = cwd {
+            turn_params["cwd"] = json!(c);
+        }
+        let turn = self.send_user_turn(agent_id, turn_params).await?;
+        Ok((cid, turn))
     }
 
     pub async fn archive_conversation(
@@ -262,6 +2109,18 @@ impl Manager {
         Ok(value)
     }
 
+    /// Forward an arbitrary Codex method with `params` unchanged. Escape hatch for methods
+    /// without a dedicated wrapper.
+    pub async fn codex_call(
+        &self,
+        agent_id: &str,
+        method: &str,
+        params: Value,
+    ) -> Result<Value> {
+        let agent = self.require_agent(agent_id).await?;
+        self.rpc_call(&agent, method, params).await
+    }
+
     async fn prepare_message_params(&self, agent: &Agent, params: Value) -> Result<Value> {
         // Normalize params into an object with at least items or text, and ensure conversationId if possible.
         let mut obj = match params {
@@ -306,6 +2165,16 @@ impl Manager {
             }
         }
 
+        // Attachments resolve relative paths against the turn's cwd, else the agent's.
+        let base_dir = obj
+            .get("cwd")
+            .and_then(|v| v.as_str())
+            .map(PathBuf::from)
+            .or_else(|| agent.cwd.clone())
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_default();
+        attach_inputs(&mut obj, &base_dir)?;
+
         // Ensure conversationId if we have a remembered one and it's missing.
         let has_cid = obj.contains_key("conversationId") || obj.contains_key("conversation_id");
         if !has_cid {
@@ -348,7 +2217,8 @@ impl Manager {
             let opt = { let mut r = agent.reader.lock().await; r.next().await };
             let Some(pkt) = opt else { return Err(anyhow!("codex closed during init")); };
             match pkt {
-                Ok(JsonRpcMessage::Response(JsonRpcResponse { id: rid, .. })) if rid == RequestId::Number(id) => {
+                Ok(JsonRpcMessage::Response(JsonRpcResponse { id: rid, result, .. })) if rid == RequestId::Number(id) => {
+                    let _ = agent.init_result.set(result);
                     break;
                 }
                 Ok(JsonRpcMessage::Error(e)) if e.id == RequestId::Number(id) => {
@@ -356,10 +2226,11 @@ impl Manager {
                 }
                 Ok(JsonRpcMessage::Notification(n)) => {
                     let payload = json!({
+                        "agentId": agent.id,
                         "method": n.notification.method,
                         "params": n.notification.params,
                     });
-                    let _ = mcp::notify_codex_event(&agent.id, payload).await;
+                    Self::emit_event(agent, payload).await;
                 }
                 Ok(_) => {}
                 Err(e) => return Err(anyhow!("transport error during init: {}", e)),
@@ -380,24 +2251,18 @@ impl Manager {
 
     fn spawn_read_loop(&self, agent: Arc<Agent>) {
         let approvals = self.approvals.clone();
+        let approval_added = self.approval_added.clone();
         tokio::spawn(async move {
             tracing::debug!("read_loop: started for agent {}", agent.id);
             loop {
                 let msg_opt = { let mut r = agent.reader.lock().await; r.next().await };
                 let Some(pkt) = msg_opt else {
                     tracing::warn!("read_loop: agent {} stream ended", agent.id);
-                    // Drain and fail any pending RPC waiters so callers don't hang
-                    let drained: Vec<oneshot::Sender<Result<Value, Value>>> = {
-                        let mut guard = agent.pending.lock().await;
-                        let mut map = std::mem::take(&mut *guard);
-                        map.drain().map(|(_, tx)| tx).collect()
-                    };
-                    for tx in drained {
-                        let _ = tx.send(Err(json!({
-                            "error": "agent terminated",
-                            "agentId": agent.id,
-                        })));
-                    }
+                    Self::fail_pending(&agent, json!({
+                        "error": "agent terminated",
+                        "agentId": agent.id,
+                    }))
+                    .await;
                     break
                 };
                 match pkt {
@@ -429,14 +2294,35 @@ impl Manager {
                     }
                     Ok(JsonRpcMessage::Notification(JsonRpcNotification { notification, .. })) => {
                         tracing::debug!("read_loop: got notification method={}", notification.method);
+                        agent.touch().await;
+                        Self::track_run_state(&agent, &notification.method, &notification.params).await;
+                        // Tie the event to the turn that produced it: the latest turn request
+                        // sent on its conversation.
+                        let cid = conversation_id_of(&notification.params)
+                            .or_else(|| notification.params.get("msg").and_then(conversation_id_of));
+                        let request_id = match &cid {
+                            Some(cid) => agent.turn_requests.lock().await.get(cid).copied(),
+                            None => None,
+                        };
                         let payload = json!({
+                            "agentId": agent.id,
+                            "conversationId": cid,
+                            "requestId": request_id,
                             "method": notification.method,
                             "params": notification.params,
                         });
-                        let _ = mcp::notify_codex_event(&agent.id, payload).await;
+                        Self::track_last_response(&agent, cid.as_deref(), &notification.method, &notification.params, request_id).await;
+                        if let Some(cid) = &cid {
+                            let mut streams = agent.turn_streams.lock().await;
+                            // A dropped receiver means the streamed call was abandoned.
+                            if streams.get(cid).is_some_and(|tx| tx.send(payload.clone()).is_err()) {
+                                streams.remove(cid);
+                            }
+                        }
+                        Self::emit_event(&agent, payload).await;
                     }
                     Ok(JsonRpcMessage::Request(JsonRpcRequest { id, request, .. })) => {
-                        // Only treat known approval methods as approvals; otherwise reply with empty result
+                        // Only treat known approval methods as approvals; forward anything else upstream
                         let method = request.method.clone();
                         if method == "applyPatchApproval" || method == "execCommandApproval" {
                             // Register pending approval
@@ -446,7 +2332,14 @@ impl Manager {
                             };
                             let key = format!("{}:{}", agent.id, req_id_str);
                             let (tx, rx) = oneshot::channel::<String>();
-                            approvals.lock().await.insert(key.clone(), tx);
+                            approvals.lock().await.insert(key.clone(), PendingApproval {
+                                method: method.clone(),
+                                params: request.params.clone(),
+                                received: Instant::now(),
+                                decide: tx,
+                            });
+                            agent.touch().await;
+                            approval_added.notify_waiters();
                             // Notify upstream client
                             let payload = json!({
                                 "kind": "approval_request",
@@ -455,46 +2348,43 @@ impl Manager {
                                 "method": request.method,
                                 "params": request.params,
                             });
-                            let _ = mcp::notify_codex_event(&agent.id, payload).await;
+                            Self::emit_event(&agent, payload).await;
                             // Wait for decision with timeout
                             let decision = match tokio::time::timeout(std::time::Duration::from_secs(60), rx).await {
                                 Ok(Ok(s)) => s,
-                                _ => "deny".to_string(),
+                                _ => {
+                                    approvals.lock().await.remove(&key);
+                                    "deny".to_string()
+                                }
                             };
                             let result = json!({ "decision": decision });
                             let resp = JsonRpcMessage::Response(JsonRpcResponse { jsonrpc: JsonRpcVersion2_0, id, result });
                             let mut w = agent.writer.lock().await;
                             if let Err(e) = w.send(resp).await { tracing::warn!("failed send approval resp: {}", e); }
                         } else {
-                            // Unknown request from Codex – log and reply with a benign empty result
                             let payload = json!({
                                 "kind": "codex_request",
                                 "agentId": agent.id,
                                 "method": method,
                                 "params": request.params,
                             });
-                            let _ = mcp::notify_codex_event(&agent.id, payload).await;
-                            let result = json!({});
-                            let resp = JsonRpcMessage::Response(JsonRpcResponse { jsonrpc: JsonRpcVersion2_0, id, result });
-                            let mut w = agent.writer.lock().await;
-                            if let Err(e) = w.send(resp).await { tracing::warn!("failed send generic resp: {}", e); }
+                            Self::emit_event(&agent, payload).await;
+                            // The upstream client may take a while (e.g. prompting a user), so relay
+                            // off the read loop to keep responses to our own requests flowing.
+                            let agent = agent.clone();
+                            tokio::spawn(async move {
+                                Self::relay_codex_request(&agent, id, &method, request.params).await;
+                            });
                         }
                     }
                     Err(e) => {
                         tracing::warn!("transport read error: {}", e);
-                        // Drain and fail any pending RPC waiters so callers don't hang
-                        let drained: Vec<oneshot::Sender<Result<Value, Value>>> = {
-                            let mut guard = agent.pending.lock().await;
-                            let mut map = std::mem::take(&mut *guard);
-                            map.drain().map(|(_, tx)| tx).collect()
-                        };
-                        for tx in drained {
-                            let _ = tx.send(Err(json!({
-                                "error": "agent read error",
-                                "message": e.to_string(),
-                                "agentId": agent.id,
-                            })));
-                        }
+                        Self::fail_pending(&agent, json!({
+                            "error": "agent read error",
+                            "message": e.to_string(),
+                            "agentId": agent.id,
+                        }))
+                        .await;
                         break;
                     }
                 }
@@ -502,20 +2392,48 @@ impl Manager {
         });
     }
 
+    /// Answer a non-approval Codex request with the upstream client's response, or with an
+    /// empty result when it cannot be forwarded (no client, or not an MCP client request).
+    async fn relay_codex_request(agent: &Agent, id: RequestId, method: &str, params: Value) {
+        let resp = match mcp::forward_codex_request(method, params).await {
+            Ok(Some(result)) => JsonRpcMessage::Response(JsonRpcResponse { jsonrpc: JsonRpcVersion2_0, id, result }),
+            Ok(None) => JsonRpcMessage::Response(JsonRpcResponse { jsonrpc: JsonRpcVersion2_0, id, result: json!({}) }),
+            Err(error) => JsonRpcMessage::Error(JsonRpcError { jsonrpc: JsonRpcVersion2_0, id, error }),
+        };
+        let mut w = agent.writer.lock().await;
+        if let Err(e) = w.send(resp).await { tracing::warn!("failed send relayed resp for {}: {}", method, e); }
+    }
+
     fn next_id() -> i64 {
-        use std::sync::atomic::{AtomicI64, Ordering};
+        use std::sync::atomic::AtomicI64;
         static NEXT: AtomicI64 = AtomicI64::new(1);
         NEXT.fetch_add(1, Ordering::Relaxed)
     }
 
     async fn rpc_call(&self, agent: &Arc<Agent>, method: &str, params: Value) -> Result<Value> {
+        self.rpc_call_with_id(agent, Self::next_id(), method, params).await
+    }
+
+    /// `rpc_call` with a caller-allocated request id (from `next_id`).
+    async fn rpc_call_with_id(
+        &self,
+        agent: &Arc<Agent>,
+        id: i64,
+        method: &str,
+        params: Value,
+    ) -> Result<Value> {
         // rmcp Request may flatten params; ensure it's an object to avoid serde flattening errors
         let params = match params {
             Value::Object(_) => params,
             Value::Null => json!({}),
             other => json!({ "value": other }),
         };
-        let id = Self::next_id();
+        if agent.draining.load(Ordering::SeqCst) {
+            return Err(AgentDrainingError { agent_id: agent.id.clone() }.into());
+        }
+        if agent.unsupported_methods.lock().await.contains(method) {
+            return Err(UnsupportedMethodError { agent_id: agent.id.clone(), method: method.to_string() }.into());
+        }
         tracing::debug!("rpc_call: method={}, id={}, params={}", method, id, serde_json::to_string(&params).unwrap_or_default());
         let req = Request::<String, Value> {
             method: method.to_string(),
@@ -530,14 +2448,22 @@ impl Manager {
         // Register waiter
         let (tx, rx) = oneshot::channel();
         agent.pending.lock().await.insert(id, tx);
+        agent.touch().await;
         // Send request
         { let mut w = agent.writer.lock().await; w.send(msg).await.map_err(|e| anyhow!("send {} failed: {}", method, e))?; }
         tracing::debug!("rpc_call: sent request id={}, waiting for response...", id);
-        match rx.await {
+        let outcome = rx.await;
+        agent.touch().await;
+        match outcome {
             Ok(Ok(val)) => {
                 tracing::debug!("rpc_call: id={} got response: {}", id, serde_json::to_string(&val).unwrap_or_default());
                 Ok(val)
             },
+            Ok(Err(err)) if err.get("code").and_then(|c| c.as_i64()) == Some(METHOD_NOT_FOUND) => {
+                tracing::warn!("rpc_call: id={} method {} not supported by agent {}", id, method, agent.id);
+                agent.unsupported_methods.lock().await.insert(method.to_string());
+                Err(UnsupportedMethodError { agent_id: agent.id.clone(), method: method.to_string() }.into())
+            },
             Ok(Err(err)) => {
                 tracing::warn!("rpc_call: id={} got error: {}", id, err);
                 Err(anyhow!("rpc error: {}", err))
@@ -558,12 +2484,75 @@ impl Manager {
             .collect()
     }
 
+    /// Resolve a pending approval. `decision` must be `allow` or `deny` (case-insensitive);
+    /// anything else is rejected and leaves the approval pending.
     pub async fn decide_approval(&self, key: &str, decision: String) -> Result<bool> {
-        if let Some(tx) = self.approvals.lock().await.remove(key) {
-            let _ = tx.send(decision);
+        let decision = normalize_decision(&decision).ok_or_else(|| {
+            anyhow!("invalid decision {decision:?}: expected \"allow\" or \"deny\"")
+        })?;
+        if let Some(pending) = self.approvals.lock().await.remove(key) {
+            let _ = pending.decide.send(decision.to_string());
             Ok(true)
         } else {
             Err(anyhow!("approval key not found: {}", key))
         }
     }
+
+    /// Decide the oldest pending approval from `agent_id` whose method equals `method` (any
+    /// approval method when `None`), waiting up to `timeout` for one to arrive. Returns
+    /// `{ decided: false, timedOut: true }` when none shows up in time.
+    pub async fn wait_and_decide_approval(
+        &self,
+        agent_id: &str,
+        method: Option<&str>,
+        decision: &str,
+        timeout: Duration,
+    ) -> Result<Value> {
+        let decision = normalize_decision(decision).ok_or_else(|| {
+            anyhow!("invalid decision {decision:?}: expected \"allow\" or \"deny\"")
+        })?;
+        self.require_agent(agent_id).await?;
+        let prefix = format!("{agent_id}:");
+        let started = Instant::now();
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            // Register for wake-ups before looking, so an approval added in between is not missed.
+            let added = self.approval_added.notified();
+            tokio::pin!(added);
+            added.as_mut().enable();
+            {
+                let mut approvals = self.approvals.lock().await;
+                let next = approvals
+                    .iter()
+                    .filter(|(key, pending)| {
+                        key.starts_with(&prefix) && method.is_none_or(|m| pending.method == m)
+                    })
+                    .min_by_key(|(_, pending)| pending.received)
+                    .map(|(key, _)| key.clone());
+                // The lock is held since the lookup, so the entry is there; should it be gone,
+                // keep waiting for the next approval rather than failing.
+                if let Some((key, pending)) = next.and_then(|key| approvals.remove_entry(&key)) {
+                    let _ = pending.decide.send(decision.to_string());
+                    return Ok(json!({
+                        "decided": true,
+                        "key": key,
+                        "agentId": agent_id,
+                        "requestId": &key[prefix.len()..],
+                        "method": pending.method,
+                        "params": pending.params,
+                        "decision": decision,
+                        "waitedMs": started.elapsed().as_millis() as u64,
+                    }));
+                }
+            }
+            if tokio::time::timeout_at(deadline, added).await.is_err() {
+                return Ok(json!({
+                    "decided": false,
+                    "timedOut": true,
+                    "agentId": agent_id,
+                    "waitedMs": started.elapsed().as_millis() as u64,
+                }));
+            }
+        }
+    }
 }
@@ -24,6 +24,7 @@ async fn main() -> Result<()> {
     tracing::info!("Starting codex-orchestrator MCP server");
 
     let state = mcp::Orchestrator::new();
+    state.start_idle_reaper();
     // Serve MCP over stdio using rmcp
     let service = state
         .serve(rmcp::transport::stdio())
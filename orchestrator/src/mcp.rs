@@ -4,7 +4,8 @@ use anyhow::{anyhow, Result};
 use rmcp::{
     ErrorData as McpError, ServerHandler,
     handler::server::{router::tool::ToolRouter, wrapper::Parameters},
-    model::{CallToolResult, Content, LoggingLevel, LoggingMessageNotification, LoggingMessageNotificationParam, ServerCapabilities, ServerInfo},
+    model::{CallToolResult, Content, LoggingLevel, LoggingMessageNotification, LoggingMessageNotificationParam, ServerCapabilities, ServerInfo, ServerRequest},
+    service::ServiceError,
     schemars::JsonSchema,
     tool, tool_handler, tool_router,
 };
@@ -16,6 +17,25 @@ use once_cell::sync::OnceCell;
 // Upstream peer handle so background tasks (codex clients) can send notifications.
 static UPSTREAM_PEER: OnceCell<rmcp::service::ClientSink> = OnceCell::new();
 
+/// `wait_and_decide_approval`: wait used when `timeoutMs` is omitted, and the longest allowed.
+const DEFAULT_APPROVAL_WAIT_MS: u64 = 30_000;
+const MAX_APPROVAL_WAIT_MS: u64 = 600_000;
+
+/// `stop_agent_graceful`: wait used when `timeoutMs` is omitted, and the longest allowed.
+const DEFAULT_DRAIN_WAIT_MS: u64 = 60_000;
+const MAX_DRAIN_WAIT_MS: u64 = 600_000;
+
+/// `get_recent_events`: events returned when `limit` is omitted, and the most a bucket keeps.
+const DEFAULT_RECENT_EVENTS: usize = 50;
+const MAX_RECENT_EVENTS: usize = 500;
+
+/// `wait_for_event`: wait used when `timeoutMs` is omitted, and the longest allowed.
+const DEFAULT_EVENT_WAIT_MS: u64 = 30_000;
+const MAX_EVENT_WAIT_MS: u64 = 600_000;
+
+/// Codex requests the orchestrator holds as pending approvals.
+const APPROVAL_METHODS: &[&str] = &["applyPatchApproval", "execCommandApproval"];
+
 pub fn set_upstream_peer(peer: rmcp::service::ClientSink) {
     let _ = UPSTREAM_PEER.set(peer);
 }
@@ -40,6 +60,54 @@ impl Orchestrator {
         }
     }
 
+    /// Start the idle-agent reaper if `CODEX_AGENT_IDLE_SECS` is configured.
+    pub fn start_idle_reaper(&self) {
+        let _ = self.inner.manager.spawn_idle_reaper();
+    }
+
+    fn agents_listing(agents: Vec<codex::AgentInfo>) -> serde_json::Value {
+        let agent_ids = agents.iter().map(|a| a.agent_id.clone()).collect();
+        serde_json::to_value(ListAgentsResult { agent_ids, agents })
+            .unwrap_or_else(|_| serde_json::json!({"agentIds": [], "agents": []}))
+    }
+
+    /// Map a manager error to an MCP error, attaching the structured `kind` when the
+    /// failure came from starting an agent, a misconfigured default model, or a method the
+    /// agent's Codex build does not implement.
+    fn agent_error(e: anyhow::Error) -> McpError {
+        let data = e
+            .downcast_ref::<codex::SpawnError>()
+            .map(|se| se.data())
+            .or_else(|| e.downcast_ref::<codex::ModelSetupError>().map(|me| me.data()))
+            .or_else(|| e.downcast_ref::<codex::UnsupportedMethodError>().map(|ue| ue.data()))
+            .or_else(|| e.downcast_ref::<codex::AgentDrainingError>().map(|de| de.data()));
+        McpError::internal_error(e.to_string(), data)
+    }
+
+    /// In strict mode, reject params that omit `conversationId` instead of letting the manager
+    /// target the agent's most recent conversation.
+    fn require_explicit_conversation(
+        strict: bool,
+        params: &serde_json::Value,
+    ) -> Result<(), McpError> {
+        if !strict {
+            return Ok(());
+        }
+        let has_cid = params
+            .get("conversationId")
+            .or_else(|| params.get("conversation_id"))
+            .and_then(|v| v.as_str())
+            .is_some_and(|s| !s.is_empty());
+        if has_cid {
+            Ok(())
+        } else {
+            Err(McpError::invalid_params(
+                "strict mode requires an explicit params.conversationId",
+                None,
+            ))
+        }
+    }
+
     fn normalize_params(params: serde_json::Value) -> serde_json::Value {
         match params {
             serde_json::Value::String(ref s) => {
@@ -64,12 +132,35 @@ pub struct SpawnAgentArgs {
     pub id: Option<String>,
     #[serde(default)]
     pub cwd: Option<String>,
+    /// When set, start a conversation and send this as its first user turn.
+    #[serde(default, rename = "initialPrompt")]
+    pub initial_prompt: Option<String>,
+    /// Free-form labels for grouping agents; see `find_agents`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Arbitrary key/value data stored with the agent and returned by `list_agents`.
+    #[serde(default)]
+    pub metadata: serde_json::Map<String, serde_json::Value>,
+    /// When an agent with `id` is already running, kill it and spawn in its place instead of
+    /// failing.
+    #[serde(default)]
+    pub replace: bool,
+    /// Codex binary for this agent, overriding `CODEX_BIN`/`PATH` resolution.
+    #[serde(default)]
+    pub bin: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct SpawnAgentResult {
     #[serde(rename = "agentId")]
     pub agent_id: String,
+    #[serde(rename = "conversationId", skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub turn: Option<serde_json::Value>,
+    /// True when the new agent replaced a running agent with the same id.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub replaced: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
@@ -79,6 +170,30 @@ pub struct ListAgentsArgs {}
 pub struct ListAgentsResult {
     #[serde(rename = "agentIds")]
     pub agent_ids: Vec<String>,
+    pub agents: Vec<codex::AgentInfo>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
+pub struct OrchestratorHealthArgs {}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
+pub struct ListAllConversationsArgs {
+    /// Items requested from each agent.
+    #[serde(rename = "pageSize")]
+    pub page_size: Option<u64>,
+    /// `nextCursors` from a previous call; only these agents are listed, from their cursors.
+    #[serde(default)]
+    pub cursors: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
+pub struct FindAgentsArgs {
+    /// Agents must carry every one of these tags.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Agents must have each of these metadata keys with an equal value.
+    #[serde(default)]
+    pub metadata: serde_json::Map<String, serde_json::Value>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
@@ -90,6 +205,24 @@ pub struct KillAgentArgs {
 #[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
 pub struct KillAgentResult {}
 
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct StopAgentGracefulArgs {
+    pub agent_id: String,
+    /// How long to let in-flight requests and turns finish before killing (default 60000, at most 600000)
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
+pub struct KillAllAgentsArgs {}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct KillAllAgentsResult {
+    pub killed: usize,
+    #[serde(rename = "agentIds")]
+    pub agent_ids: Vec<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct NewConversationArgs {
     #[serde(rename = "agentId")]
@@ -102,6 +235,9 @@ pub struct SendUserMessageArgs {
     #[serde(rename = "agentId")]
     pub agent_id: String,
     pub params: serde_json::Value,
+    /// Require an explicit `params.conversationId`; never fall back to the last conversation.
+    #[serde(default)]
+    pub strict: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
@@ -109,6 +245,22 @@ pub struct SendUserTurnArgs {
     #[serde(rename = "agentId")]
     pub agent_id: String,
     pub params: serde_json::Value,
+    /// Require an explicit `params.conversationId`; never fall back to the last conversation.
+    #[serde(default)]
+    pub strict: bool,
+    /// Wait for the turn to end, forwarding the agent's message events as they arrive.
+    #[serde(default)]
+    pub stream: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct ValidateTurnArgs {
+    #[serde(rename = "agentId")]
+    pub agent_id: String,
+    pub params: serde_json::Value,
+    /// Require an explicit `params.conversationId`; never fall back to the last conversation.
+    #[serde(default)]
+    pub strict: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
@@ -116,6 +268,9 @@ pub struct InterruptArgs {
     #[serde(rename = "agentId")]
     pub agent_id: String,
     pub params: serde_json::Value,
+    /// Require an explicit `params.conversationId`; never fall back to the last conversation.
+    #[serde(default)]
+    pub strict: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
@@ -123,8 +278,20 @@ pub struct InterruptArgs {
 pub struct ApprovalDecisionArgs {
     /// Composite key identifying a pending approval: "<agentId>:<requestId>"
     pub key: String,
-    /// "allow" or "deny"
+    /// Exactly "allow" or "deny" (case-insensitive); other values are rejected
+    pub decision: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WaitAndDecideApprovalArgs {
+    pub agent_id: String,
+    /// Only decide approvals for this method: "applyPatchApproval" or "execCommandApproval"
+    pub method_filter: Option<String>,
+    /// Exactly "allow" or "deny" (case-insensitive)
     pub decision: String,
+    /// How long to wait for a matching approval (default 30000, at most 600000)
+    pub timeout_ms: Option<u64>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema, Default)]
@@ -147,6 +314,12 @@ pub struct ResumeConversationArgs {
     #[serde(rename = "agentId")]
     pub agent_id: String,
     pub params: serde_json::Value,
+    /// Spawn a fresh agent under `agentId` when it is not running (e.g. after an orchestrator restart).
+    #[serde(default, rename = "spawnIfMissing")]
+    pub spawn_if_missing: bool,
+    /// Working directory for an agent spawned via `spawnIfMissing`.
+    #[serde(default)]
+    pub cwd: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
@@ -154,6 +327,79 @@ pub struct ArchiveConversationArgs {
     #[serde(rename = "agentId")]
     pub agent_id: String,
     pub params: serde_json::Value,
+    /// Require an explicit `params.conversationId`; never fall back to the last conversation.
+    #[serde(default)]
+    pub strict: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct CodexCallArgs {
+    #[serde(rename = "agentId")]
+    pub agent_id: String,
+    /// Codex MCP method name, e.g. `getUserSavedConfig`.
+    pub method: String,
+    /// Params forwarded verbatim; defaults to `{}`.
+    #[serde(default)]
+    pub params: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GetLastResponseArgs {
+    #[serde(rename = "agentId")]
+    pub agent_id: String,
+    /// Conversation to read; defaults to the agent's last conversation.
+    #[serde(default, rename = "conversationId")]
+    pub conversation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct GetRecentEventsArgs {
+    pub agent_id: String,
+    /// Only events of this conversation (default: every conversation and agent-wide events)
+    pub conversation_id: Option<String>,
+    /// Most recent events to return (default 50, at most 500)
+    pub limit: Option<usize>,
+    /// Only events with a seq greater than this (default 0)
+    pub after_seq: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct WaitForEventArgs {
+    pub agent_id: String,
+    /// Only events of this conversation (default: any)
+    pub conversation_id: Option<String>,
+    /// Codex event type (e.g. "task_complete") or orchestrator event kind (e.g. "approval_request")
+    pub event_type: Option<String>,
+    /// Only events with a seq greater than this (default: the latest seq, i.e. new events only)
+    pub after_seq: Option<u64>,
+    /// How long to wait for a matching event (default 30000, at most 600000)
+    pub timeout_ms: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GetAgentCapabilitiesArgs {
+    #[serde(rename = "agentId")]
+    pub agent_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GetRolloutPathArgs {
+    #[serde(rename = "agentId")]
+    pub agent_id: String,
+    /// Conversation to look up; defaults to the agent's last conversation.
+    #[serde(default, rename = "conversationId")]
+    pub conversation_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+pub struct GetConversationStateArgs {
+    #[serde(rename = "agentId")]
+    pub agent_id: String,
+    /// Conversation to inspect; defaults to the agent's last conversation.
+    #[serde(default, rename = "conversationId")]
+    pub conversation_id: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
@@ -162,35 +408,94 @@ pub struct GetConversationEventsArgs {
     pub rollout_path: String,
     #[serde(default)]
     pub limit: Option<usize>,
+    /// List the line numbers and parse errors of corrupt lines (default: false)
+    #[serde(rename = "reportSkipped", default)]
+    pub report_skipped: bool,
 }
 
 #[tool_router]
 impl Orchestrator {
-    #[tool(description = "Start a new Codex agent process (subprocess) that can manage multiple conversations. Each agent is an independent Codex MCP server.\n\nArguments:\n- id (optional): Custom identifier for the agent. Auto-generated if not provided.\n- cwd (optional): Working directory for the agent. Defaults to current directory.\n\nReturns: { agentId: string }\n\nExample: spawn_agent({ id: \"my-agent\", cwd: \"/path/to/project\" })")]
+    #[tool(description = "Start a new Codex agent process (subprocess) that can manage multiple conversations. Each agent is an independent Codex MCP server.\n\nArguments:\n- id (optional): Custom identifier for the agent. Auto-generated if not provided.\n- cwd (optional): Working directory for the agent. Defaults to current directory.\n- initialPrompt (optional): Start a conversation and send this text as its first user turn in the same call. If that fails, the agent is killed and the error returned.\n- tags (optional): String labels for grouping agents (see find_agents)\n- metadata (optional): Object of arbitrary key/value data stored with the agent\n- replace (optional): When an agent with this id is already running, start a new one and swap it in, then kill the old one; if the new agent fails to start, the running one is left alone (default: false, which fails with \"agent already exists\")\n- bin (optional): Codex binary for this agent instead of CODEX_BIN/PATH, e.g. to compare Codex builds side by side. A bare name is looked up on PATH; a path must be an executable file.\n\nReturns: { agentId: string } or, with initialPrompt, { agentId, conversationId, turn }; replaced: true is added when the new agent replaced a running one\n\nErrors carry data.kind: binary_not_found (CODEX_BIN/PATH lookup failed), invalid_binary (bin is missing or not executable), spawn_failed (binary could not be started), agent_limit_reached (CODEX_MAX_AGENTS agents are already running; includes limit and live), or init_failed (process exited or errored during the MCP handshake; includes captured stderr and, for a recognized misconfiguration such as a missing API key, a hint like running `codex login`).\n\nExample: spawn_agent({ id: \"my-agent\", cwd: \"/path/to/project\" })\nExample: spawn_agent({ cwd: \"/path/to/project\", initialPrompt: \"Fix the failing tests\" })")]
     pub async fn spawn_agent(
         &self,
-        Parameters(SpawnAgentArgs { id, cwd }): Parameters<SpawnAgentArgs>,
+        Parameters(SpawnAgentArgs {
+            id,
+            cwd,
+            initial_prompt,
+            tags,
+            metadata,
+            replace,
+            bin,
+        }): Parameters<SpawnAgentArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let agent_id = self
-            .inner
-            .manager
-            .spawn_agent(id, cwd.map(Into::into))
-            .await
-            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
-        let value = serde_json::to_value(SpawnAgentResult { agent_id })
+        let labels = codex::AgentLabels { tags, metadata };
+        let cwd: Option<std::path::PathBuf> = cwd.map(Into::into);
+        let manager = &self.inner.manager;
+        let (agent_id, replaced) = if replace {
+            manager.replace_agent_with_bin(id, cwd.clone(), labels, bin).await
+        } else {
+            manager
+                .spawn_agent_with_bin(id, cwd.clone(), labels, bin)
+                .await
+                .map(|agent_id| (agent_id, false))
+        }
+        .map_err(Self::agent_error)?;
+        let (conversation_id, turn) = match initial_prompt {
+            Some(prompt) => {
+                let (conversation_id, turn) = manager
+                    .initial_turn(&agent_id, cwd, &prompt)
+                    .await
+                    .map_err(Self::agent_error)?;
+                (Some(conversation_id), Some(turn))
+            }
+            None => (None, None),
+        };
+        let result = SpawnAgentResult {
+            agent_id,
+            conversation_id,
+            turn,
+            replaced,
+        };
+        let value = serde_json::to_value(result)
             .unwrap_or_else(|_| serde_json::json!({"ok": true}));
         Ok(CallToolResult::success(vec![Content::text(value.to_string())]))
     }
 
-    #[tool(description = "List all currently running Codex agents managed by this orchestrator.\n\nArguments: None\n\nReturns: { agentIds: string[] } - Array of agent identifiers\n\nExample: list_agents() → { \"agentIds\": [\"agent-1\", \"agent-2\"] }")]
+    #[tool(description = "List all currently running Codex agents managed by this orchestrator.\n\nArguments: None\n\nReturns: { agentIds: string[], agents: [{ agentId, cwd?, pid?, bin, tags, metadata }] } - Agent identifiers (sorted) and the labels given at spawn\n\nExample: list_agents() → { \"agentIds\": [\"agent-1\", \"agent-2\"], \"agents\": [...] }")]
     pub async fn list_agents(
         &self,
         _params: Parameters<ListAgentsArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let agent_ids = self.inner.manager.list_agents().await;
-        let value = serde_json::to_value(ListAgentsResult { agent_ids })
-            .unwrap_or_else(|_| serde_json::json!({"agentIds": []}));
-        Ok(CallToolResult::success(vec![Content::text(value.to_string())]))
+        let agents = self
+            .inner
+            .manager
+            .agent_infos(&[], &serde_json::Map::new())
+            .await;
+        Ok(CallToolResult::structured(Self::agents_listing(agents)))
+    }
+
+    #[tool(description = "Summarize the health of the orchestrator and every agent it manages in one call.\n\nArguments: None\n\nReturns: { agentCount, upstreamConnected, pendingApprovals, agents: [{ agentId, pid?, alive, exitCode?, pendingRequests, pendingApprovals, idleMs, draining }] }\n\nNote: alive is false for an agent whose process has exited but which has not been removed yet, and null while its process is being killed. draining is true while stop_agent_graceful waits for the agent's in-flight work. upstreamConnected tells whether notifications (events, approvals) can reach the MCP client.\n\nExample: orchestrator_health() → { \"agentCount\": 1, \"upstreamConnected\": true, \"agents\": [{ \"agentId\": \"agent-1\", \"alive\": true, ... }] }")]
+    pub async fn orchestrator_health(
+        &self,
+        _params: Parameters<OrchestratorHealthArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let agents = self.inner.manager.health().await;
+        let pending_approvals = self.inner.manager.list_pending_approvals().await.len();
+        Ok(CallToolResult::structured(serde_json::json!({
+            "agentCount": agents.len(),
+            "upstreamConnected": UPSTREAM_PEER.get().is_some(),
+            "pendingApprovals": pending_approvals,
+            "agents": agents
+        })))
+    }
+
+    #[tool(description = "Find running agents by the tags and metadata given at spawn_agent.\n\nArguments:\n- tags (optional): Agents must carry every one of these tags\n- metadata (optional): Agents must have each key with an equal value\n\nReturns: { agentIds: string[], agents: [{ agentId, cwd?, pid?, bin, tags, metadata }] }\n\nExample: find_agents({ tags: [\"repo:api\"], metadata: { task: \"review\" } })")]
+    pub async fn find_agents(
+        &self,
+        Parameters(FindAgentsArgs { tags, metadata }): Parameters<FindAgentsArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let agents = self.inner.manager.agent_infos(&tags, &metadata).await;
+        Ok(CallToolResult::structured(Self::agents_listing(agents)))
     }
 
     #[tool(description = "Terminate a Codex agent process and clean up its resources. All active conversations on this agent will be stopped.\n\nArguments:\n- agentId (required): Identifier of the agent to terminate\n\nReturns: { ok: true }\n\nExample: kill_agent({ agentId: \"my-agent\" })")]
@@ -209,6 +514,43 @@ impl Orchestrator {
         Ok(CallToolResult::success(vec![Content::text(value.to_string())]))
     }
 
+    #[tool(description = "Stop a Codex agent after its current work instead of killing it mid-turn, so rollouts stay complete and resumable.\n\nArguments:\n- agentId (required): Identifier of the agent to stop\n- timeoutMs (optional): How long to wait for in-flight requests and running turns before killing anyway (default: 60000, max: 600000)\n\nReturns: { agentId, drained, timedOut, waitedMs, abandonedRequests, runningConversations } - drained is true when everything finished before the kill; otherwise the requests still waiting and the conversations still running are reported\n\nNote: From the call on, new requests to the agent fail with data.kind \"agent_draining\"; approval requests can still be decided so a waiting turn can finish. A second call while the agent is draining fails the same way.\n\nExample: stop_agent_graceful({ agentId: \"my-agent\", timeoutMs: 120000 })")]
+    pub async fn stop_agent_graceful(
+        &self,
+        Parameters(StopAgentGracefulArgs { agent_id, timeout_ms }): Parameters<StopAgentGracefulArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let timeout_ms = timeout_ms.unwrap_or(DEFAULT_DRAIN_WAIT_MS);
+        if timeout_ms > MAX_DRAIN_WAIT_MS {
+            return Err(McpError::invalid_params(
+                format!("timeoutMs must be at most {MAX_DRAIN_WAIT_MS}"),
+                None,
+            ));
+        }
+        let outcome = self
+            .inner
+            .manager
+            .stop_agent_graceful(&agent_id, std::time::Duration::from_millis(timeout_ms))
+            .await
+            .map_err(Self::agent_error)?;
+        let value = serde_json::to_value(outcome)
+            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        Ok(CallToolResult::structured(value))
+    }
+
+    #[tool(description = "Terminate every Codex agent managed by this orchestrator, e.g. for teardown between tasks. In-flight requests to those agents fail immediately instead of hanging.\n\nArguments: None\n\nReturns: { killed: number, agentIds: string[] } - Count and identifiers of the agents that were terminated\n\nExample: kill_all_agents() → { \"killed\": 2, \"agentIds\": [\"agent-1\", \"agent-2\"] }")]
+    pub async fn kill_all_agents(
+        &self,
+        _params: Parameters<KillAllAgentsArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let agent_ids = self.inner.manager.kill_all_agents().await;
+        let value = serde_json::to_value(KillAllAgentsResult {
+            killed: agent_ids.len(),
+            agent_ids,
+        })
+        .unwrap_or_else(|_| serde_json::json!({"killed": 0, "agentIds": []}));
+        Ok(CallToolResult::structured(value))
+    }
+
     #[tool(description = "Start a new conversation with a Codex agent. Creates a new conversation context that can track multiple messages.\n\nArguments:\n- agentId (required): Identifier of the agent to use\n- params (optional): Configuration object\n  - prompt/topic/message (any works): Initial conversation prompt\n  - Other Codex-specific parameters as needed\n\nReturns: { conversationId: string, ... } - Conversation metadata including unique ID\n\nExample: new_conversation({ agentId: \"my-agent\", params: { prompt: \"Review the codebase\" } })")]
     pub async fn new_conversation(
         &self,
@@ -221,53 +563,92 @@ impl Orchestrator {
             .manager
             .new_conversation(&agent_id, params)
             .await
-            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+            .map_err(Self::agent_error)?;
         Ok(CallToolResult::structured(res))
     }
 
-    #[tool(description = "Send a message to an existing Codex conversation. Simpler than send_user_turn for basic message exchange.\n\nArguments:\n- agentId (required): Identifier of the agent\n- params (required): Message parameters\n  - conversationId (required): ID of the conversation\n  - message/prompt (either works): The message text to send\n\nReturns: Response from Codex agent\n\nExample: send_user_message({ agentId: \"my-agent\", params: { conversationId: \"c1\", message: \"What's next?\" } })")]
+    #[tool(description = "Send a message to an existing Codex conversation. Simpler than send_user_turn for basic message exchange.\n\nArguments:\n- agentId (required): Identifier of the agent\n- params (required): Message parameters\n  - conversationId (required): ID of the conversation\n  - message/prompt (either works): The message text to send\n  - images (optional): Image paths, data:/http(s) URLs, or { path } / { url } / { data, mimeType } objects, appended as image items\n  - files (optional): File paths (or { path }) attached as references; paths must exist\n- strict (optional): When true, params.conversationId is required and the last conversation is never used as a fallback (default: false)\n\nReturns: Response from Codex agent, with requestId added; the codex/event notifications this turn produces carry the same requestId\n\nExample: send_user_message({ agentId: \"my-agent\", params: { conversationId: \"c1\", message: \"What's next?\" } })")]
     pub async fn send_user_message(
         &self,
-        Parameters(SendUserMessageArgs { agent_id, params }): Parameters<SendUserMessageArgs>,
+        Parameters(SendUserMessageArgs {
+            agent_id,
+            params,
+            strict,
+        }): Parameters<SendUserMessageArgs>,
     ) -> Result<CallToolResult, McpError> {
         let params = Self::normalize_params(params);
+        Self::require_explicit_conversation(strict, &params)?;
         let res = self
             .inner
             .manager
             .send_user_message(&agent_id, params)
             .await
-            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+            .map_err(Self::agent_error)?;
         Ok(CallToolResult::structured(res))
     }
 
-    #[tool(description = "Send a user turn to a Codex conversation with automatic defaults for required fields. This is the recommended way to send messages.\n\nArguments:\n- agentId (required): Identifier of the agent\n- params (flexible): Can be a string, or an object with:\n  - conversationId (optional if last conversation exists): ID of the conversation\n  - text (optional if items provided): Message text - automatically converted to items format\n  - items (optional if text provided): Pre-formatted message items\n  - cwd (auto-filled): Working directory (defaults to current dir)\n  - approvalPolicy (auto-filled): Approval mode (defaults to \"never\")\n  - sandboxPolicy (auto-filled): Sandbox settings (defaults to read-only)\n  - model (auto-filled): AI model (defaults to \"gpt-4\")\n  - summary (auto-filled): Summary mode (defaults to \"auto\")\n\nReturns: Response from Codex agent\n\nExample: send_user_turn({ agentId: \"my-agent\", params: \"Hello!\" })\nExample: send_user_turn({ agentId: \"my-agent\", params: { conversationId: \"c1\", text: \"Continue\" } })")]
+    #[tool(description = "Send a user turn to a Codex conversation with automatic defaults for required fields. This is the recommended way to send messages.\n\nArguments:\n- agentId (required): Identifier of the agent\n- params (flexible): Can be a string, or an object with:\n  - conversationId (optional if last conversation exists): ID of the conversation\n  - text (optional if items provided): Message text - automatically converted to items format\n  - items (optional if text provided): Pre-formatted message items\n  - images (optional): Image paths, data:/http(s) URLs, or { path } / { url } / { data, mimeType } objects, appended as image items\n  - files (optional): File paths (or { path }) attached as references; relative paths resolve against cwd and must exist\n  - cwd (auto-filled): Working directory (defaults to current dir)\n  - approvalPolicy (auto-filled): Approval mode (defaults to \"never\")\n  - sandboxPolicy (auto-filled): Sandbox settings (defaults to read-only)\n  - model (optional): AI model; filled from CODEX_TURN_MODEL when set, otherwise omitted so Codex uses its configured model; with CODEX_VALIDATE_MODEL=1 an injected model missing from the agent's model list fails with data.kind \"unknown_model\"\n  - summary (auto-filled): Summary mode (defaults to \"auto\")\n  - overrides (optional): Object merged over the final params after defaults, for this turn only (e.g. { approvalPolicy: \"on-request\" }); not sent to Codex itself\n- strict (optional): When true, params.conversationId is required and the last conversation is never used as a fallback (default: false)\n- stream (optional): When true, the call returns only after the turn ends, and each agent_message_delta / agent_message of the turn is also sent as a codex/event notification { kind: \"turn_stream\", agentId, conversationId, requestId, type, delta | message } as it arrives (default: false)\n\nReturns: Response from Codex agent, with requestId added; the codex/event notifications this turn produces carry the same requestId. With stream, also stream: { endEvent, message, deltas }\n\nExample: send_user_turn({ agentId: \"my-agent\", params: \"Hello!\" })\nExample: send_user_turn({ agentId: \"my-agent\", params: { conversationId: \"c1\", text: \"Continue\" } })")]
     pub async fn send_user_turn(
         &self,
-        Parameters(SendUserTurnArgs { agent_id, params }): Parameters<SendUserTurnArgs>,
+        Parameters(SendUserTurnArgs {
+            agent_id,
+            params,
+            strict,
+            stream,
+        }): Parameters<SendUserTurnArgs>,
     ) -> Result<CallToolResult, McpError> {
         let params = Self::normalize_params(params);
+        Self::require_explicit_conversation(strict, &params)?;
+        let manager = &self.inner.manager;
+        let res = if stream {
+            manager.send_user_turn_streamed(&agent_id, params).await
+        } else {
+            manager.send_user_turn(&agent_id, params).await
+        }
+        .map_err(Self::agent_error)?;
+        Ok(CallToolResult::structured(res))
+    }
+
+    #[tool(description = "Dry-run send_user_turn: build the exact sendUserTurn params that would be sent to Codex, without sending them. Use it to check the text to items conversion, attachments, conversationId fallback, defaults, and overrides.\n\nArguments:\n- agentId (required): Identifier of the agent\n- params (flexible): Same as send_user_turn's params (string or object)\n- strict (optional): When true, params.conversationId is required and the last conversation is never used as a fallback (default: false)\n\nReturns: { agentId, method: \"sendUserTurn\", conversationId, defaulted: string[], params } - params is exactly what send_user_turn would send; defaulted lists the keys the orchestrator filled in rather than params or overrides\n\nNote: Nothing is sent and the conversation's run state is unchanged. Invalid params (missing attachment files, non-object overrides, a CODEX_VALIDATE_MODEL failure) fail with the same error as send_user_turn.\n\nExample: validate_turn({ agentId: \"my-agent\", params: { text: \"Continue\", overrides: { approvalPolicy: \"on-request\" } } })")]
+    pub async fn validate_turn(
+        &self,
+        Parameters(ValidateTurnArgs {
+            agent_id,
+            params,
+            strict,
+        }): Parameters<ValidateTurnArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let params = Self::normalize_params(params);
+        Self::require_explicit_conversation(strict, &params)?;
         let res = self
             .inner
             .manager
-            .send_user_turn(&agent_id, params)
+            .validate_turn(&agent_id, params)
             .await
-            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+            .map_err(Self::agent_error)?;
         Ok(CallToolResult::structured(res))
     }
 
-    #[tool(description = "Interrupt an in-progress Codex conversation, stopping any ongoing agent processing.\n\nArguments:\n- agentId (required): Identifier of the agent\n- params (optional): Interrupt parameters\n  - conversationId (required): ID of the conversation to interrupt\n\nReturns: Confirmation from Codex agent\n\nNote: Not all Codex versions support interruption. Check agent capabilities.\n\nExample: interrupt({ agentId: \"my-agent\", params: { conversationId: \"c1\" } })")]
+    #[tool(description = "Interrupt an in-progress Codex conversation, stopping any ongoing agent processing.\n\nArguments:\n- agentId (required): Identifier of the agent\n- params (optional): Interrupt parameters\n  - conversationId (required): ID of the conversation to interrupt\n- strict (optional): When true, params.conversationId is required and the last conversation is never used as a fallback (default: false)\n\nReturns: { conversationId, priorState: \"idle\" | \"running\" | \"unknown\", response } - The conversation's run state just before the interrupt and the Codex agent's response\n\nNote: Not all Codex versions support interruption. Check agent capabilities. Use get_conversation_state to skip interrupting an idle conversation.\n\nExample: interrupt({ agentId: \"my-agent\", params: { conversationId: \"c1\" } })")]
     pub async fn interrupt(
         &self,
-        Parameters(InterruptArgs { agent_id, params }): Parameters<InterruptArgs>,
+        Parameters(InterruptArgs {
+            agent_id,
+            params,
+            strict,
+        }): Parameters<InterruptArgs>,
     ) -> Result<CallToolResult, McpError> {
         let params = Self::normalize_params(params);
+        Self::require_explicit_conversation(strict, &params)?;
         let res = self
             .inner
             .manager
-            .interrupt(&agent_id, params)
+            .interrupt_with_state(&agent_id, params)
             .await
+            .map_err(Self::agent_error)?;
+        let value = serde_json::to_value(res)
             .map_err(|e| McpError::internal_error(e.to_string(), None))?;
-        Ok(CallToolResult::structured(res))
+        Ok(CallToolResult::structured(value))
     }
 
     #[tool(description = "List all pending approval requests from Codex agents waiting for user decisions.\n\nArguments: None\n\nReturns: { keys: string[] } - Array of approval keys in format \"agentId:requestId\"\n\nNote: Approvals auto-deny after 60 seconds if not decided.\n\nExample: list_pending_approvals() → { \"keys\": [\"agent-1:42\", \"agent-2:7\"] }")]
@@ -281,7 +662,7 @@ impl Orchestrator {
         Ok(CallToolResult::structured(value))
     }
 
-    #[tool(description = "Resolve a pending Codex approval request by allowing or denying it.\n\nArguments:\n- key (required): Approval key in format \"agentId:requestId\" (from list_pending_approvals)\n- decision (required): \"allow\" to approve, \"deny\" to reject\n\nReturns: { ok: true } if decision was applied\n\nNote: Invalid keys or expired approvals will return an error.\n\nExample: decide_approval({ key: \"agent-1:42\", decision: \"allow\" })")]
+    #[tool(description = "Resolve a pending Codex approval request by allowing or denying it.\n\nArguments:\n- key (required): Approval key in format \"agentId:requestId\" (from list_pending_approvals)\n- decision (required): \"allow\" to approve, \"deny\" to reject (case-insensitive; any other value is rejected and the approval stays pending)\n\nReturns: { ok: true } if decision was applied\n\nNote: Invalid keys, expired approvals, and unknown decision values return an invalid_params error.\n\nExample: decide_approval({ key: \"agent-1:42\", decision: \"allow\" })")]
     pub async fn decide_approval(
         &self,
         Parameters(ApprovalDecisionArgs { key, decision }): Parameters<ApprovalDecisionArgs>,
@@ -296,6 +677,51 @@ impl Orchestrator {
         Ok(CallToolResult::structured(value))
     }
 
+    #[tool(description = "Wait for the next pending approval from an agent and decide it, so a scripted client can auto-approve (or auto-deny) a class of requests without polling list_pending_approvals.\n\nArguments:\n- agentId (required): Agent whose approvals to decide\n- methodFilter (optional): \"applyPatchApproval\" or \"execCommandApproval\"; other approvals are left pending (default: any)\n- decision (required): \"allow\" or \"deny\" (case-insensitive)\n- timeoutMs (optional): Longest wait for a matching approval (default: 30000, max: 600000)\n\nReturns: { decided: true, key, agentId, requestId, method, params, decision, waitedMs }, or { decided: false, timedOut: true, agentId, waitedMs } when none arrived in time\n\nNote: An approval that is already pending counts; the oldest match is decided first. Decides one approval per call.\n\nExample: wait_and_decide_approval({ agentId: \"my-agent\", methodFilter: \"execCommandApproval\", decision: \"allow\", timeoutMs: 10000 })")]
+    pub async fn wait_and_decide_approval(
+        &self,
+        Parameters(WaitAndDecideApprovalArgs {
+            agent_id,
+            method_filter,
+            decision,
+            timeout_ms,
+        }): Parameters<WaitAndDecideApprovalArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        if let Some(method) = method_filter.as_deref() {
+            if !APPROVAL_METHODS.contains(&method) {
+                return Err(McpError::invalid_params(
+                    format!("methodFilter must be one of {APPROVAL_METHODS:?}, got {method:?}"),
+                    None,
+                ));
+            }
+        }
+        if codex::normalize_decision(&decision).is_none() {
+            return Err(McpError::invalid_params(
+                format!("invalid decision {decision:?}: expected \"allow\" or \"deny\""),
+                None,
+            ));
+        }
+        let timeout_ms = timeout_ms.unwrap_or(DEFAULT_APPROVAL_WAIT_MS);
+        if timeout_ms > MAX_APPROVAL_WAIT_MS {
+            return Err(McpError::invalid_params(
+                format!("timeoutMs must be at most {MAX_APPROVAL_WAIT_MS}"),
+                None,
+            ));
+        }
+        let result = self
+            .inner
+            .manager
+            .wait_and_decide_approval(
+                &agent_id,
+                method_filter.as_deref(),
+                &decision,
+                std::time::Duration::from_millis(timeout_ms),
+            )
+            .await
+            .map_err(Self::agent_error)?;
+        Ok(CallToolResult::structured(result))
+    }
+
     #[tool(description = "List all recorded conversations (rollouts) for a Codex agent with optional pagination.\n\nArguments:\n- agentId (required): Identifier of the agent\n- params (optional): Pagination parameters\n  - pageSize (optional): Number of items per page (default: 10)\n  - cursor (optional): Pagination cursor from previous response\n\nReturns: { items: [...], nextCursor?: string }\n  Each item contains: { conversationId, path, preview, timestamp }\n\nExample: list_conversations({ agentId: \"my-agent\", params: { pageSize: 20 } })")]
     pub async fn list_conversations(
         &self,
@@ -307,44 +733,247 @@ impl Orchestrator {
             .manager
             .list_conversations(&agent_id, params)
             .await
-            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+            .map_err(Self::agent_error)?;
         Ok(CallToolResult::structured(res))
     }
 
-    #[tool(description = "Resume a previously recorded Codex conversation from its rollout file, optionally overriding parameters.\n\nArguments:\n- agentId (required): Identifier of the agent\n- params (required): Resume parameters\n  - path (required): Full path to the rollout file (.jsonl)\n  - overrides (optional): Override conversation settings (model, cwd, etc.)\n\nReturns: { conversationId, model, initialMessages?: [...] } - Restored conversation metadata\n\nExample: resume_conversation({ agentId: \"my-agent\", params: { path: \"/path/to/rollout.jsonl\" } })")]
-    pub async fn resume_conversation(
+    #[tool(description = "List recorded conversations across all running agents, each item tagged with its agentId. Agents are queried concurrently.\n\nArguments:\n- pageSize (optional): Number of items requested from each agent\n- cursors (optional): The nextCursors object from a previous call; only those agents are listed, each from its cursor\n\nReturns: { items: [{ agentId, conversationId, path, preview, timestamp }], nextCursors: { [agentId]: cursor }, errors: [{ agentId, error }] }\n  Items are merged newest first. An agent whose listing fails is reported in errors instead of failing the call.\n\nExample: list_all_conversations({ pageSize: 20 })\nExample: list_all_conversations({ pageSize: 20, cursors: { \"agent-1\": \"cursor-token\" } })")]
+    pub async fn list_all_conversations(
         &self,
-        Parameters(ResumeConversationArgs { agent_id, params }): Parameters<ResumeConversationArgs>,
+        Parameters(ListAllConversationsArgs { page_size, cursors }): Parameters<ListAllConversationsArgs>,
     ) -> Result<CallToolResult, McpError> {
-        let params = Self::normalize_params(params);
         let res = self
             .inner
             .manager
-            .resume_conversation(&agent_id, params)
-            .await
-            .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+            .list_all_conversations(page_size, &cursors)
+            .await;
         Ok(CallToolResult::structured(res))
     }
 
-    #[tool(description = "Archive a Codex conversation, marking it as finished and freeing up agent resources.\n\nArguments:\n- agentId (required): Identifier of the agent\n- params (required): Archive parameters\n  - conversationId (required): ID of the conversation to archive\n\nReturns: { ok: true }\n\nNote: Archived conversations remain in rollout files and can be resumed later.\n\nExample: archive_conversation({ agentId: \"my-agent\", params: { conversationId: \"c1\" } })")]
+    #[tool(description = "Resume a previously recorded Codex conversation from its rollout file, optionally overriding parameters.\n\nArguments:\n- agentId (required): Identifier of the agent\n- params (required): Resume parameters\n  - path (required): Full path to the rollout file (.jsonl)\n  - overrides (optional): Override conversation settings (model, cwd, etc.)\n- spawnIfMissing (optional): When true and agentId is not running, spawn a fresh agent with that id before resuming (default: false)\n- cwd (optional): Working directory for an agent spawned via spawnIfMissing\n\nReturns: { agentId, spawned, conversationId, model, initialMessages?: [...] } - Restored conversation metadata plus the agent that now owns it\n\nExample: resume_conversation({ agentId: \"my-agent\", params: { path: \"/path/to/rollout.jsonl\" } })\nExample: resume_conversation({ agentId: \"my-agent\", spawnIfMissing: true, params: { path: \"/path/to/rollout.jsonl\" } })")]
+    pub async fn resume_conversation(
+        &self,
+        Parameters(ResumeConversationArgs {
+            agent_id,
+            params,
+            spawn_if_missing,
+            cwd,
+        }): Parameters<ResumeConversationArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let params = Self::normalize_params(params);
+        let (agent_id, spawned, mut res) = if spawn_if_missing {
+            self.inner
+                .manager
+                .resume_conversation_or_spawn(&agent_id, cwd.map(Into::into), params)
+                .await
+        } else {
+            self.inner
+                .manager
+                .resume_conversation(&agent_id, params)
+                .await
+                .map(|res| (agent_id, false, res))
+        }
+        .map_err(Self::agent_error)?;
+        if let serde_json::Value::Object(ref mut map) = res {
+            map.insert("agentId".to_string(), serde_json::Value::String(agent_id));
+            map.insert("spawned".to_string(), serde_json::Value::Bool(spawned));
+        }
+        Ok(CallToolResult::structured(res))
+    }
+
+    #[tool(description = "Archive a Codex conversation, marking it as finished and freeing up agent resources.\n\nArguments:\n- agentId (required): Identifier of the agent\n- params (required): Archive parameters\n  - conversationId (required): ID of the conversation to archive\n- strict (optional): When true, params.conversationId is required and the last conversation is never used as a fallback (default: false)\n\nReturns: { ok: true }\n\nNote: Archived conversations remain in rollout files and can be resumed later.\n\nExample: archive_conversation({ agentId: \"my-agent\", params: { conversationId: \"c1\" } })")]
     pub async fn archive_conversation(
         &self,
-        Parameters(ArchiveConversationArgs { agent_id, params }): Parameters<ArchiveConversationArgs>,
+        Parameters(ArchiveConversationArgs {
+            agent_id,
+            params,
+            strict,
+        }): Parameters<ArchiveConversationArgs>,
     ) -> Result<CallToolResult, McpError> {
         let params = Self::normalize_params(params);
+        Self::require_explicit_conversation(strict, &params)?;
         let res = self
             .inner
             .manager
             .archive_conversation(&agent_id, params)
             .await
+            .map_err(Self::agent_error)?;
+        Ok(CallToolResult::structured(res))
+    }
+
+    #[tool(description = "Call an arbitrary Codex MCP method on an agent and return its raw result. Use this for Codex methods that have no dedicated tool yet.\n\nArguments:\n- agentId (required): Identifier of the agent\n- method (required): Codex method name (e.g. \"getUserSavedConfig\")\n- params (optional): Params object forwarded verbatim (default: {})\n\nReturns: The method's result as returned by Codex\n\nNote: No defaults or last-conversation fallback are applied; pass exactly what the method expects.\n\nExample: codex_call({ agentId: \"my-agent\", method: \"getUserSavedConfig\" })")]
+    pub async fn codex_call(
+        &self,
+        Parameters(CodexCallArgs {
+            agent_id,
+            method,
+            params,
+        }): Parameters<CodexCallArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        if method.trim().is_empty() {
+            return Err(McpError::invalid_params("method must not be empty", None));
+        }
+        let params = params.unwrap_or_else(|| serde_json::json!({}));
+        let res = self
+            .inner
+            .manager
+            .codex_call(&agent_id, &method, params)
+            .await
+            .map_err(Self::agent_error)?;
+        Ok(CallToolResult::structured(res))
+    }
+
+    #[tool(description = "Report what an agent's Codex build advertised during the MCP initialize handshake, and which methods it has rejected as not found.\n\nArguments:\n- agentId (required): Identifier of the agent\n\nReturns: { agentId, protocolVersion, serverInfo, capabilities, unsupportedMethods: [...] }\n\nNote: Codex does not advertise individual app-server methods, so unsupportedMethods is learned from calls. Once a method is listed there, tools that need it (e.g. interrupt for interruptConversation) fail immediately with error data { kind: \"unsupported_method\", agentId, method }.\n\nExample: get_agent_capabilities({ agentId: \"my-agent\" })")]
+    pub async fn get_agent_capabilities(
+        &self,
+        Parameters(GetAgentCapabilitiesArgs { agent_id }): Parameters<GetAgentCapabilitiesArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let caps = self
+            .inner
+            .manager
+            .agent_capabilities(&agent_id)
+            .await
+            .map_err(Self::agent_error)?;
+        Ok(CallToolResult::structured(caps))
+    }
+
+    #[tool(description = "Look up the rollout file path of a conversation started or resumed through this orchestrator, for use with get_conversation_events.\n\nArguments:\n- agentId (required): Identifier of the agent\n- conversationId (optional): Conversation to look up (default: the agent's last conversation)\n\nReturns: { agentId, conversationId, rolloutPath }\n\nNote: Only paths reported by new_conversation/resume_conversation on this agent are known; use list_conversations for others.\n\nExample: get_rollout_path({ agentId: \"my-agent\" })")]
+    pub async fn get_rollout_path(
+        &self,
+        Parameters(GetRolloutPathArgs {
+            agent_id,
+            conversation_id,
+        }): Parameters<GetRolloutPathArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let (conversation_id, rollout_path) = self
+            .inner
+            .manager
+            .rollout_path(&agent_id, conversation_id.as_deref())
+            .await
+            .map_err(Self::agent_error)?;
+        Ok(CallToolResult::structured(serde_json::json!({
+            "agentId": agent_id,
+            "conversationId": conversation_id,
+            "rolloutPath": rollout_path
+        })))
+    }
+
+    #[tool(description = "Report whether a conversation has a turn in progress, as tracked from Codex task events (task_started, task_complete, turn_aborted) and from turns and interrupts sent through this orchestrator.\n\nArguments:\n- agentId (required): Identifier of the agent\n- conversationId (optional): Conversation to inspect (default: the agent's last conversation)\n\nReturns: { agentId, conversationId, state: \"idle\" | \"running\" | \"unknown\", lastEvent?, updatedAtMs? } - lastEvent is the event or request that set the state\n\nNote: \"unknown\" means no turn was sent and no task event was seen for the conversation.\n\nExample: get_conversation_state({ agentId: \"my-agent\" })")]
+    pub async fn get_conversation_state(
+        &self,
+        Parameters(GetConversationStateArgs {
+            agent_id,
+            conversation_id,
+        }): Parameters<GetConversationStateArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let state = self
+            .inner
+            .manager
+            .conversation_state(&agent_id, conversation_id.as_deref())
+            .await
+            .map_err(Self::agent_error)?;
+        let mut value = serde_json::to_value(state)
             .map_err(|e| McpError::internal_error(e.to_string(), None))?;
+        value["agentId"] = serde_json::json!(agent_id);
+        Ok(CallToolResult::structured(value))
+    }
+
+    #[tool(description = "Get the most recent assistant message of a conversation: \"what did the agent just say\".\n\nArguments:\n- agentId (required): Identifier of the agent\n- conversationId (optional): Conversation to read (default: the agent's last conversation)\n\nReturns: { agentId, conversationId, text, source: \"events\" | \"rollout\" | null, requestId?, receivedAtMs? } - text is null when no assistant message is known yet\n\nNote: The latest agent_message event seen on this orchestrator is used; otherwise the tail of the conversation's recorded rollout file is read. A turn may still be running: check get_conversation_state, or use send_user_turn with stream: true to wait for the reply.\n\nExample: get_last_response({ agentId: \"my-agent\" })")]
+    pub async fn get_last_response(
+        &self,
+        Parameters(GetLastResponseArgs {
+            agent_id,
+            conversation_id,
+        }): Parameters<GetLastResponseArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let res = self
+            .inner
+            .manager
+            .last_response(&agent_id, conversation_id.as_deref())
+            .await
+            .map_err(Self::agent_error)?;
         Ok(CallToolResult::structured(res))
     }
 
-    #[tool(description = "Read events from a Codex conversation rollout file. Returns the last N events from the rollout.\n\nArguments:\n- rolloutPath (required): Full path to the rollout file (.jsonl)\n- limit (optional): Maximum number of events to return (default: 50)\n\nReturns: { events: [...] } - Array of events from the rollout file, most recent last\n\nNote: This is useful for retrieving agent responses when MCP notifications are not visible.\nUse list_conversations to get rollout paths for active conversations.\n\nExample: get_conversation_events({ rolloutPath: \"/path/to/rollout.jsonl\", limit: 20 })")]
+    #[tool(description = "Return an agent's recently forwarded events from the orchestrator's in-memory buffer, optionally for a single conversation.\n\nArguments:\n- agentId (required): Identifier of the agent\n- conversationId (optional): Only events of this conversation (default: all conversations plus agent-wide events such as approval requests)\n- limit (optional): Most recent events to return (default: 50, max: 500)\n- afterSeq (optional): Only events with seq greater than this, for polling (default: 0)\n\nReturns: { agentId, conversationId, events: [{ agentId, conversationId, requestId, method, params, seq }, ...], count, lastSeq } - oldest first; lastSeq is the highest seq returned (or afterSeq when none)\n\nNote: Events are buffered per conversation, 500 per conversation plus 500 without a conversationId, so a busy conversation does not push out another's events. Events with no conversationId are only returned without a conversationId filter.\n\nExample: get_recent_events({ agentId: \"my-agent\", conversationId: \"conv-1\", limit: 20 })")]
+    pub async fn get_recent_events(
+        &self,
+        Parameters(GetRecentEventsArgs {
+            agent_id,
+            conversation_id,
+            limit,
+            after_seq,
+        }): Parameters<GetRecentEventsArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let limit = limit.unwrap_or(DEFAULT_RECENT_EVENTS);
+        if limit > MAX_RECENT_EVENTS {
+            return Err(McpError::invalid_params(
+                format!("limit must be at most {MAX_RECENT_EVENTS}"),
+                None,
+            ));
+        }
+        let after_seq = after_seq.unwrap_or(0);
+        let events = self
+            .inner
+            .manager
+            .recent_events(&agent_id, conversation_id.as_deref(), after_seq, limit)
+            .await
+            .map_err(Self::agent_error)?;
+        let last_seq = events
+            .last()
+            .and_then(|e| e.get("seq"))
+            .and_then(|s| s.as_u64())
+            .unwrap_or(after_seq);
+        Ok(CallToolResult::structured(serde_json::json!({
+            "agentId": agent_id,
+            "conversationId": conversation_id,
+            "count": events.len(),
+            "lastSeq": last_seq,
+            "events": events,
+        })))
+    }
+
+    #[tool(description = "Wait for the next event from an agent, optionally only from one conversation and of one type, without polling.\n\nArguments:\n- agentId (required): Identifier of the agent\n- conversationId (optional): Only events of this conversation\n- eventType (optional): Codex event type such as \"task_complete\" or \"agent_message\", or an orchestrator event kind such as \"approval_request\"\n- afterSeq (optional): Only events with seq greater than this (default: the agent's latest seq, so only new events match)\n- timeoutMs (optional): Longest wait (default: 30000, max: 600000)\n\nReturns: { matched: true, agentId, event, waitedMs }, or { matched: false, timedOut: true, agentId, afterSeq, waitedMs } when nothing matched in time\n\nNote: Pass afterSeq from get_recent_events (lastSeq) or an earlier event to also match events that arrived before the call; the first match after it is returned.\n\nExample: wait_for_event({ agentId: \"my-agent\", conversationId: \"conv-1\", eventType: \"task_complete\", timeoutMs: 120000 })")]
+    pub async fn wait_for_event(
+        &self,
+        Parameters(WaitForEventArgs {
+            agent_id,
+            conversation_id,
+            event_type,
+            after_seq,
+            timeout_ms,
+        }): Parameters<WaitForEventArgs>,
+    ) -> Result<CallToolResult, McpError> {
+        let timeout_ms = timeout_ms.unwrap_or(DEFAULT_EVENT_WAIT_MS);
+        if timeout_ms > MAX_EVENT_WAIT_MS {
+            return Err(McpError::invalid_params(
+                format!("timeoutMs must be at most {MAX_EVENT_WAIT_MS}"),
+                None,
+            ));
+        }
+        let result = self
+            .inner
+            .manager
+            .wait_for_event(
+                &agent_id,
+                conversation_id.as_deref(),
+                event_type.as_deref(),
+                after_seq,
+                std::time::Duration::from_millis(timeout_ms),
+            )
+            .await
+            .map_err(Self::agent_error)?;
+        Ok(CallToolResult::structured(result))
+    }
+
+    #[tool(description = "Read events from a Codex conversation rollout file. Returns the last N events from the rollout.\n\nArguments:\n- rolloutPath (required): Full path to the rollout file (.jsonl)\n- limit (optional): Maximum number of events to return (default: 50)\n- reportSkipped (optional): Also list corrupt lines as skipped: [{ line, error }] (default: false)\n\nReturns: { events: [...], count, skippedCount, partialLine } - Array of events from the rollout file, most recent last; skippedCount counts lines that are not valid JSON, and partialLine is true when the file ends in an unfinished line (still being written), which is left out without counting as skipped\n\nNote: This is useful for retrieving agent responses when MCP notifications are not visible.\nUse get_rollout_path or list_conversations to get rollout paths for active conversations.\n\nExample: get_conversation_events({ rolloutPath: \"/path/to/rollout.jsonl\", limit: 20 })")]
     pub async fn get_conversation_events(
         &self,
-        Parameters(GetConversationEventsArgs { rollout_path, limit }): Parameters<GetConversationEventsArgs>,
+        Parameters(GetConversationEventsArgs {
+            rollout_path,
+            limit,
+            report_skipped,
+        }): Parameters<GetConversationEventsArgs>,
     ) -> Result<CallToolResult, McpError> {
         let limit = limit.unwrap_or(50);
 
@@ -358,19 +987,25 @@ impl Orchestrator {
         .map_err(|e| McpError::invalid_params(format!("Failed to read rollout file: {}", e), None))?;
 
         // Parse JSONL - each line is an event
-        let events: Vec<serde_json::Value> = file_content
-            .lines()
-            .filter_map(|line| serde_json::from_str(line).ok())
-            .collect();
+        let codex::RolloutEvents {
+            events,
+            skipped,
+            partial_line,
+        } = codex::parse_rollout(&file_content);
 
         // Take last N events
         let start_idx = events.len().saturating_sub(limit);
         let recent_events: Vec<serde_json::Value> = events.into_iter().skip(start_idx).collect();
 
-        let result = serde_json::json!({
+        let mut result = serde_json::json!({
             "events": recent_events,
-            "count": recent_events.len()
+            "count": recent_events.len(),
+            "skippedCount": skipped.len(),
+            "partialLine": partial_line
         });
+        if report_skipped {
+            result["skipped"] = serde_json::json!(skipped);
+        }
 
         Ok(CallToolResult::structured(result))
     }
@@ -410,6 +1045,37 @@ pub async fn notify_codex_event(_agent_id: &str, _event: serde_json::Value) -> R
     Ok(())
 }
 
+/// Forward a request Codex sent to the orchestrator upstream as an MCP request and return the
+/// client's result. Only methods MCP lets a server send to its client (`elicitation/create`,
+/// `sampling/createMessage`, `roots/list`, `ping`) can be relayed; for anything else, or when
+/// no client is connected, returns `Ok(None)` so the caller can fall back to a local reply.
+/// A client-side failure comes back as the client's own error.
+pub async fn forward_codex_request(
+    method: &str,
+    params: serde_json::Value,
+) -> Result<Option<serde_json::Value>, McpError> {
+    let Some(peer) = UPSTREAM_PEER.get() else {
+        return Ok(None);
+    };
+    let mut raw = serde_json::json!({ "method": method });
+    if !params.is_null() {
+        raw["params"] = params;
+    }
+    let Ok(request) = serde_json::from_value::<ServerRequest>(raw) else {
+        return Ok(None);
+    };
+    match peer.send_request(request).await {
+        Ok(result) => serde_json::to_value(result)
+            .map(Some)
+            .map_err(|e| McpError::internal_error(e.to_string(), None)),
+        Err(ServiceError::McpError(err)) => Err(err),
+        Err(e) => Err(McpError::internal_error(
+            format!("forwarding {method} upstream failed: {e}"),
+            None,
+        )),
+    }
+}
+
 /// Request applyPatchApproval from the upstream MCP client and return decision.
 #[allow(dead_code)]
 pub async fn request_apply_patch_approval(
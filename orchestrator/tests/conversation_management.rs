@@ -1,5 +1,5 @@
 use anyhow::Result;
-use codex_orchestrator::codex::Manager;
+use codex_orchestrator::codex::{parse_rollout, Manager};
 mod util;
 
 fn set_stub_codex() {
@@ -143,6 +143,61 @@ async fn test_list_conversations_pagination() -> Result<()> {
     .await
 }
 
+#[tokio::test]
+async fn test_list_all_conversations_across_agents() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        let agent_a = mgr.spawn_agent(Some("fleet-a".to_string()), None).await?;
+        let agent_b = mgr.spawn_agent(Some("fleet-b".to_string()), None).await?;
+
+        let mut created = Vec::new();
+        for (agent_id, count) in [(&agent_a, 1), (&agent_b, 3)] {
+            for i in 0..count {
+                let conv = mgr
+                    .new_conversation(agent_id, serde_json::json!(format!("Conversation {}", i)))
+                    .await?;
+                let cid = conv
+                    .get("conversationId")
+                    .and_then(|v| v.as_str())
+                    .unwrap()
+                    .to_string();
+                created.push((agent_id.clone(), cid));
+            }
+        }
+
+        // Every agent's conversations come back tagged with the agent that owns them
+        let result = mgr
+            .list_all_conversations(None, &serde_json::Map::new())
+            .await;
+        let items = result.get("items").and_then(|v| v.as_array()).unwrap();
+        assert_eq!(items.len(), created.len());
+        for (agent_id, cid) in &created {
+            assert!(items.iter().any(|item| {
+                item.get("agentId").and_then(|v| v.as_str()) == Some(agent_id.as_str())
+                    && item.get("conversationId").and_then(|v| v.as_str()) == Some(cid.as_str())
+            }));
+        }
+        assert!(result["errors"].as_array().unwrap().is_empty());
+
+        // Paging continues only the agents that have more
+        let first = mgr
+            .list_all_conversations(Some(2), &serde_json::Map::new())
+            .await;
+        assert_eq!(first["items"].as_array().unwrap().len(), 3);
+        let cursors = first["nextCursors"].as_object().unwrap().clone();
+        assert_eq!(cursors.keys().collect::<Vec<_>>(), vec!["fleet-b"]);
+        let rest = mgr.list_all_conversations(Some(2), &cursors).await;
+        let rest_items = rest["items"].as_array().unwrap();
+        assert_eq!(rest_items.len(), 1);
+        assert_eq!(rest_items[0]["agentId"], "fleet-b");
+
+        mgr.kill_all_agents().await;
+        Ok(())
+    })
+    .await
+}
+
 #[tokio::test]
 async fn test_resume_conversation() -> Result<()> {
     set_stub_codex();
@@ -224,6 +279,142 @@ async fn test_resume_conversation_with_overrides() -> Result<()> {
     .await
 }
 
+#[tokio::test]
+async fn test_resume_conversation_spawns_missing_agent() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        let agent_id = mgr.spawn_agent(Some("rehydrate-agent".to_string()), None).await?;
+
+        let conv = mgr
+            .new_conversation(&agent_id, serde_json::json!("Before restart"))
+            .await?;
+        let rollout_path = conv
+            .get("rolloutPath")
+            .and_then(|v| v.as_str())
+            .unwrap()
+            .to_string();
+
+        // Simulate the agent disappearing (e.g. orchestrator restart)
+        mgr.kill_agent(&agent_id).await?;
+        assert!(!mgr.list_agents().await.contains(&agent_id));
+
+        let (resumed_agent, spawned, resumed) = mgr
+            .resume_conversation_or_spawn(
+                &agent_id,
+                None,
+                serde_json::json!({ "path": rollout_path }),
+            )
+            .await?;
+
+        assert!(spawned, "Missing agent should be spawned");
+        assert_eq!(resumed_agent, agent_id);
+        assert!(resumed.get("conversationId").is_some());
+        assert!(mgr.list_agents().await.contains(&agent_id));
+
+        // A running agent is reused rather than respawned
+        let (_, spawned_again, _) = mgr
+            .resume_conversation_or_spawn(
+                &agent_id,
+                None,
+                serde_json::json!({ "path": rollout_path }),
+            )
+            .await?;
+        assert!(!spawned_again, "Running agent should be reused");
+
+        mgr.kill_agent(&agent_id).await?;
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_rollout_path_cached_per_conversation() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        let agent_id = mgr.spawn_agent(Some("test-agent".to_string()), None).await?;
+
+        // No conversation yet: nothing to default to
+        assert!(mgr.rollout_path(&agent_id, None).await.is_err());
+
+        let first = mgr
+            .new_conversation(&agent_id, serde_json::json!("First"))
+            .await?;
+        let cid1 = first.get("conversationId").and_then(|v| v.as_str()).unwrap().to_string();
+        let path1 = first.get("rolloutPath").and_then(|v| v.as_str()).unwrap().to_string();
+        let second = mgr
+            .new_conversation(&agent_id, serde_json::json!("Second"))
+            .await?;
+        let cid2 = second.get("conversationId").and_then(|v| v.as_str()).unwrap().to_string();
+        let path2 = second.get("rolloutPath").and_then(|v| v.as_str()).unwrap().to_string();
+
+        // Defaults to the last conversation; explicit ids resolve their own path
+        assert_eq!(mgr.rollout_path(&agent_id, None).await?, (cid2.clone(), path2));
+        assert_eq!(mgr.rollout_path(&agent_id, Some(&cid1)).await?, (cid1.clone(), path1.clone()));
+        assert!(mgr.rollout_path(&agent_id, Some("no-such-conversation")).await.is_err());
+
+        // Resuming makes that conversation the default again
+        mgr.resume_conversation(&agent_id, serde_json::json!({"path": path1}))
+            .await?;
+        assert_eq!(mgr.rollout_path(&agent_id, None).await?, (cid1, path1));
+
+        mgr.kill_agent(&agent_id).await?;
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_last_response_from_rollout_tail() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        let agent_id = mgr.spawn_agent(Some("last-response-agent".to_string()), None).await?;
+
+        // No conversation yet: nothing to default to
+        assert!(mgr.last_response(&agent_id, None).await.is_err());
+
+        let conv = mgr
+            .new_conversation(&agent_id, serde_json::json!("Last response"))
+            .await?;
+        let cid = conv
+            .get("conversationId")
+            .and_then(|v| v.as_str())
+            .unwrap()
+            .to_string();
+        let rollout_path = conv
+            .get("rolloutPath")
+            .and_then(|v| v.as_str())
+            .unwrap()
+            .to_string();
+
+        // The stub never writes rollouts; supply one whose last assistant message is split
+        // into parts and followed by a non-message event.
+        let lines = [
+            serde_json::json!({"type": "event_msg", "payload": {"type": "agent_message", "message": "earlier"}}),
+            serde_json::json!({"type": "response_item", "payload": {"type": "message", "role": "assistant", "content": [
+                {"type": "output_text", "text": "Done: "},
+                {"type": "output_text", "text": "3 files changed"}
+            ]}}),
+            serde_json::json!({"type": "event_msg", "payload": {"type": "token_count"}}),
+        ];
+        let body: String = lines.iter().map(|l| format!("{l}\n")).collect();
+        std::fs::write(&rollout_path, body)?;
+
+        let last = mgr.last_response(&agent_id, None).await;
+        let _ = std::fs::remove_file(&rollout_path);
+        let last = last?;
+        assert_eq!(last["conversationId"], serde_json::json!(cid));
+        assert_eq!(last["source"], serde_json::json!("rollout"));
+        assert_eq!(last["text"], serde_json::json!("Done: 3 files changed"));
+
+        mgr.kill_agent(&agent_id).await?;
+        Ok(())
+    })
+    .await
+}
+
 #[tokio::test]
 async fn test_archive_conversation() -> Result<()> {
     set_stub_codex();
@@ -384,3 +575,26 @@ async fn test_full_conversation_lifecycle() -> Result<()> {
     })
     .await
 }
+
+#[test]
+fn test_rollout_parse_reports_corrupt_lines() {
+    let content = "{\"type\":\"a\"}\n{\"type\": \"b\"\n\n{\"type\":\"c\"}\n{\"type\":\"d\"";
+    let parsed = parse_rollout(content);
+    let types: Vec<&str> = parsed
+        .events
+        .iter()
+        .filter_map(|e| e["type"].as_str())
+        .collect();
+    assert_eq!(types, vec!["a", "c"]);
+    // The truncated second line is reported; the unterminated last line is still being written
+    assert_eq!(parsed.skipped.len(), 1);
+    assert_eq!(parsed.skipped[0].line, 2);
+    assert!(parsed.partial_line);
+
+    // Once terminated, a corrupt last line counts as skipped
+    let parsed = parse_rollout("{\"type\":\"a\"}\nnot json\n");
+    assert_eq!(parsed.events.len(), 1);
+    assert_eq!(parsed.skipped.len(), 1);
+    assert_eq!(parsed.skipped[0].line, 2);
+    assert!(!parsed.partial_line);
+}
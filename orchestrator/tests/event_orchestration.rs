@@ -1,5 +1,5 @@
 use anyhow::Result;
-use codex_orchestrator::codex::Manager;
+use codex_orchestrator::codex::{normalize_decision, Manager};
 mod util;
 
 fn set_stub_codex() {
@@ -24,7 +24,7 @@ async fn test_notification_from_agent() -> Result<()> {
             .unwrap()
             .to_string();
 
-        // Send a user message - stub_codex will respond and send a notification
+        // Send a user message - stub_codex plays the turn out as notifications
         let _ = mgr
             .send_user_message(
                 &agent_id,
@@ -35,12 +35,19 @@ async fn test_notification_from_agent() -> Result<()> {
             )
             .await?;
 
-        // Give the read loop time to process the notification
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
-
-        // We can't directly verify the notification was forwarded upstream in this test
-        // without a mock MCP client, but we can verify the message succeeded
-        // The notification handling is tested indirectly through the read loop
+        // The read loop buffers the turn's events under the conversation
+        let reply = mgr
+            .wait_for_event(
+                &agent_id,
+                Some(&cid),
+                Some("agent_message"),
+                Some(0),
+                std::time::Duration::from_secs(5),
+            )
+            .await?;
+        assert_eq!(reply["matched"], true, "{reply}");
+        assert_eq!(reply["event"]["method"], "codex/event");
+        assert_eq!(reply["event"]["params"]["msg"]["message"], "stub reply: hello");
 
         mgr.kill_agent(&agent_id).await?;
         Ok(())
@@ -87,28 +94,16 @@ async fn test_approval_request_flow() -> Result<()> {
             }
         });
 
-        // Give time for approval to be registered
-        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-
-        // Check if approval is pending
-        let approvals = mgr.list_pending_approvals().await;
-
-        // The approval should be in the list
-        assert!(
-            !approvals.is_empty() || send_task.is_finished(),
-            "Should have pending approval or task completed"
-        );
-
-        // If there's a pending approval, decide it
-        if let Some(key) = approvals.first() {
-            let _ = mgr.decide_approval(key, "allow".to_string()).await;
-        }
+        // The stub holds the turn until its approval is decided
+        let approvals = util::pending_approvals(&mgr).await?;
+        assert_eq!(approvals.len(), 1, "{approvals:?}");
+        assert!(!send_task.is_finished());
+        assert!(mgr.decide_approval(&approvals[0], "allow".to_string()).await?);
 
-        // Wait for the send_user_turn to complete
-        let _ = tokio::time::timeout(
-            tokio::time::Duration::from_secs(2),
-            send_task
-        ).await;
+        // Deciding the approval lets the turn complete
+        let turn = tokio::time::timeout(tokio::time::Duration::from_secs(5), send_task).await???;
+        assert!(turn["requestId"].is_i64(), "{turn}");
+        assert!(mgr.list_pending_approvals().await.is_empty());
 
         mgr.kill_agent(&agent_id).await?;
         Ok(())
@@ -155,21 +150,13 @@ async fn test_approval_timeout() -> Result<()> {
             }
         });
 
-        // Give time for approval to be registered
-        tokio::time::sleep(tokio::time::Duration::from_millis(200)).await;
-
-        // Check if approval is pending
-        let approvals = mgr.list_pending_approvals().await;
-
         // There should be a pending approval
-        let has_approval = !approvals.is_empty();
+        let approvals = util::pending_approvals(&mgr).await?;
 
         // Don't decide it - let it timeout (would take 60s in real scenario)
         // For testing purposes, we just verify the approval was registered
-
-        if has_approval {
-            eprintln!("Approval pending (will timeout if not decided): {:?}", approvals);
-        }
+        eprintln!("Approval pending (will timeout if not decided): {:?}", approvals);
+        assert!(!send_task.is_finished());
 
         // Cancel the send task
         send_task.abort();
@@ -223,12 +210,11 @@ async fn test_list_approvals() -> Result<()> {
             }
         });
 
-        // Give time for approval to be created
-        tokio::time::sleep(tokio::time::Duration::from_millis(300)).await;
-
         // Check approvals list
-        let approvals = mgr.list_pending_approvals().await;
+        let approvals = util::pending_approvals(&mgr).await?;
         eprintln!("Pending approvals: {:?}", approvals);
+        assert_eq!(approvals.len(), 1);
+        assert!(approvals[0].starts_with(&format!("{agent_id}:")));
 
         // Clean up
         for key in &approvals {
@@ -259,3 +245,182 @@ async fn test_decide_approval_invalid_key() -> Result<()> {
     })
     .await
 }
+
+#[tokio::test]
+async fn test_decide_approval_rejects_unknown_decision() -> Result<()> {
+    // Accepted values are normalized; everything else is refused
+    assert_eq!(normalize_decision(" Allow "), Some("allow"));
+    assert_eq!(normalize_decision("DENY"), Some("deny"));
+    for bad in ["approve", "yes", "", "allowed"] {
+        assert_eq!(normalize_decision(bad), None, "{bad:?} should be rejected");
+    }
+
+    // The decision is validated before the key is looked up
+    let mgr = Manager::default();
+    let err = mgr
+        .decide_approval("some-agent:1", "approve".to_string())
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("invalid decision"), "{err}");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_wait_and_decide_approval() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        let agent_id = mgr.spawn_agent(Some("auto-approve-agent".to_string()), None).await?;
+        let conv = mgr
+            .new_conversation(&agent_id, serde_json::json!("Auto approval test"))
+            .await?;
+        let cid = conv
+            .get("conversationId")
+            .and_then(|v| v.as_str())
+            .unwrap()
+            .to_string();
+
+        // Nothing pending: the wait ends at the timeout without deciding anything
+        let idle = mgr
+            .wait_and_decide_approval(&agent_id, None, "allow", std::time::Duration::from_millis(100))
+            .await?;
+        assert_eq!(idle["decided"], false);
+        assert_eq!(idle["timedOut"], true);
+
+        // Start waiting before the approval exists; it is decided as soon as it arrives
+        let waiter = tokio::spawn({
+            let mgr = mgr.clone();
+            let agent_id = agent_id.clone();
+            async move {
+                mgr.wait_and_decide_approval(
+                    &agent_id,
+                    Some("execCommandApproval"),
+                    "Allow",
+                    std::time::Duration::from_secs(5),
+                )
+                .await
+            }
+        });
+        let send_task = tokio::spawn({
+            let mgr = mgr.clone();
+            let agent_id = agent_id.clone();
+            async move {
+                mgr.send_user_turn(
+                    &agent_id,
+                    serde_json::json!({
+                        "conversationId": cid,
+                        "items": [{"type": "text", "data": {"text": "test"}}],
+                        "testApproval": true
+                    }),
+                )
+                .await
+            }
+        });
+
+        let decided = waiter.await??;
+        assert_eq!(decided["decided"], true);
+        assert_eq!(decided["method"], "execCommandApproval");
+        assert_eq!(decided["decision"], "allow");
+        assert_eq!(
+            decided["key"],
+            format!("{}:{}", agent_id, decided["requestId"].as_str().unwrap())
+        );
+        assert!(mgr.list_pending_approvals().await.is_empty());
+
+        // Invalid decisions are rejected before waiting
+        assert!(mgr
+            .wait_and_decide_approval(&agent_id, None, "yes", std::time::Duration::from_secs(5))
+            .await
+            .is_err());
+
+        let _ = tokio::time::timeout(tokio::time::Duration::from_secs(2), send_task).await;
+        mgr.kill_agent(&agent_id).await?;
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn test_events_buffered_by_conversation() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        let agent_id = mgr.spawn_agent(Some("event-buffer-agent".to_string()), None).await?;
+        let conv = mgr
+            .new_conversation(&agent_id, serde_json::json!("Event buffer test"))
+            .await?;
+        let cid = conv
+            .get("conversationId")
+            .and_then(|v| v.as_str())
+            .unwrap()
+            .to_string();
+
+        // Start waiting before the turn, so only events it produces can match
+        let waiter = tokio::spawn({
+            let mgr = mgr.clone();
+            let agent_id = agent_id.clone();
+            let cid = cid.clone();
+            async move {
+                mgr.wait_for_event(
+                    &agent_id,
+                    Some(&cid),
+                    None,
+                    None,
+                    std::time::Duration::from_secs(5),
+                )
+                .await
+            }
+        });
+        mgr.send_user_turn(
+            &agent_id,
+            serde_json::json!({
+                "conversationId": cid,
+                "items": [{"type": "text", "data": {"text": "hello"}}]
+            }),
+        )
+        .await?;
+        let waited = waiter.await??;
+        assert_eq!(waited["matched"], true);
+        assert_eq!(waited["event"]["conversationId"], cid.as_str());
+
+        // The conversation's bucket holds its events in seq order
+        let events = mgr.recent_events(&agent_id, Some(&cid), 0, 500).await?;
+        assert!(!events.is_empty());
+        assert!(events.iter().all(|e| e["conversationId"] == cid.as_str()));
+        let seqs: Vec<u64> = events.iter().map(|e| e["seq"].as_u64().unwrap()).collect();
+        assert!(seqs.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(mgr.recent_events(&agent_id, Some(&cid), 0, 1).await?.len(), 1);
+
+        // Other conversations see nothing; events after the last seq are empty
+        assert!(mgr
+            .recent_events(&agent_id, Some("no-such-conversation"), 0, 500)
+            .await?
+            .is_empty());
+        let last = *seqs.last().unwrap();
+        assert!(mgr.recent_events(&agent_id, None, last, 500).await?.is_empty());
+
+        // An earlier afterSeq matches an event that already arrived
+        let first = &events[0];
+        let replay = mgr
+            .wait_for_event(
+                &agent_id,
+                Some(&cid),
+                first["params"]["msg"]["type"].as_str(),
+                Some(0),
+                std::time::Duration::from_millis(100),
+            )
+            .await?;
+        assert_eq!(replay["matched"], true);
+        assert_eq!(replay["event"]["seq"], first["seq"]);
+
+        // Nothing new: the wait times out
+        let idle = mgr
+            .wait_for_event(&agent_id, Some(&cid), None, None, std::time::Duration::from_millis(100))
+            .await?;
+        assert_eq!(idle["timedOut"], true);
+
+        mgr.kill_agent(&agent_id).await?;
+        Ok(())
+    })
+    .await
+}
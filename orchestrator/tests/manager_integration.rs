@@ -1,5 +1,7 @@
 use anyhow::Result;
-use codex_orchestrator::codex::Manager;
+use codex_orchestrator::codex::{
+    AgentDrainingError, AgentInfo, AgentLabels, Manager, RunState, SpawnError,
+};
 mod util;
 
 fn set_stub_codex() {
@@ -59,3 +61,502 @@ async fn conversation_flow_send_message_and_turn() -> Result<()> {
     })
     .await
 }
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn concurrent_spawns_initialize_without_races() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+
+        // Spawn several agents at once with auto-generated ids; each must complete its own
+        // initialize handshake before its read loop starts.
+        let mut tasks = Vec::new();
+        for _ in 0..8 {
+            let mgr = mgr.clone();
+            tasks.push(tokio::spawn(async move { mgr.spawn_agent(None, None).await }));
+        }
+        let mut ids = Vec::new();
+        for task in tasks {
+            ids.push(task.await??);
+        }
+
+        let unique: std::collections::HashSet<_> = ids.iter().cloned().collect();
+        assert_eq!(unique.len(), ids.len(), "auto-generated agent ids must be unique");
+        let listed = mgr.list_agents().await;
+        assert_eq!(listed.len(), ids.len());
+
+        // Every agent's read loop is live: a request round-trips on each of them
+        for id in &ids {
+            let conv = mgr
+                .new_conversation(id, serde_json::json!("Concurrent spawn"))
+                .await?;
+            assert!(conv.get("conversationId").is_some());
+        }
+
+        // Racing spawns on the same explicit id leave exactly one agent registered
+        let a = mgr.clone();
+        let b = mgr.clone();
+        let (ra, rb) = tokio::join!(
+            a.spawn_agent(Some("dup-agent".to_string()), None),
+            b.spawn_agent(Some("dup-agent".to_string()), None)
+        );
+        assert!(ra.is_ok() != rb.is_ok(), "exactly one duplicate spawn should succeed");
+
+        for id in mgr.list_agents().await {
+            mgr.kill_agent(&id).await?;
+        }
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn codex_call_forwards_arbitrary_methods() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        let agent_id = mgr.spawn_agent(Some("raw-call-agent".to_string()), None).await?;
+
+        let conv = mgr
+            .codex_call(&agent_id, "newConversation", serde_json::json!({}))
+            .await?;
+        let cid = conv
+            .get("conversationId")
+            .and_then(|v| v.as_str())
+            .unwrap()
+            .to_string();
+
+        // Result comes back unmodified from the agent
+        let listed = mgr
+            .codex_call(&agent_id, "listConversations", serde_json::json!({}))
+            .await?;
+        let items = listed.get("items").and_then(|v| v.as_array()).unwrap();
+        assert!(items
+            .iter()
+            .any(|item| item.get("conversationId").and_then(|v| v.as_str()) == Some(cid.as_str())));
+
+        // Unknown agents are reported rather than forwarded
+        assert!(mgr
+            .codex_call("no-such-agent", "listConversations", serde_json::json!({}))
+            .await
+            .is_err());
+
+        mgr.kill_agent(&agent_id).await?;
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn kill_all_agents_clears_registry() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        for id in ["fleet-a", "fleet-b", "fleet-c"] {
+            mgr.spawn_agent(Some(id.to_string()), None).await?;
+        }
+
+        let killed = mgr.kill_all_agents().await;
+        assert_eq!(killed, vec!["fleet-a", "fleet-b", "fleet-c"]);
+        assert!(mgr.list_agents().await.is_empty());
+
+        // Killed agents are gone for good; a second sweep finds nothing
+        assert!(mgr
+            .new_conversation("fleet-a", serde_json::json!({}))
+            .await
+            .is_err());
+        assert!(mgr.kill_all_agents().await.is_empty());
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn reap_idle_kills_only_idle_agents() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        mgr.spawn_agent(Some("idle-agent".to_string()), None).await?;
+        mgr.spawn_agent(Some("busy-agent".to_string()), None).await?;
+
+        // Wait until both agents have been idle past the threshold
+        util::eventually("both agents to idle for 250ms", || {
+            let mgr = mgr.clone();
+            async move {
+                let report = mgr.health().await;
+                let idle = report.iter().filter(|a| a["idleMs"].as_u64() >= Some(250)).count();
+                (idle == 2).then_some(())
+            }
+        })
+        .await?;
+        // Any RPC counts as activity
+        mgr.list_conversations("busy-agent", serde_json::json!({}))
+            .await?;
+
+        let reaped = mgr.reap_idle(std::time::Duration::from_millis(200)).await;
+        assert_eq!(reaped, vec!["idle-agent"]);
+        assert_eq!(mgr.list_agents().await, vec!["busy-agent".to_string()]);
+
+        // Nothing is idle long enough under a generous threshold
+        assert!(mgr
+            .reap_idle(std::time::Duration::from_secs(60))
+            .await
+            .is_empty());
+
+        mgr.kill_all_agents().await;
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn spawn_with_prompt_starts_first_turn() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        let (agent_id, cid, _turn) = mgr
+            .spawn_with_prompt(
+                Some("prompted-agent".to_string()),
+                None,
+                Default::default(),
+                "Start working on X",
+            )
+            .await?;
+        assert_eq!(agent_id, "prompted-agent");
+        assert!(mgr.list_agents().await.contains(&agent_id));
+
+        // The new conversation is the agent's default for follow-up calls
+        let (last, _) = mgr.rollout_path(&agent_id, None).await?;
+        assert_eq!(last, cid);
+        let listed = mgr
+            .list_conversations(&agent_id, serde_json::json!({}))
+            .await?;
+        let items = listed.get("items").and_then(|v| v.as_array()).unwrap();
+        assert!(items
+            .iter()
+            .any(|item| item.get("conversationId").and_then(|v| v.as_str()) == Some(cid.as_str())));
+
+        mgr.kill_agent(&agent_id).await?;
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn labeled_agents_are_listed_and_found() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        let mut metadata = serde_json::Map::new();
+        metadata.insert("task".to_string(), serde_json::json!("review"));
+        let labels = AgentLabels {
+            tags: vec!["repo:api".to_string(), "urgent".to_string()],
+            metadata,
+        };
+        mgr.spawn_labeled_agent(Some("labeled-a".to_string()), None, labels)
+            .await?;
+        let other = AgentLabels {
+            tags: vec!["repo:web".to_string()],
+            ..Default::default()
+        };
+        mgr.spawn_labeled_agent(Some("labeled-b".to_string()), None, other)
+            .await?;
+        mgr.spawn_agent(Some("labeled-c".to_string()), None).await?;
+
+        // Listing returns every agent sorted by id, with its labels
+        let all = mgr.agent_infos(&[], &serde_json::Map::new()).await;
+        let ids: Vec<_> = all.iter().map(|a| a.agent_id.as_str()).collect();
+        assert_eq!(ids, vec!["labeled-a", "labeled-b", "labeled-c"]);
+        assert_eq!(all[0].tags, vec!["repo:api", "urgent"]);
+        assert_eq!(all[0].metadata.get("task"), Some(&serde_json::json!("review")));
+        assert!(all[2].tags.is_empty() && all[2].metadata.is_empty());
+
+        // Filters require every tag and an equal value for every metadata key
+        let found = mgr
+            .agent_infos(&["urgent".to_string()], &serde_json::Map::new())
+            .await;
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].agent_id, "labeled-a");
+        let mut wrong = serde_json::Map::new();
+        wrong.insert("task".to_string(), serde_json::json!("build"));
+        assert!(mgr
+            .agent_infos(&["repo:api".to_string()], &wrong)
+            .await
+            .is_empty());
+
+        mgr.kill_all_agents().await;
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn interrupt_reports_prior_run_state() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        let agent_id = mgr.spawn_agent(Some("run-state-agent".to_string()), None).await?;
+        let conv = mgr
+            .new_conversation(&agent_id, serde_json::json!("Run state"))
+            .await?;
+        let cid = conv
+            .get("conversationId")
+            .and_then(|v| v.as_str())
+            .unwrap()
+            .to_string();
+
+        // Nothing has been sent yet
+        let state = mgr.conversation_state(&agent_id, None).await?;
+        assert_eq!(state.conversation_id, cid);
+        assert_eq!(state.state, RunState::Unknown);
+
+        // A sent turn marks the conversation running until an interrupt ends it
+        mgr.send_user_turn(&agent_id, serde_json::json!("Keep going"))
+            .await?;
+        // (a fast stub may already have reported the task complete)
+        let state = mgr.conversation_state(&agent_id, Some(&cid)).await?;
+        assert_ne!(state.state, RunState::Unknown);
+        assert!(state.last_event.is_some());
+
+        let outcome = mgr
+            .interrupt_with_state(&agent_id, serde_json::json!({"conversationId": cid}))
+            .await?;
+        assert_eq!(outcome.conversation_id.as_deref(), Some(cid.as_str()));
+        assert_ne!(outcome.prior_state, RunState::Unknown);
+        assert!(outcome.response.get("abortReason").is_some());
+        let state = mgr.conversation_state(&agent_id, None).await?;
+        assert_eq!(state.state, RunState::Idle);
+
+        // A second interrupt finds the conversation idle
+        let outcome = mgr
+            .interrupt_with_state(&agent_id, serde_json::json!({"conversationId": cid}))
+            .await?;
+        assert_eq!(outcome.prior_state, RunState::Idle);
+
+        mgr.kill_agent(&agent_id).await?;
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn turn_results_carry_request_ids() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        let agent_id = mgr.spawn_agent(Some("request-id-agent".to_string()), None).await?;
+        mgr.new_conversation(&agent_id, serde_json::json!("Correlation"))
+            .await?;
+
+        // Each turn result reports its request id, the one stamped on the turn's events
+        let first = mgr
+            .send_user_turn(&agent_id, serde_json::json!("First"))
+            .await?;
+        let second = mgr
+            .send_user_message(&agent_id, serde_json::json!("Second"))
+            .await?;
+        let first_id = first.get("requestId").and_then(|v| v.as_i64()).unwrap();
+        let second_id = second.get("requestId").and_then(|v| v.as_i64()).unwrap();
+        assert!(second_id > first_id);
+
+        mgr.kill_agent(&agent_id).await?;
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn replace_keeps_the_running_agent_until_the_new_one_starts() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        mgr.spawn_agent(Some("swap-agent".to_string()), None).await?;
+        let pid = || async {
+            mgr.agent_infos(&[], &serde_json::Map::new()).await[0]
+                .pid
+                .expect("agent pid")
+        };
+        let old_pid = pid().await;
+
+        // A replacement that cannot start leaves the running agent in place
+        let err = mgr
+            .replace_agent_with_bin(
+                Some("swap-agent".to_string()),
+                None,
+                AgentLabels::default(),
+                Some("/nonexistent/codex-bin".to_string()),
+            )
+            .await
+            .unwrap_err();
+        let kind = err.downcast_ref::<SpawnError>().map(|e| e.kind());
+        assert_eq!(kind, Some("invalid_binary"), "{err:#}");
+        assert_eq!(pid().await, old_pid);
+        assert!(mgr.list_conversations("swap-agent", serde_json::json!({})).await.is_ok());
+
+        // A working replacement is swapped in, then the old process is killed
+        let (agent_id, replaced) = mgr
+            .replace_agent_with_bin(Some("swap-agent".to_string()), None, AgentLabels::default(), None)
+            .await?;
+        assert_eq!(agent_id, "swap-agent");
+        assert!(replaced);
+        let new_pid = pid().await;
+        assert_ne!(new_pid, old_pid);
+        assert_eq!(mgr.list_agents().await, vec!["swap-agent".to_string()]);
+        assert!(mgr.list_conversations("swap-agent", serde_json::json!({})).await.is_ok());
+
+        mgr.kill_all_agents().await;
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn spawn_with_taken_id_fails_unless_replaced() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        mgr.spawn_agent(Some("taken-agent".to_string()), None).await?;
+        let pid_of = |infos: Vec<AgentInfo>| infos[0].pid.unwrap();
+        let old_pid = pid_of(mgr.agent_infos(&[], &serde_json::Map::new()).await);
+
+        // A second spawn on the same id is refused and leaves the first agent alone
+        let err = mgr
+            .spawn_agent(Some("taken-agent".to_string()), None)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("agent already exists"));
+        assert_eq!(pid_of(mgr.agent_infos(&[], &serde_json::Map::new()).await), old_pid);
+
+        // Replacing swaps in a new agent under the id and kills the old child
+        let (_, replaced) = mgr
+            .replace_agent_with_bin(Some("taken-agent".to_string()), None, AgentLabels::default(), None)
+            .await?;
+        assert!(replaced);
+        let new_pid = pid_of(mgr.agent_infos(&[], &serde_json::Map::new()).await);
+        assert_ne!(new_pid, old_pid);
+        assert!(
+            !std::path::Path::new(&format!("/proc/{old_pid}")).exists(),
+            "replaced agent process should be killed and reaped, not orphaned"
+        );
+        assert_eq!(mgr.list_agents().await, vec!["taken-agent".to_string()]);
+
+        // Nothing to replace on an unused id
+        let (_, replaced) = mgr
+            .replace_agent_with_bin(Some("unused-agent".to_string()), None, AgentLabels::default(), None)
+            .await?;
+        assert!(!replaced);
+
+        mgr.kill_all_agents().await;
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn health_reports_each_agent() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        assert!(mgr.health().await.is_empty());
+        mgr.spawn_agent(Some("health-b".to_string()), None).await?;
+        mgr.spawn_agent(Some("health-a".to_string()), None).await?;
+
+        let report = mgr.health().await;
+        let ids: Vec<&str> = report.iter().filter_map(|a| a["agentId"].as_str()).collect();
+        assert_eq!(ids, vec!["health-a", "health-b"]);
+        for agent in &report {
+            assert_eq!(agent["alive"], serde_json::json!(true));
+            assert_eq!(agent["pendingRequests"], serde_json::json!(0));
+            assert_eq!(agent["pendingApprovals"], serde_json::json!(0));
+            assert!(agent["pid"].is_u64());
+        }
+
+        mgr.kill_agent("health-a").await?;
+        let report = mgr.health().await;
+        assert_eq!(report.len(), 1);
+        assert_eq!(report[0]["agentId"], "health-b");
+        mgr.kill_all_agents().await;
+        Ok(())
+    })
+    .await
+}
+
+#[tokio::test]
+async fn graceful_stop_waits_for_running_turns() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+
+        // Nothing in flight: the agent is stopped right away
+        mgr.spawn_agent(Some("drain-idle".to_string()), None).await?;
+        let stopped = mgr
+            .stop_agent_graceful("drain-idle", std::time::Duration::from_secs(5))
+            .await?;
+        assert!(stopped.drained && !stopped.timed_out, "{stopped:?}");
+        assert_eq!(stopped.abandoned_requests, 0);
+        assert!(!mgr.list_agents().await.contains(&"drain-idle".to_string()));
+
+        // A turn held up by an undecided approval keeps the agent alive until the timeout
+        let agent_id = mgr.spawn_agent(Some("drain-busy".to_string()), None).await?;
+        let conv = mgr
+            .new_conversation(&agent_id, serde_json::json!("Drain test"))
+            .await?;
+        let cid = conv["conversationId"].as_str().unwrap().to_string();
+        let send_task = tokio::spawn({
+            let mgr = mgr.clone();
+            let agent_id = agent_id.clone();
+            let cid = cid.clone();
+            async move {
+                mgr.send_user_turn(
+                    &agent_id,
+                    serde_json::json!({
+                        "conversationId": cid,
+                        "items": [{"type": "text", "data": {"text": "test"}}],
+                        "testApproval": true
+                    }),
+                )
+                .await
+            }
+        });
+        util::pending_approvals(&mgr).await?;
+        let stop = tokio::spawn({
+            let mgr = mgr.clone();
+            let agent_id = agent_id.clone();
+            async move {
+                mgr.stop_agent_graceful(&agent_id, std::time::Duration::from_millis(500))
+                    .await
+            }
+        });
+
+        util::eventually("the agent to start draining", || {
+            let mgr = mgr.clone();
+            async move {
+                let report = mgr.health().await;
+                report.iter().any(|a| a["draining"] == true).then_some(())
+            }
+        })
+        .await?;
+
+        // New requests are rejected while the agent drains
+        let err = mgr
+            .list_conversations(&agent_id, serde_json::json!({}))
+            .await
+            .unwrap_err();
+        assert!(err.downcast_ref::<AgentDrainingError>().is_some(), "{err}");
+        assert!(mgr
+            .stop_agent_graceful(&agent_id, std::time::Duration::from_millis(10))
+            .await
+            .is_err());
+
+        let stopped = stop.await??;
+        assert!(stopped.timed_out && !stopped.drained, "{stopped:?}");
+        assert_eq!(stopped.running_conversations, vec![cid]);
+        assert_eq!(stopped.abandoned_requests, 1);
+        assert!(mgr.list_agents().await.is_empty());
+
+        // The abandoned turn fails instead of hanging
+        assert!(send_task.await?.is_err());
+        Ok(())
+    })
+    .await
+}
@@ -1,5 +1,5 @@
 use anyhow::Result;
-use codex_orchestrator::codex::Manager;
+use codex_orchestrator::codex::{apply_turn_defaults, apply_turn_overrides, attach_inputs, Manager};
 mod util;
 
 fn set_stub_codex() {
@@ -28,7 +28,7 @@ async fn test_send_user_turn_with_string_params() -> Result<()> {
         // This should work - orchestrator will:
         // 1. Parse the string to JSON
         // 2. Convert text to items
-        // 3. Add default fields (cwd, approvalPolicy, sandboxPolicy, model, summary)
+        // 3. Add default fields (cwd, approvalPolicy, sandboxPolicy, summary)
         let result = mgr.send_user_turn(&agent_id, params_string).await;
 
         assert!(result.is_ok(), "Should handle stringified params with text field: {:?}", result);
@@ -166,3 +166,138 @@ async fn test_send_user_turn_fully_specified() -> Result<()> {
     })
     .await
 }
+
+#[tokio::test]
+async fn test_validate_turn_returns_params_without_sending() -> Result<()> {
+    set_stub_codex();
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        let agent_id = mgr.spawn_agent(Some("validate-turn-agent".to_string()), None).await?;
+
+        let conv = mgr
+            .new_conversation(&agent_id, serde_json::json!("Test"))
+            .await?;
+        let cid = conv.get("conversationId").and_then(|v| v.as_str()).unwrap();
+
+        // Text is converted, the last conversation and defaults are filled, overrides applied
+        let report = mgr
+            .validate_turn(
+                &agent_id,
+                serde_json::json!({
+                    "text": "Dry run",
+                    "cwd": "/tmp",
+                    "overrides": {"approvalPolicy": "on-request"}
+                }),
+            )
+            .await?;
+        let params = &report["params"];
+        assert_eq!(report["method"], "sendUserTurn");
+        assert_eq!(report["conversationId"], cid);
+        assert_eq!(
+            params["items"],
+            serde_json::json!([{"type": "text", "data": {"text": "Dry run"}}])
+        );
+        assert_eq!(params["cwd"], "/tmp");
+        assert_eq!(params["approvalPolicy"], "on-request");
+        assert!(params.get("text").is_none() && params.get("overrides").is_none(), "{report}");
+        let defaulted: Vec<&str> = report["defaulted"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|v| v.as_str())
+            .collect();
+        for key in ["conversationId", "sandboxPolicy", "summary"] {
+            assert!(defaulted.contains(&key), "{key} should be reported as defaulted: {report}");
+        }
+        for key in ["cwd", "approvalPolicy", "items"] {
+            assert!(!defaulted.contains(&key), "{key} came from the caller: {report}");
+        }
+
+        // Nothing was sent, so the conversation never started running
+        let state = mgr.conversation_state(&agent_id, Some(cid)).await?;
+        assert_eq!(serde_json::to_value(&state)?["state"], "unknown");
+
+        // Invalid params fail the same way a real send would
+        let err = mgr
+            .validate_turn(&agent_id, serde_json::json!({"text": "x", "overrides": "on-request"}))
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("overrides"), "{err}");
+
+        mgr.kill_agent(&agent_id).await?;
+        Ok(())
+    })
+    .await
+}
+
+// Both phases mutate CODEX_TURN_MODEL, so they run sequentially in a single test.
+#[test]
+fn test_turn_defaults_model_only_when_configured() {
+    std::env::remove_var("CODEX_TURN_MODEL");
+    let mut params = serde_json::json!({"conversationId": "c1", "items": []});
+    apply_turn_defaults(&mut params);
+    assert!(params.get("model").is_none(), "model should be left to Codex: {params}");
+    assert_eq!(params.get("summary"), Some(&serde_json::json!("auto")));
+
+    std::env::set_var("CODEX_TURN_MODEL", "gpt-5");
+    let mut params = serde_json::json!({"conversationId": "c1", "items": []});
+    apply_turn_defaults(&mut params);
+    assert_eq!(params.get("model"), Some(&serde_json::json!("gpt-5")));
+
+    // An explicit model always wins over the configured default
+    let mut params = serde_json::json!({"conversationId": "c1", "model": "o3"});
+    apply_turn_defaults(&mut params);
+    assert_eq!(params.get("model"), Some(&serde_json::json!("o3")));
+    std::env::remove_var("CODEX_TURN_MODEL");
+}
+
+#[test]
+fn test_turn_overrides_win_over_defaults() -> Result<()> {
+    let mut params = serde_json::json!({
+        "conversationId": "c1",
+        "items": [],
+        "approvalPolicy": "never",
+        "overrides": {"approvalPolicy": "on-request", "sandboxPolicy": {"mode": "workspace-write"}}
+    });
+    apply_turn_overrides(&mut params)?;
+    assert_eq!(params["approvalPolicy"], "on-request");
+    assert_eq!(params["sandboxPolicy"], serde_json::json!({"mode": "workspace-write"}));
+    assert_eq!(params["summary"], "auto");
+    assert!(params.get("overrides").is_none(), "overrides must not be forwarded: {params}");
+
+    let mut params = serde_json::json!({"conversationId": "c1", "overrides": "on-request"});
+    assert!(apply_turn_overrides(&mut params).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_attachments_become_items() -> Result<()> {
+    let dir = tempfile::tempdir()?;
+    std::fs::write(dir.path().join("shot.png"), b"png")?;
+    std::fs::write(dir.path().join("notes.md"), b"notes")?;
+
+    let mut params = serde_json::json!({
+        "items": [{"type": "text", "data": {"text": "Look at these"}}],
+        "images": ["shot.png", "https://example.com/a.png", {"data": "aGk=", "mimeType": "image/jpeg"}],
+        "files": "notes.md"
+    });
+    attach_inputs(params.as_object_mut().unwrap(), dir.path())?;
+    let items = params["items"].as_array().unwrap();
+    assert!(params.get("images").is_none() && params.get("files").is_none());
+    assert_eq!(items.len(), 5, "text first, then attachments: {params}");
+    assert_eq!(items[1]["type"], "localImage");
+    assert_eq!(
+        items[1]["data"]["path"],
+        serde_json::json!(dir.path().join("shot.png").to_string_lossy())
+    );
+    assert_eq!(items[2]["data"]["image_url"], "https://example.com/a.png");
+    assert_eq!(items[3]["data"]["image_url"], "data:image/jpeg;base64,aGk=");
+    assert_eq!(items[4]["type"], "text");
+    assert!(items[4]["data"]["text"].as_str().unwrap().ends_with("notes.md"));
+
+    // Missing files are rejected
+    let mut params = serde_json::json!({"text": "hi", "files": ["missing.txt"]});
+    let err = attach_inputs(params.as_object_mut().unwrap(), dir.path()).unwrap_err();
+    assert!(err.to_string().contains("missing.txt"), "{err}");
+    Ok(())
+}
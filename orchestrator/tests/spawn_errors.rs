@@ -0,0 +1,210 @@
+use anyhow::{anyhow, Result};
+use codex_orchestrator::codex::{init_failure_hint, AgentLabels, Manager, SpawnError};
+mod util;
+
+// These scenarios all mutate CODEX_BIN, so they run sequentially in a single test.
+#[tokio::test]
+async fn test_spawn_errors_are_classified() -> Result<()> {
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+
+        // Binary path that cannot be executed
+        std::env::set_var("CODEX_BIN", "/nonexistent/codex-bin");
+        let err = mgr
+            .spawn_agent(Some("missing-bin".to_string()), None)
+            .await
+            .err()
+            .ok_or_else(|| anyhow!("spawn should fail for a missing binary"))?;
+        let kind = err.downcast_ref::<SpawnError>().map(|e| e.kind());
+        assert_eq!(kind, Some("spawn_failed"));
+
+        // Binary that starts, writes to stderr, and exits before the handshake
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let dir = tempfile::tempdir()?;
+            let script = dir.path().join("crashing-codex");
+            std::fs::write(&script, "#!/bin/sh\necho 'boom: missing config' >&2\nexit 1\n")?;
+            std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+            std::env::set_var("CODEX_BIN", &script);
+
+            let err = mgr
+                .spawn_agent(Some("crashing".to_string()), None)
+                .await
+                .err()
+                .ok_or_else(|| anyhow!("spawn should fail when the agent exits during init"))?;
+            let spawn_err = err
+                .downcast_ref::<SpawnError>()
+                .ok_or_else(|| anyhow!("expected SpawnError, got {err:#}"))?;
+            assert_eq!(spawn_err.kind(), "init_failed");
+            let data = spawn_err.data();
+            let stderr = data.get("stderr").and_then(|v| v.as_str()).unwrap_or("");
+            assert!(stderr.contains("boom: missing config"), "stderr: {stderr:?}");
+            assert!(data["hint"].is_null());
+            assert!(mgr.list_agents().await.is_empty());
+
+            // A recognized misconfiguration attaches a hint to both the data and the message
+            let script = dir.path().join("unauthenticated-codex");
+            std::fs::write(
+                &script,
+                "#!/bin/sh\necho 'Error: 401 Unauthorized: not logged in' >&2\nexit 1\n",
+            )?;
+            std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))?;
+            std::env::set_var("CODEX_BIN", &script);
+
+            let err = mgr
+                .spawn_agent(Some("unauthenticated".to_string()), None)
+                .await
+                .err()
+                .ok_or_else(|| anyhow!("spawn should fail when the agent cannot authenticate"))?;
+            let spawn_err = err
+                .downcast_ref::<SpawnError>()
+                .ok_or_else(|| anyhow!("expected SpawnError, got {err:#}"))?;
+            assert_eq!(spawn_err.kind(), "init_failed");
+            let hint = spawn_err.data()["hint"].as_str().unwrap_or("").to_string();
+            assert!(hint.contains("codex login"), "hint: {hint:?}");
+            assert!(err.to_string().contains(&hint));
+        }
+
+        // Signatures are matched case-insensitively in stderr or the handshake error
+        let hint = init_failure_hint("OPENAI_API_KEY is not set", "");
+        assert!(hint.is_some_and(|h| h.contains("API key")), "hint: {hint:?}");
+        let hint = init_failure_hint("", "error: unexpected argument '--foo'");
+        assert!(hint.is_some_and(|h| h.contains("Upgrade Codex")), "hint: {hint:?}");
+        assert_eq!(init_failure_hint("panicked at src/main.rs", "codex closed during init"), None);
+
+        Ok(())
+    })
+    .await
+}
+
+// A per-agent `bin` bypasses CODEX_BIN entirely, so this runs alongside the test above.
+#[tokio::test]
+async fn test_per_agent_bin_is_validated_and_used() -> Result<()> {
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        let stub = env!("CARGO_BIN_EXE_stub_codex").to_string();
+
+        let agent_id = mgr
+            .spawn_agent_with_bin(
+                Some("own-bin".to_string()),
+                None,
+                AgentLabels::default(),
+                Some(stub.clone()),
+            )
+            .await?;
+        let infos = mgr.agent_infos(&[], &serde_json::Map::new()).await;
+        assert_eq!(infos.len(), 1);
+        assert_eq!(infos[0].bin, stub);
+
+        let err = mgr
+            .spawn_agent_with_bin(
+                Some("missing-own-bin".to_string()),
+                None,
+                AgentLabels::default(),
+                Some("/nonexistent/codex-bin".to_string()),
+            )
+            .await
+            .err()
+            .ok_or_else(|| anyhow!("spawn should fail for a missing per-agent binary"))?;
+        let kind = err.downcast_ref::<SpawnError>().map(|e| e.kind());
+        assert_eq!(kind, Some("invalid_binary"));
+
+        #[cfg(unix)]
+        {
+            let dir = tempfile::tempdir()?;
+            let script = dir.path().join("not-executable");
+            std::fs::write(&script, "#!/bin/sh\nexit 0\n")?;
+            let err = mgr
+                .spawn_agent_with_bin(
+                    Some("plain-file".to_string()),
+                    None,
+                    AgentLabels::default(),
+                    Some(script.to_string_lossy().into_owned()),
+                )
+                .await
+                .err()
+                .ok_or_else(|| anyhow!("spawn should fail for a non-executable binary"))?;
+            let spawn_err = err
+                .downcast_ref::<SpawnError>()
+                .ok_or_else(|| anyhow!("expected SpawnError, got {err:#}"))?;
+            assert_eq!(spawn_err.kind(), "invalid_binary");
+            assert_eq!(spawn_err.data()["details"], "not executable");
+        }
+
+        assert_eq!(mgr.list_agents().await, vec![agent_id.clone()]);
+        mgr.kill_agent(&agent_id).await?;
+        Ok(())
+    })
+    .await
+}
+
+// Only this test sets CODEX_MAX_AGENTS; the other tests in this file spawn at most one working
+// agent per manager, which the limit of two never blocks.
+#[tokio::test]
+async fn test_agent_limit_counts_live_agents() -> Result<()> {
+    util::with_timeout(async move {
+        let mgr = Manager::default();
+        let stub = env!("CARGO_BIN_EXE_stub_codex").to_string();
+        let spawn = |id: &str| {
+            mgr.spawn_agent_with_bin(
+                Some(id.to_string()),
+                None,
+                AgentLabels::default(),
+                Some(stub.clone()),
+            )
+        };
+        std::env::set_var("CODEX_MAX_AGENTS", "2");
+
+        spawn("limit-a").await?;
+        spawn("limit-b").await?;
+        let err = spawn("limit-c")
+            .await
+            .err()
+            .ok_or_else(|| anyhow!("spawn should fail once the limit is reached"))?;
+        let spawn_err = err
+            .downcast_ref::<SpawnError>()
+            .ok_or_else(|| anyhow!("expected SpawnError, got {err:#}"))?;
+        assert_eq!(spawn_err.kind(), "agent_limit_reached");
+        assert_eq!(spawn_err.data()["limit"], 2);
+        assert_eq!(spawn_err.data()["live"], 2);
+        assert_eq!(mgr.list_agents().await.len(), 2);
+
+        // A replacement takes over the slot of the agent it replaces
+        let (_, replaced) = mgr
+            .replace_agent_with_bin(
+                Some("limit-a".to_string()),
+                None,
+                AgentLabels::default(),
+                Some(stub.clone()),
+            )
+            .await?;
+        assert!(replaced);
+        assert_eq!(mgr.list_agents().await.len(), 2);
+
+        // Killing an agent frees its slot
+        mgr.kill_agent("limit-a").await?;
+        spawn("limit-c").await?;
+
+        // An agent whose process died no longer counts, even before it is removed
+        #[cfg(unix)]
+        {
+            let infos = mgr.agent_infos(&[], &serde_json::Map::new()).await;
+            let pid = infos
+                .iter()
+                .find(|info| info.agent_id == "limit-b")
+                .and_then(|info| info.pid)
+                .ok_or_else(|| anyhow!("limit-b should report a pid"))?;
+            std::process::Command::new("kill").arg("-9").arg(pid.to_string()).status()?;
+            util::eventually("limit-b's slot to free up", move || async move {
+                spawn("limit-d").await.ok()
+            })
+            .await?;
+        }
+
+        std::env::remove_var("CODEX_MAX_AGENTS");
+        mgr.kill_all_agents().await;
+        Ok(())
+    })
+    .await
+}
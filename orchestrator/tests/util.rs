@@ -16,3 +16,36 @@ where
         Err(_) => Err(anyhow!("test timeout after 60s")),
     }
 }
+
+/// Poll `check` until it yields a value, for state the manager exposes no wait for. Fails
+/// after ten seconds rather than hanging until the test timeout.
+#[allow(dead_code)]
+pub async fn eventually<F, Fut, T>(what: &str, mut check: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: std::future::Future<Output = Option<T>>,
+{
+    let deadline = tokio::time::Instant::now() + Duration::from_secs(10);
+    loop {
+        if let Some(value) = check().await {
+            return Ok(value);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(anyhow!("timed out waiting for {what}"));
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+/// Wait until at least one approval is pending and return the pending keys.
+#[allow(dead_code)]
+pub async fn pending_approvals(mgr: &codex_orchestrator::codex::Manager) -> Result<Vec<String>> {
+    eventually("a pending approval", || {
+        let mgr = mgr.clone();
+        async move {
+            let pending = mgr.list_pending_approvals().await;
+            (!pending.is_empty()).then_some(pending)
+        }
+    })
+    .await
+}